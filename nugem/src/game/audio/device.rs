@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use super::{AudioOutput, Error, OUTPUT_CHANNELS};
+
+/// Duration of the samples waiting to be played kept by the output, in seconds
+const TARGET_BUFFERED_DURATION: f32 = 0.1;
+
+/// Fraction of the difference with the target duration corrected at each tick
+const PACING_DIVISOR: isize = 8;
+
+/// Output playing the samples on the default audio device.
+///
+/// The samples written at each tick are buffered until the device requests them: silence is played when the buffer
+/// is empty. The clocks of the game and of the device drift apart, so the number of frames written at each tick is
+/// adjusted to keep the buffer around its target duration, instead of dropping samples.
+pub struct DeviceOutput {
+    _stream: cpal::Stream,
+    buffer: Arc<Mutex<VecDeque<f32>>>,
+    sample_rate: u32,
+}
+
+impl DeviceOutput {
+    pub fn new() -> Result<DeviceOutput, Error> {
+        let device = cpal::default_host().default_output_device().ok_or(Error::NoOutputDevice)?;
+        let supported_config = device.default_output_config()?;
+        let config = supported_config.config();
+        let buffer = Arc::new(Mutex::new(VecDeque::new()));
+        let stream = match supported_config.sample_format() {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, buffer.clone())?,
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, buffer.clone())?,
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, buffer.clone())?,
+        };
+        stream.play()?;
+        Ok(DeviceOutput {
+            _stream: stream,
+            buffer,
+            sample_rate: config.sample_rate.0,
+        })
+    }
+}
+
+fn build_stream<T: cpal::Sample>(device: &cpal::Device, config: &cpal::StreamConfig, buffer: Arc<Mutex<VecDeque<f32>>>) -> Result<cpal::Stream, Error> {
+    let device_channels = config.channels as usize;
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut buffer = buffer.lock().unwrap();
+            for frame in data.chunks_mut(device_channels) {
+                let left = buffer.pop_front().unwrap_or(0.);
+                let right = buffer.pop_front().unwrap_or(0.);
+                // stereo samples on the first two channels, mixed on a single channel
+                let samples = match device_channels {
+                    1 => [(left + right) / 2., 0.],
+                    _ => [left, right],
+                };
+                for (index, sample) in frame.iter_mut().enumerate() {
+                    *sample = T::from(samples.get(index).unwrap_or(&0.));
+                }
+            }
+        },
+        |err| log::error!("Audio output error: {err}"),
+    )?;
+    Ok(stream)
+}
+
+impl AudioOutput for DeviceOutput {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn write(&mut self, samples: &[f32]) -> Result<(), Error> {
+        self.buffer.lock().unwrap().extend(samples);
+        Ok(())
+    }
+    fn tick_frames(&self, frames: usize) -> usize {
+        // the frames not played yet by the device
+        let buffered_frames = self.buffer.lock().unwrap().len() / OUTPUT_CHANNELS as usize;
+        let target_frames = (self.sample_rate as f32 * TARGET_BUFFERED_DURATION) as usize;
+        paced_frames(frames, buffered_frames, target_frames)
+    }
+}
+
+/// Frames of a tick moved towards the target number of buffered frames, between none and twice the frames of the tick
+fn paced_frames(frames: usize, buffered_frames: usize, target_frames: usize) -> usize {
+    let correction = (target_frames as isize - buffered_frames as isize) / PACING_DIVISOR;
+    (frames as isize + correction).clamp(0, 2 * frames as isize) as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pacing() {
+        assert_eq!(735, paced_frames(735, 4410, 4410));
+        // the device plays faster than the game
+        assert_eq!(835, paced_frames(735, 3610, 4410));
+        assert_eq!(1286, paced_frames(735, 0, 4410));
+        assert_eq!(1470, paced_frames(735, 0, 20000));
+        // the game runs ahead of the device: nothing is dropped, the next ticks are shorter
+        assert_eq!(635, paced_frames(735, 5210, 4410));
+        assert_eq!(0, paced_frames(735, 20000, 4410));
+    }
+}
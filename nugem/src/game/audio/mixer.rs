@@ -0,0 +1,280 @@
+use std::sync::Arc;
+use nugem_snd::Sound;
+use super::{AudioOutput, Error, OUTPUT_CHANNELS};
+
+/// Maximum number of sounds played at the same time: the oldest sounds are stopped to play new ones
+pub const MAX_VOICES: usize = 32;
+
+/// Category of a sound, with its own volume
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SoundCategory {
+    #[default]
+    Effect,
+    Music,
+}
+
+/// Volumes between 0 and 1, multiplied together for each sound.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Volumes {
+    pub master: f32,
+    pub effects: f32,
+    pub music: f32,
+}
+
+impl Default for Volumes {
+    fn default() -> Volumes {
+        Volumes {
+            master: 1.,
+            effects: 1.,
+            music: 1.,
+        }
+    }
+}
+
+impl Volumes {
+    fn category_volume(&self, category: SoundCategory) -> f32 {
+        self.master * match category {
+            SoundCategory::Effect => self.effects,
+            SoundCategory::Music => self.music,
+        }
+    }
+}
+
+/// Parameters of a sound played by the mixer
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlayParameters {
+    /// Owner of the channel, such as a player: each owner has its own channels
+    pub owner: usize,
+    /// Channel of the sound: a sound on a channel stops the previous sound of the channel, `None` to always play the
+    /// sound
+    pub channel: Option<i32>,
+    /// A low priority sound can be interrupted by any sound on its channel, and cannot interrupt a normal priority sound
+    pub low_priority: bool,
+    /// Volume multiplier
+    pub volume: f32,
+    /// Position from -1 for the left to 1 for the right
+    pub pan: f32,
+    pub frequency_multiplier: f32,
+    pub looping: bool,
+    pub category: SoundCategory,
+}
+
+impl Default for PlayParameters {
+    fn default() -> PlayParameters {
+        PlayParameters {
+            owner: 0,
+            channel: None,
+            low_priority: false,
+            volume: 1.,
+            pan: 0.,
+            frequency_multiplier: 1.,
+            looping: false,
+            category: SoundCategory::Effect,
+        }
+    }
+}
+
+struct Voice {
+    sound: Arc<Sound>,
+    parameters: PlayParameters,
+    /// Position in the frames of the sound
+    position: f64,
+    /// Frames of the sound read for each output frame
+    step: f64,
+}
+
+impl Voice {
+    /// Stereo samples of the sound at the current position, with linear interpolation between frames
+    fn current_samples(&self) -> (f32, f32) {
+        let index = self.position as usize;
+        let fraction = (self.position - index as f64) as f32;
+        let next_index = match index + 1 < self.sound.frame_count() {
+            true => index + 1,
+            false if self.parameters.looping => 0,
+            false => index,
+        };
+        let stereo = |frame: &[f32]| (frame[0], frame[frame.len().min(2) - 1]);
+        let (left, right) = self.sound.frame(index).map(stereo).unwrap_or_default();
+        let (next_left, next_right) = self.sound.frame(next_index).map(stereo).unwrap_or_default();
+        (left + (next_left - left) * fraction, right + (next_right - right) * fraction)
+    }
+    /// Move to the next frame and return false at the end of the sound
+    fn advance(&mut self) -> bool {
+        self.position += self.step;
+        let frame_count = self.sound.frame_count() as f64;
+        if self.position < frame_count {
+            true
+        }
+        else if self.parameters.looping && frame_count > 0. {
+            self.position %= frame_count;
+            true
+        }
+        else {
+            false
+        }
+    }
+}
+
+/// Mixes the sounds played in the game into stereo samples, driven by the game ticks.
+pub struct Mixer {
+    sample_rate: u32,
+    ticks_per_second: u32,
+    volumes: Volumes,
+    /// Sounds being played, from the oldest to the most recent
+    voices: Vec<Voice>,
+    /// Remainder of the division of the frames by the ticks, to write the exact number of frames every second
+    frame_remainder: u32,
+    samples: Vec<f32>,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32, ticks_per_second: u32) -> Mixer {
+        Mixer {
+            sample_rate,
+            ticks_per_second: ticks_per_second.max(1),
+            volumes: Volumes::default(),
+            voices: Vec::new(),
+            frame_remainder: 0,
+            samples: Vec::new(),
+        }
+    }
+    pub fn volumes(&self) -> Volumes {
+        self.volumes
+    }
+    pub fn set_volumes(&mut self, volumes: Volumes) {
+        self.volumes = volumes;
+    }
+    /// Number of sounds being played
+    pub fn voice_count(&self) -> usize {
+        self.voices.len()
+    }
+    pub fn is_playing(&self, owner: usize, channel: i32) -> bool {
+        self.channel_voice(owner, channel).is_some()
+    }
+    fn channel_voice(&self, owner: usize, channel: i32) -> Option<usize> {
+        self.voices.iter().position(|voice| voice.parameters.owner == owner && voice.parameters.channel == Some(channel))
+    }
+    /// Play a sound, and return false if it cannot interrupt the sound on its channel
+    pub fn play(&mut self, sound: Arc<Sound>, parameters: PlayParameters) -> bool {
+        if let Some(channel) = parameters.channel {
+            if let Some(index) = self.channel_voice(parameters.owner, channel) {
+                if parameters.low_priority && !self.voices[index].parameters.low_priority {
+                    return false;
+                }
+                self.voices.remove(index);
+            }
+        }
+        if self.voices.len() >= MAX_VOICES {
+            // steal the oldest sound without a channel, or the oldest sound
+            let index = self.voices.iter().position(|voice| voice.parameters.channel.is_none()).unwrap_or(0);
+            self.voices.remove(index);
+        }
+        let step = sound.sample_rate() as f64 * parameters.frequency_multiplier.max(0.) as f64 / self.sample_rate as f64;
+        self.voices.push(Voice {
+            sound,
+            parameters,
+            position: 0.,
+            step,
+        });
+        true
+    }
+    /// Stop the sound of a channel, or all the sounds of the owner if the channel is `None`
+    pub fn stop(&mut self, owner: usize, channel: Option<i32>) {
+        self.voices.retain(|voice| voice.parameters.owner != owner || (channel.is_some() && voice.parameters.channel != channel));
+    }
+    /// Stop all the sounds of a category
+    pub fn stop_category(&mut self, category: SoundCategory) {
+        self.voices.retain(|voice| voice.parameters.category != category);
+    }
+    pub fn set_pan(&mut self, owner: usize, channel: i32, pan: f32) {
+        if let Some(index) = self.channel_voice(owner, channel) {
+            self.voices[index].parameters.pan = pan;
+        }
+    }
+    /// Mix the given number of stereo frames and return the interleaved samples
+    pub fn mix(&mut self, frame_count: usize) -> &[f32] {
+        self.samples.clear();
+        self.samples.resize(frame_count * OUTPUT_CHANNELS as usize, 0.);
+        let volumes = self.volumes;
+        self.voices.retain_mut(|voice| {
+            let volume = voice.parameters.volume * volumes.category_volume(voice.parameters.category);
+            let pan = voice.parameters.pan.clamp(-1., 1.);
+            let (left_gain, right_gain) = (volume * (1. - pan).min(1.), volume * (1. + pan).min(1.));
+            for frame in self.samples.chunks_exact_mut(OUTPUT_CHANNELS as usize) {
+                let (left, right) = voice.current_samples();
+                frame[0] += left * left_gain;
+                frame[1] += right * right_gain;
+                if !voice.advance() {
+                    return false;
+                }
+            }
+            true
+        });
+        for sample in self.samples.iter_mut() {
+            *sample = sample.clamp(-1., 1.);
+        }
+        &self.samples[..]
+    }
+    /// Mix the samples of a game tick and write them to the output, with the number of frames adjusted by the output
+    pub fn tick(&mut self, output: &mut dyn AudioOutput) -> Result<(), Error> {
+        let frames = self.sample_rate + self.frame_remainder;
+        self.frame_remainder = frames % self.ticks_per_second;
+        let samples = self.mix(output.tick_frames((frames / self.ticks_per_second) as usize));
+        output.write(samples)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::audio::NullOutput;
+
+    fn sound(samples: &[f32]) -> Arc<Sound> {
+        Arc::new(Sound::new(100, 1, samples.to_vec()))
+    }
+
+    #[test]
+    fn mix_volume_and_pan() {
+        let mut mixer = Mixer::new(100, 10);
+        mixer.set_volumes(Volumes { master: 0.5, effects: 1., music: 0. });
+        mixer.play(sound(&[0.5, 1., 0.5]), PlayParameters { pan: 1., ..Default::default() });
+        mixer.play(sound(&[1.; 8]), PlayParameters { category: SoundCategory::Music, ..Default::default() });
+        assert_eq!(&[0., 0.25, 0., 0.5, 0., 0.25, 0., 0.], mixer.mix(4));
+        // the music is still played with a null volume
+        assert_eq!(1, mixer.voice_count());
+        mixer.stop_category(SoundCategory::Music);
+        assert_eq!(0, mixer.voice_count());
+        // looping sound at half the frequency
+        mixer.set_volumes(Volumes::default());
+        mixer.play(sound(&[0., 1.]), PlayParameters { looping: true, frequency_multiplier: 0.5, ..Default::default() });
+        assert_eq!(&[0., 0., 0.5, 0.5, 1., 1., 0.5, 0.5, 0., 0.], mixer.mix(5));
+        mixer.stop(0, None);
+        assert_eq!(0, mixer.voice_count());
+    }
+
+    #[test]
+    fn channels_and_priority() {
+        let mut mixer = Mixer::new(100, 10);
+        let channel = |channel, low_priority| PlayParameters { channel: Some(channel), low_priority, ..Default::default() };
+        assert!(mixer.play(sound(&[0.1; 10]), channel(0, false)));
+        // a low priority sound does not interrupt the sound of the channel
+        assert!(!mixer.play(sound(&[0.2; 10]), channel(0, true)));
+        assert_eq!(&[0.1, 0.1], mixer.mix(1));
+        assert!(mixer.play(sound(&[0.3; 10]), channel(0, false)));
+        assert!(mixer.play(sound(&[0.4; 10]), PlayParameters { owner: 1, ..channel(0, false) }));
+        assert_eq!(2, mixer.voice_count());
+        mixer.stop(0, Some(0));
+        assert!(!mixer.is_playing(0, 0) && mixer.is_playing(1, 0));
+        // the oldest sounds without a channel are stolen
+        for _ in 0..MAX_VOICES {
+            mixer.play(sound(&[0.; 10]), PlayParameters::default());
+        }
+        assert_eq!(MAX_VOICES, mixer.voice_count());
+        assert!(mixer.is_playing(1, 0));
+        // one tick of 10 frames per second at 100 Hz
+        let mut output = NullOutput::new(100);
+        mixer.tick(&mut output).unwrap();
+        assert_eq!(10, output.frames_written());
+        assert_eq!(0, mixer.voice_count());
+    }
+}
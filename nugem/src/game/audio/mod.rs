@@ -0,0 +1,64 @@
+use std::fs::File;
+use std::io::BufWriter;
+use thiserror::Error;
+use crate::game::AudioOutputConfig;
+
+mod mixer;
+pub use self::mixer::*;
+
+mod output;
+pub use self::output::*;
+
+#[cfg(feature = "cpal")]
+mod device;
+#[cfg(feature = "cpal")]
+pub use self::device::*;
+
+mod sound_bank;
+pub use self::sound_bank::*;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error("No audio output device found")]
+    NoOutputDevice,
+    #[cfg(feature = "cpal")]
+    #[error(transparent)]
+    DefaultStreamConfigError(#[from] cpal::DefaultStreamConfigError),
+    #[cfg(feature = "cpal")]
+    #[error(transparent)]
+    BuildStreamError(#[from] cpal::BuildStreamError),
+    #[cfg(feature = "cpal")]
+    #[error(transparent)]
+    PlayStreamError(#[from] cpal::PlayStreamError),
+}
+
+/// Sample rate of the outputs without an audio device
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// Open the audio output of the configuration, or an output without sound if it cannot be opened
+pub fn open_output(config: &AudioOutputConfig) -> Box<dyn AudioOutput> {
+    match config {
+        AudioOutputConfig::Device => {
+            #[cfg(feature = "cpal")]
+            match DeviceOutput::new() {
+                Ok(output) => return Box::new(output),
+                Err(err) => log::error!("Failed to open the audio device, the sounds are disabled: {err}"),
+            }
+            #[cfg(not(feature = "cpal"))]
+            log::warn!("Built without audio device support, the sounds are disabled");
+        },
+        AudioOutputConfig::File(path) => {
+            let output = File::create(path)
+                .map_err(Error::from)
+                .and_then(|file| WavFileOutput::new(BufWriter::new(file), DEFAULT_SAMPLE_RATE));
+            match output {
+                Ok(output) => return Box::new(output),
+                Err(err) => log::error!("Failed to create the audio file {0}, the sounds are disabled: {err}", path.display()),
+            }
+        },
+        AudioOutputConfig::Null => (),
+    }
+    Box::new(NullOutput::new(DEFAULT_SAMPLE_RATE))
+}
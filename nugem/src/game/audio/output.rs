@@ -0,0 +1,122 @@
+use std::io::{Seek, SeekFrom, Write};
+use super::Error;
+
+/// Number of channels of the samples written to the outputs: left and right
+pub const OUTPUT_CHANNELS: u16 = 2;
+
+/// Destination of the samples mixed at every game tick.
+pub trait AudioOutput {
+    fn sample_rate(&self) -> u32;
+    /// Write interleaved stereo samples between -1 and 1
+    fn write(&mut self, samples: &[f32]) -> Result<(), Error>;
+    /// Number of frames to write for a tick of the given number of frames, to follow the clock of the output
+    fn tick_frames(&self, frames: usize) -> usize {
+        frames
+    }
+}
+
+/// Output dropping the samples, for machines without audio hardware.
+#[derive(Clone, Debug)]
+pub struct NullOutput {
+    sample_rate: u32,
+    frames_written: u64,
+}
+
+impl NullOutput {
+    pub fn new(sample_rate: u32) -> NullOutput {
+        NullOutput {
+            sample_rate,
+            frames_written: 0,
+        }
+    }
+    /// Number of stereo frames written since the creation of the output
+    pub fn frames_written(&self) -> u64 {
+        self.frames_written
+    }
+}
+
+impl AudioOutput for NullOutput {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn write(&mut self, samples: &[f32]) -> Result<(), Error> {
+        self.frames_written += (samples.len() / OUTPUT_CHANNELS as usize) as u64;
+        Ok(())
+    }
+}
+
+/// Output recording the samples in WAV data, with 16 bits PCM samples.
+///
+/// The header is updated after every write, so that the data is valid even if the game stops abruptly.
+pub struct WavFileOutput<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+    data_size: u32,
+}
+
+/// Size of the RIFF header, `fmt ` chunk and `data` chunk header
+const WAV_HEADER_SIZE: u32 = 44;
+
+impl<W: Write + Seek> WavFileOutput<W> {
+    pub fn new(mut writer: W, sample_rate: u32) -> Result<WavFileOutput<W>, Error> {
+        let block_align = OUTPUT_CHANNELS * 2;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(WAV_HEADER_SIZE - 8).to_le_bytes())?;
+        writer.write_all(b"WAVEfmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        // PCM format
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&OUTPUT_CHANNELS.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&16u16.to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(WavFileOutput {
+            writer,
+            sample_rate,
+            data_size: 0,
+        })
+    }
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Seek> AudioOutput for WavFileOutput<W> {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn write(&mut self, samples: &[f32]) -> Result<(), Error> {
+        let bytes: Vec<u8> = samples.iter()
+            .flat_map(|sample| ((sample.clamp(-1., 1.) * i16::MAX as f32) as i16).to_le_bytes())
+            .collect();
+        self.writer.write_all(&bytes)?;
+        self.data_size += bytes.len() as u32;
+        // sizes of the RIFF data and of the data chunk
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_all(&(WAV_HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(WAV_HEADER_SIZE as u64 - 4))?;
+        self.writer.write_all(&self.data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_wav_file() {
+        let mut output = WavFileOutput::new(Cursor::new(Vec::new()), 22050).unwrap();
+        output.write(&[0., 0.5]).unwrap();
+        output.write(&[-1., 2.]).unwrap();
+        let sound = nugem_snd::decode_wav(output.into_inner().get_ref()).unwrap();
+        assert_eq!((22050, 2), (sound.sample_rate(), sound.channels()));
+        let expected = [0, 16383, -32767, 32767].map(|sample: i16| sample as f32 / 32768.);
+        assert_eq!(&expected, sound.samples());
+    }
+}
@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use nugem_snd::{Sound, SoundFile};
+
+/// Sounds of a sound file, decoded on their first use.
+#[derive(Debug, Default)]
+pub struct SoundBank {
+    sound_file: SoundFile,
+    /// Decoded sounds, with `None` for the sounds that failed to decode
+    decoded: HashMap<(u32, u32), Option<Arc<Sound>>>,
+}
+
+impl SoundBank {
+    pub fn new(sound_file: SoundFile) -> SoundBank {
+        SoundBank {
+            sound_file,
+            decoded: HashMap::new(),
+        }
+    }
+    pub fn sound(&mut self, group: i32, number: i32) -> Option<Arc<Sound>> {
+        let key = (u32::try_from(group).ok()?, u32::try_from(number).ok()?);
+        let sound_file = &self.sound_file;
+        self.decoded.entry(key)
+            .or_insert_with(|| match sound_file.sound(key.0, key.1) {
+                Ok(sound) => Some(Arc::new(sound)),
+                Err(err) => {
+                    log::error!("Failed to decode sound {group}, {number}: {err}");
+                    None
+                },
+            })
+            .clone()
+    }
+}
@@ -1,11 +1,31 @@
 use std::path::PathBuf;
 use std::env;
+use crate::game::audio::Volumes;
+use crate::game::mugen::round::MatchRules;
+
+/// Destination of the game sounds
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AudioOutputConfig {
+    /// Default audio device
+    Device,
+    /// No sound output
+    Null,
+    /// WAV file recording the sounds
+    File(PathBuf),
+}
 
 pub struct Config {
     data_paths: Vec<PathBuf>,
+    common_paths: Vec<PathBuf>,
     window_size: (u32, u32),
     fullscreen: bool,
     ticks_per_second: u32,
+    audio_output: AudioOutputConfig,
+    volumes: Volumes,
+    /// Rounds to win a match, replacing the rules of the lifebar
+    rounds_to_win: Option<u32>,
+    /// Counts of the round timer, `Some(None)` for no time limit
+    round_time: Option<Option<i32>>,
 }
 
 const DEFAULT_WINDOW_WIDTH : u32 = 800;
@@ -17,8 +37,13 @@ impl Config {
     pub fn new() -> Config {
         let mut ticks_per_second = DEFAULT_TICKS_PER_SECOND;
         let mut data_paths = Vec::new();
+        let mut common_paths = Vec::new();
         let mut window_size = (DEFAULT_WINDOW_WIDTH, DEFAULT_WINDOW_HEIGHT);
         let mut fullscreen = DEFAULT_FULLSCREEN;
+        let mut audio_output = AudioOutputConfig::Device;
+        let mut volumes = Volumes::default();
+        let mut rounds_to_win = None;
+        let mut round_time = None;
         // taking arguments into account
         {
             let args : Vec<_> = env::args().collect();
@@ -51,6 +76,11 @@ impl Config {
                         data_paths.push(PathBuf::from(next));
                         i += 1;
                     },
+                    "--common" => {
+                        let next = &args[i+1];
+                        common_paths.push(PathBuf::from(next));
+                        i += 1;
+                    },
                     "--fps" => {
                         let next = &args[i+1];
                         match next.parse::<u32>() {
@@ -61,6 +91,40 @@ impl Config {
                             _ => (),
                         }
                     },
+                    "--no-audio" => audio_output = AudioOutputConfig::Null,
+                    "--audio-file" => {
+                        let next = &args[i+1];
+                        audio_output = AudioOutputConfig::File(PathBuf::from(next));
+                        i += 1;
+                    },
+                    "--volume" | "--sfx-volume" | "--music-volume" => {
+                        let next = &args[i+1];
+                        if let Ok(v) = next.parse::<u32>() {
+                            // volumes in percents
+                            let volume = v.min(100) as f32 / 100.;
+                            match args[i].as_str() {
+                                "--volume" => volumes.master = volume,
+                                "--sfx-volume" => volumes.effects = volume,
+                                _ => volumes.music = volume,
+                            }
+                            i += 1;
+                        }
+                    },
+                    "--rounds" => {
+                        let next = &args[i+1];
+                        if let Ok(v) = next.parse::<u32>() {
+                            rounds_to_win = Some(v.max(1));
+                            i += 1;
+                        }
+                    },
+                    "--round-time" => {
+                        let next = &args[i+1];
+                        if let Ok(v) = next.parse::<i32>() {
+                            // no time limit for a negative time
+                            round_time = Some((v >= 0).then_some(v));
+                            i += 1;
+                        }
+                    },
                     _ => (),
                 }
                 i += 1;
@@ -68,14 +132,36 @@ impl Config {
         }
         Config {
             data_paths,
+            common_paths,
             window_size,
             fullscreen,
             ticks_per_second,
+            audio_output,
+            volumes,
+            rounds_to_win,
+            round_time,
         }
     }
     pub fn data_paths(&self) -> &[PathBuf] {
         &self.data_paths[..]
     }
+    /// Folders of the files shared by the characters, such as `common1.cns`: the folders given as arguments, then the
+    /// `data` folders next to the data folders, as in the MUGEN folder structure
+    pub fn common_paths(&self) -> Vec<PathBuf> {
+        let data_folders = self.data_paths.iter()
+            .filter_map(|data_path| data_path.parent())
+            .map(|parent| parent.join("data"))
+            .filter(|path| path.is_dir());
+        self.common_paths.iter().cloned().chain(data_folders).collect()
+    }
+    /// Folders of the stages: the `stages` folders next to the data folders, as in the MUGEN folder structure
+    pub fn stage_paths(&self) -> Vec<PathBuf> {
+        self.data_paths.iter()
+            .filter_map(|data_path| data_path.parent())
+            .map(|parent| parent.join("stages"))
+            .filter(|path| path.is_dir())
+            .collect()
+    }
     pub fn window_size(&self) -> (u32, u32) {
         self.window_size
     }
@@ -85,4 +171,20 @@ impl Config {
     pub fn ticks_per_second(&self) -> u32 {
         self.ticks_per_second
     }
-}
\ No newline at end of file
+    pub fn audio_output(&self) -> &AudioOutputConfig {
+        &self.audio_output
+    }
+    pub fn volumes(&self) -> Volumes {
+        self.volumes
+    }
+    /// Rules of a match, with the settings given as arguments
+    pub fn match_rules(&self, mut rules: MatchRules) -> MatchRules {
+        if let Some(rounds_to_win) = self.rounds_to_win {
+            rules.rounds_to_win = rounds_to_win;
+        }
+        if let Some(round_time) = self.round_time {
+            rules.round_time = round_time;
+        }
+        rules
+    }
+}
@@ -12,6 +12,13 @@ pub enum Error {
     #[error("Empty texture atlas")]
     EmptyAtlas,
     #[error(transparent)]
+    AtlasPackingError(#[from] sprites::PackingError),
+    #[error("Texture atlas of {page_count} pages, more than the {max_page_count} texture layers of the GPU")]
+    TooManyAtlasPages {
+        page_count: u32,
+        max_page_count: u32,
+    },
+    #[error(transparent)]
     WgpuError(#[from] wgpu::Error),
     #[error(transparent)]
     RequestDeviceError(#[from] wgpu::RequestDeviceError),
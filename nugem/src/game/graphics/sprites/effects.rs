@@ -0,0 +1,120 @@
+/// How the colors of a sprite are combined with the colors behind it
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum SpriteBlend {
+    /// Drawn over the colors behind it, with its transparency
+    #[default]
+    Normal,
+    /// Colors of the sprite multiplied by `source` added to the colors behind it multiplied by `destination`
+    Add {
+        source: f32,
+        destination: f32,
+    },
+    /// Colors of the sprite subtracted from the colors behind it
+    Subtract,
+}
+
+/// Transform of the colors of a sprite before blending, as applied by the PalFX controllers: the colors are inverted,
+/// desaturated, then added to and multiplied
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorEffect {
+    /// Added to the red, green and blue components, from -1 to 1
+    pub add: [f32; 3],
+    /// Multipliers of the red, green and blue components after the addition
+    pub multiply: [f32; 3],
+    /// Saturation: 1 for the colors of the sprite, 0 for grayscale
+    pub color: f32,
+    /// Invert the colors
+    pub invert: bool,
+}
+
+impl ColorEffect {
+    /// Effect keeping the colors unchanged
+    pub const IDENTITY: ColorEffect = ColorEffect {
+        add: [0.; 3],
+        multiply: [1.; 3],
+        color: 1.,
+        invert: false,
+    };
+
+    /// Transformed color, from the red, green and blue components between 0 and 1 of an sRGB color.
+    ///
+    /// Reference of the transform of the sprite shader.
+    pub fn apply(&self, color: [f32; 3]) -> [f32; 3] {
+        let color = if self.invert { color.map(|component| 1. - component) } else { color };
+        let gray = color[0] * GRAY_WEIGHTS[0] + color[1] * GRAY_WEIGHTS[1] + color[2] * GRAY_WEIGHTS[2];
+        [0, 1, 2].map(|i| {
+            let saturated = gray + (color[i] - gray) * self.color;
+            ((saturated + self.add[i]) * self.multiply[i]).clamp(0., 1.)
+        })
+    }
+}
+
+impl Default for ColorEffect {
+    fn default() -> Self {
+        ColorEffect::IDENTITY
+    }
+}
+
+/// Contribution of the red, green and blue components to the gray of a color
+const GRAY_WEIGHTS: [f32; 3] = [0.299, 0.587, 0.114];
+
+/// Color of a pixel after drawing a color with an alpha over a color, with the components between 0 and 1.
+///
+/// Reference of the blending of the sprite pipelines, which blend in the color space of the render target.
+pub fn blend_color(blend: SpriteBlend, source: [f32; 4], destination: [f32; 3]) -> [f32; 3] {
+    let alpha = source[3];
+    [0, 1, 2].map(|i| {
+        let blended = match blend {
+            SpriteBlend::Normal => source[i] * alpha + destination[i] * (1. - alpha),
+            SpriteBlend::Add { source: source_factor, destination: destination_factor } => source[i] * alpha * source_factor + destination[i] * destination_factor,
+            SpriteBlend::Subtract => destination[i] - source[i] * alpha,
+        };
+        blended.clamp(0., 1.)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_color(expected: [f32; 3], color: [f32; 3]) {
+        assert!(expected.iter().zip(color.iter()).all(|(expected, component)| (expected - component).abs() < 1e-4), "{expected:?} != {color:?}");
+    }
+
+    #[test]
+    fn color_effects() {
+        let color = [0.2, 0.4, 0.8];
+        assert_color(color, ColorEffect::IDENTITY.apply(color));
+        let inverted = ColorEffect { invert: true, ..ColorEffect::IDENTITY };
+        assert_color([0.8, 0.6, 0.2], inverted.apply(color));
+        let grayscale = ColorEffect { color: 0., ..ColorEffect::IDENTITY };
+        let gray = 0.2 * 0.299 + 0.4 * 0.587 + 0.8 * 0.114;
+        assert_color([gray; 3], grayscale.apply(color));
+        // added, then multiplied, then clamped
+        let tinted = ColorEffect { add: [0.3, -0.5, 0.], multiply: [2., 1., 0.5], ..ColorEffect::IDENTITY };
+        assert_color([1., 0., 0.4], tinted.apply(color));
+        // half desaturated, from the inverted color
+        let all = ColorEffect { add: [0.1; 3], multiply: [1.; 3], color: 0.5, invert: true };
+        let inverted_gray = 0.8 * 0.299 + 0.6 * 0.587 + 0.2 * 0.114;
+        assert_color([0.8, 0.6, 0.2].map(|c| (inverted_gray + c) / 2. + 0.1), all.apply(color));
+    }
+
+    #[test]
+    fn blended_colors() {
+        let source = [0.5, 0.25, 1., 1.];
+        let destination = [0.5, 0.5, 0.5];
+        assert_color([0.5, 0.25, 1.], blend_color(SpriteBlend::Normal, source, destination));
+        assert_color([0.5, 0.375, 0.75], blend_color(SpriteBlend::Normal, [0.5, 0.25, 1., 0.5], destination));
+        // A
+        assert_color([1., 0.75, 1.], blend_color(SpriteBlend::Add { source: 1., destination: 1. }, source, destination));
+        // A1
+        assert_color([0.75, 0.5, 1.], blend_color(SpriteBlend::Add { source: 1., destination: 0.5 }, source, destination));
+        // AS128D128
+        assert_color([0.5, 0.375, 0.75], blend_color(SpriteBlend::Add { source: 0.5, destination: 0.5 }, source, destination));
+        assert_color([0., 0.25, 0.], blend_color(SpriteBlend::Subtract, source, destination));
+        // transparent pixels leave the colors behind unchanged
+        for blend in [SpriteBlend::Normal, SpriteBlend::Add { source: 1., destination: 1. }, SpriteBlend::Subtract] {
+            assert_color(destination, blend_color(blend, [1., 1., 1., 0.], destination));
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use super::{ColorEffect, SpriteBlend};
+
+/// Sprite of the texture atlas drawn on the screen, transformed around its axis
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteInstance {
+    /// Index of the sprite in the texture atlas
+    pub atlas_index: usize,
+    /// Screen position of the axis of the sprite: x, y from the top left corner of the screen, the y axis going down
+    pub position: (f32, f32),
+    /// Axis of the sprite: x, y from the top left corner of its image
+    pub axis: (f32, f32),
+    /// Horizontal and vertical mirroring around the axis
+    pub flip: (bool, bool),
+    /// Horizontal and vertical scale around the axis
+    pub scale: (f32, f32),
+    /// Rotation around the axis in degrees, counterclockwise on the screen
+    pub angle: f32,
+    /// Drawing order: the sprites with a higher priority are drawn over the others, the sprites with the same
+    /// priority being drawn in stack order
+    pub priority: i32,
+    /// Combination of the colors of the sprite with the colors behind it
+    pub blend: SpriteBlend,
+    /// Transform of the colors of the sprite before blending
+    pub color_effect: ColorEffect,
+    /// Part of the image drawn, in fractions of its size: left, top, right, bottom
+    pub crop: (f32, f32, f32, f32),
+}
+
+impl SpriteInstance {
+    /// Crop drawing the whole image
+    pub const UNCROPPED: (f32, f32, f32, f32) = (0., 0., 1., 1.);
+    /// Sprite with its axis at its top left corner, without transform
+    pub fn new(atlas_index: usize, position: (f32, f32)) -> SpriteInstance {
+        SpriteInstance {
+            atlas_index,
+            position,
+            axis: (0., 0.),
+            flip: (false, false),
+            scale: (1., 1.),
+            angle: 0.,
+            priority: 0,
+            blend: SpriteBlend::Normal,
+            color_effect: ColorEffect::IDENTITY,
+            crop: SpriteInstance::UNCROPPED,
+        }
+    }
+    /// Screen positions of the corners of the cropped image of the sprite, of the given size in pixels:
+    /// top left, bottom left, top right, bottom right of the untransformed image
+    pub fn corners(&self, (width, height): (u32, u32)) -> [[f32; 2]; 4] {
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        let flip_sign = |flipped: bool| if flipped { -1. } else { 1. };
+        let (x_sign, y_sign) = (flip_sign(self.flip.0), flip_sign(self.flip.1));
+        let corner = |x: f32, y: f32| {
+            // from the axis, mirrored then scaled
+            let x = (x - self.axis.0) * x_sign * self.scale.0;
+            let y = (y - self.axis.1) * y_sign * self.scale.1;
+            // counterclockwise on the screen with the y axis going down
+            let (x, y) = (x * cos + y * sin, y * cos - x * sin);
+            [self.position.0 + x, self.position.1 + y]
+        };
+        let (width, height) = (width as f32, height as f32);
+        let (left, top, right, bottom) = (self.crop.0 * width, self.crop.1 * height, self.crop.2 * width, self.crop.3 * height);
+        [
+            corner(left, top),
+            corner(left, bottom),
+            corner(right, top),
+            corner(right, bottom),
+        ]
+    }
+}
+
+/// Order in which to draw sprites of a stack: by priority, then in stack order
+pub fn draw_order(sprites: &[SpriteInstance]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sprites.len()).collect();
+    // the sort is stable: the sprites with the same priority stay in stack order
+    order.sort_by_key(|&index| sprites[index].priority);
+    order
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_corners(expected: [[f32; 2]; 4], corners: [[f32; 2]; 4]) {
+        for (expected, corner) in expected.iter().zip(corners.iter()) {
+            assert!((expected[0] - corner[0]).abs() < 1e-4 && (expected[1] - corner[1]).abs() < 1e-4, "{expected:?} != {corner:?}");
+        }
+    }
+
+    #[test]
+    fn transformed_corners() {
+        let mut sprite = SpriteInstance::new(0, (100., 50.));
+        sprite.axis = (10., 40.);
+        assert_corners([[90., 10.], [90., 50.], [110., 10.], [110., 50.]], sprite.corners((20, 40)));
+        // mirrored around the axis
+        sprite.flip = (true, false);
+        assert_corners([[110., 10.], [110., 50.], [90., 10.], [90., 50.]], sprite.corners((20, 40)));
+        sprite.flip = (false, true);
+        assert_corners([[90., 90.], [90., 50.], [110., 90.], [110., 50.]], sprite.corners((20, 40)));
+        // scaled from the axis, at fractional and negative positions
+        sprite.flip = (false, false);
+        sprite.scale = (0.5, 2.);
+        sprite.position = (-2.5, 0.25);
+        assert_corners([[-7.5, -79.75], [-7.5, 0.25], [2.5, -79.75], [2.5, 0.25]], sprite.corners((20, 40)));
+        // a quarter turn counterclockwise: the top of the sprite goes to the left
+        let mut sprite = SpriteInstance::new(0, (0., 0.));
+        sprite.axis = (10., 40.);
+        sprite.angle = 90.;
+        assert_corners([[-40., 10.], [0., 10.], [-40., -10.], [0., -10.]], sprite.corners((20, 40)));
+        // the right half of the image
+        let mut sprite = SpriteInstance::new(0, (0., 0.));
+        sprite.crop = (0.5, 0., 1., 1.);
+        assert_corners([[10., 0.], [10., 40.], [20., 0.], [20., 40.]], sprite.corners((20, 40)));
+    }
+
+    #[test]
+    fn priority_order() {
+        let sprites: Vec<SpriteInstance> = [0, 2, -1, 0, 2].iter().map(|&priority| SpriteInstance { priority, ..SpriteInstance::new(0, (0., 0.)) }).collect();
+        assert_eq!(vec![2, 0, 3, 1, 4], draw_order(&sprites));
+    }
+}
@@ -1,7 +1,19 @@
 use super::Error;
 
+mod packing;
+pub use self::packing::*;
+
 mod texture_atlas;
 pub use self::texture_atlas::*;
 
+mod palette_texture;
+pub use self::palette_texture::*;
+
+mod effects;
+pub use self::effects::*;
+
+mod instance;
+pub use self::instance::*;
+
 mod stack;
 pub use self::stack::*;
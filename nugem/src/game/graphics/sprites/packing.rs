@@ -0,0 +1,213 @@
+use thiserror::Error;
+
+/// Position of a rectangle in the pages of an atlas
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedRectangle {
+    pub page: usize,
+    /// Position of the top left corner in the page
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Result of the packing of rectangles in atlas pages
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Packing {
+    /// Position of each rectangle, in the order of the packed sizes
+    pub rectangles: Vec<PackedRectangle>,
+    /// Size of the area used in each page: width, height
+    pub page_sizes: Vec<(u32, u32)>,
+}
+
+impl Packing {
+    /// Size fitting all the pages: width, height
+    pub fn max_page_size(&self) -> (u32, u32) {
+        self.page_sizes.iter().fold((0, 0), |(max_width, max_height), &(width, height)| (max_width.max(width), max_height.max(height)))
+    }
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum PackingError {
+    #[error("Rectangle {index} of size {width}x{height} does not fit in a page of size {page_size}x{page_size}")]
+    RectangleTooLarge {
+        index: usize,
+        width: u32,
+        height: u32,
+        page_size: u32,
+    },
+}
+
+/// Horizontal segment of the top of the area used in a page, everything below it being used
+#[derive(Clone, Copy, Debug)]
+struct SkylineSegment {
+    x: u32,
+    y: u32,
+    width: u32,
+}
+
+/// Page packed with the skyline bottom-left algorithm, the skyline being at the bottom of the used area since the y axis
+/// goes down
+#[derive(Debug)]
+struct SkylinePage {
+    page_size: u32,
+    skyline: Vec<SkylineSegment>,
+    used_size: (u32, u32),
+}
+
+impl SkylinePage {
+    fn new(page_size: u32) -> SkylinePage {
+        SkylinePage {
+            page_size,
+            skyline: vec![SkylineSegment { x: 0, y: 0, width: page_size }],
+            used_size: (0, 0),
+        }
+    }
+    /// Lowest position of a rectangle starting at a skyline segment, if it fits in the page
+    fn fit(&self, segment_index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.skyline[segment_index].x;
+        if x + width > self.page_size {
+            return None;
+        }
+        let mut y = 0;
+        let mut covered_width = 0;
+        for segment in self.skyline[segment_index..].iter() {
+            if covered_width >= width {
+                break;
+            }
+            y = y.max(segment.y);
+            covered_width += segment.width;
+        }
+        (y + height <= self.page_size).then_some(y)
+    }
+    /// Best position of a rectangle in the page: the lowest, then the leftmost
+    fn find_position(&self, width: u32, height: u32) -> Option<(usize, u32, u32)> {
+        (0..self.skyline.len())
+            .filter_map(|segment_index| self.fit(segment_index, width, height).map(|y| (segment_index, self.skyline[segment_index].x, y)))
+            .min_by_key(|&(_, x, y)| (y, x))
+    }
+    /// Place a rectangle at a position found on a skyline segment
+    fn place(&mut self, segment_index: usize, x: u32, y: u32, width: u32, height: u32) {
+        if width == 0 {
+            return;
+        }
+        let new_segment = SkylineSegment { x, y: y + height, width };
+        self.skyline.insert(segment_index, new_segment);
+        // shrink or remove the segments under the new one
+        let right = x + width;
+        let next_index = segment_index + 1;
+        while next_index < self.skyline.len() {
+            let segment = &mut self.skyline[next_index];
+            if segment.x >= right {
+                break;
+            }
+            let segment_right = segment.x + segment.width;
+            if segment_right <= right {
+                self.skyline.remove(next_index);
+            }
+            else {
+                segment.width = segment_right - right;
+                segment.x = right;
+                break;
+            }
+        }
+        // merge the neighbour segments at the same height
+        self.skyline.dedup_by(|segment, previous| {
+            let same_height = segment.y == previous.y;
+            if same_height {
+                previous.width += segment.width;
+            }
+            same_height
+        });
+        self.used_size = (self.used_size.0.max(right), self.used_size.1.max(y + height));
+    }
+}
+
+/// Pack rectangles in square pages of a maximum size, opening new pages when the rectangles do not fit in the previous ones.
+///
+/// Each rectangle is followed by `padding` empty pixels on its right and at its bottom, so that reading the pixels next to
+/// a rectangle does not read the pixels of another one. The tallest rectangles are packed first.
+pub fn pack_rectangles(sizes: &[(u32, u32)], page_size: u32, padding: u32) -> Result<Packing, PackingError> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&index| (std::cmp::Reverse(sizes[index].1), std::cmp::Reverse(sizes[index].0)));
+    let mut pages: Vec<SkylinePage> = Vec::new();
+    let mut rectangles = vec![PackedRectangle { page: 0, x: 0, y: 0 }; sizes.len()];
+    for index in order {
+        let (width, height) = sizes[index];
+        let (padded_width, padded_height) = (width + padding, height + padding);
+        if padded_width > page_size || padded_height > page_size {
+            Err(PackingError::RectangleTooLarge { index, width, height, page_size })?;
+        }
+        let position = pages.iter()
+            .enumerate()
+            .find_map(|(page_index, page)| page.find_position(padded_width, padded_height).map(|position| (page_index, position)));
+        let (page_index, (segment_index, x, y)) = match position {
+            Some(position) => position,
+            None => {
+                let page = SkylinePage::new(page_size);
+                let position = page.find_position(padded_width, padded_height).expect("a rectangle fits in an empty page");
+                pages.push(page);
+                (pages.len() - 1, position)
+            },
+        };
+        pages[page_index].place(segment_index, x, y, padded_width, padded_height);
+        rectangles[index] = PackedRectangle { page: page_index, x, y };
+    }
+    Ok(Packing {
+        rectangles,
+        page_sizes: pages.iter().map(|page| page.used_size).collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn overlap(a: (PackedRectangle, (u32, u32)), b: (PackedRectangle, (u32, u32)), padding: u32) -> bool {
+        let ((a, (a_width, a_height)), (b, (b_width, b_height))) = (a, b);
+        a.page == b.page
+            && a.x < b.x + b_width + padding && b.x < a.x + a_width + padding
+            && a.y < b.y + b_height + padding && b.y < a.y + a_height + padding
+    }
+
+    fn check_packing(sizes: &[(u32, u32)], page_size: u32, padding: u32) -> Packing {
+        let packing = pack_rectangles(sizes, page_size, padding).unwrap();
+        assert_eq!(sizes.len(), packing.rectangles.len());
+        for (index, (&rectangle, &(width, height))) in packing.rectangles.iter().zip(sizes.iter()).enumerate() {
+            // every rectangle is in the used area of its page
+            let (page_width, page_height) = packing.page_sizes[rectangle.page];
+            assert!(rectangle.x + width + padding <= page_width && rectangle.y + height + padding <= page_height, "rectangle {index} out of its page");
+            assert!(page_width <= page_size && page_height <= page_size);
+            for (other_index, (&other, &other_size)) in packing.rectangles.iter().zip(sizes.iter()).enumerate().skip(index + 1) {
+                assert!(!overlap((rectangle, (width, height)), (other, other_size), padding), "rectangles {index} and {other_index} overlap");
+            }
+        }
+        packing
+    }
+
+    #[test]
+    fn pack_without_overlap() {
+        let sizes: Vec<(u32, u32)> = (0..200).map(|i| (1 + (i * 37) % 61, 1 + (i * 53) % 47)).collect();
+        let packing = check_packing(&sizes, 512, 1);
+        assert_eq!(1, packing.page_sizes.len());
+        // the packed area is smaller than stacking the rectangles, and mostly filled
+        let (width, height) = packing.max_page_size();
+        let stacked_area = sizes.iter().map(|size| size.1).sum::<u32>() * sizes.iter().map(|size| size.0).max().unwrap();
+        let padded_area = sizes.iter().map(|size| (size.0 + 1) * (size.1 + 1)).sum::<u32>();
+        assert!(width * height < stacked_area);
+        assert!(width * height < padded_area * 3 / 2, "{width}x{height} for an area of {padded_area}");
+    }
+
+    #[test]
+    fn spill_to_pages() {
+        let sizes = vec![(60, 60); 10];
+        let packing = check_packing(&sizes, 128, 2);
+        // 4 rectangles of 62x62 fit in a page of 128x128
+        assert_eq!(vec![(124, 124), (124, 124), (124, 62)], packing.page_sizes);
+        assert!(pack_rectangles(&[], 128, 1).unwrap().page_sizes.is_empty());
+        check_packing(&[(0, 0), (5, 0), (0, 5), (128, 1)], 129, 1);
+    }
+
+    #[test]
+    fn too_large() {
+        assert_eq!(Err(PackingError::RectangleTooLarge { index: 1, width: 64, height: 10, page_size: 64 }), pack_rectangles(&[(10, 10), (64, 10)], 64, 1));
+    }
+}
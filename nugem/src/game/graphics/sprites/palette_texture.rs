@@ -0,0 +1,92 @@
+use nugem_sff::bitmap::BitmapPixel;
+
+macro_rules! prefixed_label {
+    ($name:ident) => {
+        concat!("sprites::palette_texture::", stringify!($name))
+    };
+}
+
+// sRGB colors converted by the shader after the color effects
+const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Number of colors of a palette, one for each palette index
+pub const PALETTE_COLOR_COUNT: u32 = 256;
+
+/// Palettes applied to the indexed sprites of a texture atlas: one palette of 256 colors per row.
+///
+/// Changing the palette of sprites only uploads the colors of their row.
+#[derive(Debug)]
+pub struct PaletteTexture {
+    rows: u32,
+    texture: wgpu::Texture,
+    texture_view: wgpu::TextureView,
+}
+
+impl PaletteTexture {
+    pub fn new(device: &wgpu::Device, rows: u32) -> PaletteTexture {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: PALETTE_COLOR_COUNT,
+                // a texture needs at least one row
+                height: rows.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TEXTURE_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some(prefixed_label!(palette_texture)),
+        });
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        PaletteTexture {
+            rows,
+            texture,
+            texture_view,
+        }
+    }
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+    pub fn texture_view(&self) -> &wgpu::TextureView {
+        &self.texture_view
+    }
+    /// Upload the colors of the palette of a row
+    pub fn set_palette(&self, queue: &wgpu::Queue, row: u32, colors: &[BitmapPixel]) {
+        if row >= self.rows {
+            log::error!("Palette row {row} out of the {} rows of the palette texture", self.rows);
+            return;
+        }
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: 0, y: row, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &palette_texture_data(colors),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                // rgba so 4 bytes per color
+                bytes_per_row: core::num::NonZeroU32::new(PALETTE_COLOR_COUNT * 4),
+                rows_per_image: core::num::NonZeroU32::new(1),
+            },
+            wgpu::Extent3d {
+                width: PALETTE_COLOR_COUNT,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+/// RGBA bytes of a row of the palette texture, the colors missing from the palette being transparent
+pub fn palette_texture_data(colors: &[BitmapPixel]) -> Vec<u8> {
+    let transparent = BitmapPixel::new(0, 0, 0, 0);
+    colors.iter()
+        .copied()
+        .chain(std::iter::repeat(transparent))
+        .take(PALETTE_COLOR_COUNT as usize)
+        .flat_map(|color| [color.r(), color.g(), color.b(), color.a()])
+        .collect()
+}
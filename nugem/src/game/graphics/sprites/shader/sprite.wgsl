@@ -2,13 +2,32 @@
 
 
 struct VertexInput {
-    @location(0) position: vec2<u32>,
+    // position in pixels from the top left corner of the screen
+    @location(0) position: vec2<f32>,
     @location(1) tex_coords: vec2<f32>,
+    // layer of the atlas texture array
+    @location(2) page: u32,
+    // row of the palette texture for the indexed sprites, -1 for the sprites with their colors
+    @location(3) palette: i32,
+    // color effect: addition, multiplication, saturation, inversion if not 0
+    @location(4) color_add: vec3<f32>,
+    @location(5) color_multiply: vec3<f32>,
+    @location(6) color_saturation: f32,
+    @location(7) color_invert: u32,
+    // multiplier of the colors of the sprite for the additive blending
+    @location(8) blend_source: f32,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) page: u32,
+    @location(2) @interpolate(flat) palette: i32,
+    @location(3) @interpolate(flat) color_add: vec3<f32>,
+    @location(4) @interpolate(flat) color_multiply: vec3<f32>,
+    @location(5) @interpolate(flat) color_saturation: f32,
+    @location(6) @interpolate(flat) color_invert: u32,
+    @location(7) @interpolate(flat) blend_source: f32,
 }
 
 // global surface size storage variable
@@ -22,22 +41,58 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
     var position_2d: vec2<f32>;
-    let surface_size_f32 = vec2<f32>(f32(surface_size.x), f32(surface_size.y)); 
-    position_2d.x = 2.0 * f32(input.position.x) / surface_size_f32.x - 1.0;
-    position_2d.y = 2.0 * f32(input.position.y) / surface_size_f32.y - 1.0;
+    let surface_size_f32 = vec2<f32>(f32(surface_size.x), f32(surface_size.y));
+    position_2d.x = 2.0 * input.position.x / surface_size_f32.x - 1.0;
+    // the y axis goes up in clip space
+    position_2d.y = 1.0 - 2.0 * input.position.y / surface_size_f32.y;
     out.clip_position = vec4<f32>(position_2d, 0.0, 1.0);
     out.tex_coords = input.tex_coords;
+    out.page = input.page;
+    out.palette = input.palette;
+    out.color_add = input.color_add;
+    out.color_multiply = input.color_multiply;
+    out.color_saturation = input.color_saturation;
+    out.color_invert = input.color_invert;
+    out.blend_source = input.blend_source;
     return out;
 }
 
 // Fragment shader: texture
 
+// pages of RGBA bytes of the sprites with their colors, palette index in the red byte for the indexed sprites
 @group(1) @binding(0)
-var sprite_texture: texture_2d<f32>;
+var sprite_texture: texture_2d_array<u32>;
+// 256 sRGB colors per row
 @group(1) @binding(1)
-var sprite_sampler: sampler;
+var palette_texture: texture_2d<f32>;
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+// same as ColorEffect::apply: inverted, desaturated, then added to and multiplied
+fn color_effect(color: vec3<f32>, in: VertexOutput) -> vec3<f32> {
+    let inverted = select(color, vec3<f32>(1.0) - color, in.color_invert != 0u);
+    let gray = dot(inverted, vec3<f32>(0.299, 0.587, 0.114));
+    let saturated = mix(vec3<f32>(gray), inverted, in.color_saturation);
+    return clamp((saturated + in.color_add) * in.color_multiply, vec3<f32>(0.0), vec3<f32>(1.0));
+}
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(sprite_texture, sprite_sampler, in.tex_coords);
+    // pixelated sprites: the nearest texel
+    let texture_size = vec2<f32>(textureDimensions(sprite_texture));
+    let texel_coords = clamp(vec2<i32>(floor(in.tex_coords * texture_size)), vec2<i32>(0), vec2<i32>(texture_size) - 1);
+    let texel = textureLoad(sprite_texture, texel_coords, i32(in.page), 0);
+    var color: vec4<f32>;
+    if (in.palette >= 0) {
+        color = textureLoad(palette_texture, vec2<i32>(i32(texel.r), in.palette), 0);
+    } else {
+        color = vec4<f32>(texel) / 255.0;
+    }
+    let rgb = srgb_to_linear(color_effect(color.rgb, in));
+    // premultiplied alpha, so that the transparent pixels add and subtract nothing
+    return vec4<f32>(rgb * color.a * in.blend_source, color.a);
 }
@@ -1,4 +1,4 @@
-use super::SpriteTextureAtlas;
+use super::{draw_order, PaletteTexture, SpriteBlend, SpriteCanvasInfo, SpriteInstance, SpriteTextureAtlas};
 
 macro_rules! prefixed_label {
     ($name:ident) => {
@@ -9,25 +9,46 @@ macro_rules! prefixed_label {
 const VERTEX_SHADER_ENTRY_POINT: &'static str = "vs_main";
 const FRAGMENT_SHADER_ENTRY_POINT: &'static str = "fs_main";
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-struct AtlasSpriteCanvas {
-    pub index: usize,
-    pub width: u32,
-    pub height: u32,
-    pub x: u32,
-    pub y: u32,
+/// Render pipelines of the blend modes, the shader writing colors with premultiplied alpha: normal, additive with the
+/// destination factor as blend constant, subtractive
+const BLEND_PIPELINES: [(&str, wgpu::BlendState); 3] = [
+    (prefixed_label!(normal_render_pipeline), wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+    (prefixed_label!(add_render_pipeline), wgpu::BlendState {
+        color: wgpu::BlendComponent { src_factor: wgpu::BlendFactor::One, dst_factor: wgpu::BlendFactor::Constant, operation: wgpu::BlendOperation::Add },
+        alpha: KEEP_DESTINATION_ALPHA,
+    }),
+    (prefixed_label!(subtract_render_pipeline), wgpu::BlendState {
+        color: wgpu::BlendComponent { src_factor: wgpu::BlendFactor::One, dst_factor: wgpu::BlendFactor::One, operation: wgpu::BlendOperation::ReverseSubtract },
+        alpha: KEEP_DESTINATION_ALPHA,
+    }),
+];
+
+const KEEP_DESTINATION_ALPHA: wgpu::BlendComponent = wgpu::BlendComponent {
+    src_factor: wgpu::BlendFactor::Zero,
+    dst_factor: wgpu::BlendFactor::One,
+    operation: wgpu::BlendOperation::Add,
+};
+
+/// Index of the render pipeline of a blend mode in `BLEND_PIPELINES`
+fn blend_pipeline_index(blend: SpriteBlend) -> usize {
+    match blend {
+        SpriteBlend::Normal => 0,
+        SpriteBlend::Add { .. } => 1,
+        SpriteBlend::Subtract => 2,
+    }
 }
 
 pub struct SpriteStack {
-    sprite_canvas_stack: Vec<AtlasSpriteCanvas>,
+    sprite_canvas_stack: Vec<SpriteInstance>,
     vertex_buffer: Option<wgpu::Buffer>,
     index_buffer: wgpu::Buffer,
     full_surface_bind_group: wgpu::BindGroup,
     texture_bind_group_layout: wgpu::BindGroupLayout,
     texture_bind_group: Option<wgpu::BindGroup>,
     surface_size_storage_buffer: wgpu::Buffer,
-    pending_changes: Vec<(usize, AtlasSpriteCanvas)>,
-    render_pipeline: wgpu::RenderPipeline,
+    pending_changes: Vec<(usize, SpriteInstance)>,
+    /// Render pipelines by blend mode, in the order of `BLEND_PIPELINES`
+    render_pipelines: Vec<wgpu::RenderPipeline>,
 }
 
 impl SpriteStack {
@@ -78,16 +99,20 @@ impl SpriteStack {
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: wgpu::TextureSampleType::Uint,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
                         binding: 1,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        // This should match the filterable field of the corresponding Texture entry.
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        // the palette colors are loaded without a sampler
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
                         count: None,
                     },
                 ],
@@ -100,8 +125,8 @@ impl SpriteStack {
             push_constant_ranges: &[],
         });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some(prefixed_label!(render_pipeline)),
+        let render_pipelines = BLEND_PIPELINES.iter().map(|&(label, blend_state)| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
             layout: Some(&render_pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &shader,
@@ -113,7 +138,7 @@ impl SpriteStack {
                 entry_point: FRAGMENT_SHADER_ENTRY_POINT,
                 targets: &[Some(wgpu::ColorTargetState {
                     format: render_texture_format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    blend: Some(blend_state),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
@@ -121,7 +146,8 @@ impl SpriteStack {
                 topology: wgpu::PrimitiveTopology::TriangleList,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
+                // the flipped sprites are drawn from their back
+                cull_mode: None,
                 // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
                 polygon_mode: wgpu::PolygonMode::Fill,
                 // Requires Features::DEPTH_CLIP_CONTROL
@@ -136,7 +162,7 @@ impl SpriteStack {
                 alpha_to_coverage_enabled: false,
             },
             multiview: None,
-        });
+        })).collect();
 
         let index_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
@@ -155,21 +181,14 @@ impl SpriteStack {
             texture_bind_group: None,
             surface_size_storage_buffer,
             pending_changes: Vec::new(),
-            render_pipeline,
+            render_pipelines,
         }
     }
 
     /// Push a new sprite on top. Always invalidates the vertex buffer.
-    pub fn push_sprite(&mut self, atlas_index: usize, x: u32, y: u32, width: u32, height: u32) -> usize {
+    pub fn push_sprite(&mut self, sprite: SpriteInstance) -> usize {
         let result = self.sprite_canvas_stack.len();
-        let sprite_canvas = AtlasSpriteCanvas {
-            index: atlas_index,
-            width,
-            height,
-            x,
-            y,
-        };
-        self.sprite_canvas_stack.push(sprite_canvas.clone());
+        self.sprite_canvas_stack.push(sprite);
         self.invalidate_vertex_buffer();
         result
     }
@@ -186,15 +205,8 @@ impl SpriteStack {
         }
     }
     /// Insert a sprite in the sprite stack. Always invalidates the vertex buffer.
-    pub fn insert_sprite(&mut self, sprite_index: usize, atlas_index: usize, x: u32, y: u32, width: u32, height: u32) {
-        let sprite_canvas = AtlasSpriteCanvas {
-            index: atlas_index,
-            width,
-            height,
-            x,
-            y,
-        };
-        self.sprite_canvas_stack.insert(sprite_index, sprite_canvas);
+    pub fn insert_sprite(&mut self, sprite_index: usize, sprite: SpriteInstance) {
+        self.sprite_canvas_stack.insert(sprite_index, sprite);
         self.invalidate_vertex_buffer();
     }
     /// Clear the sprite stack. Never invalidates the vertex buffer.
@@ -207,15 +219,8 @@ impl SpriteStack {
         self.pending_changes.push((index1, self.sprite_canvas_stack[index2].clone()));
     }
     /// Modify a sprite in the sprite stack. Never invalidates the vertex buffer.
-    pub fn update_sprite(&mut self, sprite_index: usize, atlas_index: usize, x: u32, y: u32, width: u32, height: u32) {
-        let canvas = AtlasSpriteCanvas {
-            index: atlas_index,
-            width,
-            height,
-            x,
-            y,
-        };
-        let pending_change = (sprite_index, canvas);
+    pub fn update_sprite(&mut self, sprite_index: usize, sprite: SpriteInstance) {
+        let pending_change = (sprite_index, sprite);
         self.pending_changes.push(pending_change);
     }
 
@@ -228,41 +233,9 @@ impl SpriteStack {
         self.vertex_buffer = None;
     }
 
-    fn sprite_vertice(texture_atlas: &SpriteTextureAtlas, sprite_canvas: &AtlasSpriteCanvas) -> SpriteVertice {
-        // world coordinates are have the y-axis pointing up
-        let position_corners = {
-            let x = sprite_canvas.x;
-            let y = sprite_canvas.y;
-            let width = sprite_canvas.width;
-            let height = sprite_canvas.height;
-            (
-                [x, y + height], // top left
-                [x, y], // bottom left
-                [x + width, y + height], // top right
-                [x + width, y], // bottom right
-            )
-        };
-        // textures coordinates are have the y-axis pointing down
-        let texture_corners = {
-            let sprite_canvas_dimensions = texture_atlas.sprite_canvas_dimensions(sprite_canvas.index).expect("Unable to find sprite in atlas");
-            let atlas_dimensions = texture_atlas.atlas_dimensions();
-            let (atlas_width, atlas_height) = (atlas_dimensions.0 as f32, atlas_dimensions.1 as f32);
-            let (width, height) = (sprite_canvas_dimensions.pixel_size.0 as f32 / atlas_width, sprite_canvas_dimensions.pixel_size.1 as f32/ atlas_height);
-            let vertical_row = sprite_canvas_dimensions.v_start_index as f32/ atlas_height;
-            // texture dimensions are vertically inverted
-            (
-                [0., vertical_row], // top left
-                [0., vertical_row + height], // bottom left
-                [width, vertical_row], // top right
-                [width, vertical_row + height], // bottom right
-            )
-        };
-        [
-            SpriteVertex { position: position_corners.0, sprite_texture_coords: texture_corners.0 }, // top left
-            SpriteVertex { position: position_corners.1, sprite_texture_coords: texture_corners.1 }, // bottom left
-            SpriteVertex { position: position_corners.2, sprite_texture_coords: texture_corners.2 }, // top right
-            SpriteVertex { position: position_corners.3, sprite_texture_coords: texture_corners.3 }, // bottom right
-        ]
+    fn sprite_vertice(texture_atlas: &SpriteTextureAtlas, sprite_canvas: &SpriteInstance) -> SpriteVertice {
+        let sprite_canvas_dimensions = texture_atlas.sprite_canvas_dimensions(sprite_canvas.atlas_index).expect("Unable to find sprite in atlas");
+        sprite_vertice(sprite_canvas, sprite_canvas_dimensions, texture_atlas.atlas_dimensions())
     }
 
     fn sprite_vertice_buffer_data(sprite_vertice: &SpriteVertice) -> &[u8] {
@@ -272,7 +245,7 @@ impl SpriteStack {
     const fn sprite_vertex_buffer_size() -> u64 {
         core::mem::size_of::<SpriteVertice>() as u64
     }
-    fn buffer_write_sprite(sprite_canvas: &AtlasSpriteCanvas, buffer_sprite_index: usize, vertex_buffer: &wgpu::Buffer, texture_atlas: &SpriteTextureAtlas, queue: &wgpu::Queue) {
+    fn buffer_write_sprite(sprite_canvas: &SpriteInstance, buffer_sprite_index: usize, vertex_buffer: &wgpu::Buffer, texture_atlas: &SpriteTextureAtlas, queue: &wgpu::Queue) {
         let sprite_vertice = Self::sprite_vertice(texture_atlas, sprite_canvas);
         let data = Self::sprite_vertice_buffer_data(&sprite_vertice);
         let offset = buffer_sprite_index as u64 * Self::sprite_vertex_buffer_size();
@@ -312,7 +285,7 @@ impl SpriteStack {
         let dimensions_array: [u32; 2] = [width, height];
         queue.write_buffer(&self.surface_size_storage_buffer, 0, bytemuck::cast_slice(dimensions_array.as_slice()));
     }
-    pub fn set_texture_atlas(&mut self, texture_atlas: &SpriteTextureAtlas, device: &wgpu::Device) {
+    pub fn set_texture_atlas(&mut self, texture_atlas: &SpriteTextureAtlas, palette_texture: &PaletteTexture, device: &wgpu::Device) {
         let texture_bind_group = device.create_bind_group(
             &wgpu::BindGroupDescriptor {
                 layout: &self.texture_bind_group_layout,
@@ -323,7 +296,7 @@ impl SpriteStack {
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: wgpu::BindingResource::Sampler(texture_atlas.sampler()),
+                        resource: wgpu::BindingResource::TextureView(palette_texture.texture_view()),
                     }
                 ],
                 label: Some(prefixed_label!(texture_bind_group)),
@@ -371,8 +344,6 @@ impl SpriteStack {
             depth_stencil_attachment: None,
         });
 
-        render_pass.set_pipeline(&self.render_pipeline);
-        
         render_pass.set_bind_group(0, &self.full_surface_bind_group, &[]);
         if let Some(texture_bind_group) = self.texture_bind_group.as_ref() {
             render_pass.set_bind_group(1, texture_bind_group, &[]);
@@ -382,8 +353,19 @@ impl SpriteStack {
         }
         render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
 
-        for sprite_index in 0..self.sprite_canvas_stack.len() as u32 {
-            let base_vertex = sprite_index * Self::sprite_vertice_count();
+        let mut current_pipeline_index = None;
+        for sprite_index in draw_order(&self.sprite_canvas_stack) {
+            let blend = self.sprite_canvas_stack[sprite_index].blend;
+            let pipeline_index = blend_pipeline_index(blend);
+            if current_pipeline_index != Some(pipeline_index) {
+                render_pass.set_pipeline(&self.render_pipelines[pipeline_index]);
+                current_pipeline_index = Some(pipeline_index);
+            }
+            if let SpriteBlend::Add { destination, .. } = blend {
+                let destination = destination as f64;
+                render_pass.set_blend_constant(wgpu::Color { r: destination, g: destination, b: destination, a: 1. });
+            }
+            let base_vertex = sprite_index as u32 * Self::sprite_vertice_count();
             render_pass.draw_indexed(0..Self::sprite_indices_number(), base_vertex as i32, 0..1);
         }
     }
@@ -395,16 +377,72 @@ impl SpriteStack {
     }
 }
 
+
+/// Vertices of a sprite: the transformed corners of its cropped image in screen pixels, and the corners of the cropped
+/// image in the atlas texture
+fn sprite_vertice(sprite: &SpriteInstance, sprite_canvas_dimensions: &SpriteCanvasInfo, atlas_dimensions: (u32, u32)) -> SpriteVertice {
+    let position_corners = sprite.corners(sprite_canvas_dimensions.pixel_size);
+    // textures coordinates are have the y-axis pointing down
+    let texture_corners = {
+        let (atlas_width, atlas_height) = (atlas_dimensions.0 as f32, atlas_dimensions.1 as f32);
+        let (width, height) = (sprite_canvas_dimensions.pixel_size.0 as f32 / atlas_width, sprite_canvas_dimensions.pixel_size.1 as f32/ atlas_height);
+        let x = sprite_canvas_dimensions.position.0 as f32 / atlas_width;
+        let y = sprite_canvas_dimensions.position.1 as f32 / atlas_height;
+        let (left, top) = (x + sprite.crop.0 * width, y + sprite.crop.1 * height);
+        let (right, bottom) = (x + sprite.crop.2 * width, y + sprite.crop.3 * height);
+        [
+            [left, top], // top left
+            [left, bottom], // bottom left
+            [right, top], // top right
+            [right, bottom], // bottom right
+        ]
+    };
+    let page = sprite_canvas_dimensions.page;
+    let palette = sprite_canvas_dimensions.palette.map_or(-1, |palette_row| palette_row as i32);
+    let effect = &sprite.color_effect;
+    let blend_source = match sprite.blend {
+        SpriteBlend::Add { source, .. } => source,
+        SpriteBlend::Normal | SpriteBlend::Subtract => 1.,
+    };
+    [0, 1, 2, 3].map(|corner| SpriteVertex {
+        position: position_corners[corner],
+        sprite_texture_coords: texture_corners[corner],
+        page,
+        palette,
+        color_add: effect.add,
+        color_multiply: effect.multiply,
+        color_saturation: effect.color,
+        color_invert: effect.invert as u32,
+        blend_source,
+    })
+}
+
 #[repr(C)]
 #[derive(Default, Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct SpriteVertex {
-    position: [u32; 2],
+    /// Position on the screen in pixels, from the top left corner
+    position: [f32; 2],
     sprite_texture_coords: [f32; 2],
+    /// Atlas page of the sprite: layer of the atlas texture array
+    page: u32,
+    /// Row of the palette texture of an indexed sprite, -1 for a sprite with its colors
+    palette: i32,
+    /// Color effect of the sprite
+    color_add: [f32; 3],
+    color_multiply: [f32; 3],
+    color_saturation: f32,
+    /// 1 to invert the colors, 0 otherwise
+    color_invert: u32,
+    /// Multiplier of the colors of the sprite, for the additive blending
+    blend_source: f32,
 }
 
 impl SpriteVertex {
     fn buffer_layout<'a>() -> wgpu::VertexBufferLayout<'a> {
-        const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Uint32x2, 1 => Float32x2];
+        const ATTRIBUTES: [wgpu::VertexAttribute; 9] = wgpu::vertex_attr_array![
+            0 => Float32x2, 1 => Float32x2, 2 => Uint32, 3 => Sint32,
+            4 => Float32x3, 5 => Float32x3, 6 => Float32, 7 => Uint32, 8 => Float32,
+        ];
         wgpu::VertexBufferLayout {
             array_stride: core::mem::size_of::<SpriteVertex>() as _,
             step_mode: wgpu::VertexStepMode::Vertex,
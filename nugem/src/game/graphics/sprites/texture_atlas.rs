@@ -1,4 +1,5 @@
-use crate::game::graphics::{surface::BitmapSurface, State};
+use crate::game::graphics::{surface::{BitmapSurface, IndexedSurface}, State};
+use super::{pack_rectangles, PackingError};
 
 macro_rules! prefixed_label {
     ($name:ident) => {
@@ -6,7 +7,13 @@ macro_rules! prefixed_label {
     };
 }
 
-const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+/// The texels are read without filtering: the RGBA bytes of the sprites with their colors, or the palette index in the
+/// red byte for the indexed sprites
+const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Uint;
+
+/// Empty pixels on the right and at the bottom of each sprite, for the pixels at the edges of a sprite not to show its
+/// neighbours
+const ATLAS_PADDING: u32 = 1;
 
 #[derive(Debug)]
 pub struct SpriteTextureAtlas {
@@ -14,7 +21,6 @@ pub struct SpriteTextureAtlas {
     sprites: Vec<SpriteCanvasInfo>,
     texture: wgpu::Texture,
     texture_view: wgpu::TextureView,
-    sampler: wgpu::Sampler,
 }
 
 impl SpriteTextureAtlas {
@@ -24,6 +30,7 @@ impl SpriteTextureAtlas {
     pub fn sprite_canvas_dimensions(&self, sprite_index: usize) -> Option<&SpriteCanvasInfo> {
         self.sprites.get(sprite_index)
     }
+    /// Size of each page of the atlas
     pub fn atlas_dimensions(&self) -> (u32, u32) {
         self.atlas_dimensions
     }
@@ -33,15 +40,57 @@ impl SpriteTextureAtlas {
     pub fn texture_view(&self) -> &wgpu::TextureView {
         &self.texture_view
     }
-    pub fn sampler(&self) -> &wgpu::Sampler {
-        &self.sampler
+}
+
+
+/// Surface of a sprite added to the atlas
+#[derive(Clone, Debug)]
+enum AtlasSurface {
+    Colors(BitmapSurface),
+    /// Palette indices, with the row of the palette texture applied to them
+    Indices(IndexedSurface, u32),
+}
+
+impl AtlasSurface {
+    fn width(&self) -> u32 {
+        match self {
+            AtlasSurface::Colors(surface) => surface.width(),
+            AtlasSurface::Indices(surface, _) => surface.width(),
+        }
+    }
+    fn height(&self) -> u32 {
+        match self {
+            AtlasSurface::Colors(surface) => surface.height(),
+            AtlasSurface::Indices(surface, _) => surface.height(),
+        }
+    }
+    fn palette(&self) -> Option<u32> {
+        match self {
+            AtlasSurface::Colors(_) => None,
+            AtlasSurface::Indices(_, palette) => Some(*palette),
+        }
+    }
+    /// Texels of the surface, 4 bytes per pixel
+    fn texels(&self) -> Vec<u8> {
+        match self {
+            AtlasSurface::Colors(surface) => surface.pixels().iter().flat_map(|pixel| [pixel.r(), pixel.g(), pixel.b(), pixel.a()]).collect(),
+            AtlasSurface::Indices(surface, _) => surface.indices().iter().flat_map(|&index| [index, 0, 0, 0]).collect(),
+        }
     }
 }
 
+/// Texels of the pages of an atlas, one page after the other, and the sprites in them
+#[derive(Debug)]
+struct AtlasCanvas {
+    texels: Vec<u8>,
+    sprites: Vec<SpriteCanvasInfo>,
+    page_size: (u32, u32),
+    page_count: u32,
+}
 
 #[derive(Clone, Debug)]
 pub struct SpriteTextureAtlasBuilder {
-    surfaces: Vec<BitmapSurface>,
+    surfaces: Vec<AtlasSurface>,
 }
 
 impl SpriteTextureAtlasBuilder {
@@ -52,64 +101,60 @@ impl SpriteTextureAtlasBuilder {
     }
     pub fn add_surface(&mut self, surface: BitmapSurface) -> usize {
         let id = self.surfaces.len();
-        self.surfaces.push(surface);
+        self.surfaces.push(AtlasSurface::Colors(surface));
         id
     }
-    fn build_sprites_canvas(&self) -> (Vec<u8>, Vec<SpriteCanvasInfo>, u32, u32) {
-        // find the total width and the max height in the textures
-        let (max_width, total_height) = {
-            let mut max_width = 0u32;
-            let mut total_height= 0u32;
-            for ref surface in self.surfaces.iter() {
-                total_height += surface.height();
-                let width = surface.width();
-                if width > max_width {
-                    max_width = width;
-                }
-            }
-            (max_width, total_height)
-        };
+    /// Add a surface of palette indices, drawn with the palette of a row of the palette texture
+    pub fn add_indexed_surface(&mut self, surface: IndexedSurface, palette_row: u32) -> usize {
+        let id = self.surfaces.len();
+        self.surfaces.push(AtlasSurface::Indices(surface, palette_row));
+        id
+    }
+    /// Pack the surfaces in atlas pages of a maximum size
+    fn build_sprites_canvas(&self, max_page_size: u32) -> Result<AtlasCanvas, PackingError> {
+        let sizes: Vec<(u32, u32)> = self.surfaces.iter().map(|surface| (surface.width(), surface.height())).collect();
+        let packing = pack_rectangles(&sizes, max_page_size, ATLAS_PADDING)?;
+        // all the pages have the same size in the texture array
+        let (page_width, page_height) = packing.max_page_size();
+        let page_count = packing.page_sizes.len() as u32;
+        let page_byte_size = 4 * page_width as usize * page_height as usize;
+        let mut total_rgba = vec![0; page_byte_size * page_count as usize];
         let mut sprites = Vec::new();
-        let mut total_rgba = Vec::with_capacity(4 * max_width as usize * total_height as usize);
-        let mut current_height = 0u32;
-        for surface in self.surfaces.iter() {
+        for (surface, rectangle) in self.surfaces.iter().zip(packing.rectangles.iter()) {
             sprites.push(SpriteCanvasInfo {
-                    v_start_index: current_height,
-                    pixel_size: (surface.width(), surface.height())
-                });
-            {
-                let padding_size = 4 * (max_width - surface.width()) as usize;
-                let mut surface_pixel_iter = surface.pixels().iter().copied();
-                for _ in 0..(surface.height() as usize) {
-                    for _ in 0..(surface.width() as usize) {
-                        let pixel = surface_pixel_iter.next().unwrap();
-                        total_rgba.push(pixel.r());
-                        total_rgba.push(pixel.g());
-                        total_rgba.push(pixel.b());
-                        total_rgba.push(pixel.a());
-                    }
-                    total_rgba.extend(std::iter::repeat(0).take(padding_size));
-                }
+                page: rectangle.page as u32,
+                position: (rectangle.x, rectangle.y),
+                pixel_size: (surface.width(), surface.height()),
+                palette: surface.palette(),
+            });
+            let texels = surface.texels();
+            let line_size = 4 * surface.width() as usize;
+            for line_index in 0..(surface.height() as usize) {
+                let start = rectangle.page * page_byte_size + 4 * ((rectangle.y as usize + line_index) * page_width as usize + rectangle.x as usize);
+                total_rgba[start..start + line_size].copy_from_slice(&texels[line_index * line_size..(line_index + 1) * line_size]);
             }
-            current_height += surface.height();
         }
-        (total_rgba, sprites, max_width, total_height)
+        Ok(AtlasCanvas {
+            texels: total_rgba,
+            sprites,
+            page_size: (page_width, page_height),
+            page_count,
+        })
     }
-    fn build_texture(&self, texture_rgba: &[u8], width: u32, height: u32, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<(wgpu::Texture, wgpu::TextureView, wgpu::Sampler), super::Error> {
-        if texture_rgba.len() == 0 {
+    fn build_texture(&self, texture_rgba: &[u8], (width, height): (u32, u32), page_count: u32, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<(wgpu::Texture, wgpu::TextureView), super::Error> {
+        if texture_rgba.is_empty() {
             Err(super::Error::EmptyAtlas)?;
         }
 
         let texture_size = wgpu::Extent3d {
             width,
             height,
-            depth_or_array_layers: 1,
+            depth_or_array_layers: page_count,
         };
 
         let atlas_texture = device.create_texture(
             &wgpu::TextureDescriptor {
-                // All textures are stored as 3D, we represent our 2D texture
-                // by setting depth to 1.
+                // the pages of the atlas are the layers of a 2D texture array
                 size: texture_size,
                 mip_level_count: 1,
                 sample_count: 1,
@@ -137,43 +182,43 @@ impl SpriteTextureAtlasBuilder {
                 offset: 0,
                 // rgba so 4 bytes per row
                 bytes_per_row: core::num::NonZeroU32::new(texture_size.width * 4 * core::mem::size_of::<u8>() as u32),
-                rows_per_image: core::num::NonZeroU32::new(texture_size.height * core::mem::size_of::<u8>() as u32),
+                rows_per_image: core::num::NonZeroU32::new(texture_size.height),
             },
             texture_size,
         );
 
-        let texture_view = atlas_texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            // pixelated filter modes
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            label: Some(prefixed_label!(sampler)),
+        let texture_view = atlas_texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(prefixed_label!(texture_view)),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
             ..Default::default()
         });
 
-        Ok((atlas_texture, texture_view, sampler))
+        Ok((atlas_texture, texture_view))
     }
     pub fn build(self, state: &State) -> Result<SpriteTextureAtlas, super::Error> {
-        // build the texture mega-surface: all textures aligned vertically
-        let (total_rgba, sprites, max_width, total_height) = self.build_sprites_canvas();
-        let (texture, texture_view, sampler) = self.build_texture(total_rgba.as_slice(), max_width, total_height, state.device(), state.queue())?;
-        Ok(SpriteTextureAtlas { 
-            atlas_dimensions: (max_width, total_height),
-            sprites,
+        // pack the textures in pages, as large as the GPU allows
+        let limits = state.limits();
+        let canvas = self.build_sprites_canvas(limits.max_texture_dimension_2d)?;
+        if canvas.page_count > limits.max_texture_array_layers {
+            Err(super::Error::TooManyAtlasPages { page_count: canvas.page_count, max_page_count: limits.max_texture_array_layers })?;
+        }
+        let (texture, texture_view) = self.build_texture(canvas.texels.as_slice(), canvas.page_size, canvas.page_count, state.device(), state.queue())?;
+        Ok(SpriteTextureAtlas {
+            atlas_dimensions: canvas.page_size,
+            sprites: canvas.sprites,
             texture,
             texture_view,
-            sampler,
         })
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct SpriteCanvasInfo {
-    /// Vertical start index in the mega-surface, from the top; to know the total height, calculate with the next sprite display
-    pub v_start_index: u32,
+    /// Atlas page of the sprite: layer of the texture array
+    pub page: u32,
+    /// Position of the top left corner of the sprite in its page
+    pub position: (u32, u32),
     pub pixel_size: (u32, u32),
+    /// Row of the palette texture applied to the palette indices of an indexed sprite, `None` if the sprite has its colors
+    pub palette: Option<u32>,
 }
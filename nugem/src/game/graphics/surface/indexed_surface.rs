@@ -0,0 +1,88 @@
+use nugem_sff::bitmap::IndexedBitmapRenderer;
+use super::RendererError;
+
+/// Palette indices of a sprite image, the colors being looked up in a palette when drawing it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexedSurface {
+    w: usize,
+    h: usize,
+    indices: Vec<u8>,
+}
+
+impl IndexedSurface {
+    pub fn new(width: u32, height: u32) -> IndexedSurface {
+        let w = width as usize;
+        let h = height as usize;
+        // the index 0 is transparent
+        IndexedSurface {
+            w,
+            h,
+            indices: vec![0; w * h],
+        }
+    }
+    pub fn width(&self) -> u32 {
+        self.w as u32
+    }
+    pub fn height(&self) -> u32 {
+        self.h as u32
+    }
+    pub fn indices(&self) -> &[u8] {
+        &self.indices
+    }
+}
+
+#[derive(Debug)]
+pub struct IndexedSurfaceRenderer {
+    cursor: usize,
+    surface: IndexedSurface,
+}
+
+impl IndexedSurfaceRenderer {
+    pub fn take(self) -> IndexedSurface {
+        self.surface
+    }
+}
+
+impl IndexedBitmapRenderer for IndexedSurfaceRenderer {
+    type Error = RendererError;
+    type Initializer = ();
+    fn initialize_surface(_initializer: Self::Initializer, width: u64, height: u64) -> Result<Self, Self::Error> {
+        let surface = IndexedSurface::new(width as u32, height as u32);
+        Ok(IndexedSurfaceRenderer { cursor: 0, surface })
+    }
+    fn render_indices(&mut self, index: u8, count: u64) -> Result<(), Self::Error> {
+        let end = (self.cursor + count as usize).min(self.surface.indices.len());
+        self.surface.indices[self.cursor..end].fill(index);
+        self.cursor = end;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use nugem_sff::writer::{Compression, IndexedImage, SpriteContent, SpriteModel};
+    use crate::game::graphics::surface::BitmapSurfaceRenderer;
+    use super::*;
+
+    #[test]
+    fn indexed_sprite_colors() {
+        let mut model = SpriteModel::new();
+        model.add_palette(1, 1, vec![[0, 0, 0], [255, 255, 255], [0, 0, 255]]);
+        model.add_palette(1, 2, vec![[0, 0, 0], [255, 0, 0], [0, 255, 0]]);
+        let pixels = vec![0, 1, 1, 2, 2, 2, 0, 1];
+        model.add_sprite(0, 0, (0, 0), SpriteContent::Image(IndexedImage { width: 4, height: 2, pixels: pixels.clone() }), 0);
+        let mut data = Vec::new();
+        model.write_v2(&mut data, Compression::LZ5).unwrap();
+        let sff_data = nugem_sff::SpriteFile::read(Cursor::new(data), []).unwrap();
+        let surface = sff_data.render_sprite_indexed::<IndexedSurfaceRenderer>((), 0, 0).unwrap().surface.take();
+        assert_eq!((4, 2, pixels.as_slice()), (surface.width(), surface.height(), surface.indices()));
+        // looking up the indices in the palette gives the colors of the rendered sprite, for each palette
+        for palette_index in 0..2 {
+            let palette = sff_data.sprite_palette(0, 0, palette_index).unwrap();
+            let colors: Vec<_> = surface.indices().iter().map(|&index| palette[index as usize]).collect();
+            let rendered = sff_data.render_sprite::<BitmapSurfaceRenderer>((), 0, 0, palette_index).unwrap().surface.take();
+            assert_eq!(rendered.pixels(), colors.as_slice());
+        }
+    }
+}
@@ -1,2 +1,5 @@
 mod bitmap_surface;
-pub use self::bitmap_surface::*;
\ No newline at end of file
+pub use self::bitmap_surface::*;
+
+mod indexed_surface;
+pub use self::indexed_surface::*;
@@ -30,7 +30,6 @@ impl Device {
             Device::Gamepad { name, .. } => name.as_str(),
         }
     }
-    #[allow(dead_code)]
     pub fn state(&self) -> &State {
         match self {
             Device::Keyboard { current_state, .. } => current_state,
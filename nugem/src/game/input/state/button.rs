@@ -9,3 +9,7 @@ pub enum Button {
     Start,
     Back,
 }
+
+impl Button {
+    pub const ALL: [Button; 8] = [Button::A, Button::B, Button::C, Button::X, Button::Y, Button::Z, Button::Start, Button::Back];
+}
@@ -63,13 +63,26 @@ impl Directional {
         else {
             match *self {
                 Up => match tested_input { Up | UpBackward | UpForward => true, _ => false },
-                Forward => match tested_input { Forward | UpForward | UpBackward => true, _ => false },
+                Forward => match tested_input { Forward | UpForward | DownForward => true, _ => false },
                 Down => match tested_input { Down | DownBackward | DownForward => true, _ => false },
                 Backward => match tested_input { Backward | UpBackward | DownBackward => true, _ => false },
                 _ => *self == tested_input,
             }
         }
     }
+    /// Same direction with forward and backward swapped
+    pub fn mirrored(&self) -> Self {
+        use Directional::*;
+        match *self {
+            UpForward => UpBackward,
+            Forward => Backward,
+            DownForward => DownBackward,
+            DownBackward => DownForward,
+            Backward => Forward,
+            UpBackward => UpForward,
+            other => other,
+        }
+    }
     pub fn motion(&self, motion: DirectionalMotion) -> Self {
         use DirectionalMotion::*;
         use Directional::*;
@@ -1,4 +1,4 @@
-use super::{AcceptInputState, ButtonState, Directional, PartialState};
+use super::{AcceptInputState, Button, ButtonState, Directional, PartialState};
 
 /// Input state.
 #[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
@@ -28,6 +28,30 @@ impl State {
             back: ButtonState::Up,
         }
     }
+    pub fn button(&self, button: Button) -> ButtonState {
+        match button {
+            Button::A => self.a,
+            Button::B => self.b,
+            Button::C => self.c,
+            Button::X => self.x,
+            Button::Y => self.y,
+            Button::Z => self.z,
+            Button::Start => self.start,
+            Button::Back => self.back,
+        }
+    }
+    pub fn button_mut(&mut self, button: Button) -> &mut ButtonState {
+        match button {
+            Button::A => &mut self.a,
+            Button::B => &mut self.b,
+            Button::C => &mut self.c,
+            Button::X => &mut self.x,
+            Button::Y => &mut self.y,
+            Button::Z => &mut self.z,
+            Button::Start => &mut self.start,
+            Button::Back => &mut self.back,
+        }
+    }
 }
 
 macro_rules! take_input {
@@ -1,9 +1,11 @@
 pub mod graphics;
 
+pub mod audio;
+
 pub mod mugen;
 
 mod config;
-pub use self::config::Config;
+pub use self::config::{AudioOutputConfig, Config};
 
 mod game;
 pub use self::game::Game;
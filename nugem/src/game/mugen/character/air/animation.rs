@@ -1,18 +1,25 @@
-#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
+use std::collections::BTreeMap;
+
+/// Animations of a character, indexed by action number.
+pub type Animations = BTreeMap<u32, Animation>;
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct Animation {
     steps: Vec<AnimationSteps>,
     looping_frame: Option<(usize, usize)>,
 }
 
-#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct AnimationSteps {
     collisions: Vec<CollisionBox>,
     frames: Vec<AnimationFrame>,
 }
 
-#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
+#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
 pub enum CollisionType {
+    /// Clsn2: the boxes that can be hit
     Normal,
+    /// Clsn1: the boxes that hit
     Attack,
 }
 
@@ -22,13 +29,35 @@ pub struct CollisionBox {
     coordinates: [i16; 4],
 }
 
-#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
+/// How a frame is drawn over what is behind it
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    /// Additive blending, with the source and destination factors out of 256: `A` is 256/256, `A1` is 256/128 and
+    /// `AS<n>D<m>` is n/m
+    Add {
+        source: u16,
+        destination: u16,
+    },
+    /// Subtractive blending: `S`
+    Subtract,
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct AnimationFrame {
     pub group: u16,
     pub image: u16,
     pub offset: (i16, i16),
+    /// Duration of the frame, or `None` for a frame displayed forever (`-1` ticks)
     pub ticks: Option<u16>,
+    /// Horizontal and vertical flips
     pub flip: (bool, bool),
+    pub blend: BlendMode,
+    /// Horizontal and vertical scale (MUGEN 1.1)
+    pub scale: (f32, f32),
+    /// Rotation angle in degrees (MUGEN 1.1)
+    pub angle: f32,
 }
 
 impl Animation {
@@ -44,6 +73,32 @@ impl Animation {
     pub fn looping_frame(&self) -> Option<(usize, usize)> {
         self.looping_frame.clone()
     }
+    /// Frames of all the steps, in order
+    pub fn frames(&self) -> impl Iterator<Item = &AnimationFrame> {
+        self.steps.iter().flat_map(AnimationSteps::frames)
+    }
+    /// Number of frames of all the steps
+    pub fn frame_count(&self) -> usize {
+        self.steps.iter().map(|step| step.frames().len()).sum()
+    }
+    /// Frame and collision boxes at an index of `frames()`
+    pub fn frame(&self, index: usize) -> Option<(&AnimationFrame, &[CollisionBox])> {
+        let mut index = index;
+        for step in &self.steps {
+            match step.frames().get(index) {
+                Some(frame) => return Some((frame, step.collisions())),
+                None => index -= step.frames().len(),
+            }
+        }
+        None
+    }
+    /// Index in `frames()` of the frame where the animation starts again after its last frame
+    pub fn loop_start(&self) -> usize {
+        match self.looping_frame {
+            Some((step, frame)) => self.steps.iter().take(step).map(|step| step.frames().len()).sum::<usize>() + frame,
+            None => 0,
+        }
+    }
 }
 
 impl AnimationSteps {
@@ -53,13 +108,32 @@ impl AnimationSteps {
             frames,
         }
     }
-    #[allow(dead_code)]
+    /// Collision boxes of the frames of the step
     pub fn collisions(&self) -> &[CollisionBox] {
         &self.collisions[..]
     }
     pub fn frames(&self) -> &[AnimationFrame] {
         &self.frames[..]
     }
+    pub fn push_frame(&mut self, frame: AnimationFrame) {
+        self.frames.push(frame);
+    }
+}
+
+impl AnimationFrame {
+    /// Frame with no flip, blending, scaling or rotation
+    pub fn new(group: u16, image: u16, offset: (i16, i16), ticks: Option<u16>) -> AnimationFrame {
+        AnimationFrame {
+            group,
+            image,
+            offset,
+            ticks,
+            flip: (false, false),
+            blend: BlendMode::Normal,
+            scale: (1.0, 1.0),
+            angle: 0.0,
+        }
+    }
 }
 
 impl CollisionBox {
@@ -69,4 +143,11 @@ impl CollisionBox {
             coordinates,
         }
     }
+    pub fn collision_type(&self) -> CollisionType {
+        self.collision_type
+    }
+    /// Coordinates of two opposite corners, from the axis of the character facing right: `[x1, y1, x2, y2]`
+    pub fn coordinates(&self) -> [i16; 4] {
+        self.coordinates
+    }
 }
@@ -1,60 +1,78 @@
-use super::{Animation, AnimationFrame};
+use super::{Animation, AnimationFrame, CollisionBox};
 
-#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Debug)]
+/// Playback of an animation.
+///
+/// Elements are the frames of the animation numbered from 1, as in the `AnimElem` trigger. Times are counted in
+/// ticks from the start of the animation, or from the loop start once the animation has looped. Frames with infinite
+/// ticks (`-1`) are held forever and count as 0 ticks in the length of the animation.
+#[derive(Clone, PartialEq, Debug)]
 pub struct Animator {
     animation: Animation,
-    current_step: usize,
+    /// Start time of each frame
+    start_times: Vec<i32>,
+    /// Index of the current frame
     current_frame: usize,
+    /// Ticks elapsed in the current frame
     tick_timer: u16,
+    /// Ticks elapsed since the start of the animation, or since the loop start once it has looped
+    time: i32,
+    looped: bool,
+    /// The animation has reached its end and started again during the last tick
+    loop_completed: bool,
 }
 
 impl Animator {
     pub fn new(animation: Animation) -> Animator {
+        let start_times = animation.frames()
+            .scan(0, |time, frame| {
+                let start_time = *time;
+                *time += frame_length(frame);
+                Some(start_time)
+            })
+            .collect();
         Animator {
             animation,
-            current_step: 0,
+            start_times,
             current_frame: 0,
             tick_timer: 0,
+            time: 0,
+            looped: false,
+            loop_completed: false,
         }
     }
     pub fn reset(&mut self) {
-        self.current_step = 0;
         self.current_frame = 0;
         self.tick_timer = 0;
+        self.time = 0;
+        self.looped = false;
+        self.loop_completed = false;
     }
-    fn current_animation(&self) -> Option<&AnimationFrame> {
-        self.animation.steps().get(self.current_step)
-            .and_then(|step| step.frames().get(self.current_frame))
+    pub fn current_frame(&self) -> Option<&AnimationFrame> {
+        self.animation.frame(self.current_frame).map(|(frame, _)| frame)
     }
+    /// Collision boxes of the current frame
+    pub fn current_collisions(&self) -> &[CollisionBox] {
+        self.animation.frame(self.current_frame).map(|(_, collisions)| collisions).unwrap_or_default()
+    }
+    /// Advance the animation by one tick. Returns true if the displayed frame changed.
     pub fn tick(&mut self) -> bool {
-        if let Some(tick_max) = self.current_animation().and_then(|f| f.ticks) {
-            self.tick_timer += 1;
-            if self.tick_timer >= tick_max {
-                self.tick_timer = 0;
-                self.current_frame += 1;
-                if self.current_frame >= self.animation.steps()[self.current_step].frames().len() {
-                    let (start_step, start_frame) = {
-                        if let Some(looping_frame_info) = self.animation.looping_frame() {
-                            looping_frame_info
-                        }
-                        else {
-                            (0, 0)
-                        }
-                    };
-                    self.current_step += 1;
-                    if self.current_step >= self.animation.steps().len() {
-                        self.current_step = start_step;
-                        self.current_frame = start_frame;
-                    }
-                    else {
-                        self.current_frame = 0;
-                    }
-                }
-                true
-            }
-            else {
-                false
+        self.loop_completed = false;
+        let tick_max = match self.current_frame().and_then(|f| f.ticks) {
+            Some(tick_max) => tick_max,
+            None => return false,
+        };
+        self.tick_timer += 1;
+        self.time += 1;
+        if self.tick_timer >= tick_max {
+            self.tick_timer = 0;
+            self.current_frame += 1;
+            if self.current_frame >= self.start_times.len() {
+                self.current_frame = self.animation.loop_start();
+                self.time = self.start_times.get(self.current_frame).copied().unwrap_or(0);
+                self.looped = true;
+                self.loop_completed = true;
             }
+            true
         }
         else {
             false
@@ -64,7 +82,150 @@ impl Animator {
         &self.animation
     }
     pub fn current_display_info(&self) -> Option<(u16, u16)> {
-        self.current_animation()
+        self.current_frame()
             .map(|frame| (frame.group, frame.image))
     }
+    /// Number of the current element, starting from 1
+    pub fn element(&self) -> usize {
+        self.current_frame + 1
+    }
+    /// Ticks elapsed in the current element
+    pub fn element_time(&self) -> i32 {
+        self.tick_timer as i32
+    }
+    /// Ticks elapsed since an element started, negative if it has not started yet: the `AnimElemTime` trigger
+    pub fn time_since_element(&self, element: usize) -> Option<i32> {
+        let start_time = self.start_times.get(element.checked_sub(1)?)?;
+        Some(self.time - start_time)
+    }
+    /// Number of the element displayed some ticks from now: the `AnimElemNo` trigger
+    pub fn element_at(&self, time_offset: i32) -> Option<usize> {
+        let mut time = self.time + time_offset;
+        if time < 0 {
+            return None;
+        }
+        let length = self.length();
+        let loop_start_time = self.start_times.get(self.animation.loop_start()).copied().unwrap_or(0);
+        if time >= length && length > loop_start_time {
+            time = loop_start_time + (time - length) % (length - loop_start_time);
+        }
+        let index = self.start_times.iter().rposition(|&start_time| start_time <= time)?;
+        Some(index + 1)
+    }
+    /// Total length of the animation in ticks
+    pub fn length(&self) -> i32 {
+        self.animation.frames().map(frame_length).sum()
+    }
+    /// Ticks until the end of the animation: the `AnimTime` trigger.
+    ///
+    /// It is negative while the animation plays, and 0 on the tick where it ends and starts again from its loop start.
+    pub fn animation_time(&self) -> i32 {
+        if self.loop_completed {
+            0
+        }
+        else {
+            self.time - self.length()
+        }
+    }
+    /// Check if the animation has reached its end at least once
+    pub fn has_looped(&self) -> bool {
+        self.looped
+    }
+    /// Jump to the start of an element, numbered from 1. Returns false if the element does not exist.
+    pub fn set_element(&mut self, element: usize) -> bool {
+        match element.checked_sub(1).and_then(|index| self.start_times.get(index).map(|&time| (index, time))) {
+            Some((index, time)) => {
+                self.current_frame = index;
+                self.tick_timer = 0;
+                self.time = time;
+                self.loop_completed = false;
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+/// Ticks of a frame in the length of its animation
+fn frame_length(frame: &AnimationFrame) -> i32 {
+    frame.ticks.map(i32::from).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::AnimationSteps;
+
+    fn animation(ticks: &[Option<u16>], looping_frame: Option<(usize, usize)>) -> Animation {
+        let frames = ticks.iter().enumerate().map(|(image, &ticks)| AnimationFrame::new(0, image as u16, (0, 0), ticks)).collect();
+        Animation::new(vec![AnimationSteps::new(Vec::new(), frames)], looping_frame)
+    }
+
+    #[test]
+    fn hold_infinite_frame() {
+        let mut animator = Animator::new(animation(&[Some(1), None], None));
+        assert!(animator.tick());
+        for _ in 0..100 {
+            assert!(!animator.tick());
+        }
+        assert_eq!(Some((0, 1)), animator.current_display_info());
+        assert_eq!(2, animator.element());
+        assert_eq!(0, animator.animation_time());
+        assert!(!animator.has_looped());
+    }
+
+    #[test]
+    fn elements_and_times() {
+        let mut animator = Animator::new(animation(&[Some(2), Some(3)], None));
+        assert_eq!(5, animator.length());
+        assert_eq!((1, 0, -5), (animator.element(), animator.element_time(), animator.animation_time()));
+        assert_eq!(Some(0), animator.time_since_element(1));
+        assert_eq!(Some(-2), animator.time_since_element(2));
+        assert_eq!(Some(2), animator.element_at(2));
+        animator.tick();
+        animator.tick();
+        assert_eq!((2, 0, -3), (animator.element(), animator.element_time(), animator.animation_time()));
+        assert_eq!(Some(0), animator.time_since_element(2));
+        animator.tick();
+        assert_eq!((2, 1, -2), (animator.element(), animator.element_time(), animator.animation_time()));
+        assert_eq!(None, animator.time_since_element(3));
+        animator.tick();
+        animator.tick();
+        // end of the animation: back to the first element
+        assert_eq!((1, 0, 0), (animator.element(), animator.element_time(), animator.animation_time()));
+        assert!(animator.has_looped());
+        assert_eq!(Some(0), animator.time_since_element(1));
+        animator.tick();
+        assert_eq!(-4, animator.animation_time());
+    }
+
+    #[test]
+    fn loop_start() {
+        let mut animator = Animator::new(animation(&[Some(1), Some(2), Some(2)], Some((0, 1))));
+        for _ in 0..5 {
+            animator.tick();
+        }
+        assert_eq!((2, 0), (animator.element(), animator.animation_time()));
+        animator.tick();
+        assert_eq!((2, -3), (animator.element(), animator.animation_time()));
+        assert_eq!(Some(2), animator.element_at(3));
+        // the first element is not displayed again
+        assert_eq!(Some(2), animator.element_at(4));
+        assert_eq!(Some(3), animator.element_at(5));
+        for _ in 0..3 {
+            animator.tick();
+        }
+        assert_eq!((2, 0), (animator.element(), animator.animation_time()));
+    }
+
+    #[test]
+    fn jump_to_element() {
+        let mut animator = Animator::new(animation(&[Some(2), Some(3), Some(4)], None));
+        assert!(animator.set_element(3));
+        assert_eq!((3, 0, -4), (animator.element(), animator.element_time(), animator.animation_time()));
+        assert_eq!(Some(5), animator.time_since_element(1));
+        assert!(!animator.set_element(0));
+        assert!(!animator.set_element(4));
+        assert_eq!(3, animator.element());
+    }
 }
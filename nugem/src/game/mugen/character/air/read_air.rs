@@ -1,12 +1,11 @@
-use std::collections::HashMap;
-use crate::game::mugen::format::generic_def::{DefLine, Categories};
+use crate::game::mugen::format::generic_def::{DefLine, Categories, Category};
 use std::io::BufReader;
 use regex::Regex;
 use lazy_static::lazy_static;
 use super::*;
 
-pub fn read_air_file<R: std::io::Read>(read: R) -> HashMap<u32, Animation>  {
-    let mut result_map = HashMap::new();
+pub fn read_air_file<R: std::io::Read>(read: R) -> Animations  {
+    let mut result_map = Animations::new();
     for (_, category) in Categories::read_def(BufReader::new(read)) {
         let cat_name = category.name().to_lowercase();
         lazy_static! {
@@ -15,62 +14,261 @@ pub fn read_air_file<R: std::io::Read>(read: R) -> HashMap<u32, Animation>  {
         if let Some(c) = REGEX_BEGIN_ACTION.captures(cat_name.as_str()) {
             if let Some(digits) = c.get(1) {
                 if let Ok(number) = digits.as_str().parse::<u32>() {
-                    let mut line_iterator = category.into_lines().into_iter();
-                    // let mut default_normal_collision = None;
-                    // let mut default_attack_collision = None;
-                    let mut current_animation_frames = Vec::new();
-                    let mut looping_frame = None;
-                    while let Some((_line_number, line)) = line_iterator.next() {
-                        lazy_static! {
-                            static ref REGEX_CLSN_DEFAULT: Regex = Regex::new(r"^Clsn(1|2)Default: ([0-9]+)$").unwrap();
-                            static ref REGEX_CLSN: Regex = Regex::new(r"^Clsn(1|2): ([0-9]+)$").unwrap();
-                            static ref REGEX_CLSN_KEY: Regex = Regex::new(r"^Clsn\[([0-9+])\]$").unwrap();
-                        }
-                        if let DefLine::Simple(line_string) = line {
-                            if let Some(collision_definition) = REGEX_CLSN.captures(line_string.as_str()) {
-                                if collision_definition.get(1).is_some() && collision_definition.get(2).is_some() {
-                                }
-                            }
-                            else {
-                                if let Some(_collision_default) = REGEX_CLSN_DEFAULT.captures(&line_string.as_str()) {
-                                    // TODO
-                                }
-                                else {
-                                    match line_string.as_str() {
-                                        "Loopstart" => {
-                                            // TODO implement different steps
-                                            looping_frame = Some((0, current_animation_frames.len()));
-                                        },
-                                        _ => {
-                                            // try for a frame line
-                                            let strings: Vec<&str> = line_string.split(',').collect();
-                                            if strings.len() >= 5 {
-                                                let group_res = strings[0].trim().parse();
-                                                let image_res = strings[1].trim().parse();
-                                                let offset_x_res = strings[2].trim().parse();
-                                                let offset_y_res = strings[3].trim().parse();
-                                                let ticks_res = strings[4].trim().parse();
-                                                if group_res.is_ok() && image_res.is_ok() && offset_x_res.is_ok() && offset_y_res.is_ok() && ticks_res.is_ok() {
-                                                    current_animation_frames.push(AnimationFrame {
-                                                        group: group_res.unwrap(),
-                                                        image: image_res.unwrap(),
-                                                        offset: (offset_x_res.unwrap(), offset_y_res.unwrap()),
-                                                        ticks: Some(ticks_res.unwrap()),
-                                                        flip: (false, false),
-                                                    });
-                                                }
-                                            }
-                                        },
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    let animation = Animation::new(vec![AnimationSteps::new(Vec::new(), current_animation_frames)], looping_frame);
-                    result_map.insert(number, animation);
+                    result_map.insert(number, read_action(category));
                 }
             }
         }
     }
     result_map
 }
+
+/// Index of the boxes of a collision type in the per-type arrays
+fn collision_index(collision_type: CollisionType) -> usize {
+    match collision_type {
+        CollisionType::Attack => 0,
+        CollisionType::Normal => 1,
+    }
+}
+
+/// Collision boxes being read after a `Clsn1:`, `Clsn2:`, `Clsn1Default:` or `Clsn2Default:` line
+struct CollisionDefinition {
+    collision_type: CollisionType,
+    default: bool,
+    expected_count: usize,
+    boxes: Vec<CollisionBox>,
+}
+
+/// Read the frames of an action.
+///
+/// Frames that share the same collision boxes are grouped in steps. The boxes of a frame are the boxes defined just
+/// before it with `Clsn1:` and `Clsn2:`, or else the last default boxes defined with `Clsn1Default:` and
+/// `Clsn2Default:`, for each collision type.
+fn read_action(category: Category) -> Animation {
+    let mut steps: Vec<AnimationSteps> = Vec::new();
+    let mut default_boxes: [Vec<CollisionBox>; 2] = Default::default();
+    let mut frame_boxes: [Option<Vec<CollisionBox>>; 2] = Default::default();
+    let mut collision_definition: Option<CollisionDefinition> = None;
+    let mut looping_frame = None;
+    let mut loop_start = false;
+    for (line_number, line) in category.into_lines() {
+        lazy_static! {
+            static ref REGEX_CLSN: Regex = Regex::new(r"(?i)^clsn(1|2)(default)?\s*:\s*([0-9]+)$").unwrap();
+            static ref REGEX_CLSN_KEY: Regex = Regex::new(r"(?i)^clsn(1|2)\s*\[\s*[0-9]+\s*\]$").unwrap();
+        }
+        match line {
+            DefLine::KeyValue(key, value) => {
+                let collision_type = REGEX_CLSN_KEY.captures(key.trim()).map(|c| collision_type(&c[1]));
+                match (collision_type, collision_definition.as_mut()) {
+                    (Some(collision_type), Some(definition)) if collision_type == definition.collision_type => {
+                        match read_collision_box(collision_type, &value) {
+                            Some(collision_box) => definition.boxes.push(collision_box),
+                            None => log::error!("Invalid collision box at line {line_number}: {value}"),
+                        }
+                    },
+                    _ => log::error!("Unexpected AIR key at line {line_number}: {key}={value}"),
+                }
+            },
+            DefLine::Simple(line_string) => {
+                let line_string = line_string.trim();
+                if let Some(c) = REGEX_CLSN.captures(line_string) {
+                    end_collision_definition(collision_definition.take(), &mut default_boxes, &mut frame_boxes);
+                    collision_definition = Some(CollisionDefinition {
+                        collision_type: collision_type(&c[1]),
+                        default: c.get(2).is_some(),
+                        expected_count: c[3].parse().unwrap_or(0),
+                        boxes: Vec::new(),
+                    });
+                }
+                else if line_string.eq_ignore_ascii_case("loopstart") {
+                    loop_start = true;
+                }
+                else if let Some(frame) = read_frame(line_number, line_string) {
+                    end_collision_definition(collision_definition.take(), &mut default_boxes, &mut frame_boxes);
+                    let collisions: Vec<CollisionBox> = frame_boxes.iter_mut()
+                        .zip(default_boxes.iter())
+                        .flat_map(|(frame_boxes, default_boxes)| frame_boxes.take().unwrap_or_else(|| default_boxes.clone()))
+                        .collect();
+                    match steps.last_mut() {
+                        Some(step) if step.collisions() == &collisions[..] => step.push_frame(frame),
+                        _ => steps.push(AnimationSteps::new(collisions, vec![frame])),
+                    }
+                    if loop_start {
+                        let step_index = steps.len() - 1;
+                        looping_frame = Some((step_index, steps[step_index].frames().len() - 1));
+                        loop_start = false;
+                    }
+                }
+                else {
+                    log::error!("Invalid AIR line at line {line_number}: {line_string}");
+                }
+            },
+        }
+    }
+    Animation::new(steps, looping_frame)
+}
+
+fn collision_type(clsn_number: &str) -> CollisionType {
+    match clsn_number {
+        "1" => CollisionType::Attack,
+        _ => CollisionType::Normal,
+    }
+}
+
+fn end_collision_definition(definition: Option<CollisionDefinition>, default_boxes: &mut [Vec<CollisionBox>; 2], frame_boxes: &mut [Option<Vec<CollisionBox>>; 2]) {
+    if let Some(definition) = definition {
+        if definition.boxes.len() != definition.expected_count {
+            log::warn!("Expected {0} {1:?} collision boxes, found {2}", definition.expected_count, definition.collision_type, definition.boxes.len());
+        }
+        let index = collision_index(definition.collision_type);
+        if definition.default {
+            default_boxes[index] = definition.boxes;
+        }
+        else {
+            frame_boxes[index] = Some(definition.boxes);
+        }
+    }
+}
+
+/// Read the coordinates of a collision box: `x1, y1, x2, y2`
+fn read_collision_box(collision_type: CollisionType, value: &str) -> Option<CollisionBox> {
+    let coordinates: Vec<i16> = value.split(',').map(|s| s.trim().parse().ok()).collect::<Option<_>>()?;
+    let coordinates: [i16; 4] = coordinates.try_into().ok()?;
+    Some(CollisionBox::new(collision_type, coordinates))
+}
+
+/// Read a frame line: `group, image, x offset, y offset, ticks[, flip[, blend[, x scale, y scale[, angle]]]]`
+///
+/// A tick count of -1 makes the frame last forever. Invalid optional fields are ignored.
+fn read_frame(line_number: u64, line_string: &str) -> Option<AnimationFrame> {
+    let strings: Vec<&str> = line_string.split(',').map(str::trim).collect();
+    if strings.len() < 5 {
+        return None;
+    }
+    let group = strings[0].parse().ok()?;
+    let image = strings[1].parse().ok()?;
+    let offset = (strings[2].parse().ok()?, strings[3].parse().ok()?);
+    let ticks = match strings[4].parse::<i32>().ok()? {
+        ticks if ticks < 0 => None,
+        ticks => Some(u16::try_from(ticks).ok()?),
+    };
+    let mut frame = AnimationFrame::new(group, image, offset, ticks);
+    if let Some(flip_string) = strings.get(5) {
+        match read_flip(flip_string) {
+            Some(flip) => frame.flip = flip,
+            None => log::warn!("Invalid frame flip at line {line_number}: {flip_string}"),
+        }
+    }
+    if let Some(blend_string) = strings.get(6) {
+        match read_blend(blend_string) {
+            Some(blend) => frame.blend = blend,
+            None => log::warn!("Invalid frame blending at line {line_number}: {blend_string}"),
+        }
+    }
+    let float_field = |index: usize, default: f32| match strings.get(index) {
+        Some(s) if !s.is_empty() => s.parse().unwrap_or_else(|_| {
+            log::warn!("Invalid frame value at line {line_number}: {s}");
+            default
+        }),
+        _ => default,
+    };
+    frame.scale = (float_field(7, 1.0), float_field(8, 1.0));
+    frame.angle = float_field(9, 0.0);
+    Some(frame)
+}
+
+/// Read the flip field of a frame: `H`, `V`, `HV` or `VH`
+fn read_flip(flip_string: &str) -> Option<(bool, bool)> {
+    match flip_string.to_uppercase().as_str() {
+        "" => Some((false, false)),
+        "H" => Some((true, false)),
+        "V" => Some((false, true)),
+        "HV" | "VH" => Some((true, true)),
+        _ => None,
+    }
+}
+
+/// Read the blending field of a frame: `A`, `A1`, `S` or `AS<source>D<destination>`
+fn read_blend(blend_string: &str) -> Option<BlendMode> {
+    lazy_static! {
+        static ref REGEX_BLEND_ALPHA: Regex = Regex::new("^AS([0-9]+)D([0-9]+)$").unwrap();
+    }
+    let blend_string = blend_string.to_uppercase();
+    match blend_string.as_str() {
+        "" => Some(BlendMode::Normal),
+        "A" => Some(BlendMode::Add { source: 256, destination: 256 }),
+        "A1" => Some(BlendMode::Add { source: 256, destination: 128 }),
+        "S" => Some(BlendMode::Subtract),
+        _ => {
+            let c = REGEX_BLEND_ALPHA.captures(&blend_string)?;
+            Some(BlendMode::Add {
+                source: c[1].parse().ok()?,
+                destination: c[2].parse().ok()?,
+            })
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_collision_boxes() {
+        let air = b"
+; Standing light punch
+[Begin Action 200]
+Clsn2Default: 2
+ Clsn2[0] = -10,  0, 10,-79
+ Clsn2[1] =  -4,-92,  6,-79
+200,0, 0,0, 3
+Clsn1: 1
+ Clsn1[0] =  17,-65, 59,-54
+Clsn2: 3
+ Clsn2[0] = -10,  0, 10,-79
+ Clsn2[1] =  -4,-92,  6,-79
+ Clsn2[2] =  10,-65, 59,-54
+200,1, 0,0, 4
+200,2, 0,0, 6
+Loopstart
+200,0, 0,0, 5
+";
+        let animations = read_air_file(Cursor::new(air));
+        let animation = &animations[&200];
+        let steps = animation.steps();
+        assert_eq!(3, steps.len());
+        let body_boxes = [
+            CollisionBox::new(CollisionType::Normal, [-10, 0, 10, -79]),
+            CollisionBox::new(CollisionType::Normal, [-4, -92, 6, -79]),
+        ];
+        assert_eq!(&body_boxes[..], steps[0].collisions());
+        assert_eq!(1, steps[0].frames().len());
+        // the attack frame
+        assert_eq!(4, steps[1].collisions().len());
+        assert_eq!(&CollisionBox::new(CollisionType::Attack, [17, -65, 59, -54]), &steps[1].collisions()[0]);
+        assert_eq!(&CollisionBox::new(CollisionType::Normal, [10, -65, 59, -54]), &steps[1].collisions()[3]);
+        assert_eq!(1, steps[1].frames().len());
+        // per-frame boxes only apply to the following frame
+        assert_eq!(&body_boxes[..], steps[2].collisions());
+        assert_eq!(vec![(200, 2), (200, 0)], steps[2].frames().iter().map(|f| (f.group, f.image)).collect::<Vec<_>>());
+        assert_eq!(Some((2, 1)), animation.looping_frame());
+    }
+
+    #[test]
+    fn read_frame_options() {
+        let frame = read_frame(1, "10, 2, -5, 3, -1").unwrap();
+        assert_eq!(AnimationFrame::new(10, 2, (-5, 3), None), frame);
+        let frame = read_frame(1, "10,2, 0,0, 4, H, A").unwrap();
+        assert_eq!((true, false), frame.flip);
+        assert_eq!(BlendMode::Add { source: 256, destination: 256 }, frame.blend);
+        let frame = read_frame(1, "10,2, 0,0, 4, vh, as128d64, 1.5, 2, 45").unwrap();
+        assert_eq!((true, true), frame.flip);
+        assert_eq!(BlendMode::Add { source: 128, destination: 64 }, frame.blend);
+        assert_eq!((1.5, 2.0), frame.scale);
+        assert_eq!(45.0, frame.angle);
+        let frame = read_frame(1, "10,2, 0,0, 4, , S").unwrap();
+        assert_eq!((false, false), frame.flip);
+        assert_eq!(BlendMode::Subtract, frame.blend);
+        assert!(read_frame(1, "10,2, 0,0").is_none());
+    }
+}
@@ -1,15 +1,22 @@
 use std::ffi::OsStr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use super::character_info::{self, CharacterInfo};
+use super::constants::{read_constants, Constants};
 use super::command::CommandConfiguration;
-use super::{command, file_reader};
-use crate::game::mugen::character::air::{read_air_file, Animation};
+use super::{command, file_reader, state};
+use super::file_reader::FileReader;
+use crate::game::mugen::character::air::{read_air_file, Animations};
 use crate::game::mugen::format::generic_def::Categories;
-use std::collections::HashMap;
 use character_info::ValidInfo;
 use nugem_sff::v1::Palette;
 use log::error;
 
+/// Keys of the state files in the `[Files]` section of the character definition, by priority
+const STATE_FILE_KEYS: [&str; 10] = ["st", "st1", "st2", "st3", "st4", "st5", "st6", "st7", "st8", "st9"];
+
+/// Key of the common state file in the `[Files]` section of the character definition
+const COMMON_STATE_FILE_KEY: &str = "stcommon";
+
 pub struct Character {
     info: CharacterInfo,
     file_reader: Box<dyn file_reader::FileReader>,
@@ -39,6 +46,10 @@ impl Character {
         self.info["info"]["displayname"].as_str()
     }
 
+    pub fn author_name(&self) -> &str {
+        self.info.get("info").and_then(|info| info.get("author")).map_or("", String::as_str)
+    }
+
     /// Opens a character with a given name and file reader
     pub fn open(name: &OsStr, mut file_reader: Box<dyn file_reader::FileReader>) -> Option<Character> {
         let file_names: Vec<PathBuf> = file_reader.file_names().into_iter().flatten().collect();
@@ -73,7 +84,17 @@ impl Character {
         nugem_sff::SpriteFile::read(sprite_file, external_palettes_files)
     }
 
-    fn read_animations_opt(&mut self) -> Option<HashMap<u32, Animation>> {
+    /// Read the sound file of the character, with no sounds if the character has none
+    pub fn read_sounds(&mut self) -> Result<nugem_snd::SoundFile, nugem_snd::LoadingError> {
+        let Some(sound_file_name) = self.info.get("files").and_then(|f| f.get("sound")).filter(|name| !name.is_empty()) else {
+            return Ok(nugem_snd::SoundFile::default());
+        };
+        let sound_path = self.character_files_path_root.join(sound_file_name);
+        let sound_file = self.file_reader.read_file(&sound_path)?;
+        nugem_snd::SoundFile::read(sound_file)
+    }
+
+    fn read_animations_opt(&mut self) -> Option<Animations> {
         let anim_file_name = self.info.get("files").and_then(|f| f.get("anim"))?;
         let anim_file_path = self.character_files_path_root.join(anim_file_name);
         let anim_file = self.file_reader.read_file(&anim_file_path).ok()?;
@@ -81,12 +102,12 @@ impl Character {
         Some(air_file)
     }
 
-    pub fn read_animations(&mut self) -> HashMap<u32, Animation> {
+    pub fn read_animations(&mut self) -> Animations {
         if let Some(air_file) = self.read_animations_opt() {
             air_file
         } else {
             log::error!("Failed to read animation file for character {0}", self.name());
-            HashMap::default()
+            Animations::default()
         }
     }
 
@@ -124,4 +145,107 @@ impl Character {
         file_reader.read_file(&cmd_file_path)
             .map(|cmd_file| command::read_cmd_file(cmd_file, character_name))
     }
+
+    /// Read the states of the character files `st` and `st1` to `st9`, then the common states of `stcommon`.
+    ///
+    /// The states of the first files override the states of the next ones. Each file is looked up in the character
+    /// folder, then with the common file readers, such as the `data` folder of MUGEN.
+    pub fn read_states(&mut self, common_file_readers: &mut [Box<dyn FileReader>]) -> std::io::Result<state::States>
+    {
+        let (info, file_reader) = (&self.info, &mut self.file_reader);
+        let character_name = Self::name_from_borrowed_info(info);
+        let files = info.get("files").ok_or_else(|| std::io::Error::other("Missing files section"))?;
+        let mut states = state::States::new();
+        let mut state_file_found = false;
+        for key in STATE_FILE_KEYS.into_iter().chain([COMMON_STATE_FILE_KEY]) {
+            if let Some(file_name) = files.get(key) {
+                let character_file_path = self.character_files_path_root.join(file_name);
+                let file_states = match file_reader.read_file(&character_file_path) {
+                    Ok(file) => Ok(state::read_cns_file(file, character_name)),
+                    Err(e) => read_common_file(file_name, common_file_readers, character_name).ok_or(e),
+                };
+                match file_states {
+                    Ok(file_states) if key == COMMON_STATE_FILE_KEY => state::add_common_states(&mut states, file_states),
+                    Ok(file_states) => state::add_states(&mut states, file_states),
+                    Err(e) => {
+                        log::error!("Failed to read state file {file_name} for character {character_name}: {e}");
+                        continue;
+                    },
+                }
+                state_file_found = true;
+            }
+        }
+        if state_file_found {
+            Ok(states)
+        }
+        else {
+            Err(std::io::Error::other("No state file found"))
+        }
+    }
+
+    pub fn read_constants(&mut self) -> std::io::Result<Constants>
+    {
+        let (info, file_reader) = (&self.info, &mut self.file_reader);
+        let cns_file_name = info.get("files").and_then(|f| f.get("cns")).ok_or_else(|| std::io::Error::other("Missing CNS file path"))?;
+        let cns_file_path = self.character_files_path_root.join(cns_file_name);
+        file_reader.read_file(&cns_file_path)
+            .map(|cns_file| read_constants(Categories::read_def(cns_file)))
+    }
+}
+
+/// Read the states of a file with the first common file reader that has it
+fn read_common_file(file_name: &str, common_file_readers: &mut [Box<dyn FileReader>], character_name: &str) -> Option<state::States> {
+    let file_path = Path::new(file_name);
+    // the common files may be referenced with their folder: `data/common1.cns`
+    let file_paths = [Some(file_path), file_path.file_name().map(Path::new)];
+    common_file_readers.iter_mut().find_map(|common_file_reader| {
+        file_paths.iter().flatten().find_map(|path| {
+            common_file_reader.read_file(path).ok().map(|file| state::read_cns_file(file, character_name))
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::file_reader::ReadSeek;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    struct FileReaderMemory(HashMap<PathBuf, &'static [u8]>);
+
+    impl FileReader for FileReaderMemory {
+        fn read_file<'a>(&'a mut self, path: &Path) -> std::io::Result<Box<dyn ReadSeek + 'a>> {
+            self.0.get(path)
+                .map(|&data| Box::new(Cursor::new(data)) as Box<dyn ReadSeek>)
+                .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))
+        }
+        fn file_names<'a>(&'a mut self) -> std::io::Result<Box<dyn Iterator<Item = PathBuf> + 'a>> {
+            Ok(Box::new(self.0.keys().cloned()))
+        }
+    }
+
+    fn file_reader(files: &[(&str, &'static [u8])]) -> Box<dyn FileReader> {
+        Box::new(FileReaderMemory(files.iter().map(|&(name, data)| (PathBuf::from(name), data)).collect()))
+    }
+
+    #[test]
+    fn read_state_files() {
+        let character_files = file_reader(&[
+            ("test.def", b"[Info]\nname = test\ndisplayname = Test\n[Files]\nsprite = test.sff\nst = test.cns\nst1 = more.cns\nstcommon = common1.cns\n"),
+            ("test.cns", b"[Statedef 0]\ntype = S\n[Statedef 20]\ntype = S\n"),
+            ("more.cns", b"[Statedef 20]\ntype = C\n[Statedef 30]\ntype = C\n"),
+        ]);
+        let mut common_file_readers = vec![
+            file_reader(&[("other.cns", b"[Statedef 50]\ntype = A\n")]),
+            file_reader(&[("common1.cns", b"[Statedef 0]\ntype = A\n[Statedef 40]\ntype = A\n")]),
+        ];
+        let mut character = Character::open(OsStr::new("test"), character_files).unwrap();
+        let states = character.read_states(&mut common_file_readers).unwrap();
+        assert_eq!(vec![0, 20, 30, 40], states.keys().copied().collect::<Vec<_>>());
+        // the character states override the next state files and the common states
+        assert_eq!(state::StateType::Standing, states[&0].state_type);
+        assert_eq!(state::StateType::Standing, states[&20].state_type);
+        assert_eq!(state::StateType::Air, states[&40].state_type);
+    }
 }
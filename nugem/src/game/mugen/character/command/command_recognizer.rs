@@ -0,0 +1,338 @@
+use std::collections::VecDeque;
+use crate::game::input::{self, Button, ButtonState};
+use crate::game::mugen::character::state::Facing;
+use super::{ButtonRemap, Command, CommandConfiguration, CommandInputState, InputModifier, InputSymbol, RelativeInput};
+
+/// Recognizes the commands of a character from the input history of its player.
+#[derive(Clone, Debug)]
+pub struct CommandRecognizer {
+    relative_input: RelativeInput,
+    /// Input states of the last ticks, remapped and relative to the facing of the player at the time of the input,
+    /// the state of the current tick being the last one
+    history: VecDeque<input::State>,
+    history_length: usize,
+    /// Remaining number of ticks during which each command is active, by index in the command configuration
+    buffer_timers: Vec<u16>,
+    active_commands: Vec<String>,
+}
+
+impl CommandRecognizer {
+    pub fn new(configuration: &CommandConfiguration, facing: Facing) -> CommandRecognizer {
+        // the history must cover the longest command time, plus the longest hold time before a release
+        let longest_time = configuration.commands.iter()
+            .map(|command| command_time(command, configuration))
+            .max()
+            .unwrap_or(0);
+        let longest_hold_time = configuration.commands.iter()
+            .flat_map(|command| command.input.inputs())
+            .filter_map(|input| match input.modifier {
+                InputModifier::Release { time } => time,
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        let history_length = longest_time as usize + longest_hold_time as usize + 2;
+        let mut history = VecDeque::with_capacity(history_length);
+        history.push_back(input::State::new());
+        CommandRecognizer {
+            relative_input: RelativeInput::new(facing),
+            history,
+            history_length,
+            buffer_timers: vec![0; configuration.commands.len()],
+            active_commands: Vec::new(),
+        }
+    }
+
+    /// Add the physical input state of a new tick, and update the active commands.
+    ///
+    /// The inputs are interpreted with the facing of the player if it is allowed to turn, or else with its last facing
+    /// when it was.
+    pub fn tick(&mut self, configuration: &CommandConfiguration, input_state: &input::State, facing: Facing, can_turn: bool) {
+        self.relative_input.update_facing(facing, can_turn);
+        if self.history.len() >= self.history_length {
+            self.history.pop_front();
+        }
+        self.history.push_back(remapped(&self.relative_input.translate(input_state), &configuration.remap));
+        self.active_commands.clear();
+        for (command, buffer_timer) in configuration.commands.iter().zip(self.buffer_timers.iter_mut()) {
+            let inputs = command.input.inputs();
+            let current_tick = self.history.len() - 1;
+            let earliest_tick = current_tick.saturating_sub(command_time(command, configuration) as usize);
+            let completed = match inputs.last() {
+                Some(last_input) => input_at(&self.history, last_input, current_tick) && previous_inputs_match(&self.history, inputs, current_tick, earliest_tick),
+                None => false,
+            };
+            if completed {
+                *buffer_timer = command.buffer_time.unwrap_or(configuration.default_buffer_time).max(1);
+            }
+            if *buffer_timer > 0 {
+                *buffer_timer -= 1;
+                if !self.active_commands.contains(&command.name) {
+                    self.active_commands.push(command.name.clone());
+                }
+            }
+        }
+    }
+
+    /// Names of the commands active at the current tick
+    pub fn active_commands(&self) -> &[String] {
+        &self.active_commands[..]
+    }
+}
+
+fn command_time(command: &Command, configuration: &CommandConfiguration) -> u16 {
+    command.time.unwrap_or(configuration.default_time).max(1)
+}
+
+/// Apply the button remapping of the character to an input state
+fn remapped(input_state: &input::State, remap: &ButtonRemap) -> input::State {
+    let mut remapped_state = input_state.clone();
+    for button in Button::ALL {
+        *remapped_state.button_mut(button) = ButtonState::Up;
+    }
+    for button in Button::ALL {
+        if input_state.button(button) == ButtonState::Down {
+            if let Some(remapped_button) = remap.remapped(button) {
+                *remapped_state.button_mut(remapped_button) = ButtonState::Down;
+            }
+        }
+    }
+    remapped_state
+}
+
+/// Check if the inputs before `inputs[index]`, matched at `tick`, can be found in the history from `earliest_tick`
+fn previous_inputs_match(history: &VecDeque<input::State>, inputs: &[CommandInputState], tick: usize, earliest_tick: usize) -> bool {
+    let index = inputs.len() - 1;
+    if index == 0 {
+        return true;
+    }
+    let (previous_input, current_input) = (&inputs[index - 1], &inputs[index]);
+    // two successive inputs can happen on the same tick, unless they use the same symbol
+    let latest_tick = match previous_input.symbol == current_input.symbol {
+        true => tick.saturating_sub(1),
+        false => tick,
+    };
+    for candidate_tick in (earliest_tick..=latest_tick).rev() {
+        if current_input.strict && !no_other_input(history, &current_input.symbol, candidate_tick, tick) {
+            // earlier ticks would only add more inputs in between
+            break;
+        }
+        if input_at(history, previous_input, candidate_tick) && previous_inputs_match(history, &inputs[..index], candidate_tick, earliest_tick) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Check that nothing but the given symbol changed between `previous_tick` and `tick`, for strict inputs
+fn no_other_input(history: &VecDeque<input::State>, symbol: &InputSymbol, previous_tick: usize, tick: usize) -> bool {
+    let unchanged = |tick: usize, ignored: Option<&InputSymbol>| {
+        let (before, after) = (&history[tick - 1], &history[tick]);
+        let direction_ignored = matches!(ignored, Some(InputSymbol::Direction(_)));
+        (direction_ignored || before.directional == after.directional)
+            && Button::ALL.iter().all(|&button| {
+                let button_ignored = matches!(ignored, Some(InputSymbol::Buttons(buttons)) if buttons.contains(&button));
+                button_ignored || before.button(button) == after.button(button)
+            })
+    };
+    (previous_tick + 1..tick).all(|between_tick| unchanged(between_tick, None)) && unchanged(tick, Some(symbol))
+}
+
+/// Check if the input is recognized at the given tick of the history
+fn input_at(history: &VecDeque<input::State>, input: &CommandInputState, tick: usize) -> bool {
+    if tick == 0 {
+        // no previous state
+        return false;
+    }
+    let pressed = |tick: usize| symbol_pressed(&input.symbol, input.partial, &history[tick]);
+    match input.modifier {
+        InputModifier::Normal => pressed(tick) && !pressed(tick - 1),
+        InputModifier::HoldDown => pressed(tick),
+        InputModifier::Release { time } => {
+            let hold_time = time.unwrap_or(0) as usize;
+            !pressed(tick) && (1..=hold_time.max(1)).all(|ticks_before| tick >= ticks_before && pressed(tick - ticks_before))
+        },
+    }
+}
+
+fn symbol_pressed(symbol: &InputSymbol, partial: bool, input_state: &input::State) -> bool {
+    match symbol {
+        InputSymbol::Direction(direction) => direction.test_input(input_state.directional, !partial),
+        InputSymbol::Buttons(buttons) => buttons.iter().all(|&button| input_state.button(button) == ButtonState::Down),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use crate::game::input::Directional;
+    use crate::game::mugen::character::command::read_cmd_file;
+
+    const CMD: &[u8] = b"
+[Remap]
+x = y
+y = x
+
+[Defaults]
+command.time = 15
+command.buffer.time = 1
+
+[Command]
+name = \"QCF_x\"
+command = ~D, DF, F, x
+
+[Command]
+name = \"FF\"
+command = F, F
+time = 10
+
+[Command]
+name = \"charge_a\"
+command = ~30a
+
+[Command]
+name = \"holdfwd\"
+command = /$F
+time = 1
+
+[Command]
+name = \"x\"
+command = x
+time = 1
+
+[Command]
+name = \"y\"
+command = y
+time = 1
+buffer.time = 3
+";
+
+    fn input_state(directional: Directional, buttons: &[Button]) -> input::State {
+        let mut input_state = input::State::new();
+        input_state.directional = directional;
+        for &button in buttons {
+            *input_state.button_mut(button) = ButtonState::Down;
+        }
+        input_state
+    }
+
+    /// Feed the inputs of a player facing right, each for a number of ticks, and return the active commands after each tick
+    fn recognize(inputs: &[(Directional, &[Button], usize)]) -> Vec<Vec<String>> {
+        let inputs: Vec<_> = inputs.iter().map(|&(directional, buttons, ticks)| (directional, buttons, ticks, Facing::Right, true)).collect();
+        recognize_facing(&inputs)
+    }
+
+    /// Feed physical inputs with the facing of the player and whether it can turn
+    fn recognize_facing(inputs: &[(Directional, &[Button], usize, Facing, bool)]) -> Vec<Vec<String>> {
+        let configuration = read_cmd_file(Cursor::new(CMD), "test");
+        let mut recognizer = CommandRecognizer::new(&configuration, Facing::Right);
+        let mut active_commands = Vec::new();
+        for &(directional, buttons, ticks, facing, can_turn) in inputs {
+            for _ in 0..ticks {
+                recognizer.tick(&configuration, &input_state(directional, buttons), facing, can_turn);
+                active_commands.push(recognizer.active_commands().to_vec());
+            }
+        }
+        active_commands
+    }
+
+    fn active_at(active_commands: &[Vec<String>], tick: usize, name: &str) -> bool {
+        active_commands[tick].iter().any(|command| command == name)
+    }
+
+    #[test]
+    fn recognize_motion() {
+        use Directional::*;
+        let active_commands = recognize(&[(Neutral, &[], 2), (Down, &[], 3), (DownForward, &[], 2), (Forward, &[], 2), (Forward, &[Button::Y], 2)]);
+        // the Y button is remapped to X
+        assert!(active_at(&active_commands, 9, "QCF_x"));
+        assert!(active_at(&active_commands, 9, "x"));
+        assert!(!active_at(&active_commands, 9, "y"));
+        assert!(!active_at(&active_commands, 8, "QCF_x"));
+        assert!(!active_at(&active_commands, 10, "QCF_x"));
+        // too slow
+        let active_commands = recognize(&[(Down, &[], 3), (DownForward, &[], 10), (Forward, &[], 10), (Forward, &[Button::Y], 1)]);
+        assert!(!active_at(&active_commands, 23, "QCF_x"));
+    }
+
+    #[test]
+    fn recognize_hold_and_release() {
+        use Directional::*;
+        let active_commands = recognize(&[(Neutral, &[], 1), (DownForward, &[], 2), (Neutral, &[], 1)]);
+        assert!(!active_at(&active_commands, 0, "holdfwd"));
+        assert!(active_at(&active_commands, 1, "holdfwd"));
+        assert!(active_at(&active_commands, 2, "holdfwd"));
+        assert!(!active_at(&active_commands, 3, "holdfwd"));
+        let active_commands = recognize(&[(Neutral, &[Button::A], 30), (Neutral, &[], 1)]);
+        assert!(active_at(&active_commands, 30, "charge_a"));
+        let active_commands = recognize(&[(Neutral, &[Button::A], 29), (Neutral, &[], 1)]);
+        assert!(!active_at(&active_commands, 29, "charge_a"));
+    }
+
+    #[test]
+    fn recognize_strict_inputs() {
+        use Directional::*;
+        // F, F is expanded to F, >~F, >F
+        let active_commands = recognize(&[(Neutral, &[], 1), (Forward, &[], 2), (Neutral, &[], 2), (Forward, &[], 1)]);
+        assert!(active_at(&active_commands, 5, "FF"));
+        // another input between the two presses
+        let active_commands = recognize(&[(Neutral, &[], 1), (Forward, &[], 2), (Neutral, &[], 1), (Neutral, &[Button::B], 1), (Forward, &[Button::B], 1)]);
+        assert!(!active_at(&active_commands, 5, "FF"));
+    }
+
+    #[test]
+    fn command_buffer_time() {
+        use Directional::*;
+        // the X button is remapped to Y
+        let active_commands = recognize(&[(Neutral, &[], 1), (Neutral, &[Button::X], 1), (Neutral, &[], 4)]);
+        assert!(!active_at(&active_commands, 0, "y"));
+        assert!(active_at(&active_commands, 1, "y"));
+        assert!(active_at(&active_commands, 3, "y"));
+        assert!(!active_at(&active_commands, 4, "y"));
+    }
+
+    #[test]
+    fn recognize_both_facings() {
+        use Directional::*;
+        use Facing::*;
+        // physical directions: forward is right
+        let qcf_right = [(Down, &[][..], 2), (DownForward, &[][..], 2), (Forward, &[][..], 2), (Forward, &[Button::Y][..], 1)];
+        let qcf_left = [(Down, &[][..], 2), (DownBackward, &[][..], 2), (Backward, &[][..], 2), (Backward, &[Button::Y][..], 1)];
+        for (facing, qcf, mirrored_qcf) in [(Right, qcf_right, qcf_left), (Left, qcf_left, qcf_right)] {
+            let with_facing = |inputs: &[(Directional, &'static [Button], usize)]| -> Vec<_> {
+                inputs.iter().map(|&(directional, buttons, ticks)| (directional, buttons, ticks, facing, true)).collect()
+            };
+            let active_commands = recognize_facing(&with_facing(&qcf));
+            assert!(active_at(&active_commands, 6, "QCF_x"), "facing {facing:?}");
+            let active_commands = recognize_facing(&with_facing(&mirrored_qcf));
+            assert!(!active_at(&active_commands, 6, "QCF_x"), "facing {facing:?}");
+            let active_commands = recognize_facing(&with_facing(&[(Neutral, &[], 1), (DownForward, &[], 1), (Backward, &[], 1)]));
+            let holding_forward = [active_at(&active_commands, 1, "holdfwd"), active_at(&active_commands, 2, "holdfwd")];
+            assert_eq!(facing == Right, holding_forward[0], "facing {facing:?}");
+            assert_eq!(facing == Left, holding_forward[1], "facing {facing:?}");
+        }
+    }
+
+    #[test]
+    fn keep_facing_until_allowed_to_turn() {
+        use Directional::*;
+        use Facing::*;
+        // the player crosses the opponent during the motion while jumping: the facing is kept
+        let active_commands = recognize_facing(&[
+            (Down, &[], 2, Right, true),
+            (DownForward, &[], 2, Right, false),
+            (Forward, &[], 2, Left, false),
+            (Forward, &[Button::Y], 1, Left, false),
+        ]);
+        assert!(active_at(&active_commands, 6, "QCF_x"));
+        // the player turns during the motion
+        let active_commands = recognize_facing(&[
+            (Down, &[], 2, Right, true),
+            (DownForward, &[], 2, Right, true),
+            (Forward, &[], 2, Left, true),
+            (Forward, &[Button::Y], 1, Left, true),
+        ]);
+        assert!(!active_at(&active_commands, 6, "QCF_x"));
+    }
+}
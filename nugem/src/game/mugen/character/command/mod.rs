@@ -1,3 +1,5 @@
+use super::state::States;
+
 mod command_input;
 
 pub use self::command_input::*;
@@ -13,6 +15,12 @@ pub use self::read_cmd::*;
 mod button_remap;
 pub use self::button_remap::*;
 
+mod command_recognizer;
+pub use self::command_recognizer::*;
+
+mod relative_input;
+pub use self::relative_input::*;
+
 #[derive(Debug)]
 pub struct CommandConfiguration {
     pub commands: Vec<Command>,
@@ -21,5 +29,6 @@ pub struct CommandConfiguration {
     pub default_time: u16,
     /// Default value for the "buffer.time" parameter of a Command. Minimum 1, maximum 30.
     pub default_buffer_time: u16,
-
+    /// States of the `[Statedef -1]` section
+    pub states: States,
 }
@@ -3,16 +3,18 @@ use super::{Command, CommandConfiguration, CommandInput};
 use crate::game::mugen::format::generic_def::{DefLine, Categories, Category};
 use std::io::Read;
 use super::{ButtonRemap, command_input_parser::parse_command_input};
+use crate::game::mugen::character::state::{read_state_categories, States};
 
 pub fn read_cmd_file<R: Read>(read: R, character_name: &str) -> CommandConfiguration {
     let mut default_buffer_time = 1;
     let mut default_time = 15;
     let mut commands = Vec::new();
-    let mut reading_statedef = false;
+    let mut state_categories = Vec::new();
     let mut remap = ButtonRemap::default();
     log::info!("Reading CMD file for character {character_name}");
     for (cat_line_number, category) in Categories::read_def(read) {
         let cat_name = category.name().to_lowercase();
+        let reading_statedef = !state_categories.is_empty();
         match (cat_name.as_str(), reading_statedef) {
             ("remap", false) => {
                 remap.read_cmd_remap(category);
@@ -26,19 +28,19 @@ pub fn read_cmd_file<R: Read>(read: R, character_name: &str) -> CommandConfigura
                     Err((name, e)) => log::error!("Error reading command \"{name}\" at line {cat_line_number}: {e}"),
                 }
             },
-            ("statedef -1", false) => reading_statedef = true,
+            ("statedef -1", false) => state_categories.push((cat_line_number, category)),
             (other_category, false) => log::error!("Unknown CMD category at line {cat_line_number}: {other_category}"),
-            (_state_trigger, true) => {
-                // State triggers
-                // TODO
-            },
+            (_state_category, true) => state_categories.push((cat_line_number, category)),
         }
     }
+    let mut states = States::new();
+    read_state_categories(state_categories, &mut states);
     CommandConfiguration {
         commands,
         remap,
         default_buffer_time,
         default_time,
+        states,
     }
 }
 
@@ -108,3 +110,37 @@ fn read_cmd_command<'a>(category: Category) -> Result<Command, (String, String)>
         Err((String::new(), "Missing command name or input".to_owned()))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use crate::game::mugen::character::state::ControllerType;
+    use crate::game::mugen::trigger::Expression;
+
+    #[test]
+    fn read_cmd_states() {
+        let cmd = b"
+[Command]
+name = \"QCF_x\"
+command = ~D, DF, F, x
+
+[Statedef -1]
+
+[State -1, Light Kung Fu Palm]
+type = ChangeState
+value = 1000
+triggerall = command = \"QCF_x\"
+trigger1 = statetype = S
+trigger1 = ctrl
+";
+        let configuration = read_cmd_file(Cursor::new(cmd), "test");
+        assert_eq!(1, configuration.commands.len());
+        let controllers = &configuration.states[&-1].controllers;
+        assert_eq!(1, controllers.len());
+        assert_eq!("Light Kung Fu Palm", controllers[0].label);
+        assert_eq!(ControllerType::ChangeState, controllers[0].controller_type);
+        assert_eq!(vec![Expression::Command("QCF_x".to_owned())], controllers[0].triggers.all);
+        assert_eq!(2, controllers[0].triggers.groups[&1].len());
+    }
+}
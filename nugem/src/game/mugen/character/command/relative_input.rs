@@ -0,0 +1,33 @@
+use crate::game::input;
+use crate::game::mugen::character::state::Facing;
+
+/// Converts input states from physical directions, where forward is right, to directions relative to the facing of a player.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RelativeInput {
+    /// Facing used to interpret the inputs
+    facing: Facing,
+}
+
+impl RelativeInput {
+    pub fn new(facing: Facing) -> RelativeInput {
+        RelativeInput {
+            facing,
+        }
+    }
+    /// Update the facing used to interpret the inputs. As in MUGEN, it only changes when the character is allowed to
+    /// turn: a motion started before jumping over the opponent keeps its direction until the character lands.
+    pub fn update_facing(&mut self, facing: Facing, can_turn: bool) {
+        if can_turn {
+            self.facing = facing;
+        }
+    }
+    pub fn translate(&self, input_state: &input::State) -> input::State {
+        match self.facing {
+            Facing::Right => input_state.clone(),
+            Facing::Left => input::State {
+                directional: input_state.directional.mirrored(),
+                ..input_state.clone()
+            },
+        }
+    }
+}
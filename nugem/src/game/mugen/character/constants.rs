@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use crate::game::mugen::format::generic_def::{Categories, DefLine};
+
+/// Sections of the CNS file holding the constants
+const CONSTANT_CATEGORIES: [&str; 4] = ["data", "size", "velocity", "movement"];
+
+/// Values of the constants missing from the CNS file
+const DEFAULT_CONSTANTS: [(&str, &[f32]); 36] = [
+    ("data.life", &[1000.]),
+    ("data.power", &[3000.]),
+    ("data.attack", &[100.]),
+    ("data.defence", &[100.]),
+    ("data.fall.defence_up", &[50.]),
+    ("data.liedown.time", &[60.]),
+    ("data.airjuggle", &[15.]),
+    ("data.sparkno", &[2.]),
+    ("data.guard.sparkno", &[40.]),
+    ("size.xscale", &[1.]),
+    ("size.yscale", &[1.]),
+    ("size.ground.back", &[15.]),
+    ("size.ground.front", &[16.]),
+    ("size.air.back", &[12.]),
+    ("size.air.front", &[12.]),
+    ("size.height", &[60.]),
+    ("size.attack.dist", &[160.]),
+    ("size.proj.attack.dist", &[90.]),
+    ("size.head.pos", &[-5., -90.]),
+    ("size.mid.pos", &[-5., -60.]),
+    ("velocity.walk.fwd", &[2.4]),
+    ("velocity.walk.back", &[-2.2]),
+    ("velocity.run.fwd", &[4.6, 0.]),
+    ("velocity.run.back", &[-4.5, -3.8]),
+    ("velocity.jump.neu", &[0., -8.4]),
+    ("velocity.jump.back", &[-2.55]),
+    ("velocity.jump.fwd", &[2.5]),
+    ("velocity.runjump.back", &[-2.55, -8.1]),
+    ("velocity.runjump.fwd", &[4., -8.1]),
+    ("velocity.airjump.neu", &[0., -8.1]),
+    ("velocity.airjump.back", &[-2.55]),
+    ("velocity.airjump.fwd", &[2.5]),
+    ("movement.airjump.num", &[1.]),
+    ("movement.yaccel", &[0.44]),
+    ("movement.stand.friction", &[0.85]),
+    ("movement.crouch.friction", &[0.82]),
+];
+
+/// Constants of a character, from the `[Data]`, `[Size]`, `[Velocity]` and `[Movement]` sections of its CNS file.
+///
+/// Constants are named after their section and key, as in the `Const` trigger: `velocity.walk.fwd`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Constants {
+    values: HashMap<String, Vec<f32>>,
+}
+
+impl Default for Constants {
+    fn default() -> Constants {
+        Constants {
+            values: DEFAULT_CONSTANTS.iter().map(|&(name, values)| (name.to_owned(), values.to_vec())).collect(),
+        }
+    }
+}
+
+impl Constants {
+    /// Value of a constant of the `Const` trigger. The components of the vectors are named with a `.x`, `.y` or `.z`
+    /// suffix: `velocity.jump.neu.y`.
+    pub fn get(&self, name: &str) -> Option<f32> {
+        let name = name.to_lowercase();
+        if let Some(values) = self.values.get(&name) {
+            return values.first().copied();
+        }
+        let (vector_name, component) = name.rsplit_once('.')?;
+        let index = match component {
+            "x" => 0,
+            "y" => 1,
+            "z" => 2,
+            _ => None?,
+        };
+        let values = self.values.get(vector_name)?;
+        // missing components of a vector are 0
+        Some(values.get(index).copied().unwrap_or(0.))
+    }
+    fn value(&self, name: &str, index: usize) -> f32 {
+        self.values.get(name).and_then(|values| values.get(index)).copied().unwrap_or(0.)
+    }
+    pub fn life(&self) -> i32 {
+        self.value("data.life", 0) as i32
+    }
+    pub fn power(&self) -> i32 {
+        self.value("data.power", 0) as i32
+    }
+    /// Common spark of the hits of the HitDefs without `sparkno` or `guard.sparkno`
+    pub fn spark_number(&self, guarded: bool) -> i32 {
+        match guarded {
+            true => self.value("data.guard.sparkno", 0) as i32,
+            false => self.value("data.sparkno", 0) as i32,
+        }
+    }
+    /// Velocity of a movement from the `[Velocity]` section, with the x velocity towards the front: `walk.fwd`
+    pub fn velocity(&self, movement: &str) -> (f32, f32) {
+        let name = format!("velocity.{movement}");
+        (self.value(&name, 0), self.value(&name, 1))
+    }
+    /// Vertical acceleration of the gravity
+    pub fn y_acceleration(&self) -> f32 {
+        self.value("movement.yaccel", 0)
+    }
+    /// Factor of the horizontal velocity at each tick when standing
+    pub fn stand_friction(&self) -> f32 {
+        self.value("movement.stand.friction", 0)
+    }
+    /// Factor of the horizontal velocity at each tick when crouching
+    pub fn crouch_friction(&self) -> f32 {
+        self.value("movement.crouch.friction", 0)
+    }
+    /// Width of the character behind its axis on the ground
+    pub fn ground_back(&self) -> f32 {
+        self.value("size.ground.back", 0)
+    }
+    /// Width of the character in front of its axis on the ground
+    pub fn ground_front(&self) -> f32 {
+        self.value("size.ground.front", 0)
+    }
+    /// Default distance in front of the character from which the opponent can guard its attacks
+    pub fn attack_distance(&self) -> f32 {
+        self.value("size.attack.dist", 0)
+    }
+    pub fn height(&self) -> f32 {
+        self.value("size.height", 0)
+    }
+}
+
+/// Read the constants of a CNS file
+pub fn read_constants<R: std::io::Read>(categories: Categories<R>) -> Constants {
+    let mut constants = Constants::default();
+    for (_, category) in categories {
+        let category_name = category.name().to_lowercase();
+        if !CONSTANT_CATEGORIES.contains(&category_name.as_str()) {
+            continue;
+        }
+        for (line_number, line) in category.into_lines() {
+            if let DefLine::KeyValue(key, value) = line {
+                let values: Option<Vec<f32>> = value.split(',').map(|v| v.trim().parse().ok()).collect();
+                match values {
+                    Some(values) => {
+                        constants.values.insert(format!("{category_name}.{0}", key.trim().to_lowercase()), values);
+                    },
+                    None => log::warn!("Invalid constant value at line {line_number}: {key} = {value}"),
+                }
+            }
+        }
+    }
+    constants
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_cns_constants() {
+        let cns = b"
+[Data]
+life = 1200
+[Size]
+ground.front = 20
+[Velocity]
+walk.fwd  = 2.5
+jump.neu = 0,-9
+[Movement]
+yaccel = .5
+[Statedef 0]
+type = S
+";
+        let constants = read_constants(Categories::read_def(Cursor::new(&cns[..])));
+        assert_eq!(1200, constants.life());
+        assert_eq!(20., constants.ground_front());
+        assert_eq!(15., constants.ground_back());
+        assert_eq!((0., -9.), constants.velocity("jump.neu"));
+        assert_eq!(0.5, constants.y_acceleration());
+        assert_eq!(Some(2.5), constants.get("velocity.walk.fwd.x"));
+        assert_eq!(Some(0.), constants.get("velocity.walk.fwd.y"));
+        assert_eq!(Some(-9.), constants.get("Velocity.Jump.Neu.Y"));
+        assert_eq!(Some(0.85), constants.get("movement.stand.friction"));
+        assert_eq!(None, constants.get("velocity.unknown.x"));
+    }
+}
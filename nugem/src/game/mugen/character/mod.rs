@@ -2,7 +2,10 @@ mod character;
 mod character_info;
 pub use self::character::*;
 pub use self::character_info::*;
+mod constants;
+pub use self::constants::*;
 pub mod command;
 pub mod air;
+pub mod state;
 pub mod file_reader;
 pub mod directory_reader;
\ No newline at end of file
@@ -0,0 +1,59 @@
+use crate::game::mugen::trigger::{TriggerContext, Value};
+use super::StateController;
+
+/// Vertical shake of the screen requested by an EnvShake controller
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EnvShake {
+    /// Duration in ticks
+    pub time: i32,
+    /// Angle of the sine wave per tick, in degrees
+    pub frequency: f32,
+    /// Amplitude in pixels, negative to start upwards
+    pub amplitude: f32,
+    /// Angle of the sine wave at the start, in degrees
+    pub phase: f32,
+}
+
+impl EnvShake {
+    /// Vertical offset of the screen after some ticks of the shake
+    pub fn offset(&self, elapsed: i32) -> f32 {
+        if elapsed >= self.time {
+            return 0.;
+        }
+        self.amplitude * (self.phase + self.frequency * elapsed as f32).to_radians().sin()
+    }
+}
+
+/// Read the parameters of an EnvShake controller
+pub fn read_env_shake(controller: &StateController, context: &dyn TriggerContext) -> Option<EnvShake> {
+    let value = |key: &str| controller.expression(key).and_then(|expression| expression.evaluate(context));
+    let frequency = value("freq").map(Value::as_float).unwrap_or(60.).clamp(0., 180.);
+    Some(EnvShake {
+        time: value("time")?.as_int(),
+        frequency,
+        amplitude: value("ampl").map(Value::as_float).unwrap_or(-4.),
+        // the fast shakes start at their highest point
+        phase: value("phase").map(Value::as_float).unwrap_or(if frequency >= 90. { 90. } else { 0. }),
+    })
+}
+
+/// Read the parameters of a ScreenBound controller: whether the player stays on the screen, and whether the camera
+/// follows the player horizontally and vertically
+pub fn read_screen_bound(controller: &StateController, context: &dyn TriggerContext) -> (bool, (bool, bool)) {
+    let flag = |key: &str, index: usize| {
+        controller.expressions(key).get(index).and_then(|expression| expression.evaluate(context)).is_some_and(|flag| flag.as_int() != 0)
+    };
+    (flag("value", 0), (flag("movecamera", 0), flag("movecamera", 1)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shake_offsets() {
+        let shake = EnvShake { time: 4, frequency: 90., amplitude: -4., phase: 90. };
+        let offsets: Vec<i32> = (0..5).map(|elapsed| shake.offset(elapsed).round() as i32).collect();
+        assert_eq!(vec![-4, 0, 4, 0, 0], offsets);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::game::mugen::trigger::Value;
+use super::HitType;
+
+/// Information about the last hit received by a player, read by the `GetHitVar` trigger.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GetHitVars {
+    pub damage: i32,
+    pub guarded: bool,
+    pub ground_type: HitType,
+    pub air_type: HitType,
+    /// Velocity of the hit, with x towards the front of the player
+    pub velocity: (f32, f32),
+    /// Ticks of hit shake given by the hit
+    pub hit_shake_time: i32,
+    /// Ticks remaining before the player recovers from the hit
+    pub hit_time: i32,
+    pub slide_time: i32,
+    pub ctrl_time: i32,
+    pub fall: bool,
+    pub fall_velocity: (Option<f32>, f32),
+    pub fall_recover: bool,
+    /// Ticks remaining before the player can recover from the fall
+    pub fall_recover_time: i32,
+    pub fall_damage: i32,
+    /// Number of hits received in the current combo
+    pub hit_count: i32,
+}
+
+impl Default for GetHitVars {
+    fn default() -> Self {
+        GetHitVars {
+            damage: 0,
+            guarded: false,
+            ground_type: HitType::None,
+            air_type: HitType::None,
+            velocity: (0., 0.),
+            hit_shake_time: 0,
+            hit_time: 0,
+            slide_time: 0,
+            ctrl_time: 0,
+            fall: false,
+            fall_velocity: (None, 0.),
+            fall_recover: true,
+            fall_recover_time: 0,
+            fall_damage: 0,
+            hit_count: 0,
+        }
+    }
+}
+
+impl GetHitVars {
+    /// Value of a `GetHitVar` parameter, by lowercase name
+    pub fn get(&self, name: &str) -> Option<Value> {
+        let hit_type = |hit_type| Value::Int(match hit_type {
+            HitType::None => 0,
+            HitType::High => 1,
+            HitType::Low => 2,
+            HitType::Trip => 3,
+        });
+        let value = match name {
+            "damage" => Value::Int(self.damage),
+            "guarded" => Value::from(self.guarded),
+            "groundtype" => hit_type(self.ground_type),
+            "airtype" => hit_type(self.air_type),
+            "xvel" => Value::Float(self.velocity.0),
+            "yvel" => Value::Float(self.velocity.1),
+            "hitshaketime" => Value::Int(self.hit_shake_time),
+            "hittime" => Value::Int(self.hit_time),
+            "slidetime" => Value::Int(self.slide_time),
+            "ctrltime" => Value::Int(self.ctrl_time),
+            "hitcount" => Value::Int(self.hit_count),
+            "fall" => Value::from(self.fall),
+            "fall.xvel" => Value::Float(self.fall_velocity.0.unwrap_or(self.velocity.0)),
+            "fall.yvel" => Value::Float(self.fall_velocity.1),
+            "fall.recover" => Value::from(self.fall_recover),
+            "fall.recovertime" => Value::Int(self.fall_recover_time),
+            "fall.damage" => Value::Int(self.fall_damage),
+            _ => None?,
+        };
+        Some(value)
+    }
+}
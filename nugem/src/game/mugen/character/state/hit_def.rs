@@ -0,0 +1,284 @@
+use crate::game::mugen::trigger::{TriggerContext, Value};
+use super::{MoveType, PlayerState, StateController, StateType};
+
+/// States a hit can reach, from the `hitflag` and `guardflag` parameters of a HitDef.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct HitFlags {
+    /// `H`: standing
+    pub high: bool,
+    /// `L`: crouching
+    pub low: bool,
+    /// `A`: in the air
+    pub air: bool,
+    /// `F`: falling
+    pub falling: bool,
+    /// `D`: lying down
+    pub down: bool,
+    /// `+`: only players being hit, `-`: only players not being hit
+    pub being_hit: Option<bool>,
+}
+
+impl HitFlags {
+    pub fn from_flags(flags: &str) -> HitFlags {
+        let mut hit_flags = HitFlags::default();
+        for flag in flags.trim().chars() {
+            match flag.to_ascii_uppercase() {
+                'H' => hit_flags.high = true,
+                'L' => hit_flags.low = true,
+                'M' => {
+                    hit_flags.high = true;
+                    hit_flags.low = true;
+                },
+                'A' => hit_flags.air = true,
+                'F' => hit_flags.falling = true,
+                'D' => hit_flags.down = true,
+                '+' => hit_flags.being_hit = Some(true),
+                '-' => hit_flags.being_hit = Some(false),
+                _ => log::warn!("Unknown hit flag {flag} in {flags}"),
+            }
+        }
+        hit_flags
+    }
+    /// Check if the flags allow reaching a player in its current state
+    pub fn reach(&self, player: &PlayerState) -> bool {
+        let being_hit = player.move_type == MoveType::BeingHit;
+        if self.being_hit.map(|required| required != being_hit).unwrap_or(false) {
+            return false;
+        }
+        let falling = being_hit && player.get_hit.fall;
+        match player.state_type {
+            StateType::Standing => self.high,
+            StateType::Crouching => self.low,
+            StateType::Air => self.air || (falling && self.falling),
+            StateType::Lying => self.down,
+            StateType::Unchanged => false,
+        }
+    }
+}
+
+/// Spark displayed when a hit lands: `sparkno = 2` for a common spark, or `sparkno = S2` for a spark of the attacker.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SparkReference {
+    pub own: bool,
+    pub number: i32,
+}
+
+/// Sound played when a hit lands: `hitsound = 5, 2` for a common sound, or `hitsound = S5, 2` for a sound of the attacker.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SoundReference {
+    pub own: bool,
+    pub group: i32,
+    pub number: i32,
+}
+
+/// `ground.type` and `air.type` parameters of a HitDef: the part of the body that is hit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HitType {
+    High,
+    Low,
+    Trip,
+    None,
+}
+
+impl HitType {
+    pub fn from_symbol(symbol: &str) -> Option<HitType> {
+        match symbol.trim().to_lowercase().chars().next() {
+            Some('h') => Some(HitType::High),
+            Some('l') => Some(HitType::Low),
+            Some('t') => Some(HitType::Trip),
+            Some('n') => Some(HitType::None),
+            _ => None,
+        }
+    }
+}
+
+/// Fall parameters of a HitDef
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HitFall {
+    /// The hit makes a player on the ground fall
+    pub fall: bool,
+    /// The hit makes a player in the air fall
+    pub air_fall: bool,
+    /// Horizontal velocity of the fall, or `None` to keep the velocity of the hit
+    pub x_velocity: Option<f32>,
+    pub y_velocity: f32,
+    pub recover: bool,
+    pub recover_time: i32,
+    pub damage: i32,
+}
+
+/// Attack of a player, set by a HitDef controller.
+#[derive(Clone, PartialEq, Debug)]
+pub struct HitDef {
+    /// `attr` parameter: state type and attack class, such as `S, NA`
+    pub attribute: String,
+    pub hit_flags: HitFlags,
+    pub guard_flags: HitFlags,
+    /// Damage when hitting and when guarded
+    pub damage: (i32, i32),
+    /// Pause time of the attacker and hit shake time of the defender when hitting
+    pub pause_time: (i32, i32),
+    /// Pause time of the attacker and hit shake time of the defender when guarded
+    pub guard_pause_time: (i32, i32),
+    /// Distance in front of the attacker from which the defender can guard, the `size.attack.dist` constant of the
+    /// attacker by default
+    pub guard_distance: Option<f32>,
+    pub spark: Option<SparkReference>,
+    pub guard_spark: Option<SparkReference>,
+    /// Position of the spark: x from the axis of the defender towards the attacker, y from the axis of the attacker
+    pub spark_position: (f32, f32),
+    pub hit_sound: Option<SoundReference>,
+    pub guard_sound: Option<SoundReference>,
+    pub ground_type: HitType,
+    pub air_type: HitType,
+    /// Velocity given to the defender hit on the ground, with x towards the front of the defender
+    pub ground_velocity: (f32, f32),
+    /// Velocity given to the defender hit in the air
+    pub air_velocity: (f32, f32),
+    /// Horizontal velocity given to the defender guarding on the ground
+    pub guard_velocity: f32,
+    /// Velocity given to the defender guarding in the air
+    pub air_guard_velocity: (f32, f32),
+    pub ground_hit_time: i32,
+    pub ground_slide_time: i32,
+    pub air_hit_time: i32,
+    pub guard_hit_time: i32,
+    pub guard_slide_time: i32,
+    pub guard_ctrl_time: i32,
+    /// State of the attacker when hitting
+    pub p1_state: Option<i32>,
+    /// State of the defender when hit
+    pub p2_state: Option<i32>,
+    pub fall: HitFall,
+}
+
+/// Read the parameters of a HitDef controller
+pub fn read_hit_def(controller: &StateController, context: &dyn TriggerContext) -> HitDef {
+    let values = |key: &str| -> Vec<Value> {
+        controller.expressions(key).iter().filter_map(|expression| expression.evaluate(context)).collect()
+    };
+    let int = |key: &str, index: usize| values(key).get(index).map(|v| v.as_int());
+    let float = |key: &str, index: usize| values(key).get(index).map(|v| v.as_float());
+    let flags = |key: &str, default: &str| HitFlags::from_flags(controller.parameter(key).unwrap_or(default));
+    let hit_type = |key: &str| controller.parameter(key).and_then(HitType::from_symbol).unwrap_or(HitType::High);
+    let pause_time = (int("pausetime", 0).unwrap_or(0), int("pausetime", 1).unwrap_or(0));
+    let ground_velocity = (float("ground.velocity", 0).unwrap_or(0.), float("ground.velocity", 1).unwrap_or(0.));
+    let air_velocity = (float("air.velocity", 0).unwrap_or(0.), float("air.velocity", 1).unwrap_or(0.));
+    let ground_hit_time = int("ground.hittime", 0).unwrap_or(0);
+    let guard_hit_time = int("guard.hittime", 0).unwrap_or(ground_hit_time);
+    let guard_slide_time = int("guard.slidetime", 0).unwrap_or(guard_hit_time);
+    let fall = int("fall", 0).unwrap_or(0) != 0;
+    HitDef {
+        attribute: controller.parameter("attr").unwrap_or_default().to_owned(),
+        hit_flags: flags("hitflag", "MAF"),
+        guard_flags: flags("guardflag", ""),
+        damage: (int("damage", 0).unwrap_or(0), int("damage", 1).unwrap_or(0)),
+        pause_time,
+        guard_pause_time: (int("guard.pausetime", 0).unwrap_or(pause_time.0), int("guard.pausetime", 1).unwrap_or(pause_time.1)),
+        guard_distance: float("guard.dist", 0),
+        spark: controller.parameter("sparkno").and_then(read_spark),
+        guard_spark: controller.parameter("guard.sparkno").and_then(read_spark),
+        spark_position: (float("sparkxy", 0).unwrap_or(0.), float("sparkxy", 1).unwrap_or(0.)),
+        hit_sound: controller.parameter("hitsound").and_then(read_sound),
+        guard_sound: controller.parameter("guardsound").and_then(read_sound),
+        ground_type: hit_type("ground.type"),
+        air_type: controller.parameter("air.type").and_then(HitType::from_symbol).unwrap_or_else(|| hit_type("ground.type")),
+        ground_velocity,
+        air_velocity,
+        guard_velocity: float("guard.velocity", 0).unwrap_or(ground_velocity.0),
+        air_guard_velocity: (
+            float("airguard.velocity", 0).unwrap_or(air_velocity.0 * 1.5),
+            float("airguard.velocity", 1).unwrap_or(air_velocity.1 / 2.),
+        ),
+        ground_hit_time,
+        ground_slide_time: int("ground.slidetime", 0).unwrap_or(0),
+        air_hit_time: int("air.hittime", 0).unwrap_or(20),
+        guard_hit_time,
+        guard_slide_time,
+        guard_ctrl_time: int("guard.ctrltime", 0).unwrap_or(guard_slide_time),
+        p1_state: int("p1stateno", 0),
+        p2_state: int("p2stateno", 0),
+        fall: HitFall {
+            fall,
+            air_fall: int("air.fall", 0).map(|f| f != 0).unwrap_or(fall),
+            x_velocity: float("fall.xvelocity", 0),
+            y_velocity: float("fall.yvelocity", 0).unwrap_or(-4.5),
+            recover: int("fall.recover", 0).map(|r| r != 0).unwrap_or(true),
+            recover_time: int("fall.recovertime", 0).unwrap_or(4),
+            damage: int("fall.damage", 0).unwrap_or(0),
+        },
+    }
+}
+
+/// Split the `S` prefix of a reference to a spark or a sound of the attacker
+fn split_own_prefix(text: &str) -> (bool, &str) {
+    let text = text.trim();
+    match text.strip_prefix(['s', 'S']) {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    }
+}
+
+fn read_spark(text: &str) -> Option<SparkReference> {
+    let (own, number) = split_own_prefix(text);
+    Some(SparkReference {
+        own,
+        number: number.trim().parse().ok()?,
+    })
+}
+
+fn read_sound(text: &str) -> Option<SoundReference> {
+    let (own, text) = split_own_prefix(text);
+    let (group, number) = text.split_once(',').unwrap_or((text, "0"));
+    Some(SoundReference {
+        own,
+        group: group.trim().parse().ok()?,
+        number: number.trim().parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::mugen::character::state::{read_cns_file, Facing, PlayerContext, TriggerEnvironment};
+    use crate::game::mugen::character::air::Animations;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_hit_def_parameters() {
+        let cns = b"
+[Statedef 200]
+[State 200, hit]
+type = HitDef
+trigger1 = 1
+attr = S, NA
+damage = 23, 2
+hitflag = MAF
+guardflag = MA
+pausetime = 12, 12
+sparkno = s3
+hitsound = 5, 0
+guardsound = S6, 1
+ground.velocity = -4
+air.velocity = -2, -3
+ground.hittime = 10
+fall = 1
+";
+        let states = read_cns_file(Cursor::new(cns), "test");
+        let controller = &states[&200].controllers[0];
+        let player = PlayerState::new(Facing::Right);
+        let animations = Animations::new();
+        let hit_def = read_hit_def(controller, &PlayerContext::new(&player, &animations, TriggerEnvironment::default()));
+        assert_eq!((23, 2), hit_def.damage);
+        assert_eq!((12, 12), hit_def.guard_pause_time);
+        assert!(hit_def.hit_flags.high && hit_def.hit_flags.low && hit_def.hit_flags.air && hit_def.hit_flags.falling);
+        assert!(!hit_def.guard_flags.falling);
+        assert_eq!(Some(SparkReference { own: true, number: 3 }), hit_def.spark);
+        assert_eq!(Some(SoundReference { own: false, group: 5, number: 0 }), hit_def.hit_sound);
+        assert_eq!(Some(SoundReference { own: true, group: 6, number: 1 }), hit_def.guard_sound);
+        assert_eq!((-4., 0.), hit_def.ground_velocity);
+        assert_eq!(-4., hit_def.guard_velocity);
+        assert_eq!((10, 10, 10), (hit_def.guard_hit_time, hit_def.guard_slide_time, hit_def.guard_ctrl_time));
+        assert!(hit_def.fall.fall && hit_def.fall.air_fall);
+    }
+}
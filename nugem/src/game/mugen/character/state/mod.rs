@@ -1,2 +1,35 @@
-pub mod read_cns;
+mod state_def;
+pub use self::state_def::*;
 
+mod read_cns;
+pub use self::read_cns::*;
+
+mod hit_def;
+pub use self::hit_def::*;
+
+mod sound_command;
+pub use self::sound_command::*;
+
+mod pal_fx;
+pub use self::pal_fx::*;
+
+mod env_shake;
+pub use self::env_shake::*;
+
+mod get_hit;
+pub use self::get_hit::*;
+
+mod player_state;
+pub use self::player_state::*;
+
+mod random;
+pub use self::random::*;
+
+mod player_context;
+pub use self::player_context::*;
+
+mod physics;
+pub use self::physics::*;
+
+mod state_machine;
+pub use self::state_machine::*;
@@ -0,0 +1,151 @@
+use crate::game::mugen::character::air::BlendMode;
+use crate::game::mugen::trigger::{TriggerContext, Value};
+use super::StateController;
+
+/// Color effect applied to the sprites of a player by a PalFX controller, in the units of MUGEN
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PalFx {
+    /// Remaining duration in ticks, negative for an effect lasting until replaced
+    pub time: i32,
+    /// Added to the red, green and blue components, 255 being the full component
+    pub add: [i32; 3],
+    /// Multipliers of the red, green and blue components, 256 keeping the component
+    pub mul: [i32; 3],
+    /// Amplitude of a sine wave added to the components, and its period in ticks
+    pub sin_add: ([i32; 3], i32),
+    /// Saturation: 256 for the colors of the sprites, 0 for grayscale
+    pub color: i32,
+    pub invert_all: bool,
+    /// Ticks since the start of the effect
+    pub elapsed: i32,
+}
+
+impl PalFx {
+    /// Added components at the current tick, including the sine wave
+    pub fn current_add(&self) -> [i32; 3] {
+        let (amplitude, period) = self.sin_add;
+        if period <= 0 {
+            return self.add;
+        }
+        let sine = (std::f32::consts::TAU * self.elapsed as f32 / period as f32).sin();
+        [0, 1, 2].map(|i| self.add[i] + (amplitude[i] as f32 * sine).round() as i32)
+    }
+    /// Advance the effect by one tick. Returns false when the effect is over.
+    pub(super) fn tick(&mut self) -> bool {
+        self.elapsed += 1;
+        if self.time > 0 {
+            self.time -= 1;
+            self.time > 0
+        }
+        else {
+            self.time < 0
+        }
+    }
+}
+
+/// Read the parameters of a PalFX controller
+pub fn read_pal_fx(controller: &StateController, context: &dyn TriggerContext) -> PalFx {
+    let ints = |key: &str| -> Vec<i32> {
+        controller.expressions(key).iter().filter_map(|expression| expression.evaluate(context)).map(Value::as_int).collect()
+    };
+    let components = |key: &str, default: i32| -> [i32; 3] {
+        let values = ints(key);
+        [0, 1, 2].map(|i| values.get(i).copied().unwrap_or(default))
+    };
+    let sin_add = ints("sinadd");
+    PalFx {
+        time: ints("time").first().copied().unwrap_or(0),
+        add: components("add", 0),
+        mul: components("mul", 256),
+        sin_add: ([0, 1, 2].map(|i| sin_add.get(i).copied().unwrap_or(0)), sin_add.get(3).copied().unwrap_or(0)),
+        color: ints("color").first().copied().unwrap_or(256).clamp(0, 256),
+        invert_all: ints("invertall").first().is_some_and(|&invert| invert != 0),
+        elapsed: 0,
+    }
+}
+
+/// Read the source and destination palettes of a RemapPal controller, as group and item numbers, or `None` without
+/// destination
+pub fn read_remap_pal(controller: &StateController, context: &dyn TriggerContext) -> Option<((i32, i32), (i32, i32))> {
+    let palette = |key: &str| -> Option<(i32, i32)> {
+        let values: Vec<i32> = controller.expressions(key).iter().filter_map(|expression| expression.evaluate(context)).map(Value::as_int).collect();
+        Some((*values.first()?, values.get(1).copied().unwrap_or(0)))
+    };
+    Some((palette("source").unwrap_or((1, 1)), palette("dest")?))
+}
+
+/// Read the blending of a Trans controller: `trans = add`, `add1`, `addalpha` with `alpha = source, destination`,
+/// `sub`, `none` or `default`
+pub fn read_trans(controller: &StateController, context: &dyn TriggerContext) -> Option<BlendMode> {
+    let alpha: Vec<u16> = controller.expressions("alpha").iter()
+        .filter_map(|expression| expression.evaluate(context))
+        .map(|value| value.as_int().clamp(0, 256) as u16)
+        .collect();
+    let trans = controller.parameter("trans").unwrap_or("default").trim().to_lowercase();
+    match trans.as_str() {
+        "none" | "default" => Some(BlendMode::Normal),
+        "add" => Some(BlendMode::Add { source: 256, destination: 256 }),
+        "add1" => Some(BlendMode::Add { source: 256, destination: 128 }),
+        "addalpha" => Some(BlendMode::Add {
+            source: alpha.first().copied().unwrap_or(256),
+            destination: alpha.get(1).copied().unwrap_or(0),
+        }),
+        "sub" => Some(BlendMode::Subtract),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::mugen::character::state::{read_cns_file, ControllerType, Facing, PlayerContext, PlayerState, TriggerEnvironment};
+    use crate::game::mugen::character::air::Animations;
+
+    #[test]
+    fn read_color_controllers() {
+        let cns = b"
+[Statedef 0]
+
+[State 0, pal]
+type = PalFX
+trigger1 = 1
+time = 2
+add = 10, 20, 30
+sinadd = 100, 0, -100, 4
+color = 0
+invertall = 1
+
+[State 0, trans]
+type = Trans
+trigger1 = 1
+trans = addalpha
+alpha = 128, 64
+
+[State 0, remap]
+type = RemapPal
+trigger1 = 1
+dest = 1, 2 + 1
+
+[State 0, no destination]
+type = RemapPal
+trigger1 = 1
+source = 2, 1
+";
+        let states = read_cns_file(std::io::Cursor::new(cns), "test");
+        let controllers = &states[&0].controllers;
+        let player = PlayerState::new(Facing::Right);
+        let animations = Animations::new();
+        let context = PlayerContext::new(&player, &animations, TriggerEnvironment::default());
+        assert_eq!(ControllerType::PalFX, controllers[0].controller_type);
+        let mut pal_fx = read_pal_fx(&controllers[0], &context);
+        assert_eq!(PalFx { time: 2, add: [10, 20, 30], mul: [256; 3], sin_add: ([100, 0, -100], 4), color: 0, invert_all: true, elapsed: 0 }, pal_fx);
+        assert_eq!([10, 20, 30], pal_fx.current_add());
+        // a quarter of the period of the sine wave
+        assert!(pal_fx.tick());
+        assert_eq!([110, 20, -70], pal_fx.current_add());
+        assert!(!pal_fx.tick());
+        assert_eq!(Some(BlendMode::Add { source: 128, destination: 64 }), read_trans(&controllers[1], &context));
+        assert_eq!(Some(((1, 1), (1, 3))), read_remap_pal(&controllers[2], &context));
+        assert_eq!(None, read_remap_pal(&controllers[3], &context));
+    }
+}
@@ -0,0 +1,64 @@
+use crate::game::mugen::character::Constants;
+use super::{Physics, PlayerState};
+
+/// State entered when a character with air physics reaches the ground
+pub const LANDING_STATE: i32 = 52;
+
+/// Move a player by its velocity for one tick, then apply the friction or the gravity of its physics type.
+///
+/// Returns true if the player has landed: it is then put back on the ground, and should enter the landing state.
+pub fn apply_physics(player: &mut PlayerState, constants: &Constants) -> bool {
+    player.position.0 += player.velocity.0 * player.facing_sign();
+    player.position.1 += player.velocity.1;
+    match player.physics {
+        Physics::Standing => player.velocity.0 *= constants.stand_friction(),
+        Physics::Crouching => player.velocity.0 *= constants.crouch_friction(),
+        Physics::Air => {
+            if player.position.1 >= 0. && player.velocity.1 > 0. {
+                player.position.1 = 0.;
+                player.velocity.1 = 0.;
+                return true;
+            }
+            player.velocity.1 += constants.y_acceleration();
+        },
+        Physics::None | Physics::Unchanged => (),
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::mugen::character::state::Facing;
+
+    #[test]
+    fn jump_and_land() {
+        let constants = Constants::default();
+        let mut player = PlayerState::new(Facing::Left);
+        player.physics = Physics::Air;
+        player.velocity = (2., -4.);
+        let mut ticks = 0;
+        while !apply_physics(&mut player, &constants) {
+            ticks += 1;
+            assert!(player.position.1 <= 0.);
+            assert!(ticks < 100);
+        }
+        // the jump lasts until the gravity brings the character back to the ground
+        assert_eq!(19, ticks);
+        assert_eq!(-40., player.position.0);
+        assert_eq!((0., 0.), (player.position.1, player.velocity.1));
+    }
+
+    #[test]
+    fn friction() {
+        let constants = Constants::default();
+        let mut player = PlayerState::new(Facing::Right);
+        player.velocity = (4., 0.);
+        apply_physics(&mut player, &constants);
+        assert_eq!((4., 0.), player.position);
+        assert_eq!(4. * constants.stand_friction(), player.velocity.0);
+        player.physics = Physics::None;
+        apply_physics(&mut player, &constants);
+        assert_eq!(4. * constants.stand_friction(), player.velocity.0);
+    }
+}
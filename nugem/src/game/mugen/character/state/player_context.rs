@@ -0,0 +1,420 @@
+use lazy_static::lazy_static;
+use crate::game::mugen::character::air::Animations;
+use crate::game::mugen::character::Constants;
+use crate::game::mugen::round::RoundInfo;
+use crate::game::mugen::trigger::{Axis, Redirection, SymbolTrigger, Trigger, TriggerContext, Value};
+use super::{Facing, MoveType, PlayerState, RandomGenerator};
+
+/// Top left corner and size of the screen used without a fight: 320 x 240 pixels centered on the stage, with the
+/// ground 200 pixels below the top
+const DEFAULT_SCREEN: ((f32, f32), (f32, f32)) = ((-160., -200.), (320., 240.));
+
+/// Information about a player read by the triggers, other than its state.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlayerInfo<'a> {
+    /// Name and author of the character
+    pub name: &'a str,
+    pub author_name: &'a str,
+    /// Side of the player, 1 for the first player and 2 for the second one, also used as its ID
+    pub team_side: i32,
+    /// Number of the palette of the player, from 1
+    pub palette_number: i32,
+}
+
+impl Default for PlayerInfo<'_> {
+    fn default() -> Self {
+        PlayerInfo {
+            name: "",
+            author_name: "",
+            team_side: 1,
+            palette_number: 1,
+        }
+    }
+}
+
+/// Data used by the triggers of a player, other than its own state.
+#[derive(Clone, Copy)]
+pub struct TriggerEnvironment<'a> {
+    /// Names of the active commands of the player
+    pub commands: &'a [String],
+    /// Constants of the character of the player
+    pub constants: &'a Constants,
+    pub info: PlayerInfo<'a>,
+    /// State, animations, constants and information of the opponent
+    pub opponent: Option<(&'a PlayerState, &'a Animations, &'a Constants, PlayerInfo<'a>)>,
+    /// Round of the match
+    pub round: RoundInfo,
+    /// Ticks since the start of the match
+    pub game_time: i32,
+    /// Top left corner and size of the screen, in the coordinates of the players
+    pub screen: ((f32, f32), (f32, f32)),
+    pub random: &'a RandomGenerator,
+}
+
+impl Default for TriggerEnvironment<'_> {
+    fn default() -> Self {
+        lazy_static! {
+            static ref DEFAULT_CONSTANTS: Constants = Constants::default();
+        }
+        static DEFAULT_RANDOM: RandomGenerator = RandomGenerator::new(1);
+        TriggerEnvironment {
+            commands: &[],
+            constants: &DEFAULT_CONSTANTS,
+            info: PlayerInfo::default(),
+            opponent: None,
+            round: RoundInfo::default(),
+            game_time: 0,
+            screen: DEFAULT_SCREEN,
+            random: &DEFAULT_RANDOM,
+        }
+    }
+}
+
+/// Trigger context of a player during a fight.
+pub struct PlayerContext<'a> {
+    player: &'a PlayerState,
+    animations: &'a Animations,
+    environment: TriggerEnvironment<'a>,
+}
+
+impl<'a> PlayerContext<'a> {
+    pub fn new(player: &'a PlayerState, animations: &'a Animations, environment: TriggerEnvironment<'a>) -> PlayerContext<'a> {
+        PlayerContext {
+            player,
+            animations,
+            environment,
+        }
+    }
+    /// Distance to the opponent, with x towards the front of the player. The body distance is the horizontal
+    /// distance between the widths of the players.
+    fn opponent_distance(&self, axis: Axis, body: bool) -> Option<f32> {
+        let player = self.player;
+        let (opponent, _, opponent_constants, _) = self.environment.opponent?;
+        let distance = match axis {
+            Axis::X => {
+                let distance = (opponent.position.0 - player.position.0) * player.facing_sign();
+                if !body {
+                    return Some(distance);
+                }
+                let width = |player: &PlayerState, constants: &Constants, x: f32| match player.is_behind(x) {
+                    true => constants.ground_back(),
+                    false => constants.ground_front(),
+                };
+                let widths = width(player, self.environment.constants, opponent.position.0) + width(opponent, opponent_constants, player.position.0);
+                distance - widths * distance.signum()
+            },
+            Axis::Y => opponent.position.1 - player.position.1,
+        };
+        Some(distance)
+    }
+    /// Distances from the player to the edges of the screen in front of it and behind it
+    fn edge_distances(&self) -> (f32, f32) {
+        let ((left, _), (width, _)) = self.environment.screen;
+        let x = self.player.position.0;
+        match self.player.facing {
+            Facing::Right => (left + width - x, x - left),
+            Facing::Left => (x - left, left + width - x),
+        }
+    }
+    /// Check if the opponent is attacking with a HitDef, and the player is in front of it within its guard distance
+    fn in_guard_distance(&self) -> bool {
+        let Some((opponent, _, opponent_constants, _)) = self.environment.opponent else {
+            return false;
+        };
+        let distance = (self.player.position.0 - opponent.position.0) * opponent.facing_sign();
+        opponent.move_type == MoveType::Attack && opponent.hit_def.as_ref().is_some_and(|hit_def| {
+            (0. ..=hit_def.guard_distance.unwrap_or_else(|| opponent_constants.attack_distance())).contains(&distance)
+        })
+    }
+    /// Context of the opponent, with the player as its opponent
+    fn opponent_context(&self) -> Option<Box<dyn TriggerContext + '_>> {
+        let (opponent, opponent_animations, opponent_constants, opponent_info) = self.environment.opponent?;
+        let environment = TriggerEnvironment {
+            commands: &[],
+            constants: opponent_constants,
+            info: opponent_info,
+            opponent: Some((self.player, self.animations, self.environment.constants, self.environment.info)),
+            round: self.environment.round.opponent(),
+            ..self.environment
+        };
+        Some(Box::new(PlayerContext::new(opponent, opponent_animations, environment)))
+    }
+}
+
+impl TriggerContext for PlayerContext<'_> {
+    fn trigger(&self, trigger: Trigger, argument: Option<i32>) -> Option<Value> {
+        let player = self.player;
+        let index = || argument.and_then(|i| usize::try_from(i).ok());
+        let value = match trigger {
+            Trigger::Time => Value::Int(player.state_time),
+            Trigger::StateNo => Value::Int(player.state_number),
+            Trigger::PrevStateNo => Value::Int(player.previous_state_number),
+            Trigger::Anim => Value::Int(player.animation_number()? as i32),
+            Trigger::AnimTime => Value::Int(player.animator()?.animation_time()),
+            Trigger::AnimElemTime => Value::Int(player.animator()?.time_since_element(index()?)?),
+            Trigger::AnimElemNo => Value::Int(player.animator()?.element_at(argument?)? as i32),
+            Trigger::AnimExist | Trigger::SelfAnimExist => {
+                let animation_number = u32::try_from(argument?).ok();
+                Value::from(animation_number.map(|n| self.animations.contains_key(&n)).unwrap_or(false))
+            },
+            Trigger::Ctrl => Value::from(player.ctrl),
+            Trigger::Alive => Value::from(player.life > 0),
+            Trigger::Life => Value::Int(player.life),
+            Trigger::LifeMax => Value::Int(player.life_max),
+            Trigger::Power => Value::Int(player.power),
+            Trigger::PowerMax => Value::Int(player.power_max),
+            Trigger::Facing => Value::Int(match player.facing {
+                Facing::Right => 1,
+                Facing::Left => -1,
+            }),
+            Trigger::Pos(Axis::X) => Value::Float(player.position.0),
+            Trigger::Pos(Axis::Y) => Value::Float(player.position.1),
+            Trigger::Vel(Axis::X) => Value::Float(player.velocity.0),
+            Trigger::Vel(Axis::Y) => Value::Float(player.velocity.1),
+            Trigger::ScreenPos(Axis::X) => Value::Float(player.position.0 - self.environment.screen.0.0),
+            Trigger::ScreenPos(Axis::Y) => Value::Float(player.position.1 - self.environment.screen.0.1),
+            // positive towards the back of the player
+            Trigger::HitVel(Axis::X) => Value::Float(-player.get_hit.velocity.0),
+            Trigger::HitVel(Axis::Y) => Value::Float(player.get_hit.velocity.1),
+            Trigger::P2Dist(axis) => Value::Float(self.opponent_distance(axis, false)?),
+            Trigger::P2BodyDist(axis) => Value::Float(self.opponent_distance(axis, true)?),
+            // the players are not helpers, without parent or root
+            Trigger::ParentDist(_) | Trigger::RootDist(_) => None?,
+            Trigger::FrontEdgeDist => Value::Float(self.edge_distances().0),
+            Trigger::BackEdgeDist => Value::Float(self.edge_distances().1),
+            Trigger::FrontEdgeBodyDist => Value::Float(self.edge_distances().0 - self.environment.constants.ground_front()),
+            Trigger::BackEdgeBodyDist => Value::Float(self.edge_distances().1 - self.environment.constants.ground_back()),
+            Trigger::Var => Value::Int(*player.vars.get(index()?)?),
+            Trigger::FVar => Value::Float(*player.fvars.get(index()?)?),
+            Trigger::SysVar => Value::Int(*player.sys_vars.get(index()?)?),
+            Trigger::SysFVar => Value::Float(*player.sys_fvars.get(index()?)?),
+            Trigger::MoveContact => Value::Int(player.move_contact),
+            Trigger::MoveHit => Value::Int(player.move_hit),
+            Trigger::MoveGuarded => Value::Int(player.move_guarded),
+            // the ReversalDef controllers are not run
+            Trigger::MoveReversed => Value::Int(0),
+            Trigger::HitCount => Value::Int(player.hit_count),
+            Trigger::UniqHitCount => Value::Int(player.unique_hit_count),
+            Trigger::HitPauseTime => Value::Int(player.hit_pause),
+            Trigger::HitShakeOver => Value::from(player.hit_pause <= 0),
+            Trigger::HitOver => Value::from(player.get_hit.hit_time <= 0),
+            Trigger::HitFall => Value::from(player.get_hit.fall),
+            Trigger::CanRecover => Value::from(player.get_hit.fall && player.get_hit.fall_recover && player.get_hit.fall_recover_time <= 0),
+            Trigger::InGuardDist => Value::from(self.in_guard_distance()),
+            Trigger::NumEnemy => Value::from(self.environment.opponent.is_some()),
+            // the fights are between two players, without helpers, explods or projectiles
+            Trigger::NumPartner | Trigger::NumHelper | Trigger::NumTarget | Trigger::NumExplod | Trigger::NumProj | Trigger::IsHelper => Value::Int(0),
+            Trigger::Id | Trigger::TeamSide => Value::Int(self.environment.info.team_side),
+            Trigger::PalNo => Value::Int(self.environment.info.palette_number),
+            Trigger::RoundNo => Value::Int(self.environment.round.round_no),
+            Trigger::RoundState => Value::Int(self.environment.round.round_state.number()),
+            Trigger::RoundsExisted => Value::Int(self.environment.round.rounds_existed),
+            Trigger::MatchOver => Value::from(self.environment.round.match_over),
+            Trigger::Win => Value::from(self.environment.round.win),
+            Trigger::Lose => Value::from(self.environment.round.lose),
+            Trigger::DrawGame => Value::from(self.environment.round.draw_game),
+            // a single match is played
+            Trigger::MatchNo => Value::Int(1),
+            Trigger::GameTime => Value::Int(self.environment.game_time),
+            Trigger::Random => Value::Int(self.environment.random.next_below(1000) as i32),
+        };
+        Some(value)
+    }
+    fn command(&self, name: &str) -> Option<bool> {
+        Some(self.environment.commands.iter().any(|command| command == name))
+    }
+    fn constant(&self, name: &str) -> Option<Value> {
+        self.environment.constants.get(name).map(Value::Float)
+    }
+    fn hit_var(&self, name: &str) -> Option<Value> {
+        self.player.get_hit.get(name)
+    }
+    fn symbol(&self, trigger: SymbolTrigger) -> Option<String> {
+        match trigger {
+            SymbolTrigger::StateType => Some(self.player.state_type.symbol().to_owned()),
+            SymbolTrigger::MoveType => Some(self.player.move_type.symbol().to_owned()),
+            SymbolTrigger::Name => Some(self.environment.info.name.to_owned()),
+            SymbolTrigger::AuthorName => Some(self.environment.info.author_name.to_owned()),
+        }
+    }
+    fn redirect(&self, redirection: Redirection, argument: Option<i32>) -> Option<Box<dyn TriggerContext + '_>> {
+        let opponent_info = self.environment.opponent.map(|(_, _, _, info)| info);
+        match (redirection, argument) {
+            (Redirection::P2 | Redirection::Enemy | Redirection::EnemyNear, None | Some(0)) => self.opponent_context(),
+            (Redirection::PlayerId, Some(id)) if id == self.environment.info.team_side => {
+                Some(Box::new(PlayerContext::new(self.player, self.animations, self.environment)))
+            },
+            (Redirection::PlayerId, Some(id)) if opponent_info.is_some_and(|info| info.team_side == id) => self.opponent_context(),
+            // no parent, root, partner, helper, target or second opponent
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::mugen::trigger::parse_expression;
+    use std::io::Cursor;
+    use crate::game::mugen::character::state::{read_cns_file, read_hit_def, HitDef};
+
+    fn evaluate(expression: &str, player: &PlayerState, environment: TriggerEnvironment) -> Option<Value> {
+        let animations = Animations::new();
+        parse_expression(expression).unwrap().evaluate(&PlayerContext::new(player, &animations, environment))
+    }
+
+    /// Player at x = -20 facing an opponent at x = 30 and 10 pixels above the ground
+    fn players() -> (PlayerState, PlayerState) {
+        let mut player = PlayerState::new(Facing::Right);
+        player.position = (-20., 0.);
+        let mut opponent = PlayerState::new(Facing::Left);
+        opponent.position = (30., -10.);
+        (player, opponent)
+    }
+
+    fn with_opponent<'a>(opponent: &'a PlayerState, animations: &'a Animations, constants: &'a Constants) -> TriggerEnvironment<'a> {
+        TriggerEnvironment { opponent: Some((opponent, animations, constants, PlayerInfo::default())), ..TriggerEnvironment::default() }
+    }
+
+    #[test]
+    fn hit_count() {
+        let (mut player, _) = players();
+        player.hit_count = 3;
+        assert_eq!(Some(Value::Int(3)), evaluate("HitCount", &player, TriggerEnvironment::default()));
+    }
+
+    #[test]
+    fn uniq_hit_count() {
+        let (mut player, _) = players();
+        player.unique_hit_count = 2;
+        assert_eq!(Some(Value::Int(2)), evaluate("UniqHitCount", &player, TriggerEnvironment::default()));
+    }
+
+    #[test]
+    fn random() {
+        let (player, _) = players();
+        let random = RandomGenerator::new(42);
+        let environment = TriggerEnvironment { random: &random, ..TriggerEnvironment::default() };
+        let values: Vec<i32> = (0..100).map(|_| evaluate("Random", &player, environment).unwrap().as_int()).collect();
+        assert!(values.iter().all(|value| (0..1000).contains(value)));
+        assert!(values.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn p2_dist() {
+        let (mut player, opponent) = players();
+        let (animations, constants) = (Animations::new(), Constants::default());
+        let environment = with_opponent(&opponent, &animations, &constants);
+        assert_eq!(Some(Value::Float(50.)), evaluate("P2Dist X", &player, environment));
+        assert_eq!(Some(Value::Float(-10.)), evaluate("P2Dist Y", &player, environment));
+        // x is towards the front of the player
+        player.facing = Facing::Left;
+        assert_eq!(Some(Value::Float(-50.)), evaluate("P2Dist X", &player, environment));
+        assert_eq!(None, evaluate("P2Dist X", &player, TriggerEnvironment::default()));
+    }
+
+    #[test]
+    fn p2_body_dist() {
+        let (player, mut opponent) = players();
+        let (animations, constants) = (Animations::new(), Constants::default());
+        // front widths of 16 pixels
+        assert_eq!(Some(Value::Float(18.)), evaluate("P2BodyDist X", &player, with_opponent(&opponent, &animations, &constants)));
+        assert_eq!(Some(Value::Float(-10.)), evaluate("P2BodyDist Y", &player, with_opponent(&opponent, &animations, &constants)));
+        // the back of the opponent is 15 pixels wide
+        opponent.facing = Facing::Right;
+        assert_eq!(Some(Value::Float(19.)), evaluate("P2BodyDist X", &player, with_opponent(&opponent, &animations, &constants)));
+        // the player is behind the opponent
+        assert_eq!(Some(Value::Int(1)), evaluate("p2, P2BodyDist X = -19", &player, with_opponent(&opponent, &animations, &constants)));
+    }
+
+    #[test]
+    fn redirections() {
+        let (player, mut opponent) = players();
+        opponent.life = 250;
+        let (animations, constants) = (Animations::new(), Constants::default());
+        let opponent_info = PlayerInfo { team_side: 2, ..PlayerInfo::default() };
+        let environment = TriggerEnvironment { opponent: Some((&opponent, &animations, &constants, opponent_info)), ..TriggerEnvironment::default() };
+        assert_eq!(Some(Value::Int(250)), evaluate("enemy, life", &player, environment));
+        assert_eq!(Some(Value::Int(250)), evaluate("playerid(2), life", &player, environment));
+        assert_eq!(Some(Value::Int(1000)), evaluate("playerid(1), life", &player, environment));
+        // no such player
+        for expression in ["enemynear(1), life", "playerid(3), life", "parent, life", "root, life", "partner, life", "helper(1200), var(3)", "target, life"] {
+            assert_eq!(None, evaluate(expression, &player, environment), "{expression}");
+        }
+        assert_eq!(None, evaluate("ParentDist X", &player, environment));
+        assert_eq!(Some(Value::Int(0)), evaluate("NumTarget(1200)", &player, environment));
+    }
+
+    #[test]
+    fn front_edge_dist() {
+        let (mut player, _) = players();
+        // screen from x = -160 to x = 160
+        assert_eq!(Some(Value::Float(180.)), evaluate("FrontEdgeDist", &player, TriggerEnvironment::default()));
+        assert_eq!(Some(Value::Float(164.)), evaluate("FrontEdgeBodyDist", &player, TriggerEnvironment::default()));
+        player.facing = Facing::Left;
+        assert_eq!(Some(Value::Float(140.)), evaluate("FrontEdgeDist", &player, TriggerEnvironment::default()));
+    }
+
+    #[test]
+    fn back_edge_dist() {
+        let (mut player, _) = players();
+        let environment = TriggerEnvironment { screen: ((-100., -200.), (320., 240.)), ..TriggerEnvironment::default() };
+        assert_eq!(Some(Value::Float(80.)), evaluate("BackEdgeDist", &player, environment));
+        assert_eq!(Some(Value::Float(65.)), evaluate("BackEdgeBodyDist", &player, environment));
+        player.facing = Facing::Left;
+        assert_eq!(Some(Value::Float(240.)), evaluate("BackEdgeDist", &player, environment));
+    }
+
+    #[test]
+    fn screen_pos() {
+        let (mut player, _) = players();
+        player.position.1 = -30.;
+        let environment = TriggerEnvironment { screen: ((-100., -180.), (320., 240.)), ..TriggerEnvironment::default() };
+        assert_eq!(Some(Value::Float(80.)), evaluate("ScreenPos X", &player, environment));
+        assert_eq!(Some(Value::Float(150.)), evaluate("ScreenPos Y", &player, environment));
+    }
+
+    #[test]
+    fn game_time() {
+        let (player, _) = players();
+        let environment = TriggerEnvironment { game_time: 1234, ..TriggerEnvironment::default() };
+        assert_eq!(Some(Value::Int(1)), evaluate("GameTime > Time", &player, environment));
+        assert_eq!(Some(Value::Int(1234)), evaluate("GameTime", &player, environment));
+    }
+
+    #[test]
+    fn can_recover() {
+        let (mut player, _) = players();
+        player.get_hit.fall = true;
+        player.get_hit.fall_recover_time = 3;
+        assert_eq!(Some(Value::Int(0)), evaluate("CanRecover", &player, TriggerEnvironment::default()));
+        player.get_hit.fall_recover_time = 0;
+        assert_eq!(Some(Value::Int(1)), evaluate("CanRecover", &player, TriggerEnvironment::default()));
+        player.get_hit.fall_recover = false;
+        assert_eq!(Some(Value::Int(0)), evaluate("CanRecover", &player, TriggerEnvironment::default()));
+    }
+
+    #[test]
+    fn in_guard_dist() {
+        let (player, mut opponent) = players();
+        let (animations, constants) = (Animations::new(), Constants::default());
+        assert_eq!(Some(Value::Int(0)), evaluate("InGuardDist", &player, with_opponent(&opponent, &animations, &constants)));
+        opponent.move_type = MoveType::Attack;
+        opponent.hit_def = Some(hit_def(""));
+        assert_eq!(Some(Value::Int(1)), evaluate("InGuardDist", &player, with_opponent(&opponent, &animations, &constants)));
+        opponent.hit_def = Some(hit_def("guard.dist = 40"));
+        assert_eq!(Some(Value::Int(0)), evaluate("InGuardDist", &player, with_opponent(&opponent, &animations, &constants)));
+        // behind the attacker
+        opponent.hit_def = Some(hit_def(""));
+        opponent.facing = Facing::Right;
+        assert_eq!(Some(Value::Int(0)), evaluate("InGuardDist", &player, with_opponent(&opponent, &animations, &constants)));
+    }
+
+    /// HitDef controller with the given parameters
+    fn hit_def(parameters: &str) -> HitDef {
+        let cns = format!("[Statedef 200]\n[State 200, hit]\ntype = HitDef\ntrigger1 = 1\n{parameters}\n");
+        let states = read_cns_file(Cursor::new(cns), "test");
+        let (player, animations) = (PlayerState::new(Facing::Right), Animations::new());
+        read_hit_def(&states[&200].controllers[0], &PlayerContext::new(&player, &animations, TriggerEnvironment::default()))
+    }
+}
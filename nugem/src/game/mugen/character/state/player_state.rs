@@ -0,0 +1,193 @@
+use crate::game::mugen::character::air::{Animations, Animator, BlendMode};
+use super::{EnvShake, GetHitVars, HitDef, MoveType, PalFx, Physics, SoundCommand, StateType};
+
+pub const VAR_COUNT: usize = 60;
+pub const FVAR_COUNT: usize = 40;
+pub const SYSVAR_COUNT: usize = 5;
+
+const DEFAULT_LIFE: i32 = 1000;
+const DEFAULT_POWER_MAX: i32 = 3000;
+
+/// Direction in which a character is facing.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
+pub enum Facing {
+    Left,
+    Right,
+}
+
+impl Facing {
+    pub fn turned(self) -> Facing {
+        match self {
+            Facing::Left => Facing::Right,
+            Facing::Right => Facing::Left,
+        }
+    }
+}
+
+/// Runtime variables of a character during a fight.
+#[derive(Clone, Debug)]
+pub struct PlayerState {
+    pub state_number: i32,
+    pub previous_state_number: i32,
+    /// Number of ticks since entering the current state
+    pub state_time: i32,
+    pub state_type: StateType,
+    pub move_type: MoveType,
+    pub physics: Physics,
+    pub ctrl: bool,
+    pub facing: Facing,
+    /// Position of the axis of the character: x from the center of the stage, y from the ground, upwards negative
+    pub position: (f32, f32),
+    /// Velocity of the character, with x towards the front
+    pub velocity: (f32, f32),
+    pub life: i32,
+    pub life_max: i32,
+    pub power: i32,
+    pub power_max: i32,
+    /// Air juggle points required by the current attack
+    pub juggle: i32,
+    pub sprite_priority: i32,
+    /// Blending of the sprites set by a Trans controller during the current tick
+    pub trans: Option<BlendMode>,
+    /// Color effect of the sprites, until its time runs out
+    pub pal_fx: Option<PalFx>,
+    /// Palette displayed instead of the selected one after a RemapPal controller, as group and item numbers
+    pub remapped_palette: Option<(i32, i32)>,
+    /// The player stays on the screen, unless a ScreenBound controller disables it during the current tick
+    pub screen_bound: bool,
+    /// The camera follows the player horizontally and vertically, unless a ScreenBound controller disables it during
+    /// the current tick
+    pub camera_follow: (bool, bool),
+    /// Shake of the screen requested by an EnvShake controller, until the fight handles it
+    pub env_shake: Option<EnvShake>,
+    /// Attack of the player, until it makes contact or the state changes
+    pub hit_def: Option<HitDef>,
+    /// Ticks since the attacks of the current state hit or were guarded, starting from 1, or 0 without contact
+    pub move_contact: i32,
+    /// Ticks since the attacks of the current state hit, starting from 1, or 0 without hit
+    pub move_hit: i32,
+    /// Ticks since the attacks of the current state were guarded, starting from 1, or 0 if not guarded
+    pub move_guarded: i32,
+    /// Hits of the attacks of the player since entering the current state, kept by the states with `hitcountpersist`
+    pub hit_count: i32,
+    /// Hits of the attacks of the player on each opponent since entering the current state
+    pub unique_hit_count: i32,
+    /// Ticks remaining in the pause or the shake caused by a hit
+    pub hit_pause: i32,
+    pub get_hit: GetHitVars,
+    /// Sound actions requested by the state controllers, until the fight handles them
+    pub sound_commands: Vec<SoundCommand>,
+    pub vars: [i32; VAR_COUNT],
+    pub fvars: [f32; FVAR_COUNT],
+    pub sys_vars: [i32; SYSVAR_COUNT],
+    pub sys_fvars: [f32; SYSVAR_COUNT],
+    animation_number: Option<u32>,
+    animator: Option<Animator>,
+    /// True if the animation was changed during the current tick
+    animation_changed: bool,
+}
+
+impl PlayerState {
+    pub fn new(facing: Facing) -> PlayerState {
+        PlayerState {
+            state_number: 0,
+            previous_state_number: 0,
+            state_time: 0,
+            state_type: StateType::Standing,
+            move_type: MoveType::Idle,
+            physics: Physics::Standing,
+            ctrl: true,
+            facing,
+            position: (0., 0.),
+            velocity: (0., 0.),
+            life: DEFAULT_LIFE,
+            life_max: DEFAULT_LIFE,
+            power: 0,
+            power_max: DEFAULT_POWER_MAX,
+            juggle: 0,
+            sprite_priority: 0,
+            trans: None,
+            pal_fx: None,
+            remapped_palette: None,
+            screen_bound: true,
+            camera_follow: (true, true),
+            env_shake: None,
+            hit_def: None,
+            move_contact: 0,
+            move_hit: 0,
+            move_guarded: 0,
+            hit_count: 0,
+            unique_hit_count: 0,
+            hit_pause: 0,
+            get_hit: GetHitVars::default(),
+            sound_commands: Vec::new(),
+            vars: [0; VAR_COUNT],
+            fvars: [0.; FVAR_COUNT],
+            sys_vars: [0; SYSVAR_COUNT],
+            sys_fvars: [0.; SYSVAR_COUNT],
+            animation_number: None,
+            animator: None,
+            animation_changed: false,
+        }
+    }
+    pub fn animation_number(&self) -> Option<u32> {
+        self.animation_number
+    }
+    pub fn animator(&self) -> Option<&Animator> {
+        self.animator.as_ref()
+    }
+    /// Change the current animation. Returns false if the animation does not exist.
+    pub fn change_animation(&mut self, animation_number: u32, animations: &Animations) -> bool {
+        match animations.get(&animation_number) {
+            Some(animation) => {
+                self.animation_number = Some(animation_number);
+                self.animator = Some(Animator::new(animation.clone()));
+                self.animation_changed = true;
+                true
+            },
+            None => false,
+        }
+    }
+    /// Jump to an element of the current animation, numbered from 1. Returns false if the element does not exist.
+    pub fn set_animation_element(&mut self, element: usize) -> bool {
+        self.animator.as_mut().map(|animator| animator.set_element(element)).unwrap_or(false)
+    }
+    /// Advance the animation by one tick, unless it was changed during the current tick
+    pub(super) fn tick_animation(&mut self) {
+        if !self.animation_changed {
+            if let Some(animator) = self.animator.as_mut() {
+                animator.tick();
+            }
+        }
+        self.animation_changed = false;
+    }
+    /// Check if the character is allowed to turn to face the opponent: standing or crouching, with control
+    pub fn can_turn(&self) -> bool {
+        self.ctrl && matches!(self.state_type, StateType::Standing | StateType::Crouching)
+    }
+    /// Check if a horizontal position is behind the character
+    pub fn is_behind(&self, x: f32) -> bool {
+        (x - self.position.0) * self.facing_sign() < 0.
+    }
+    /// Turn the character to face a horizontal position behind it. Returns true if the character turned.
+    pub fn face(&mut self, x: f32) -> bool {
+        let behind = self.is_behind(x);
+        if behind {
+            self.facing = self.facing.turned();
+        }
+        behind
+    }
+    /// Sign of the x coordinates in front of the character
+    pub fn facing_sign(&self) -> f32 {
+        match self.facing {
+            Facing::Right => 1.,
+            Facing::Left => -1.,
+        }
+    }
+    pub fn add_power(&mut self, power: i32) {
+        self.power = (self.power + power).clamp(0, self.power_max);
+    }
+    pub fn set_life(&mut self, life: i32) {
+        self.life = life.clamp(0, self.life_max);
+    }
+}
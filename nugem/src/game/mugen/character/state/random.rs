@@ -0,0 +1,25 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Generator of pseudo-random numbers shared by the players of a fight, for the `Random` trigger.
+#[derive(Debug)]
+pub struct RandomGenerator {
+    state: AtomicU32,
+}
+
+impl RandomGenerator {
+    pub const fn new(seed: u32) -> RandomGenerator {
+        // the xorshift state must not be 0
+        RandomGenerator {
+            state: AtomicU32::new(if seed == 0 { 1 } else { seed }),
+        }
+    }
+    /// Next number of the sequence, between 0 and `bound` excluded
+    pub fn next_below(&self, bound: u32) -> u32 {
+        let mut x = self.state.load(Ordering::Relaxed);
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state.store(x, Ordering::Relaxed);
+        x % bound.max(1)
+    }
+}
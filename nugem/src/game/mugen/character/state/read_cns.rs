@@ -1,8 +1,279 @@
-use std::collections::HashMap;
-use ::game::mugen::format::generic_def::{Categories, DefLine, GenericDef};
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use super::{Command, CommandInput, CommandInputState};
+use std::collections::{btree_map::Entry, HashMap};
+use std::io::Read;
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::game::mugen::format::generic_def::{Categories, Category, DefLine};
+use crate::game::mugen::trigger::{parse_expression, Expression};
+use super::{ControllerType, MoveType, Parameter, Physics, StateController, StateDef, StateType, States, Triggers};
 
-pub fn read_cns_file(_: File) {
+/// Read the state definitions of a CNS file.
+pub fn read_cns_file<R: Read>(read: R, character_name: &str) -> States {
+    log::info!("Reading CNS file for character {character_name}");
+    let mut states = States::new();
+    read_state_categories(Categories::read_def(read), &mut states);
+    states
+}
+
+/// Read the `[Statedef]` and `[State]` categories into the given states. Other categories are ignored.
+///
+/// State definitions already present in `states` are kept: a state defined twice keeps its first definition.
+pub fn read_state_categories<I: IntoIterator<Item = (u64, Category)>>(categories: I, states: &mut States) {
+    // number of the state definition being read, if it was not already defined
+    let mut current_state: Option<i32> = None;
+    for (cat_line_number, category) in categories {
+        lazy_static! {
+            static ref REGEX_STATEDEF: Regex = Regex::new(r"(?i)^statedef\s+(-?[0-9]+)").unwrap();
+            static ref REGEX_STATE: Regex = Regex::new(r"(?i)^state\s+(-?[0-9]+)\s*(?:,\s*(.*))?$").unwrap();
+        }
+        if let Some(c) = REGEX_STATEDEF.captures(category.name()) {
+            current_state = None;
+            match c[1].parse() {
+                Ok(number) if states.contains_key(&number) => log::warn!("State {number} at line {cat_line_number} is already defined, ignoring it"),
+                Ok(number) => {
+                    states.insert(number, read_statedef(number, category));
+                    current_state = Some(number);
+                },
+                Err(e) => log::error!("Invalid state number at line {cat_line_number}: {e}"),
+            }
+        }
+        else if let Some(c) = REGEX_STATE.captures(category.name()) {
+            let label = c.get(2).map(|m| m.as_str().to_owned()).unwrap_or_default();
+            if let Some(state_def) = current_state.and_then(|number| states.get_mut(&number)) {
+                match read_state_controller(label, category) {
+                    Ok(controller) => state_def.controllers.push(controller),
+                    Err(e) => log::error!("Error reading state controller at line {cat_line_number}: {e}"),
+                }
+            }
+        }
+        else {
+            log::trace!("Ignoring category at line {cat_line_number}: {0}", category.name());
+        }
+    }
+}
+
+/// Add new states, keeping the first definition of the states already defined.
+pub fn add_states(states: &mut States, new_states: States) {
+    for (number, state_def) in new_states {
+        match states.entry(number) {
+            Entry::Vacant(entry) => {
+                entry.insert(state_def);
+            },
+            Entry::Occupied(_) => log::warn!("State {number} is already defined, ignoring it"),
+        }
+    }
+}
+
+/// Add the states of a common file such as `common1.cns`: the states already defined by the character override them.
+pub fn add_common_states(states: &mut States, common_states: States) {
+    for (number, state_def) in common_states {
+        states.entry(number).or_insert(state_def);
+    }
+}
+
+fn read_statedef(number: i32, category: Category) -> StateDef {
+    let mut state_def = StateDef::new(number);
+    for (line_number, line) in category.into_lines() {
+        match line {
+            DefLine::KeyValue(key, value) => {
+                let key_name = key.to_lowercase();
+                let valid = match key_name.as_str() {
+                    "type" => StateType::from_symbol(&value).map(|v| state_def.state_type = v).is_some(),
+                    "movetype" => MoveType::from_symbol(&value).map(|v| state_def.move_type = v).is_some(),
+                    "physics" => Physics::from_symbol(&value).map(|v| state_def.physics = v).is_some(),
+                    "anim" => parse_int(&value).map(|v| state_def.anim = Some(v)).is_some(),
+                    "ctrl" => parse_bool(&value).map(|v| state_def.ctrl = Some(v)).is_some(),
+                    "velset" => parse_float_pair(&value).map(|v| state_def.velset = Some(v)).is_some(),
+                    "poweradd" => parse_int(&value).map(|v| state_def.poweradd = Some(v)).is_some(),
+                    "juggle" => parse_int(&value).map(|v| state_def.juggle = Some(v)).is_some(),
+                    "facep2" => parse_bool(&value).map(|v| state_def.facep2 = v).is_some(),
+                    "hitdefpersist" => parse_bool(&value).map(|v| state_def.hitdefpersist = v).is_some(),
+                    "movehitpersist" => parse_bool(&value).map(|v| state_def.movehitpersist = v).is_some(),
+                    "hitcountpersist" => parse_bool(&value).map(|v| state_def.hitcountpersist = v).is_some(),
+                    "sprpriority" => parse_int(&value).map(|v| state_def.sprpriority = Some(v)).is_some(),
+                    _ => {
+                        log::error!("Unknown statedef key at line {line_number}: {key}={value}");
+                        true
+                    },
+                };
+                if !valid {
+                    log::error!("Invalid statedef value at line {line_number}: {key}={value}");
+                }
+            },
+            DefLine::Simple(value) => log::error!("Unknown statedef value at line {line_number}: {value}"),
+        }
+    }
+    state_def
+}
+
+fn read_state_controller(label: String, category: Category) -> Result<StateController, String> {
+    let mut controller_type = None;
+    let mut triggers = Triggers::default();
+    let mut parameters = HashMap::new();
+    let mut persistent = 1;
+    let mut ignore_hit_pause = false;
+    for (line_number, line) in category.into_lines() {
+        lazy_static! {
+            static ref REGEX_TRIGGER: Regex = Regex::new(r"^trigger([0-9]+)$").unwrap();
+        }
+        match line {
+            DefLine::KeyValue(key, value) => {
+                let key_name = key.to_lowercase();
+                if key_name == "type" {
+                    controller_type = Some(ControllerType::from_name(&value));
+                }
+                else if key_name == "triggerall" {
+                    triggers.push(None, read_trigger(line_number, &value));
+                }
+                else if let Some(c) = REGEX_TRIGGER.captures(&key_name) {
+                    match c[1].parse::<usize>() {
+                        Ok(number) if number > 0 => triggers.push(Some(number), read_trigger(line_number, &value)),
+                        _ => log::error!("Invalid trigger number at line {line_number}: {key}"),
+                    }
+                }
+                else if key_name == "persistent" {
+                    match parse_int(&value) {
+                        Some(v) => persistent = v,
+                        None => log::error!("Invalid persistent value at line {line_number}: {value}"),
+                    }
+                }
+                else if key_name == "ignorehitpause" {
+                    match parse_bool(&value) {
+                        Some(v) => ignore_hit_pause = v,
+                        None => log::error!("Invalid ignorehitpause value at line {line_number}: {value}"),
+                    }
+                }
+                else {
+                    parameters.insert(key_name, Parameter::new(value));
+                }
+            },
+            DefLine::Simple(value) => log::error!("Unknown state controller value at line {line_number}: {value}"),
+        }
+    }
+    let controller_type = controller_type.ok_or_else(|| format!("Missing type for state controller \"{label}\""))?;
+    if let ControllerType::Unknown(name) = &controller_type {
+        log::warn!("Unknown state controller type \"{name}\" for state controller \"{label}\"");
+    }
+    // the trigger groups must start from trigger1 and be consecutive
+    if let Some(missing_group) = (1..).zip(triggers.groups.keys()).find_map(|(expected, &number)| (number != expected).then_some(expected)) {
+        log::warn!("Missing trigger{missing_group} for state controller \"{label}\", ignoring the following triggers");
+        triggers.groups.split_off(&missing_group);
+    }
+    if triggers.groups.is_empty() {
+        Err(format!("Missing trigger1 for state controller \"{label}\""))?;
+    }
+    Ok(StateController {
+        label,
+        controller_type,
+        triggers,
+        parameters,
+        persistent,
+        ignore_hit_pause,
+    })
+}
+
+fn read_trigger(line_number: u64, condition: &str) -> Expression {
+    parse_expression(condition).unwrap_or_else(|e| {
+        log::error!("Invalid trigger at line {line_number}: {e}");
+        // the controller cannot be triggered by an invalid condition
+        Expression::Int(0)
+    })
+}
+
+/// Parse an integer value, truncating decimal values.
+pub fn parse_int(value: &str) -> Option<i32> {
+    let value = value.trim();
+    value.parse().ok()
+        .or_else(|| value.parse::<f32>().ok().map(|f| f as i32))
+}
+
+pub fn parse_bool(value: &str) -> Option<bool> {
+    parse_int(value).map(|v| v != 0)
+}
+
+/// Parse a pair of float values such as `x, y`. A missing second value is 0.
+pub fn parse_float_pair(value: &str) -> Option<(f32, f32)> {
+    let mut components = value.split(',').map(str::trim);
+    let x = components.next()?.parse().ok()?;
+    let y = match components.next() {
+        Some(y) if !y.is_empty() => y.parse().ok()?,
+        _ => 0.,
+    };
+    Some((x, y))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_cns_states() {
+        let cns = b"
+[Data]
+life = 1000
+
+; Stand
+[Statedef 0]
+type = S
+physics = S
+sprpriority = 0
+
+[State 0, 1]
+type = ChangeAnim
+trigger1 = Anim != 0 && Anim != 5
+trigger2 = Anim = 5 && AnimTime = 0 ;Turn anim over
+value = 0
+
+[State 0, 2]
+type = VelSet
+trigger1 = Time = 0
+y = 0
+
+; Walk
+[Statedef 20]
+type    = S
+physics = S
+sprpriority = 0
+ctrl = 1
+velset = 2.4, -0.5
+anim = 20
+
+[State 20, Walk forward]
+type = VelSet
+triggerall = command = \"holdfwd\"
+trigger1 = 1
+trigger3 = 1
+trigger4000000000 = 1
+x = const(velocity.walk.fwd.x)
+persistent = 0
+";
+        let states = read_cns_file(Cursor::new(cns), "test");
+        assert_eq!(2, states.len());
+        let stand = &states[&0];
+        assert_eq!(StateType::Standing, stand.state_type);
+        assert_eq!(MoveType::Idle, stand.move_type);
+        assert_eq!(Physics::Standing, stand.physics);
+        assert_eq!(Some(0), stand.sprpriority);
+        assert_eq!(None, stand.anim);
+        assert_eq!(2, stand.controllers.len());
+        assert_eq!(ControllerType::ChangeAnim, stand.controllers[0].controller_type);
+        assert_eq!(
+            vec![vec![parse_expression("Anim != 0 && Anim != 5").unwrap()], vec![parse_expression("Anim = 5 && AnimTime = 0").unwrap()]],
+            stand.controllers[0].triggers.groups.values().cloned().collect::<Vec<_>>()
+        );
+        assert_eq!(Some("0"), stand.controllers[0].parameter("value"));
+        assert_eq!(ControllerType::VelSet, stand.controllers[1].controller_type);
+        let walk = &states[&20];
+        assert_eq!(Some(true), walk.ctrl);
+        assert_eq!(Some((2.4, -0.5)), walk.velset);
+        assert_eq!(Some(20), walk.anim);
+        let walk_controller = &walk.controllers[0];
+        assert_eq!("Walk forward", walk_controller.label);
+        assert_eq!(vec![Expression::Command("holdfwd".to_owned())], walk_controller.triggers.all);
+        // trigger3 and the following triggers are ignored without trigger2
+        assert_eq!(1, walk_controller.triggers.groups.len());
+        assert_eq!(0, walk_controller.persistent);
+        assert_eq!(Some("const(velocity.walk.fwd.x)"), walk_controller.parameter("x"));
+        assert_eq!(Some(&Expression::Const("velocity.walk.fwd.x".to_owned())), walk_controller.expression("x"));
+    }
 }
@@ -0,0 +1,75 @@
+use crate::game::mugen::trigger::{TriggerContext, Value};
+use super::{ControllerType, SoundReference, StateController};
+
+/// Horizontal position of a sound played by a player
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SoundPan {
+    /// `pan`: offset from the player towards its front
+    Relative(f32),
+    /// `abspan`: offset from the center of the screen
+    Absolute(f32),
+}
+
+/// Sound action requested by a PlaySnd, StopSnd or SndPan controller, handled by the fight.
+#[derive(Clone, PartialEq, Debug)]
+pub enum SoundCommand {
+    Play {
+        sound: SoundReference,
+        /// Channel of the player, or `None` to play the sound on a free channel
+        channel: Option<i32>,
+        low_priority: bool,
+        /// Volume multiplier, from the `volumescale` percentage
+        volume: f32,
+        frequency_multiplier: f32,
+        looping: bool,
+        pan: SoundPan,
+    },
+    Stop {
+        /// Channel to stop, or `None` for all the channels of the player
+        channel: Option<i32>,
+    },
+    Pan {
+        channel: i32,
+        pan: SoundPan,
+    },
+}
+
+/// Read the parameters of a sound controller
+pub fn read_sound_command(controller: &StateController, context: &dyn TriggerContext) -> Option<SoundCommand> {
+    let value = |key: &str| controller.expression(key).and_then(|expression| expression.evaluate(context));
+    // -1 for no channel
+    let channel = value("channel").map(Value::as_int).filter(|&channel| channel >= 0);
+    let pan = match (value("pan"), value("abspan")) {
+        (_, Some(abs_pan)) => SoundPan::Absolute(abs_pan.as_float()),
+        (pan, None) => SoundPan::Relative(pan.map(Value::as_float).unwrap_or(0.)),
+    };
+    match controller.controller_type {
+        ControllerType::PlaySnd => Some(SoundCommand::Play {
+            sound: controller.parameter("value").and_then(read_own_sound)?,
+            channel,
+            low_priority: value("lowpriority").map(|l| l.as_int() != 0).unwrap_or(false),
+            volume: value("volumescale").map(|v| v.as_float() / 100.).unwrap_or(1.),
+            frequency_multiplier: value("freqmul").map(Value::as_float).unwrap_or(1.),
+            looping: value("loop").map(|l| l.as_int() != 0).unwrap_or(false),
+            pan,
+        }),
+        ControllerType::StopSnd => Some(SoundCommand::Stop { channel }),
+        ControllerType::SndPan => Some(SoundCommand::Pan { channel: channel?, pan }),
+        _ => None,
+    }
+}
+
+/// Read the sound of a PlaySnd controller: `value = 5, 2` for a sound of the player, or `value = F5, 2` for a common sound
+fn read_own_sound(text: &str) -> Option<SoundReference> {
+    let text = text.trim();
+    let (own, text) = match text.strip_prefix(['f', 'F']) {
+        Some(rest) => (false, rest),
+        None => (true, text),
+    };
+    let (group, number) = text.split_once(',').unwrap_or((text, "0"));
+    Some(SoundReference {
+        own,
+        group: group.trim().parse().ok()?,
+        number: number.trim().parse().ok()?,
+    })
+}
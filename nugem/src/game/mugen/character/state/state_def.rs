@@ -0,0 +1,321 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::game::mugen::trigger::{parse_expression_list, Expression};
+
+/// All the state definitions of a character, indexed by state number.
+pub type States = BTreeMap<i32, StateDef>;
+
+/// `type` parameter of a state definition: the posture of the character.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
+pub enum StateType {
+    Standing,
+    Crouching,
+    Air,
+    Lying,
+    Unchanged,
+}
+
+/// `movetype` parameter of a state definition.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
+pub enum MoveType {
+    Idle,
+    Attack,
+    BeingHit,
+    Unchanged,
+}
+
+/// `physics` parameter of a state definition.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
+pub enum Physics {
+    Standing,
+    Crouching,
+    Air,
+    None,
+    Unchanged,
+}
+
+impl StateType {
+    pub fn from_symbol(symbol: &str) -> Option<StateType> {
+        match symbol.trim().to_lowercase().as_str() {
+            "s" => Some(StateType::Standing),
+            "c" => Some(StateType::Crouching),
+            "a" => Some(StateType::Air),
+            "l" => Some(StateType::Lying),
+            "u" => Some(StateType::Unchanged),
+            _ => None,
+        }
+    }
+    pub fn symbol(self) -> &'static str {
+        match self {
+            StateType::Standing => "S",
+            StateType::Crouching => "C",
+            StateType::Air => "A",
+            StateType::Lying => "L",
+            StateType::Unchanged => "U",
+        }
+    }
+}
+
+impl MoveType {
+    pub fn from_symbol(symbol: &str) -> Option<MoveType> {
+        match symbol.trim().to_lowercase().as_str() {
+            "i" => Some(MoveType::Idle),
+            "a" => Some(MoveType::Attack),
+            "h" => Some(MoveType::BeingHit),
+            "u" => Some(MoveType::Unchanged),
+            _ => None,
+        }
+    }
+    pub fn symbol(self) -> &'static str {
+        match self {
+            MoveType::Idle => "I",
+            MoveType::Attack => "A",
+            MoveType::BeingHit => "H",
+            MoveType::Unchanged => "U",
+        }
+    }
+}
+
+impl Physics {
+    pub fn from_symbol(symbol: &str) -> Option<Physics> {
+        match symbol.trim().to_lowercase().as_str() {
+            "s" => Some(Physics::Standing),
+            "c" => Some(Physics::Crouching),
+            "a" => Some(Physics::Air),
+            "n" => Some(Physics::None),
+            "u" => Some(Physics::Unchanged),
+            _ => None,
+        }
+    }
+}
+
+/// A `[Statedef]` block and the `[State]` controllers that follow it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct StateDef {
+    pub number: i32,
+    pub state_type: StateType,
+    pub move_type: MoveType,
+    pub physics: Physics,
+    /// Animation to change to when entering the state
+    pub anim: Option<i32>,
+    /// Control flag set when entering the state
+    pub ctrl: Option<bool>,
+    /// Velocity set when entering the state
+    pub velset: Option<(f32, f32)>,
+    /// Power added when entering the state
+    pub poweradd: Option<i32>,
+    /// Air juggle points required by the attacks of the state
+    pub juggle: Option<i32>,
+    /// If true, the character turns to face the opponent when entering the state
+    pub facep2: bool,
+    /// If true, the active HitDef is kept when entering the state
+    pub hitdefpersist: bool,
+    /// If true, the move hit information is kept when entering the state
+    pub movehitpersist: bool,
+    /// If true, the hit counter is kept when entering the state
+    pub hitcountpersist: bool,
+    /// Drawing priority set when entering the state
+    pub sprpriority: Option<i32>,
+    /// State controllers, in the order in which they are run
+    pub controllers: Vec<StateController>,
+}
+
+impl StateDef {
+    pub fn new(number: i32) -> StateDef {
+        StateDef {
+            number,
+            state_type: StateType::Standing,
+            move_type: MoveType::Idle,
+            physics: Physics::None,
+            anim: None,
+            ctrl: None,
+            velset: None,
+            poweradd: None,
+            juggle: None,
+            facep2: false,
+            hitdefpersist: false,
+            movehitpersist: false,
+            hitcountpersist: false,
+            sprpriority: None,
+            controllers: Vec::new(),
+        }
+    }
+}
+
+/// A `[State]` block: a controller run when its triggers are true.
+#[derive(Clone, PartialEq, Debug)]
+pub struct StateController {
+    /// Label of the controller in the state block name: `[State 200, label]`
+    pub label: String,
+    pub controller_type: ControllerType,
+    pub triggers: Triggers,
+    /// Parameters of the controller, with lowercase keys
+    pub parameters: HashMap<String, Parameter>,
+    /// 0: run once per state entry, 1: run every time the triggers are true, n: run once every n times the triggers are true
+    pub persistent: i32,
+    pub ignore_hit_pause: bool,
+}
+
+impl StateController {
+    /// Text of a parameter
+    pub fn parameter(&self, key: &str) -> Option<&str> {
+        self.parameters.get(key).map(|parameter| parameter.text.as_str())
+    }
+    /// First expression of a parameter
+    pub fn expression(&self, key: &str) -> Option<&Expression> {
+        self.expressions(key).first()
+    }
+    /// Comma-separated expressions of a parameter, such as `pausetime = 10, 12`
+    pub fn expressions(&self, key: &str) -> &[Expression] {
+        self.parameters.get(key).map(|parameter| &parameter.expressions[..]).unwrap_or_default()
+    }
+}
+
+/// Value of a state controller parameter.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Parameter {
+    pub text: String,
+    /// Expressions of the value, empty if the value is not a list of expressions, such as `attr = S, NA`
+    pub expressions: Vec<Expression>,
+}
+
+impl Parameter {
+    pub fn new(text: String) -> Parameter {
+        let expressions = parse_expression_list(&text).unwrap_or_default();
+        Parameter {
+            text,
+            expressions,
+        }
+    }
+}
+
+/// Trigger conditions of a state controller.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Triggers {
+    /// `triggerall` conditions: all of them must be true
+    pub all: Vec<Expression>,
+    /// `triggerN` conditions, grouped by number starting from `trigger1`: the controller is run if all the conditions of one of the groups are true
+    pub groups: BTreeMap<usize, Vec<Expression>>,
+}
+
+impl Triggers {
+    pub fn push(&mut self, trigger_number: Option<usize>, condition: Expression) {
+        match trigger_number {
+            None => self.all.push(condition),
+            Some(number) => self.groups.entry(number).or_default().push(condition),
+        }
+    }
+}
+
+macro_rules! controller_types {
+    ($($variant:ident),* $(,)?) => {
+        /// Type of a state controller.
+        #[derive(Clone, PartialEq, Eq, Debug, Hash)]
+        pub enum ControllerType {
+            $($variant,)*
+            Unknown(String),
+        }
+
+        impl ControllerType {
+            pub fn from_name(name: &str) -> ControllerType {
+                let lowercase_name = name.trim().to_lowercase();
+                $(
+                    if lowercase_name == stringify!($variant).to_lowercase() {
+                        return ControllerType::$variant;
+                    }
+                )*
+                ControllerType::Unknown(name.trim().to_owned())
+            }
+        }
+    };
+}
+
+controller_types!(
+    AfterImage,
+    AfterImageTime,
+    AllPalFX,
+    AngleAdd,
+    AngleDraw,
+    AngleMul,
+    AngleSet,
+    AppendToClipboard,
+    AssertSpecial,
+    AttackDist,
+    AttackMulSet,
+    BGPalFX,
+    BindToParent,
+    BindToRoot,
+    BindToTarget,
+    ChangeAnim,
+    ChangeAnim2,
+    ChangeState,
+    ClearClipboard,
+    CtrlSet,
+    DefenceMulSet,
+    DestroySelf,
+    DisplayToClipboard,
+    EnvColor,
+    EnvShake,
+    Explod,
+    ExplodBindTime,
+    FallEnvShake,
+    ForceFeedback,
+    GameMakeAnim,
+    Gravity,
+    Helper,
+    HitAdd,
+    HitBy,
+    HitDef,
+    HitFallDamage,
+    HitFallSet,
+    HitFallVel,
+    HitOverride,
+    HitVelSet,
+    LifeAdd,
+    LifeSet,
+    MakeDust,
+    ModifyExplod,
+    MoveHitReset,
+    NotHitBy,
+    Null,
+    Offset,
+    PalFX,
+    ParentVarAdd,
+    ParentVarSet,
+    Pause,
+    PlayerPush,
+    PlaySnd,
+    PosAdd,
+    PosFreeze,
+    PosSet,
+    PowerAdd,
+    PowerSet,
+    Projectile,
+    RemapPal,
+    RemoveExplod,
+    ReversalDef,
+    ScreenBound,
+    SelfState,
+    SndPan,
+    SprPriority,
+    StateTypeSet,
+    StopSnd,
+    SuperPause,
+    TargetBind,
+    TargetDrop,
+    TargetFacing,
+    TargetLifeAdd,
+    TargetPowerAdd,
+    TargetState,
+    TargetVelAdd,
+    TargetVelSet,
+    Trans,
+    Turn,
+    VarAdd,
+    VarRandom,
+    VarRangeSet,
+    VarSet,
+    VelAdd,
+    VelMul,
+    VelSet,
+    Width,
+);
@@ -0,0 +1,715 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::game::mugen::character::air::Animations;
+use crate::game::mugen::trigger::{TriggerContext, Value};
+use super::{ControllerType, MoveType, Physics, PlayerState, StateController, StateDef, StateType, States, Triggers};
+use super::{apply_physics, read_env_shake, read_hit_def, read_pal_fx, read_remap_pal, read_screen_bound, read_sound_command, read_trans, PlayerContext, TriggerEnvironment, FVAR_COUNT, LANDING_STATE, SYSVAR_COUNT, VAR_COUNT};
+
+/// States run before the current state at every tick, in this order
+const SPECIAL_STATES: [i32; 3] = [-3, -2, -1];
+
+/// Animations of a character turning to face the opponent, standing and crouching
+const TURN_ANIMATIONS: [u32; 2] = [5, 6];
+
+/// Maximum number of state changes in a single tick, to avoid infinite loops between states
+const MAX_STATE_CHANGES_PER_TICK: usize = 64;
+
+/// Runs the state controllers of a player.
+#[derive(Clone, Debug, Default)]
+pub struct StateMachine {
+    /// Number of times the triggers of a controller were true since entering the current state, by state number and controller index
+    persistence_counters: HashMap<(i32, usize), i32>,
+}
+
+/// Change of state requested by a state controller
+struct StateChange {
+    state_number: i32,
+    ctrl: Option<bool>,
+    anim: Option<i32>,
+}
+
+impl StateMachine {
+    pub fn new() -> StateMachine {
+        Self::default()
+    }
+
+    /// Run one tick of the state machine: the special states, then the current state, then the physics and the animation.
+    ///
+    /// During a hit pause, only the controllers with `ignorehitpause` run, and the player does not move. A player who
+    /// can turn first turns to face the opponent behind it.
+    pub fn tick(&mut self, player: &mut PlayerState, states: &States, animations: &Animations, environment: &TriggerEnvironment) {
+        let paused = player.hit_pause > 0;
+        let opponent_x = environment.opponent.map(|(opponent, _, _, _)| opponent.position.0);
+        if let Some(opponent_x) = opponent_x.filter(|_| !paused && player.can_turn()) {
+            turn(player, opponent_x, animations);
+        }
+        // the Trans and ScreenBound controllers have to run at every tick to keep their effect
+        player.trans = None;
+        player.screen_bound = true;
+        player.camera_follow = (true, true);
+        if player.pal_fx.as_mut().is_some_and(|pal_fx| !pal_fx.tick()) {
+            player.pal_fx = None;
+        }
+        let mut state_change = None;
+        for special_state_number in SPECIAL_STATES {
+            if let Some(special_state) = states.get(&special_state_number) {
+                state_change = self.run_state(player, special_state, animations, environment, paused);
+                if state_change.is_some() {
+                    break;
+                }
+            }
+        }
+        let mut state_change_count = 0;
+        loop {
+            if let Some(change) = state_change.take() {
+                state_change_count += 1;
+                if state_change_count > MAX_STATE_CHANGES_PER_TICK {
+                    log::error!("Too many state changes in one tick, stopping in state {0}", player.state_number);
+                    break;
+                }
+                self.change_state(player, change, states, animations, opponent_x);
+            }
+            match states.get(&player.state_number) {
+                Some(current_state) => state_change = self.run_state(player, current_state, animations, environment, paused),
+                None => break,
+            }
+            if state_change.is_none() {
+                break;
+            }
+        }
+        if paused {
+            player.hit_pause -= 1;
+            return;
+        }
+        if apply_physics(player, environment.constants) && states.contains_key(&LANDING_STATE) {
+            self.enter_state(player, LANDING_STATE, states, animations, opponent_x);
+        }
+        player.tick_animation();
+        player.state_time += 1;
+        for counter in [&mut player.move_contact, &mut player.move_hit, &mut player.move_guarded] {
+            if *counter > 0 {
+                *counter += 1;
+            }
+        }
+        for counter in [&mut player.get_hit.hit_time, &mut player.get_hit.fall_recover_time] {
+            if *counter > 0 {
+                *counter -= 1;
+            }
+        }
+    }
+
+    /// Enter the given state, applying the parameters of its state definition. The states with `facep2` turn the player
+    /// to face the horizontal position of the opponent.
+    pub fn enter_state(&mut self, player: &mut PlayerState, state_number: i32, states: &States, animations: &Animations, opponent_x: Option<f32>) {
+        let change = StateChange {
+            state_number,
+            ctrl: None,
+            anim: None,
+        };
+        self.change_state(player, change, states, animations, opponent_x);
+    }
+
+    fn change_state(&mut self, player: &mut PlayerState, change: StateChange, states: &States, animations: &Animations, opponent_x: Option<f32>) {
+        player.previous_state_number = player.state_number;
+        player.state_number = change.state_number;
+        player.state_time = 0;
+        self.persistence_counters.clear();
+        if let Some(ctrl) = change.ctrl {
+            player.ctrl = ctrl;
+        }
+        if let Some(anim) = change.anim {
+            change_animation(player, anim, animations);
+        }
+        match states.get(&change.state_number) {
+            Some(state_def) => apply_state_def(player, state_def, animations, opponent_x),
+            None => log::error!("State {0} not found", change.state_number),
+        }
+    }
+
+    /// Run the controllers of a state until one of them changes the state
+    fn run_state(&mut self, player: &mut PlayerState, state_def: &StateDef, animations: &Animations, environment: &TriggerEnvironment, paused: bool) -> Option<StateChange> {
+        for (controller_index, controller) in state_def.controllers.iter().enumerate() {
+            if paused && !controller.ignore_hit_pause {
+                continue;
+            }
+            if triggered(&controller.triggers, &PlayerContext::new(player, animations, *environment)) {
+                let counter = self.persistence_counters.entry((state_def.number, controller_index)).or_insert(0);
+                if persistence_allows(controller.persistent, counter) {
+                    let state_change = run_controller(player, controller, animations, environment);
+                    if state_change.is_some() {
+                        return state_change;
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+fn triggered(triggers: &Triggers, context: &dyn TriggerContext) -> bool {
+    triggers.all.iter().all(|condition| condition.is_true(context))
+        && triggers.groups.values().any(|group| group.iter().all(|condition| condition.is_true(context)))
+}
+
+/// Check if a controller whose triggers are true can be run, according to its `persistent` parameter
+fn persistence_allows(persistent: i32, counter: &mut i32) -> bool {
+    let allowed = match persistent {
+        0 => *counter == 0,
+        n if n > 1 => *counter % n == 0,
+        _ => true,
+    };
+    *counter += 1;
+    allowed
+}
+
+fn apply_state_def(player: &mut PlayerState, state_def: &StateDef, animations: &Animations, opponent_x: Option<f32>) {
+    if !state_def.hitdefpersist {
+        player.hit_def = None;
+    }
+    if !state_def.movehitpersist {
+        player.move_contact = 0;
+        player.move_hit = 0;
+        player.move_guarded = 0;
+    }
+    if !state_def.hitcountpersist {
+        player.hit_count = 0;
+        player.unique_hit_count = 0;
+    }
+    if state_def.state_type != StateType::Unchanged {
+        player.state_type = state_def.state_type;
+    }
+    if state_def.move_type != MoveType::Unchanged {
+        player.move_type = state_def.move_type;
+    }
+    if state_def.physics != Physics::Unchanged {
+        player.physics = state_def.physics;
+    }
+    if let Some(anim) = state_def.anim {
+        change_animation(player, anim, animations);
+    }
+    if let Some(ctrl) = state_def.ctrl {
+        player.ctrl = ctrl;
+    }
+    if let Some(velocity) = state_def.velset {
+        player.velocity = velocity;
+    }
+    if let Some(power) = state_def.poweradd {
+        player.add_power(power);
+    }
+    if let Some(juggle) = state_def.juggle {
+        player.juggle = juggle;
+    }
+    if let Some(sprite_priority) = state_def.sprpriority {
+        player.sprite_priority = sprite_priority;
+    }
+    if let Some(opponent_x) = opponent_x.filter(|_| state_def.facep2) {
+        player.face(opponent_x);
+    }
+}
+
+/// Turn a player to face the opponent behind it, with the turning animation of its state type if the character has one
+fn turn(player: &mut PlayerState, opponent_x: f32, animations: &Animations) {
+    if player.face(opponent_x) {
+        let turn_animation = match player.state_type {
+            StateType::Crouching => TURN_ANIMATIONS[1],
+            _ => TURN_ANIMATIONS[0],
+        };
+        player.change_animation(turn_animation, animations);
+    }
+}
+
+fn change_animation(player: &mut PlayerState, anim: i32, animations: &Animations) {
+    let changed = u32::try_from(anim).map(|anim| player.change_animation(anim, animations)).unwrap_or(false);
+    if !changed {
+        log::error!("Animation {anim} not found for state {0}", player.state_number);
+    }
+}
+
+/// Run a state controller and return the state change it requests, if any
+fn run_controller(player: &mut PlayerState, controller: &StateController, animations: &Animations, environment: &TriggerEnvironment) -> Option<StateChange> {
+    let value = |player: &PlayerState, key: &str| controller.expression(key).and_then(|e| e.evaluate(&PlayerContext::new(player, animations, *environment)));
+    let integer = |player: &PlayerState, key: &str| value(player, key).map(Value::as_int);
+    match &controller.controller_type {
+        ControllerType::Null => (),
+        ControllerType::ChangeState | ControllerType::SelfState => {
+            match integer(player, "value") {
+                Some(state_number) => return Some(StateChange {
+                    state_number,
+                    ctrl: integer(player, "ctrl").map(|ctrl| ctrl != 0),
+                    anim: integer(player, "anim"),
+                }),
+                None => log::error!("Missing value for state controller \"{0}\" in state {1}", controller.label, player.state_number),
+            }
+        },
+        ControllerType::ChangeAnim | ControllerType::ChangeAnim2 => {
+            if let Some(anim) = integer(player, "value") {
+                change_animation(player, anim, animations);
+                if let Some(element) = integer(player, "elem") {
+                    if !usize::try_from(element).map(|element| player.set_animation_element(element)).unwrap_or(false) {
+                        log::error!("Element {element} not found in animation {anim}");
+                    }
+                }
+            }
+        },
+        ControllerType::CtrlSet => {
+            if let Some(ctrl) = integer(player, "value") {
+                player.ctrl = ctrl != 0;
+            }
+        },
+        ControllerType::VarSet | ControllerType::VarAdd => {
+            let add = controller.controller_type == ControllerType::VarAdd;
+            for (variable, value) in controller_variables(controller, &PlayerContext::new(player, animations, *environment)) {
+                set_variable(player, variable, value, add);
+            }
+        },
+        ControllerType::VarRangeSet => {
+            let first = integer(player, "first").unwrap_or(0).max(0) as usize;
+            if let Some(value) = integer(player, "value") {
+                let last = integer(player, "last").map(|l| l.max(0) as usize).unwrap_or(VAR_COUNT - 1).min(VAR_COUNT - 1);
+                for var in player.vars.iter_mut().take(last + 1).skip(first) {
+                    *var = value;
+                }
+            }
+            else if let Some(value) = value(player, "fvalue").map(Value::as_float) {
+                let last = integer(player, "last").map(|l| l.max(0) as usize).unwrap_or(FVAR_COUNT - 1).min(FVAR_COUNT - 1);
+                for fvar in player.fvars.iter_mut().take(last + 1).skip(first) {
+                    *fvar = value;
+                }
+            }
+        },
+        ControllerType::StateTypeSet => {
+            if let Some(state_type) = controller.parameter("statetype").and_then(StateType::from_symbol) {
+                player.state_type = state_type;
+            }
+            if let Some(move_type) = controller.parameter("movetype").and_then(MoveType::from_symbol) {
+                player.move_type = move_type;
+            }
+            if let Some(physics) = controller.parameter("physics").and_then(Physics::from_symbol) {
+                player.physics = physics;
+            }
+        },
+        ControllerType::Turn => player.facing = player.facing.turned(),
+        ControllerType::PowerAdd => {
+            if let Some(power) = integer(player, "value") {
+                player.add_power(power);
+            }
+        },
+        ControllerType::PowerSet => {
+            if let Some(power) = integer(player, "value") {
+                player.power = power.clamp(0, player.power_max);
+            }
+        },
+        ControllerType::LifeAdd => {
+            if let Some(life) = integer(player, "value") {
+                let kill = integer(player, "kill").map(|k| k != 0).unwrap_or(true);
+                let minimum_life = if kill { 0 } else { 1.min(player.life) };
+                player.set_life((player.life + life).max(minimum_life));
+            }
+        },
+        ControllerType::LifeSet => {
+            if let Some(life) = integer(player, "value") {
+                player.set_life(life);
+            }
+        },
+        ControllerType::VelSet | ControllerType::VelAdd | ControllerType::VelMul => {
+            let operation: fn(f32, f32) -> f32 = match controller.controller_type {
+                ControllerType::VelSet => |_, value| value,
+                ControllerType::VelAdd => |velocity, value| velocity + value,
+                _ => |velocity, value| velocity * value,
+            };
+            let (x, y) = (value(player, "x"), value(player, "y"));
+            if let Some(x) = x {
+                player.velocity.0 = operation(player.velocity.0, x.as_float());
+            }
+            if let Some(y) = y {
+                player.velocity.1 = operation(player.velocity.1, y.as_float());
+            }
+        },
+        ControllerType::PosSet => {
+            let (x, y) = (value(player, "x"), value(player, "y"));
+            if let Some(x) = x {
+                player.position.0 = x.as_float();
+            }
+            if let Some(y) = y {
+                player.position.1 = y.as_float();
+            }
+        },
+        ControllerType::PosAdd => {
+            let (x, y) = (value(player, "x"), value(player, "y"));
+            if let Some(x) = x {
+                player.position.0 += x.as_float() * player.facing_sign();
+            }
+            if let Some(y) = y {
+                player.position.1 += y.as_float();
+            }
+        },
+        ControllerType::HitDef => player.hit_def = Some(read_hit_def(controller, &PlayerContext::new(player, animations, *environment))),
+        ControllerType::PlaySnd | ControllerType::StopSnd | ControllerType::SndPan => {
+            let sound_command = read_sound_command(controller, &PlayerContext::new(player, animations, *environment));
+            match sound_command {
+                Some(sound_command) => player.sound_commands.push(sound_command),
+                None => log::error!("Invalid sound parameters for state controller \"{0}\" in state {1}", controller.label, player.state_number),
+            }
+        },
+        ControllerType::Gravity => player.velocity.1 += environment.constants.y_acceleration(),
+        ControllerType::SprPriority => {
+            if let Some(sprite_priority) = integer(player, "value") {
+                player.sprite_priority = sprite_priority;
+            }
+        },
+        ControllerType::PalFX => player.pal_fx = Some(read_pal_fx(controller, &PlayerContext::new(player, animations, *environment))),
+        ControllerType::RemapPal => {
+            match read_remap_pal(controller, &PlayerContext::new(player, animations, *environment)) {
+                // the palette 1,1 is the one selected for the player
+                Some(((1, 1), dest)) => player.remapped_palette = (dest != (1, 1)).then_some(dest),
+                Some((source, _)) => log::error!("Unsupported source palette {source:?} for state controller \"{0}\" in state {1}", controller.label, player.state_number),
+                None => log::error!("Missing dest parameter for state controller \"{0}\" in state {1}", controller.label, player.state_number),
+            }
+        },
+        ControllerType::Trans => {
+            match read_trans(controller, &PlayerContext::new(player, animations, *environment)) {
+                Some(trans) => player.trans = Some(trans),
+                None => log::error!("Invalid trans parameter for state controller \"{0}\" in state {1}", controller.label, player.state_number),
+            }
+        },
+        ControllerType::ScreenBound => {
+            (player.screen_bound, player.camera_follow) = read_screen_bound(controller, &PlayerContext::new(player, animations, *environment));
+        },
+        ControllerType::EnvShake => {
+            match read_env_shake(controller, &PlayerContext::new(player, animations, *environment)) {
+                Some(env_shake) => player.env_shake = Some(env_shake),
+                None => log::error!("Missing time parameter for state controller \"{0}\" in state {1}", controller.label, player.state_number),
+            }
+        },
+        other => log::trace!("Unsupported state controller type {other:?}"),
+    }
+    None
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Variable {
+    Var(usize),
+    FVar(usize),
+    SysVar(usize),
+    SysFVar(usize),
+}
+
+/// Values set by a VarSet or VarAdd controller: either with the `v`/`fv`/`sysvar`/`sysfvar` and `value` parameters, or with `var(n) = value` parameters
+fn controller_variables(controller: &StateController, context: &dyn TriggerContext) -> Vec<(Variable, f32)> {
+    lazy_static! {
+        static ref REGEX_VARIABLE: Regex = Regex::new(r"^(var|fvar|sysvar|sysfvar)\s*\(\s*([0-9]+)\s*\)$").unwrap();
+    }
+    let index = |key: &str| controller.expression(key).and_then(|e| e.evaluate(context)).and_then(|i| usize::try_from(i.as_int()).ok());
+    let value = |variable: Variable, parameter: &str| {
+        let value = controller.expression(parameter)?.evaluate(context)?;
+        match variable {
+            Variable::Var(_) | Variable::SysVar(_) => Some(value.as_int() as f32),
+            Variable::FVar(_) | Variable::SysFVar(_) => Some(value.as_float()),
+        }
+    };
+    let mut variables = Vec::new();
+    let explicit_variable = index("v").map(Variable::Var)
+        .or_else(|| index("fv").map(Variable::FVar))
+        .or_else(|| index("sysvar").map(Variable::SysVar))
+        .or_else(|| index("sysfvar").map(Variable::SysFVar));
+    if let Some(variable) = explicit_variable {
+        variables.extend(value(variable, "value").map(|v| (variable, v)));
+    }
+    for key in controller.parameters.keys() {
+        if let Some(c) = REGEX_VARIABLE.captures(key) {
+            if let Ok(i) = c[2].parse() {
+                let variable = match &c[1] {
+                    "var" => Variable::Var(i),
+                    "fvar" => Variable::FVar(i),
+                    "sysvar" => Variable::SysVar(i),
+                    _ => Variable::SysFVar(i),
+                };
+                variables.extend(value(variable, key).map(|v| (variable, v)));
+            }
+        }
+    }
+    variables
+}
+
+fn set_variable(player: &mut PlayerState, variable: Variable, value: f32, add: bool) {
+    fn assign<T: Copy + std::ops::Add<Output = T>>(slot: Option<&mut T>, value: T, add: bool) -> bool {
+        match slot {
+            Some(slot) => {
+                *slot = if add { *slot + value } else { value };
+                true
+            },
+            None => false,
+        }
+    }
+    let valid = match variable {
+        Variable::Var(i) => assign(player.vars.get_mut(i), value as i32, add),
+        Variable::FVar(i) => assign(player.fvars.get_mut(i), value, add),
+        Variable::SysVar(i) => assign(player.sys_vars.get_mut(i), value as i32, add),
+        Variable::SysFVar(i) => assign(player.sys_fvars.get_mut(i), value, add),
+    };
+    if !valid {
+        log::error!("Invalid variable {variable:?} (maximum indices: {VAR_COUNT} vars, {FVAR_COUNT} fvars, {SYSVAR_COUNT} sysvars)");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use crate::game::mugen::character::air::{Animation, AnimationFrame, AnimationSteps};
+    use crate::game::mugen::character::Constants;
+    use crate::game::mugen::character::state::{read_cns_file, Facing, PlayerInfo, SoundCommand, SoundPan, SoundReference};
+
+    fn animations(numbers: &[u32]) -> Animations {
+        numbers.iter().map(|&number| {
+            let frame = AnimationFrame::new(number as u16, 0, (0, 0), Some(1));
+            (number, Animation::new(vec![AnimationSteps::new(Vec::new(), vec![frame])], None))
+        }).collect()
+    }
+
+    #[test]
+    fn state_machine_tick() {
+        let cns = b"
+[Statedef -1]
+[State -1, go to 200]
+type = ChangeState
+trigger1 = var(0) = 1
+value = 200
+
+[Statedef 0]
+type = S
+ctrl = 1
+anim = 0
+
+[State 0, count]
+type = VarAdd
+trigger1 = 1
+v = 1
+value = 2
+
+[State 0, once]
+type = VarAdd
+trigger1 = 1
+var(2) = 1
+persistent = 0
+
+[State 0, every 3]
+type = VarAdd
+trigger1 = 1
+var(3) = 1
+persistent = 3
+
+[State 0, command]
+type = VarSet
+trigger1 = command = \"holdfwd\"
+var(5) = StateNo + Time + 100
+
+[Statedef 200]
+type = S
+movetype = A
+ctrl = 0
+anim = 200
+poweradd = 50
+
+[State 200, back to 0]
+type = ChangeState
+trigger1 = 1
+value = 0
+ctrl = 1
+";
+        let states = read_cns_file(Cursor::new(cns), "test");
+        let animations = animations(&[0, 200]);
+        let mut player = PlayerState::new(Facing::Right);
+        let mut state_machine = StateMachine::new();
+        state_machine.enter_state(&mut player, 0, &states, &animations, None);
+        assert_eq!(Some(0), player.animation_number());
+        for _ in 0..4 {
+            state_machine.tick(&mut player, &states, &animations, &TriggerEnvironment::default());
+        }
+        assert_eq!(0, player.state_number);
+        assert_eq!(4, player.state_time);
+        assert_eq!(8, player.vars[1]);
+        assert_eq!(1, player.vars[2]);
+        assert_eq!(2, player.vars[3]);
+        assert_eq!(0, player.power);
+        // state 200 immediately changes back to state 0, in the same tick
+        state_machine.enter_state(&mut player, 200, &states, &animations, None);
+        assert_eq!(MoveType::Attack, player.move_type);
+        assert!(!player.ctrl);
+        assert_eq!(50, player.power);
+        state_machine.tick(&mut player, &states, &animations, &TriggerEnvironment::default());
+        assert_eq!(0, player.state_number);
+        assert_eq!(200, player.previous_state_number);
+        assert_eq!(1, player.state_time);
+        assert_eq!(MoveType::Idle, player.move_type);
+        assert!(player.ctrl);
+        assert_eq!(Some(0), player.animation_number());
+        // counters are reset when entering a state
+        assert_eq!(2, player.vars[2]);
+        assert_eq!(0, player.vars[5]);
+        let commands = vec!["holdfwd".to_owned()];
+        let environment = TriggerEnvironment { commands: &commands, ..TriggerEnvironment::default() };
+        state_machine.tick(&mut player, &states, &animations, &environment);
+        assert_eq!(101, player.vars[5]);
+        // the -1 state changes to state 200
+        player.vars[0] = 1;
+        state_machine.tick(&mut player, &states, &animations, &TriggerEnvironment::default());
+        assert_eq!(0, player.state_number);
+        assert_eq!(200, player.previous_state_number);
+        assert_eq!(100, player.power);
+    }
+
+    #[test]
+    fn face_opponent() {
+        let cns = b"
+[Statedef 0]
+type = S
+ctrl = 1
+anim = 0
+
+[Statedef 100]
+type = S
+ctrl = 0
+facep2 = 1
+";
+        let states = read_cns_file(Cursor::new(cns), "test");
+        let animations = animations(&[0, 5]);
+        let constants = Constants::default();
+        let mut opponent = PlayerState::new(Facing::Right);
+        opponent.position.0 = -50.;
+        let environment = TriggerEnvironment { opponent: Some((&opponent, &animations, &constants, PlayerInfo::default())), ..TriggerEnvironment::default() };
+        let mut player = PlayerState::new(Facing::Right);
+        let mut state_machine = StateMachine::new();
+        state_machine.enter_state(&mut player, 0, &states, &animations, None);
+        // standing with control: the player turns to face the opponent behind it
+        state_machine.tick(&mut player, &states, &animations, &environment);
+        assert_eq!((Facing::Left, Some(5)), (player.facing, player.animation_number()));
+        // without control, the player keeps its facing
+        player.facing = Facing::Right;
+        player.ctrl = false;
+        state_machine.tick(&mut player, &states, &animations, &environment);
+        assert_eq!(Facing::Right, player.facing);
+        // facep2 turns the player when entering the state
+        state_machine.enter_state(&mut player, 100, &states, &animations, Some(opponent.position.0));
+        assert_eq!(Facing::Left, player.facing);
+        state_machine.enter_state(&mut player, 100, &states, &animations, Some(50.));
+        assert_eq!(Facing::Right, player.facing);
+    }
+
+    #[test]
+    fn jump_controllers() {
+        let cns = b"
+[Statedef 40]
+type = S
+physics = S
+
+[State 40, jump]
+type = VelSet
+trigger1 = Time = 0
+x = const(velocity.jump.fwd.x)
+y = const(velocity.jump.neu.y)
+
+[State 40, airborne]
+type = ChangeState
+trigger1 = Time = 1
+value = 50
+
+[Statedef 50]
+type = A
+physics = A
+
+[State 50, nudge]
+type = PosAdd
+trigger1 = Time = 0
+x = 10
+
+[Statedef 52]
+type = S
+physics = S
+";
+        let states = read_cns_file(Cursor::new(cns), "test");
+        let animations = animations(&[]);
+        let environment = TriggerEnvironment::default();
+        let mut player = PlayerState::new(Facing::Left);
+        let mut state_machine = StateMachine::new();
+        state_machine.enter_state(&mut player, 40, &states, &animations, None);
+        state_machine.tick(&mut player, &states, &animations, &environment);
+        assert_eq!((-2.5, -8.4), player.position);
+        state_machine.tick(&mut player, &states, &animations, &environment);
+        assert_eq!(50, player.state_number);
+        // the stand friction applied to the x velocity during the first tick
+        assert_eq!(-2.5 - 10. - 2.5 * 0.85, player.position.0);
+        let mut ticks = 0;
+        while player.state_number == 50 {
+            state_machine.tick(&mut player, &states, &animations, &environment);
+            ticks += 1;
+            assert!(ticks < 100);
+        }
+        assert_eq!(LANDING_STATE, player.state_number);
+        assert_eq!(0., player.position.1);
+    }
+
+    #[test]
+    fn sound_controllers() {
+        let cns = b"
+[Statedef 200]
+[State 200, swing]
+type = PlaySnd
+trigger1 = Time = 0
+value = F5, 2
+channel = 0
+lowpriority = 1
+volumescale = 50
+pan = 20
+
+[State 200, voice]
+type = PlaySnd
+trigger1 = Time = 0
+value = 100, 1
+
+[State 200, move]
+type = SndPan
+trigger1 = Time = 1
+channel = 0
+abspan = -40
+
+[State 200, stop]
+type = StopSnd
+trigger1 = Time = 1
+channel = -1
+";
+        let states = read_cns_file(Cursor::new(cns), "test");
+        let animations = animations(&[]);
+        let environment = TriggerEnvironment::default();
+        let mut player = PlayerState::new(Facing::Right);
+        let mut state_machine = StateMachine::new();
+        state_machine.enter_state(&mut player, 200, &states, &animations, None);
+        state_machine.tick(&mut player, &states, &animations, &environment);
+        assert_eq!(vec![
+            SoundCommand::Play {
+                sound: SoundReference { own: false, group: 5, number: 2 },
+                channel: Some(0),
+                low_priority: true,
+                volume: 0.5,
+                frequency_multiplier: 1.,
+                looping: false,
+                pan: SoundPan::Relative(20.),
+            },
+            SoundCommand::Play {
+                sound: SoundReference { own: true, group: 100, number: 1 },
+                channel: None,
+                low_priority: false,
+                volume: 1.,
+                frequency_multiplier: 1.,
+                looping: false,
+                pan: SoundPan::Relative(0.),
+            },
+        ], std::mem::take(&mut player.sound_commands));
+        state_machine.tick(&mut player, &states, &animations, &environment);
+        assert_eq!(vec![
+            SoundCommand::Pan { channel: 0, pan: SoundPan::Absolute(-40.) },
+            SoundCommand::Stop { channel: None },
+        ], player.sound_commands);
+    }
+}
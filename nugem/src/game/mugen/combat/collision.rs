@@ -0,0 +1,54 @@
+use crate::game::mugen::character::air::CollisionType;
+use crate::game::mugen::character::state::PlayerState;
+
+/// Axis-aligned rectangle in stage coordinates.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rectangle {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Rectangle {
+    /// Rectangle from two opposite corners
+    pub fn from_corners((x1, y1): (f32, f32), (x2, y2): (f32, f32)) -> Rectangle {
+        Rectangle {
+            left: x1.min(x2),
+            top: y1.min(y2),
+            right: x1.max(x2),
+            bottom: y1.max(y2),
+        }
+    }
+    pub fn intersects(&self, other: &Rectangle) -> bool {
+        self.left <= other.right && other.left <= self.right && self.top <= other.bottom && other.top <= self.bottom
+    }
+}
+
+/// Collision boxes of a type in the current animation frame of a player, in stage coordinates.
+///
+/// The boxes are mirrored when the player faces left, and when the frame is flipped.
+pub fn collision_rectangles(player: &PlayerState, collision_type: CollisionType) -> Vec<Rectangle> {
+    let animator = match player.animator() {
+        Some(animator) => animator,
+        None => return Vec::new(),
+    };
+    let (flip_x, flip_y) = animator.current_frame().map(|frame| frame.flip).unwrap_or_default();
+    let x_sign = if flip_x { -player.facing_sign() } else { player.facing_sign() };
+    let y_sign = if flip_y { -1. } else { 1. };
+    let (x, y) = player.position;
+    animator.current_collisions().iter()
+        .filter(|collision_box| collision_box.collision_type() == collision_type)
+        .map(|collision_box| {
+            let [x1, y1, x2, y2] = collision_box.coordinates().map(f32::from);
+            Rectangle::from_corners((x + x1 * x_sign, y + y1 * y_sign), (x + x2 * x_sign, y + y2 * y_sign))
+        })
+        .collect()
+}
+
+/// Check if the attack boxes of a player touch the normal boxes of another player
+pub fn attack_collides(attacker: &PlayerState, defender: &PlayerState) -> bool {
+    let attack_rectangles = collision_rectangles(attacker, CollisionType::Attack);
+    let defender_rectangles = collision_rectangles(defender, CollisionType::Normal);
+    attack_rectangles.iter().any(|attack| defender_rectangles.iter().any(|body| attack.intersects(body)))
+}
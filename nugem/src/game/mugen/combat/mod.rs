@@ -0,0 +1,5 @@
+mod collision;
+pub use self::collision::*;
+
+mod resolver;
+pub use self::resolver::*;
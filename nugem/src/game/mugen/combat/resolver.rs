@@ -0,0 +1,284 @@
+use std::ops::RangeInclusive;
+use crate::game::mugen::character::state::{GetHitVars, HitDef, MoveType, PlayerState, SoundReference, SparkReference, StateType};
+use super::attack_collides;
+
+/// States of a player guarding
+const GUARD_STATES: RangeInclusive<i32> = 120..=155;
+
+/// States entered when guarding a hit, standing, crouching and in the air
+const GUARD_HIT_STATES: [i32; 3] = [150, 152, 154];
+
+/// States entered when hit, standing, crouching, in the air and lying down
+const GET_HIT_STATES: [i32; 4] = [5000, 5010, 5020, 5080];
+
+/// Attack of a player that made contact with the other player.
+#[derive(Clone, PartialEq, Debug)]
+pub struct HitResult {
+    /// Index of the attacking player
+    pub attacker: usize,
+    /// Index of the player hit
+    pub defender: usize,
+    pub guarded: bool,
+    /// State the attacker should enter, from the `p1stateno` parameter of the HitDef
+    pub attacker_state: Option<i32>,
+    /// State the defender should enter
+    pub defender_state: i32,
+    /// Spark of the `sparkno` or `guard.sparkno` parameter, `None` for the default spark of the attacker
+    pub spark: Option<SparkReference>,
+    /// Position of the spark in the coordinates of the players
+    pub spark_position: (f32, f32),
+    pub sound: Option<SoundReference>,
+}
+
+/// Find the attacks of the players that make contact during the current tick, and apply their effects to both players.
+///
+/// The attack boxes of the active HitDef of each player are tested against the normal boxes of the other player. Both
+/// attacks are resolved when the players hit each other in the same tick. A HitDef makes contact only once: it is
+/// removed from the attacker after a hit or a guard. `holding_back` tells if each player is holding the back
+/// direction to guard.
+///
+/// The state changes of the results are left to the caller: the state numbers refer to the states of the player
+/// entering them.
+pub fn resolve_hits(mut players: [&mut PlayerState; 2], holding_back: [bool; 2]) -> Vec<HitResult> {
+    let contacts: Vec<(usize, usize, bool)> = [(0, 1), (1, 0)].into_iter()
+        .filter_map(|(attacker, defender)| {
+            let hit_def = players[attacker].hit_def.as_ref()?;
+            let guarded = can_guard(players[defender], holding_back[defender]) && hit_def.guard_flags.reach(players[defender]);
+            if !guarded && !hit_def.hit_flags.reach(players[defender]) {
+                return None;
+            }
+            attack_collides(players[attacker], players[defender]).then_some((attacker, defender, guarded))
+        })
+        .collect();
+    contacts.into_iter()
+        .filter_map(|(attacker, defender, guarded)| {
+            let hit_def = players[attacker].hit_def.take()?;
+            Some(apply_hit(&mut players, attacker, defender, guarded, &hit_def))
+        })
+        .collect()
+}
+
+/// Check if a player can guard an attack
+fn can_guard(player: &PlayerState, holding_back: bool) -> bool {
+    holding_back
+        && (player.ctrl || GUARD_STATES.contains(&player.state_number))
+        && player.state_type != StateType::Lying
+}
+
+fn apply_hit(players: &mut [&mut PlayerState; 2], attacker_index: usize, defender_index: usize, guarded: bool, hit_def: &HitDef) -> HitResult {
+    let (damage, pause_time) = match guarded {
+        true => (hit_def.damage.1, hit_def.guard_pause_time),
+        false => (hit_def.damage.0, hit_def.pause_time),
+    };
+    let attacker = &mut players[attacker_index];
+    attacker.hit_pause = pause_time.0;
+    attacker.move_contact = 1;
+    if guarded {
+        attacker.move_guarded = 1;
+    }
+    else {
+        attacker.move_hit = 1;
+        attacker.hit_count += 1;
+        attacker.unique_hit_count += 1;
+    }
+    let attacker_facing_sign = attacker.facing_sign();
+    let attacker_y = attacker.position.1;
+    let defender = &mut players[defender_index];
+    let state_index = match defender.state_type {
+        StateType::Crouching => 1,
+        StateType::Air => 2,
+        StateType::Lying => 3,
+        StateType::Standing | StateType::Unchanged => 0,
+    };
+    let in_air = defender.state_type == StateType::Air;
+    let velocity = match (guarded, in_air) {
+        (false, false) => hit_def.ground_velocity,
+        (false, true) => hit_def.air_velocity,
+        (true, false) => (hit_def.guard_velocity, 0.),
+        (true, true) => hit_def.air_guard_velocity,
+    };
+    let previous_hit_count = if defender.move_type == MoveType::BeingHit { defender.get_hit.hit_count } else { 0 };
+    defender.get_hit = GetHitVars {
+        damage,
+        guarded,
+        ground_type: hit_def.ground_type,
+        air_type: hit_def.air_type,
+        velocity,
+        hit_shake_time: pause_time.1,
+        hit_time: match (guarded, in_air) {
+            (true, _) => hit_def.guard_hit_time,
+            (false, true) => hit_def.air_hit_time,
+            (false, false) => hit_def.ground_hit_time,
+        },
+        slide_time: if guarded { hit_def.guard_slide_time } else { hit_def.ground_slide_time },
+        ctrl_time: if guarded { hit_def.guard_ctrl_time } else { 0 },
+        fall: !guarded && if in_air { hit_def.fall.air_fall } else { hit_def.fall.fall },
+        fall_velocity: (hit_def.fall.x_velocity, hit_def.fall.y_velocity),
+        fall_recover: hit_def.fall.recover,
+        fall_recover_time: hit_def.fall.recover_time,
+        fall_damage: hit_def.fall.damage,
+        hit_count: if guarded { previous_hit_count } else { previous_hit_count + 1 },
+    };
+    defender.set_life(defender.life - damage);
+    defender.hit_pause = pause_time.1;
+    defender.velocity = velocity;
+    defender.move_type = MoveType::BeingHit;
+    defender.ctrl = false;
+    let defender_state = match guarded {
+        true => GUARD_HIT_STATES[state_index.min(2)],
+        false => hit_def.p2_state.unwrap_or(GET_HIT_STATES[state_index]),
+    };
+    let spark_position = (defender.position.0 - hit_def.spark_position.0 * attacker_facing_sign, attacker_y + hit_def.spark_position.1);
+    HitResult {
+        attacker: attacker_index,
+        defender: defender_index,
+        guarded,
+        attacker_state: if guarded { None } else { hit_def.p1_state },
+        defender_state,
+        spark: if guarded { hit_def.guard_spark } else { hit_def.spark },
+        spark_position,
+        sound: if guarded { hit_def.guard_sound } else { hit_def.hit_sound },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use crate::game::mugen::character::air::{read_air_file, Animations};
+    use crate::game::mugen::character::state::{read_cns_file, Facing, HitFlags, StateMachine, States, TriggerEnvironment};
+
+    const AIR: &[u8] = b"
+[Begin Action 0]
+Clsn2Default: 1
+ Clsn2[0] = -10, 0, 10, -80
+0,0, 0,0, -1
+
+[Begin Action 200]
+Clsn2Default: 1
+ Clsn2[0] = -10, 0, 10, -80
+Clsn1: 1
+ Clsn1[0] = 0, -70, 60, -60
+200,0, 0,0, 10
+";
+
+    const CNS: &[u8] = b"
+[Statedef 0]
+type = S
+ctrl = 1
+anim = 0
+
+[Statedef 200]
+type = S
+movetype = A
+ctrl = 0
+anim = 200
+
+[State 200, hit]
+type = HitDef
+trigger1 = Time = 0
+damage = 30, 5
+guardflag = MA
+pausetime = 8, 10
+guard.pausetime = 6, 7
+ground.velocity = -5
+ground.hittime = 12
+sparkxy = -10, -65
+guard.sparkno = 40
+hitsound = 5, 0
+p1stateno = 210
+
+[Statedef 210]
+type = S
+movetype = A
+hitcountpersist = 1
+";
+
+    /// Two players 40 pixels apart facing each other, the first one attacking
+    fn fight() -> ([PlayerState; 2], States, Animations) {
+        let animations = read_air_file(Cursor::new(AIR));
+        let states = read_cns_file(Cursor::new(CNS), "test");
+        let mut players = [PlayerState::new(Facing::Right), PlayerState::new(Facing::Left)];
+        players[0].position.0 = -20.;
+        players[1].position.0 = 20.;
+        let mut state_machine = StateMachine::new();
+        state_machine.enter_state(&mut players[0], 200, &states, &animations, None);
+        state_machine.enter_state(&mut players[1], 0, &states, &animations, None);
+        state_machine.tick(&mut players[0], &states, &animations, &TriggerEnvironment::default());
+        (players, states, animations)
+    }
+
+    #[test]
+    fn hit() {
+        let (mut players, _, _) = fight();
+        assert!(players[0].hit_def.is_some());
+        let results = resolve_hits(players.each_mut(), [false, false]);
+        assert_eq!(1, results.len());
+        let result = &results[0];
+        assert_eq!((0, 1, false), (result.attacker, result.defender, result.guarded));
+        assert_eq!((Some(210), 5000), (result.attacker_state, result.defender_state));
+        // 10 pixels in front of the defender, at the height of the attacker
+        assert_eq!((None, (30., -65.)), (result.spark, result.spark_position));
+        assert_eq!(Some(SoundReference { own: false, group: 5, number: 0 }), result.sound);
+        assert_eq!(970, players[1].life);
+        assert_eq!((8, 10), (players[0].hit_pause, players[1].hit_pause));
+        assert_eq!((1, 1, 0), (players[0].move_contact, players[0].move_hit, players[0].move_guarded));
+        assert_eq!((1, 1), (players[0].hit_count, players[0].unique_hit_count));
+        assert_eq!((-5., 0.), players[1].velocity);
+        assert_eq!(MoveType::BeingHit, players[1].move_type);
+        assert_eq!((12, 1), (players[1].get_hit.hit_time, players[1].get_hit.hit_count));
+        // a HitDef only hits once
+        assert!(resolve_hits(players.each_mut(), [false, false]).is_empty());
+        assert_eq!(970, players[1].life);
+    }
+
+    #[test]
+    fn hit_counts() {
+        let (mut players, states, animations) = fight();
+        resolve_hits(players.each_mut(), [false, false]);
+        let mut state_machine = StateMachine::new();
+        // the follow-up state of the attack keeps the hit count
+        state_machine.enter_state(&mut players[0], 210, &states, &animations, None);
+        assert_eq!((1, 1), (players[0].hit_count, players[0].unique_hit_count));
+        state_machine.enter_state(&mut players[0], 0, &states, &animations, None);
+        assert_eq!((0, 0), (players[0].hit_count, players[0].unique_hit_count));
+    }
+
+    #[test]
+    fn guard() {
+        let (mut players, _, _) = fight();
+        let results = resolve_hits(players.each_mut(), [false, true]);
+        assert_eq!(1, results.len());
+        assert!(results[0].guarded);
+        assert_eq!((None, 150), (results[0].attacker_state, results[0].defender_state));
+        assert_eq!(Some(SparkReference { own: false, number: 40 }), results[0].spark);
+        assert_eq!(995, players[1].life);
+        assert_eq!((6, 7), (players[0].hit_pause, players[1].hit_pause));
+        assert_eq!((1, 0, 1), (players[0].move_contact, players[0].move_hit, players[0].move_guarded));
+        // a guarded attack does not count as a hit
+        assert_eq!((0, 0), (players[0].hit_count, players[0].unique_hit_count));
+        // crouching players cannot guard high attacks
+        let (mut players, _, _) = fight();
+        players[1].state_type = StateType::Crouching;
+        players[0].hit_def.as_mut().unwrap().guard_flags = HitFlags::from_flags("H");
+        let results = resolve_hits(players.each_mut(), [false, true]);
+        assert_eq!((false, 5010), (results[0].guarded, results[0].defender_state));
+    }
+
+    #[test]
+    fn miss() {
+        let (mut players, states, animations) = fight();
+        // out of reach
+        players[1].position.0 = 100.;
+        assert!(resolve_hits(players.each_mut(), [false, false]).is_empty());
+        // facing away from the defender
+        players[1].position.0 = 20.;
+        players[0].facing = Facing::Left;
+        assert!(resolve_hits(players.each_mut(), [false, false]).is_empty());
+        // the HitDef is removed when the state changes
+        players[0].facing = Facing::Right;
+        StateMachine::new().enter_state(&mut players[0], 0, &states, &animations, None);
+        assert!(players[0].hit_def.is_none());
+        assert!(resolve_hits(players.each_mut(), [false, false]).is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use crate::game::mugen::format::generic_def::{Categories, DefLine};
+use crate::game::mugen::stage::{number, number_pair, StageSection};
+
+/// Widths of the glyphs of a font
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FontType {
+    /// All the glyphs have the width of the font size
+    Fixed,
+    /// Each glyph has its own width
+    #[default]
+    Variable,
+}
+
+/// Color banks of a FNT v2 font
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BankType {
+    /// The banks are the palettes of the sprite file
+    #[default]
+    Palette,
+    /// The banks are the sprite groups, each with its own glyphs
+    Sprite,
+}
+
+/// Glyph of a FNT v1 font in the image of the font: its horizontal position and its width
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GlyphRegion {
+    pub x: u32,
+    pub width: u32,
+}
+
+/// Text definition of a font: the `[Def]` section, and the `[Map]` section of a FNT v1 font
+#[derive(Clone, PartialEq, Debug)]
+pub struct FontDef {
+    /// Width of the glyphs of a fixed font, and height of the lines
+    pub size: (u32, u32),
+    /// Space between the glyphs, and between the lines
+    pub spacing: (i32, i32),
+    /// Number of colors of each color bank of a FNT v1 font
+    pub colors: u32,
+    /// Offset of the glyphs from the position of the text
+    pub offset: (i32, i32),
+    pub font_type: FontType,
+    pub bank_type: BankType,
+    /// Sprite file of the glyphs of a FNT v2 font, from the folder of the font
+    pub sprite_file: Option<String>,
+    /// Glyphs of a FNT v1 font in its image
+    pub glyphs: BTreeMap<char, GlyphRegion>,
+}
+
+impl Default for FontDef {
+    fn default() -> FontDef {
+        FontDef {
+            size: (0, 0),
+            spacing: (0, 0),
+            colors: 255,
+            offset: (0, 0),
+            font_type: FontType::default(),
+            bank_type: BankType::default(),
+            sprite_file: None,
+            glyphs: BTreeMap::new(),
+        }
+    }
+}
+
+/// Read the text of a FNT v1 font or the definition of a FNT v2 font.
+///
+/// The lines of the `[Map]` section are a character, or its code such as `0x3B`, followed by the position and the
+/// width of the glyph in the image for a variable font. The glyphs of a fixed font follow each other in the image, a
+/// glyph index after the character placing it at another position.
+pub fn read_font_def<R: std::io::Read>(categories: Categories<R>) -> FontDef {
+    let mut font_def = FontDef::default();
+    let mut map_lines = Vec::new();
+    for (_, category) in categories {
+        let name = category.name().trim().to_lowercase();
+        match name.as_str() {
+            "def" => {
+                let section: StageSection = category.into_lines().into_iter()
+                    .filter_map(|(_, line)| match line {
+                        DefLine::KeyValue(key, value) => Some((key.to_lowercase(), value)),
+                        DefLine::Simple(_) => None,
+                    })
+                    .collect();
+                read_def_section(&mut font_def, &section);
+            },
+            "map" => map_lines.extend(category.into_lines().into_iter().filter_map(|(_, line)| match line {
+                DefLine::Simple(line) => Some(line),
+                DefLine::KeyValue(..) => None,
+            })),
+            _ => (),
+        }
+    }
+    // the fixed glyphs are placed with the size of the font
+    for (line_index, line) in map_lines.iter().enumerate() {
+        let mut values = line.split_whitespace();
+        let Some(character) = values.next().and_then(parse_character) else {
+            continue;
+        };
+        let mut value = || values.next().and_then(|value| value.parse::<u32>().ok());
+        let glyph = match font_def.font_type {
+            FontType::Fixed => {
+                let index = value().unwrap_or(line_index as u32);
+                GlyphRegion { x: index * font_def.size.0, width: font_def.size.0 }
+            },
+            FontType::Variable => match (value(), value()) {
+                (Some(x), Some(width)) => GlyphRegion { x, width },
+                _ => continue,
+            },
+        };
+        font_def.glyphs.insert(character, glyph);
+    }
+    font_def
+}
+
+fn read_def_section(font_def: &mut FontDef, section: &StageSection) {
+    let pair = |key: &str, default: (i32, i32)| {
+        let (x, y) = number_pair(section, key, (default.0 as f32, default.1 as f32));
+        (x as i32, y as i32)
+    };
+    let size = pair("size", (0, 0));
+    font_def.size = (size.0.max(0) as u32, size.1.max(0) as u32);
+    font_def.spacing = pair("spacing", (0, 0));
+    font_def.colors = number(section, "colors", font_def.colors as f32).clamp(0., 255.) as u32;
+    font_def.offset = pair("offset", (0, 0));
+    font_def.font_type = match section.get("type").map(|font_type| font_type.trim().to_lowercase()).as_deref() {
+        Some("fixed") => FontType::Fixed,
+        _ => FontType::Variable,
+    };
+    font_def.bank_type = match section.get("banktype").map(|bank_type| bank_type.trim().to_lowercase()).as_deref() {
+        Some("sprite") => BankType::Sprite,
+        _ => BankType::Palette,
+    };
+    font_def.sprite_file = section.get("file").map(|file| file.trim().to_owned()).filter(|file| !file.is_empty());
+}
+
+/// Character of a line of the `[Map]` section: the character itself, or its hexadecimal code
+fn parse_character(text: &str) -> Option<char> {
+    let mut characters = text.chars();
+    let first = characters.next()?;
+    if characters.next().is_none() {
+        return Some(first);
+    }
+    let code = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"))?;
+    char::from_u32(u32::from_str_radix(code, 16).ok()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_glyph_maps() {
+        let variable_def = r#"
+[Def]
+Size = 6, 9
+Spacing = 1, 2
+Colors = 32
+Offset = 0, -1
+Type = Variable
+
+[Map]
+A 0 5
+= 5 4     ; the equal sign
+0x3B 9 2
+B
+"#;
+        let font_def = read_font_def(Categories::read_def(Cursor::new(variable_def)));
+        assert_eq!(((6, 9), (1, 2), 32, (0, -1)), (font_def.size, font_def.spacing, font_def.colors, font_def.offset));
+        // the glyph without position is ignored
+        assert_eq!(vec![
+            (';', GlyphRegion { x: 9, width: 2 }),
+            ('=', GlyphRegion { x: 5, width: 4 }),
+            ('A', GlyphRegion { x: 0, width: 5 }),
+        ], font_def.glyphs.into_iter().collect::<Vec<_>>());
+        let fixed_def = "[Def]\nSize = 4, 6\nType = Fixed\n[Map]\n0\n1\n2 5\n";
+        let font_def = read_font_def(Categories::read_def(Cursor::new(fixed_def)));
+        assert_eq!(Some(&GlyphRegion { x: 4, width: 4 }), font_def.glyphs.get(&'1'));
+        assert_eq!(Some(&GlyphRegion { x: 20, width: 4 }), font_def.glyphs.get(&'2'));
+        let v2_def = "[FNT v2]\nfntversion = 2,00\n[Def]\nType = bitmap\nBankType = sprite\nSize = 8, 8\nFile = \"font.sff\"\n";
+        let font_def = read_font_def(Categories::read_def(Cursor::new(v2_def)));
+        assert_eq!((BankType::Sprite, Some("font.sff")), (font_def.bank_type, font_def.sprite_file.as_deref()));
+    }
+}
@@ -0,0 +1,253 @@
+use std::convert::Infallible;
+use std::io::{self, Cursor};
+use thiserror::Error;
+use nugem_sff::bitmap::{BitmapPixel, BitmapRenderer};
+use nugem_sff::{RenderedSprite, SpriteFile};
+use crate::game::mugen::format::generic_def::Categories;
+use super::{read_font_def, BankType, FontDef, FontType};
+
+/// Signature at the start of a FNT v1 file
+const FNT_SIGNATURE: &[u8; 12] = b"ElecbyteFnt\0";
+
+#[derive(Debug, Error)]
+pub enum FontError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error("Invalid FNT header")]
+    InvalidHeader,
+    #[error("Invalid font image")]
+    ImageError(#[from] nugem_sff::v1::RenderingError<Infallible>),
+    #[error("No sprite file in the font definition")]
+    MissingSpriteFile,
+    #[error("Invalid font sprite file")]
+    SpriteFileError(#[from] nugem_sff::LoadingError),
+}
+
+/// Images of the glyphs of a font
+#[derive(Debug)]
+pub enum FontImage {
+    /// FNT v1: palette indices of the PCX image with all the glyphs side by side, and its 256 colors
+    Pcx {
+        size: (u16, u16),
+        indices: Vec<u8>,
+        colors: Vec<BitmapPixel>,
+    },
+    /// FNT v2: a sprite per glyph, numbered with the code of its character
+    Sprites(SpriteFile),
+}
+
+/// Size of a glyph in the image of the font
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Glyph {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Bitmap font of a FNT v1 or FNT v2 file
+#[derive(Debug)]
+pub struct Font {
+    def: FontDef,
+    image: FontImage,
+}
+
+impl Font {
+    pub fn new(def: FontDef, image: FontImage) -> Font {
+        Font {
+            def,
+            image,
+        }
+    }
+    pub fn def(&self) -> &FontDef {
+        &self.def
+    }
+    pub fn image(&self) -> &FontImage {
+        &self.image
+    }
+    /// Characters with a glyph
+    pub fn characters(&self) -> Vec<char> {
+        match &self.image {
+            FontImage::Pcx { .. } => self.def.glyphs.keys().copied().collect(),
+            FontImage::Sprites(sprite_file) => sprite_file.images(0).into_iter().filter_map(|code| char::from_u32(code as u32)).collect(),
+        }
+    }
+    /// Size of the glyph of a character, `None` for the characters without glyph such as the spaces
+    pub fn glyph(&self, character: char) -> Option<Glyph> {
+        match &self.image {
+            FontImage::Pcx { size, .. } => {
+                let region = self.def.glyphs.get(&character)?;
+                Some(Glyph { width: region.width, height: size.1 as u32 })
+            },
+            FontImage::Sprites(sprite_file) => {
+                let sprite = sprite_file.sprite(0, u16::try_from(character as u32).ok()?)?;
+                let width = match self.def.font_type {
+                    FontType::Fixed => self.def.size.0,
+                    FontType::Variable => sprite.size.0 as u32,
+                };
+                Some(Glyph { width, height: sprite.size.1 as u32 })
+            },
+        }
+    }
+    /// Number of color banks of the font
+    pub fn bank_count(&self) -> usize {
+        let bank_count = match (&self.image, self.def.bank_type) {
+            // the palette index 0 is transparent
+            (FontImage::Pcx { .. }, _) => 255 / self.def.colors.max(1) as usize,
+            (FontImage::Sprites(sprite_file), BankType::Palette) => sprite_file.palette_count(),
+            (FontImage::Sprites(sprite_file), BankType::Sprite) => sprite_file.groups().len(),
+        };
+        bank_count.max(1)
+    }
+    /// Render the glyph of a character with the colors of a bank, the first bank being used for the invalid banks
+    pub fn render_glyph<R: BitmapRenderer>(&self, renderer_params: R::Initializer, character: char, bank: i32) -> Result<Option<RenderedSprite<R>>, nugem_sff::RenderingError<R::Error>> {
+        let bank = usize::try_from(bank).ok().filter(|&bank| bank < self.bank_count()).unwrap_or(0);
+        match &self.image {
+            FontImage::Pcx { size, indices, colors } => {
+                let Some(region) = self.def.glyphs.get(&character) else {
+                    return Ok(None);
+                };
+                let renderer_error = |err| nugem_sff::RenderingError::from(nugem_sff::v1::RenderingError::RendererError(err));
+                let (image_width, image_height) = (size.0 as usize, size.1 as usize);
+                let mut surface = R::initialize_surface(renderer_params, region.width as u64, image_height as u64).map_err(renderer_error)?;
+                for y in 0..image_height {
+                    for x in region.x as usize..(region.x + region.width) as usize {
+                        // the glyphs outside of the image are transparent
+                        let index = match x < image_width {
+                            true => indices.get(y * image_width + x).copied().unwrap_or(0),
+                            false => 0,
+                        };
+                        let color_index = bank_color_index(index, self.def.colors, bank);
+                        surface.render_single_pixel(colors[color_index as usize]).map_err(renderer_error)?;
+                    }
+                }
+                Ok(Some(RenderedSprite { surface, axis: (0, 0) }))
+            },
+            FontImage::Sprites(sprite_file) => {
+                let Ok(code) = u16::try_from(character as u32) else {
+                    return Ok(None);
+                };
+                let (group, palette_index) = match self.def.bank_type {
+                    BankType::Palette => (0, bank),
+                    BankType::Sprite => (sprite_file.groups().get(bank).copied().unwrap_or(0), 0),
+                };
+                if sprite_file.sprite(group, code).is_none() {
+                    return Ok(None);
+                }
+                sprite_file.render_sprite(renderer_params, group, code, palette_index).map(Some)
+            },
+        }
+    }
+}
+
+/// Palette index of a pixel of a FNT v1 font drawn with a color bank.
+///
+/// The glyphs use the last colors of the palette, and the banks are the groups of colors before them: the bank 1 uses
+/// the colors just before the bank 0.
+pub fn bank_color_index(index: u8, colors: u32, bank: usize) -> u8 {
+    let shift = colors as usize * bank;
+    match index as usize {
+        0 => 0,
+        index if index + colors as usize > 255 && index > shift => (index - shift) as u8,
+        _ => index,
+    }
+}
+
+/// Read a FNT v1 file, or the definition of a FNT v2 font with its sprite file.
+///
+/// The files are read with their paths from the folder of the font file, the sprite file of a FNT v2 font being in
+/// the same folder as its definition.
+pub fn read_font(font_path: &str, mut read_file: impl FnMut(&str) -> io::Result<Vec<u8>>) -> Result<Font, FontError> {
+    let data = read_file(font_path)?;
+    if data.starts_with(FNT_SIGNATURE) {
+        return read_fnt_v1(&data);
+    }
+    let def = read_font_def(Categories::read_def(Cursor::new(&data)));
+    let sprite_file_name = def.sprite_file.as_deref().ok_or(FontError::MissingSpriteFile)?;
+    let sprite_path = match font_path.rsplit_once(['/', '\\']) {
+        Some((folder, _)) => format!("{folder}/{sprite_file_name}"),
+        None => sprite_file_name.to_owned(),
+    };
+    let sprite_file = SpriteFile::read(Cursor::new(read_file(&sprite_path)?), Vec::new())?;
+    Ok(Font::new(def, FontImage::Sprites(sprite_file)))
+}
+
+/// Read a FNT v1 file: a header with the positions of the PCX image and of the text definition in the file
+pub fn read_fnt_v1(data: &[u8]) -> Result<Font, FontError> {
+    let header_u32 = |index: usize| Some(u32::from_le_bytes(data.get(index..index + 4)?.try_into().ok()?) as usize);
+    let section = |offset: usize, size: usize| data.get(header_u32(offset)?..header_u32(offset)?.checked_add(header_u32(size)?)?);
+    // the version and the comment are not used
+    let pcx_data = section(16, 20).ok_or(FontError::InvalidHeader)?;
+    let text = section(24, 28).ok_or(FontError::InvalidHeader)?;
+    let def = read_font_def(Categories::read_def(Cursor::new(text)));
+    let pcx_image = nugem_sff::v1::read_pcx_image(pcx_data)?;
+    let colors = match pcx_image.palette {
+        Some(palette) => (0..=u8::MAX).map(|index| nugem_sff::v1::palette_pixel(&palette, index)).collect(),
+        None => (0..=u8::MAX).map(|index| BitmapPixel::new(index, index, index, if index > 0 { u8::MAX } else { 0 })).collect(),
+    };
+    Ok(Font::new(def, FontImage::Pcx {
+        size: pcx_image.size,
+        indices: pcx_image.indices,
+        colors,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::graphics::surface::BitmapSurfaceRenderer;
+    use crate::game::mugen::font::TextAlignment;
+
+    /// Uncompressed 8-bit PCX image, with the palette of the colors (index, 0, 0)
+    fn pcx_data(width: u16, height: u16, indices: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; 128];
+        data[0..4].copy_from_slice(&[0x0A, 5, 0, 8]);
+        data[8..10].copy_from_slice(&(width - 1).to_le_bytes());
+        data[10..12].copy_from_slice(&(height - 1).to_le_bytes());
+        data[65] = 1;
+        data[66..68].copy_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(indices);
+        data.push(0x0C);
+        data.extend((0..=u8::MAX).flat_map(|index| [index, 0, 0]));
+        data
+    }
+
+    fn fnt_data(pcx: &[u8], text: &str) -> Vec<u8> {
+        let mut data = FNT_SIGNATURE.to_vec();
+        data.extend_from_slice(&[0, 1, 0, 0]);
+        for (offset, size) in [(64, pcx.len()), (64 + pcx.len(), text.len())] {
+            data.extend_from_slice(&(offset as u32).to_le_bytes());
+            data.extend_from_slice(&(size as u32).to_le_bytes());
+        }
+        data.resize(64, 0);
+        data.extend_from_slice(pcx);
+        data.extend_from_slice(text.as_bytes());
+        data
+    }
+
+    #[test]
+    fn read_fnt_with_banks() {
+        // two glyphs of 2 x 2 pixels, drawn with the last 2 colors of the palette
+        let pcx = pcx_data(4, 2, &[255, 0, 254, 255, 0, 255, 255, 254]);
+        let font = read_fnt_v1(&fnt_data(&pcx, "[Def]\nSize = 2, 2\nSpacing = 1, 0\nColors = 2\nType = Variable\n[Map]\na 0 2\nb 2 2\n")).unwrap();
+        assert_eq!(vec!['a', 'b'], font.characters());
+        assert_eq!((Some(Glyph { width: 2, height: 2 }), None), (font.glyph('a'), font.glyph(' ')));
+        assert_eq!(127, font.bank_count());
+        let red = |glyph: RenderedSprite<BitmapSurfaceRenderer>| glyph.surface.take().pixels().iter().map(BitmapPixel::r).collect::<Vec<_>>();
+        let glyph = |bank| font.render_glyph::<BitmapSurfaceRenderer>((), 'b', bank).unwrap().map(red);
+        assert_eq!(Some(vec![254, 255, 255, 254]), glyph(0));
+        // the bank 1 uses the 2 colors before the bank 0
+        assert_eq!(Some(vec![252, 253, 253, 252]), glyph(1));
+        assert_eq!(glyph(0), glyph(200));
+        assert!(font.render_glyph::<BitmapSurfaceRenderer>((), 'c', 0).unwrap().is_none());
+        let positions: Vec<(f32, f32)> = font.layout("ba b", (10., 20.), TextAlignment::Left).iter().map(|quad| quad.position).collect();
+        assert_eq!(vec![(10., 18.), (13., 18.), (19., 18.)], positions);
+    }
+
+    #[test]
+    fn missing_fnt_sections() {
+        let mut data = fnt_data(&[], "");
+        data[20..24].copy_from_slice(&100u32.to_le_bytes());
+        assert!(matches!(read_fnt_v1(&data), Err(FontError::InvalidHeader)));
+        let missing_file = read_font("font/f.def", |_| Ok(b"[Def]\nType = bitmap\n".to_vec()));
+        assert!(matches!(missing_file, Err(FontError::MissingSpriteFile)));
+    }
+}
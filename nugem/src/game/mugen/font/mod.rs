@@ -0,0 +1,8 @@
+mod font_def;
+pub use self::font_def::*;
+
+mod font_reader;
+pub use self::font_reader::*;
+
+mod text_layout;
+pub use self::text_layout::*;
@@ -0,0 +1,109 @@
+use super::{Font, Glyph};
+
+/// Horizontal alignment of a text on its position
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TextAlignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl TextAlignment {
+    /// Alignment numbered as in the font parameters: 1 for left, 0 for center, -1 for right
+    pub fn from_number(number: i32) -> TextAlignment {
+        match number.signum() {
+            1 => TextAlignment::Left,
+            -1 => TextAlignment::Right,
+            _ => TextAlignment::Center,
+        }
+    }
+    /// Alignment of a text mirrored horizontally
+    pub fn mirrored(self) -> TextAlignment {
+        match self {
+            TextAlignment::Left => TextAlignment::Right,
+            TextAlignment::Center => TextAlignment::Center,
+            TextAlignment::Right => TextAlignment::Left,
+        }
+    }
+}
+
+/// Glyph of a character of a text placed on the screen
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GlyphQuad {
+    pub character: char,
+    /// Position of the top left corner of the glyph
+    pub position: (f32, f32),
+    pub size: (f32, f32),
+}
+
+impl Font {
+    /// Glyphs of a text drawn at a position: the bottom of the first line, on the left, center or right of the lines
+    /// depending on the alignment. The characters without glyph, such as the spaces, are blank spaces of the width of
+    /// the font.
+    pub fn layout(&self, text: &str, position: (f32, f32), alignment: TextAlignment) -> Vec<GlyphQuad> {
+        layout_text(text, position, alignment, self.def().size, self.def().spacing, self.def().offset, |character| self.glyph(character))
+    }
+}
+
+/// Glyphs of a text, with the size, spacing and offset of its font and the size of its glyphs
+pub fn layout_text(
+    text: &str,
+    position: (f32, f32),
+    alignment: TextAlignment,
+    size: (u32, u32),
+    spacing: (i32, i32),
+    offset: (i32, i32),
+    glyph: impl Fn(char) -> Option<Glyph>,
+) -> Vec<GlyphQuad> {
+    let mut quads = Vec::new();
+    let line_height = size.1 as f32 + spacing.1 as f32;
+    for (line_index, line) in text.lines().enumerate() {
+        let glyphs: Vec<(char, Option<Glyph>)> = line.chars().map(|character| (character, glyph(character))).collect();
+        let advance = |glyph: &Option<Glyph>| glyph.map_or(size.0, |glyph| glyph.width) as f32 + spacing.0 as f32;
+        // no spacing after the last glyph
+        let width = (glyphs.iter().map(|(_, glyph)| advance(glyph)).sum::<f32>() - spacing.0 as f32).max(0.);
+        let mut x = match alignment {
+            TextAlignment::Left => position.0,
+            TextAlignment::Center => position.0 - (width / 2.).floor(),
+            TextAlignment::Right => position.0 - width,
+        } + offset.0 as f32;
+        let bottom = position.1 + line_index as f32 * line_height + offset.1 as f32;
+        for (character, glyph) in glyphs.iter() {
+            if let Some(glyph) = glyph {
+                quads.push(GlyphQuad {
+                    character: *character,
+                    position: (x, bottom - size.1 as f32),
+                    size: (glyph.width as f32, glyph.height as f32),
+                });
+            }
+            x += advance(glyph);
+        }
+    }
+    quads
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn aligned_layout() {
+        // glyphs 3 pixels wide, 'i' 1 pixel wide, 5 pixels high
+        let glyph = |character: char| match character {
+            ' ' => None,
+            'i' => Some(Glyph { width: 1, height: 5 }),
+            _ => Some(Glyph { width: 3, height: 5 }),
+        };
+        let layout = |text, alignment| layout_text(text, (100., 50.), alignment, (3, 5), (1, 2), (0, 0), glyph);
+        let quad = |character, x: f32, y: f32, width: f32| GlyphQuad { character, position: (x, y), size: (width, 5.) };
+        assert_eq!(vec![quad('h', 100., 45., 3.), quad('i', 104., 45., 1.), quad('a', 110., 45., 3.)], layout("hi a", TextAlignment::Left));
+        // 13 pixels wide
+        assert_eq!(quad('h', 94., 45., 3.), layout("hi a", TextAlignment::Center)[0]);
+        assert_eq!(quad('a', 97., 45., 3.), layout("hi a", TextAlignment::Right)[2]);
+        // the lines are aligned separately
+        assert_eq!(vec![quad('a', 97., 45., 3.), quad('b', 93., 52., 3.), quad('c', 97., 52., 3.)], layout("a\nbc", TextAlignment::Right));
+        let offset = layout_text("a", (100., 50.), TextAlignment::Left, (3, 5), (1, 2), (2, -1), glyph);
+        assert_eq!(vec![quad('a', 102., 44., 3.)], offset);
+    }
+}
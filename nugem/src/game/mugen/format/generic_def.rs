@@ -55,7 +55,8 @@ impl<R: Read> Iterator for Categories<R> {
                     // read the next category
                     lazy_static! {
                         static ref REGEX_CATEGORY: Regex = Regex::new(r"^\s*\[\s*(.*?)\s*\]").unwrap();
-                        static ref REGEX_KV_QUOTED: Regex = Regex::new(r#"^\s*([^;]+?)\s*=\s*"([^\r\n;]+?)""#).unwrap();
+                        // the key of a quoted value cannot contain '=': expressions such as `trigger1 = command = "x"` are unquoted values
+                        static ref REGEX_KV_QUOTED: Regex = Regex::new(r#"^\s*([^;=]+?)\s*=\s*"([^\r\n;]+?)""#).unwrap();
                         static ref REGEX_KV_UNQUOTED: Regex = Regex::new(r"^\s*([^;]+?)\s*=\s*((\s*[^\n;\s]+)*)\s*").unwrap();
                         static ref REGEX_SIMPLE: Regex = Regex::new(r"^\s*([^;\n\s]+?(?:\s*[^;\n]+)*)").unwrap();
                     }
@@ -161,13 +162,14 @@ mod test {
             world = \"hello\"
 
             [info -3]
+            trigger1 = command = \"holdfwd\" ; comment
 ";
         let categories : Vec<_> = Categories::read_def(Cursor::new(test_string)).collect();
         assert_eq!(
             &vec![
                 (2, Category { name: "info".into(), lines: vec![(3, DefLine::KeyValue("hello".into(), "world".into())), (5, DefLine::KeyValue("other_hello".into(), "world!".into()))]}),
                 (7, Category { name: "info2".into(), lines: vec![(10, DefLine::KeyValue("number".into(), "23".into())), (14, DefLine::KeyValue("test".into(), "ok this is it".into())), (16, DefLine::Simple("simple value".into())), (22, DefLine::KeyValue("world".into(), "hello".into()))]}),
-                (24, Category { name: "info -3".into(), lines: vec![]}),
+                (24, Category { name: "info -3".into(), lines: vec![(25, DefLine::KeyValue("trigger1".into(), "command = \"holdfwd\"".into()))]}),
                 ]
             , &categories);
     }
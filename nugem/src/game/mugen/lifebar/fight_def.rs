@@ -0,0 +1,446 @@
+use std::collections::BTreeMap;
+use crate::game::mugen::font::TextAlignment;
+use crate::game::mugen::format::generic_def::{Categories, DefLine};
+use crate::game::mugen::round::MatchRules;
+use crate::game::mugen::stage::{number, number_pair, numbers, StageSection};
+
+/// Font of a text element: `font = <font number>, <color bank>, <alignment>`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FontReference {
+    /// Number of the font in the `[Files]` section, from `font1`
+    pub font: u32,
+    pub bank: i32,
+    pub alignment: TextAlignment,
+}
+
+/// Part of the lifebar drawn with an animation, a sprite or a text, from the parameters sharing a prefix such as
+/// `p1.bg0.anim`, `p1.bg0.spr` and `p1.bg0.offset`
+#[derive(Clone, PartialEq, Debug)]
+pub struct LifebarElement {
+    pub anim: Option<u32>,
+    pub sprite: Option<(u16, u16)>,
+    pub font: Option<FontReference>,
+    pub text: Option<String>,
+    /// Offset from the position of the section
+    pub offset: (f32, f32),
+    /// -1 to mirror the element horizontally
+    pub facing: i32,
+    pub scale: (f32, f32),
+    /// Ticks during which an announcement is displayed
+    pub display_time: Option<i32>,
+}
+
+impl Default for LifebarElement {
+    fn default() -> Self {
+        LifebarElement {
+            anim: None,
+            sprite: None,
+            font: None,
+            text: None,
+            offset: (0., 0.),
+            facing: 1,
+            scale: (1., 1.),
+            display_time: None,
+        }
+    }
+}
+
+impl LifebarElement {
+    /// Check if the element draws nothing
+    pub fn is_empty(&self) -> bool {
+        self.anim.is_none() && self.sprite.is_none() && self.font.is_none()
+    }
+}
+
+/// `[Files]` section: the sprite file and the fonts of the lifebar
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct LifebarFiles {
+    pub sprite_file: String,
+    pub sound_file: Option<String>,
+    /// Font files by number
+    pub fonts: BTreeMap<u32, String>,
+}
+
+/// Gauge of a player in the `[Lifebar]` or `[Powerbar]` sections
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct BarDef {
+    pub position: (f32, f32),
+    /// Backgrounds, drawn behind the gauge
+    pub bg0: LifebarElement,
+    pub bg1: LifebarElement,
+    /// Gauge lagging behind after a damage
+    pub mid: LifebarElement,
+    /// Gauge of the current value
+    pub front: LifebarElement,
+    /// Horizontal range of the gauge from the position, from empty to full
+    pub range_x: (f32, f32),
+    /// Level of the power bar
+    pub counter: LifebarElement,
+}
+
+/// Face of a player in the `[Face]` section, the sprites of the face coming from the sprites of the character
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct FaceDef {
+    pub position: (f32, f32),
+    pub bg: LifebarElement,
+    pub face: LifebarElement,
+}
+
+/// Name of a player in the `[Name]` section
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct NameDef {
+    pub position: (f32, f32),
+    pub bg: LifebarElement,
+    pub name: LifebarElement,
+}
+
+/// `[Time]` section: the round timer
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TimeDef {
+    pub position: (f32, f32),
+    pub bg: LifebarElement,
+    pub counter: LifebarElement,
+    pub frames_per_count: Option<i32>,
+}
+
+/// `[Combo]` section: the hit counter of the first player, mirrored for the second player
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct ComboDef {
+    pub position: (f32, f32),
+    /// Horizontal position at which the counter slides in from
+    pub start_x: f32,
+    pub counter: LifebarElement,
+    pub text: LifebarElement,
+    /// Ticks during which the counter stays after the end of the combo
+    pub display_time: i32,
+}
+
+/// `[Round]` section: the rules of the match and the announcements
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct RoundDef {
+    pub position: (f32, f32),
+    /// Values of the rules of the match given by the section
+    pub rules: Vec<(String, f32)>,
+    /// Announcement of the rounds without their own announcement, its text formatting `%i` with the round number
+    pub round_default: LifebarElement,
+    /// Announcements of specific rounds, from `round1`
+    pub rounds: BTreeMap<u32, LifebarElement>,
+    pub fight: LifebarElement,
+    pub ko: LifebarElement,
+    pub double_ko: LifebarElement,
+    pub time_over: LifebarElement,
+    /// Announcement of the winner, its text formatting `%s` with the name of the winner
+    pub win: LifebarElement,
+    pub draw: LifebarElement,
+}
+
+impl RoundDef {
+    /// Rules of the match with the settings of the section
+    pub fn match_rules(&self, mut rules: MatchRules) -> MatchRules {
+        for (key, value) in self.rules.iter() {
+            let ticks = *value as i32;
+            match key.as_str() {
+                "match.wins" => rules.rounds_to_win = ticks.max(1) as u32,
+                "match.maxdrawgames" => rules.max_draw_games = ticks,
+                "start.waittime" => rules.start_wait_time = ticks,
+                "round.time" => rules.round_display_time = ticks,
+                "fight.time" => rules.fight_display_time = ticks,
+                "ctrl.time" => rules.ctrl_time = ticks,
+                "slow.time" => rules.ko_slow_time = ticks,
+                "over.waittime" => rules.over_wait_time = ticks,
+                "over.time" => rules.over_time = ticks,
+                _ => (),
+            }
+        }
+        rules
+    }
+    /// Announcement of a round
+    pub fn round(&self, round: u32) -> &LifebarElement {
+        self.rounds.get(&round).unwrap_or(&self.round_default)
+    }
+}
+
+/// Kind of a round won, shown by the win icons
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WinKind {
+    Normal,
+    Special,
+    Hyper,
+    Throw,
+    Cheese,
+    Time,
+    Suicide,
+    Teammate,
+}
+
+impl WinKind {
+    const ALL: [(WinKind, &'static str); 8] = [
+        (WinKind::Normal, "n"),
+        (WinKind::Special, "s"),
+        (WinKind::Hyper, "h"),
+        (WinKind::Throw, "throw"),
+        (WinKind::Cheese, "c"),
+        (WinKind::Time, "t"),
+        (WinKind::Suicide, "suicide"),
+        (WinKind::Teammate, "teammate"),
+    ];
+}
+
+/// Win icons of a player in the `[WinIcon]` section
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct WinIconDef {
+    pub position: (f32, f32),
+    /// Offset between two icons
+    pub icon_offset: (f32, f32),
+    pub icons: Vec<(WinKind, LifebarElement)>,
+    /// Drawn over the icon of a round won without damage
+    pub perfect: LifebarElement,
+    /// Count of the wins, drawn instead of the icons after `use_icon_up_to` wins
+    pub counter: LifebarElement,
+    pub use_icon_up_to: usize,
+}
+
+impl WinIconDef {
+    pub fn icon(&self, kind: WinKind) -> Option<&LifebarElement> {
+        self.icons.iter().find(|(icon_kind, _)| *icon_kind == kind).map(|(_, icon)| icon)
+    }
+}
+
+/// Definition of the lifebars and the other displays of a fight, from a `fight.def` file
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct LifebarDef {
+    pub files: LifebarFiles,
+    pub lifebar: [BarDef; 2],
+    pub powerbar: [BarDef; 2],
+    pub face: [FaceDef; 2],
+    pub name: [NameDef; 2],
+    pub time: TimeDef,
+    pub combo: ComboDef,
+    pub round: RoundDef,
+    pub win_icon: [WinIconDef; 2],
+}
+
+impl LifebarDef {
+    /// Elements drawn with the sprites of the lifebar
+    pub fn elements(&self) -> Vec<&LifebarElement> {
+        let mut elements = Vec::new();
+        for bar in self.lifebar.iter().chain(self.powerbar.iter()) {
+            elements.extend([&bar.bg0, &bar.bg1, &bar.mid, &bar.front, &bar.counter]);
+        }
+        for (face, name) in self.face.iter().zip(self.name.iter()) {
+            elements.extend([&face.bg, &name.bg, &name.name]);
+        }
+        elements.extend([&self.time.bg, &self.time.counter, &self.combo.counter, &self.combo.text]);
+        let round = &self.round;
+        elements.push(&round.round_default);
+        elements.extend(round.rounds.values());
+        elements.extend([&round.fight, &round.ko, &round.double_ko, &round.time_over, &round.win, &round.draw]);
+        for win_icon in self.win_icon.iter() {
+            elements.extend(win_icon.icons.iter().map(|(_, icon)| icon));
+            elements.extend([&win_icon.perfect, &win_icon.counter]);
+        }
+        elements
+    }
+}
+
+/// Read the sections of a `fight.def` file. The parameters of each player are prefixed with `p1.` and `p2.`.
+pub fn read_lifebar_def<R: std::io::Read>(categories: Categories<R>) -> LifebarDef {
+    let mut lifebar_def = LifebarDef::default();
+    for (_, category) in categories {
+        let name = category.name().trim().to_lowercase();
+        let section: StageSection = category.into_lines().into_iter()
+            .filter_map(|(_, line)| match line {
+                DefLine::KeyValue(key, value) => Some((key.to_lowercase(), value)),
+                DefLine::Simple(_) => None,
+            })
+            .collect();
+        let players = ["p1.", "p2."];
+        match name.as_str() {
+            "files" => lifebar_def.files = read_files(&section),
+            "lifebar" => lifebar_def.lifebar = players.map(|player| read_bar(&section, player)),
+            "powerbar" => lifebar_def.powerbar = players.map(|player| read_bar(&section, player)),
+            "face" => lifebar_def.face = players.map(|player| FaceDef {
+                position: number_pair(&section, &format!("{player}pos"), (0., 0.)),
+                bg: read_element(&section, &format!("{player}bg")),
+                face: read_element(&section, &format!("{player}face")),
+            }),
+            "name" => lifebar_def.name = players.map(|player| NameDef {
+                position: number_pair(&section, &format!("{player}pos"), (0., 0.)),
+                bg: read_element(&section, &format!("{player}bg")),
+                name: read_element(&section, &format!("{player}name")),
+            }),
+            "time" => lifebar_def.time = TimeDef {
+                position: number_pair(&section, "pos", (0., 0.)),
+                bg: read_element(&section, "bg"),
+                counter: read_element(&section, "counter"),
+                frames_per_count: numbers(&section, "framespercount").first().map(|&frames| frames as i32),
+            },
+            "combo" => lifebar_def.combo = ComboDef {
+                position: number_pair(&section, "pos", (0., 0.)),
+                start_x: number(&section, "start.x", 0.),
+                counter: read_element(&section, "counter"),
+                text: read_element(&section, "text"),
+                display_time: number(&section, "displaytime", 90.) as i32,
+            },
+            "round" => lifebar_def.round = read_round(&section),
+            "winicon" => lifebar_def.win_icon = players.map(|player| read_win_icon(&section, player)),
+            // the [Begin Action] sections of the animations are read as an AIR file
+            _ => (),
+        }
+    }
+    lifebar_def
+}
+
+fn read_files(section: &StageSection) -> LifebarFiles {
+    let fonts = section.iter()
+        .filter_map(|(key, path)| Some((key.strip_prefix("font")?.parse().ok()?, path.clone())))
+        .collect();
+    LifebarFiles {
+        sprite_file: section.get("sff").cloned().unwrap_or_default(),
+        sound_file: section.get("snd").cloned(),
+        fonts,
+    }
+}
+
+fn read_bar(section: &StageSection, player: &str) -> BarDef {
+    BarDef {
+        position: number_pair(section, &format!("{player}pos"), (0., 0.)),
+        bg0: read_element(section, &format!("{player}bg0")),
+        bg1: read_element(section, &format!("{player}bg1")),
+        mid: read_element(section, &format!("{player}mid")),
+        front: read_element(section, &format!("{player}front")),
+        range_x: number_pair(section, &format!("{player}range.x"), (0., 0.)),
+        counter: read_element(section, &format!("{player}counter")),
+    }
+}
+
+fn read_round(section: &StageSection) -> RoundDef {
+    const RULE_KEYS: [&str; 9] = [
+        "match.wins", "match.maxdrawgames", "start.waittime", "round.time", "fight.time", "ctrl.time", "slow.time", "over.waittime", "over.time",
+    ];
+    let rounds = (1..=9)
+        .map(|round| (round, read_element(section, &format!("round{round}"))))
+        .filter(|(_, element)| !element.is_empty())
+        .collect();
+    RoundDef {
+        position: number_pair(section, "pos", (0., 0.)),
+        rules: RULE_KEYS.iter().filter_map(|&key| Some((key.to_owned(), *numbers(section, key).first()?))).collect(),
+        round_default: read_element(section, "round.default"),
+        rounds,
+        fight: read_element(section, "fight"),
+        ko: read_element(section, "ko"),
+        double_ko: read_element(section, "dko"),
+        time_over: read_element(section, "to"),
+        win: read_element(section, "win"),
+        draw: read_element(section, "draw"),
+    }
+}
+
+fn read_win_icon(section: &StageSection, player: &str) -> WinIconDef {
+    WinIconDef {
+        position: number_pair(section, &format!("{player}pos"), (0., 0.)),
+        icon_offset: number_pair(section, &format!("{player}iconoffset"), (0., 0.)),
+        icons: WinKind::ALL.iter()
+            .map(|&(kind, key)| (kind, read_element(section, &format!("{player}{key}"))))
+            .filter(|(_, icon)| !icon.is_empty())
+            .collect(),
+        perfect: read_element(section, &format!("{player}perfect")),
+        counter: read_element(section, &format!("{player}counter")),
+        use_icon_up_to: number(section, "useiconupto", 4.).max(0.) as usize,
+    }
+}
+
+/// Read the parameters of an element from their prefix
+pub fn read_element(section: &StageSection, prefix: &str) -> LifebarElement {
+    let value = |key: &str| section.get(&format!("{prefix}.{key}"));
+    let values = |key: &str| numbers(section, &format!("{prefix}.{key}"));
+    let font = values("font");
+    let sprite = values("spr");
+    LifebarElement {
+        anim: values("anim").first().and_then(|&action| u32::try_from(action as i64).ok()),
+        sprite: match sprite[..] {
+            [group, image, ..] => Some((group as u16, image as u16)),
+            _ => None,
+        },
+        font: font.first().and_then(|&font_number| u32::try_from(font_number as i64).ok()).map(|font_number| FontReference {
+            font: font_number,
+            bank: font.get(1).map_or(0, |&bank| bank as i32),
+            alignment: TextAlignment::from_number(font.get(2).map_or(0, |&alignment| alignment as i32)),
+        }),
+        text: value("text").cloned(),
+        offset: number_pair(section, &format!("{prefix}.offset"), (0., 0.)),
+        facing: if values("facing").first().is_some_and(|&facing| facing < 0.) { -1 } else { 1 },
+        scale: number_pair(section, &format!("{prefix}.scale"), (1., 1.)),
+        display_time: values("displaytime").first().map(|&time| time as i32),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_fight_def() {
+        let def = b"
+[Files]
+sff = fight.sff
+font1 = font/f-4x6.fnt
+font2 = font/f-6x9.fnt
+
+[Lifebar]
+p1.pos = 140,12
+p1.bg0.anim = 10
+p1.mid.spr = 12,0
+p1.front.spr = 13,0
+p1.range.x = 0,-127
+p2.pos = 179,12
+p2.front.spr = 13,0
+p2.front.facing = -1
+p2.range.x = 0,127
+
+[Name]
+p1.pos = 1,35
+p1.name.font = 2,0,1
+p2.name.font = 2,3,-1
+
+[Round]
+match.wins = 3
+slow.time = 30
+round.default.font = 2
+round.default.text = \"Round %i\"
+round.default.displaytime = 60
+round3.text = \"Final Round\"
+round3.font = 1,0,0
+ko.anim = 100
+
+[WinIcon]
+p1.n.spr = 20,0
+p1.t.spr = 21,0
+useiconupto = 3
+";
+        let lifebar_def = read_lifebar_def(Categories::read_def(Cursor::new(&def[..])));
+        assert_eq!("fight.sff", lifebar_def.files.sprite_file);
+        assert_eq!(Some(&"font/f-6x9.fnt".to_owned()), lifebar_def.files.fonts.get(&2));
+        let [first_bar, second_bar] = &lifebar_def.lifebar;
+        assert_eq!((140., 12.), first_bar.position);
+        assert_eq!(Some(10), first_bar.bg0.anim);
+        assert_eq!(Some((12, 0)), first_bar.mid.sprite);
+        assert!(first_bar.bg1.is_empty());
+        assert_eq!((0., 127.), second_bar.range_x);
+        assert_eq!(-1, second_bar.front.facing);
+        assert_eq!(Some(FontReference { font: 2, bank: 3, alignment: TextAlignment::Right }), lifebar_def.name[1].name.font);
+        assert_eq!(TextAlignment::Left, lifebar_def.name[0].name.font.unwrap().alignment);
+        let round = &lifebar_def.round;
+        assert_eq!(Some("Round %i"), round.round(2).text.as_deref());
+        assert_eq!(Some(60), round.round(2).display_time);
+        assert_eq!(Some("Final Round"), round.round(3).text.as_deref());
+        assert_eq!(Some(100), round.ko.anim);
+        let rules = round.match_rules(MatchRules::default());
+        assert_eq!((3, 30, 30), (rules.rounds_to_win, rules.ko_slow_time, rules.start_wait_time));
+        let win_icon = &lifebar_def.win_icon[0];
+        assert_eq!(Some((21, 0)), win_icon.icon(WinKind::Time).and_then(|icon| icon.sprite));
+        assert_eq!(None, win_icon.icon(WinKind::Throw));
+        assert_eq!(3, win_icon.use_icon_up_to);
+    }
+}
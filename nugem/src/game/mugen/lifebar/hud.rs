@@ -0,0 +1,448 @@
+use std::collections::HashMap;
+use crate::game::mugen::character::air::{Animations, Animator};
+use crate::game::mugen::round::Announcement;
+use super::{BarDef, FontReference, LifebarDef, LifebarElement, WinKind};
+
+/// Size of the screen in the coordinates of the lifebar
+pub const LIFEBAR_SCREEN_SIZE: (f32, f32) = (320., 240.);
+
+/// Ticks during which the damage stays on the life gauge before draining
+const LAG_DELAY: i32 = 30;
+
+/// Part of the life gauge drained by the damage each tick
+const LAG_SPEED: f32 = 0.005;
+
+/// Ticks during which an announcement without display time is displayed
+const DEFAULT_ANNOUNCEMENT_TIME: i32 = 60;
+
+/// Gauge of the life of a player, keeping the damage visible for a while: the lagging value stays still after a
+/// damage, then drains down to the current value
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LifeGauge {
+    /// Current value, as a fraction of the gauge
+    value: f32,
+    lagged: f32,
+    /// Ticks before the lagging value drains
+    lag_timer: i32,
+}
+
+impl LifeGauge {
+    pub fn new(value: f32) -> LifeGauge {
+        LifeGauge {
+            value,
+            lagged: value,
+            lag_timer: 0,
+        }
+    }
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+    pub fn lagged(&self) -> f32 {
+        self.lagged
+    }
+    pub fn tick(&mut self, value: f32) {
+        if value < self.value {
+            self.lag_timer = LAG_DELAY;
+        }
+        self.value = value;
+        if self.lag_timer > 0 {
+            self.lag_timer -= 1;
+        }
+        else {
+            self.lagged -= LAG_SPEED;
+        }
+        self.lagged = self.lagged.max(value);
+    }
+}
+
+/// Hit counter of a player, staying displayed for a while after the end of the combo
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct ComboCounter {
+    hits: i32,
+    /// Ticks during which the counter stays displayed
+    remaining: i32,
+}
+
+impl ComboCounter {
+    /// Hits displayed, or `None` if the counter is hidden
+    pub fn displayed_hits(&self) -> Option<i32> {
+        (self.remaining > 0).then_some(self.hits)
+    }
+    /// Update the counter with the hits of the current combo, displayed from the second hit
+    pub fn tick(&mut self, hits: i32, display_time: i32) {
+        if hits >= 2 {
+            self.hits = hits;
+            self.remaining = display_time.max(1);
+        }
+        else {
+            self.remaining = (self.remaining - 1).max(0);
+        }
+    }
+}
+
+/// State of a player displayed by the lifebar
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlayerHud<'a> {
+    pub life: i32,
+    pub life_max: i32,
+    pub power: i32,
+    pub power_max: i32,
+    pub name: &'a str,
+    /// Hits of the current combo of the player
+    pub combo: i32,
+}
+
+impl PlayerHud<'_> {
+    fn life_ratio(&self) -> f32 {
+        ratio(self.life, self.life_max)
+    }
+}
+
+fn ratio(value: i32, max: i32) -> f32 {
+    (value as f32 / max.max(1) as f32).clamp(0., 1.)
+}
+
+/// Image of a sprite of the lifebar
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HudImage {
+    /// Sprite of the sprite file of the lifebar
+    Lifebar(u16, u16),
+    /// Sprite of the sprite file of the character of a player
+    Character { player: usize, group: u16, image: u16 },
+}
+
+/// Sprite or text drawn by the lifebar, in the coordinates of the lifebar screen
+#[derive(Clone, PartialEq, Debug)]
+pub enum HudDraw {
+    Sprite {
+        image: HudImage,
+        /// Position of the axis of the sprite
+        position: (f32, f32),
+        flip: (bool, bool),
+        scale: (f32, f32),
+        /// Horizontal range of the screen where the sprite is visible, for the gauges
+        visible_x: Option<(f32, f32)>,
+    },
+    Text {
+        font: FontReference,
+        text: String,
+        position: (f32, f32),
+    },
+}
+
+/// Displays of a fight: the gauges, the faces and names of the players, the timer, the hit counters, the announcements
+/// and the win icons.
+///
+/// The lifebar keeps what changes over time, and lays the elements out for the drawing.
+#[derive(Clone, Debug)]
+pub struct Hud {
+    life: [LifeGauge; 2],
+    combos: [ComboCounter; 2],
+    /// Current announcement, and the ticks since its start
+    announcement: Option<(Announcement, i32)>,
+    /// Rounds won by each player, and whether they were won without damage
+    wins: [Vec<(WinKind, bool)>; 2],
+    /// Animations of the lifebar, by action number
+    animators: HashMap<u32, Animator>,
+}
+
+impl Hud {
+    pub fn new(animations: &Animations) -> Hud {
+        Hud {
+            life: [LifeGauge::new(1.); 2],
+            combos: [ComboCounter::default(); 2],
+            announcement: None,
+            wins: [Vec::new(), Vec::new()],
+            animators: animations.iter().map(|(&action, animation)| (action, Animator::new(animation.clone()))).collect(),
+        }
+    }
+    pub fn life_gauge(&self, player: usize) -> &LifeGauge {
+        &self.life[player]
+    }
+    pub fn announcement(&self) -> Option<Announcement> {
+        self.announcement.map(|(announcement, _)| announcement)
+    }
+    /// Display an announcement, replacing the current one
+    pub fn announce(&mut self, announcement: Announcement, lifebar_def: &LifebarDef) {
+        self.announcement = Some((announcement, 0));
+        if let Some(animator) = announcement_element(lifebar_def, announcement).anim.and_then(|action| self.animators.get_mut(&action)) {
+            animator.reset();
+        }
+    }
+    /// Add a win icon to a player
+    pub fn add_win(&mut self, player: usize, kind: WinKind, perfect: bool) {
+        self.wins[player].push((kind, perfect));
+    }
+    /// Remove the win icons, for a new match
+    pub fn clear_wins(&mut self) {
+        self.wins.iter_mut().for_each(Vec::clear);
+    }
+    /// Reset the gauges to the current values, for a new round
+    pub fn reset_gauges(&mut self, players: &[PlayerHud; 2]) {
+        self.life = players.each_ref().map(|player| LifeGauge::new(player.life_ratio()));
+        self.combos = [ComboCounter::default(); 2];
+    }
+    pub fn tick(&mut self, lifebar_def: &LifebarDef, players: &[PlayerHud; 2]) {
+        for ((gauge, combo), player) in self.life.iter_mut().zip(self.combos.iter_mut()).zip(players.iter()) {
+            gauge.tick(player.life_ratio());
+            combo.tick(player.combo, lifebar_def.combo.display_time);
+        }
+        if let Some((announcement, elapsed)) = self.announcement.as_mut() {
+            *elapsed += 1;
+            let display_time = announcement_element(lifebar_def, *announcement).display_time.unwrap_or(DEFAULT_ANNOUNCEMENT_TIME);
+            if *elapsed >= display_time {
+                self.announcement = None;
+            }
+        }
+        for animator in self.animators.values_mut() {
+            animator.tick();
+        }
+    }
+    /// Elements to draw, in drawing order
+    pub fn layout(&self, lifebar_def: &LifebarDef, players: &[PlayerHud; 2], round: u32, timer: Option<i32>) -> Vec<HudDraw> {
+        let mut draws = Vec::new();
+        for (player_index, player) in players.iter().enumerate() {
+            let gauge = &self.life[player_index];
+            self.bar(&lifebar_def.lifebar[player_index], gauge.value(), gauge.lagged(), &mut draws);
+            let power_bar = &lifebar_def.powerbar[player_index];
+            let power = ratio(player.power, player.power_max);
+            self.bar(power_bar, power, power, &mut draws);
+            // levels of 1000 power
+            self.element(&power_bar.counter, power_bar.position, None, Some((player.power / 1000).to_string()), &mut draws);
+            let face = &lifebar_def.face[player_index];
+            self.element(&face.bg, face.position, None, None, &mut draws);
+            self.element(&face.face, face.position, Some(player_index), None, &mut draws);
+            let name = &lifebar_def.name[player_index];
+            self.element(&name.bg, name.position, None, None, &mut draws);
+            self.element(&name.name, name.position, None, Some(player.name.to_owned()), &mut draws);
+        }
+        let time = &lifebar_def.time;
+        self.element(&time.bg, time.position, None, None, &mut draws);
+        if let Some(timer) = timer {
+            self.element(&time.counter, time.position, None, Some(timer.to_string()), &mut draws);
+        }
+        for (player_index, combo) in self.combos.iter().enumerate() {
+            if let Some(hits) = combo.displayed_hits() {
+                self.combo(lifebar_def, player_index, hits, &mut draws);
+            }
+        }
+        for (player_index, wins) in self.wins.iter().enumerate() {
+            self.win_icons(lifebar_def, player_index, wins, &mut draws);
+        }
+        if let Some((announcement, _)) = self.announcement {
+            let round_def = &lifebar_def.round;
+            let element = announcement_element(lifebar_def, announcement);
+            let text = element.text.as_ref().map(|text| match announcement {
+                Announcement::Round(_) => text.replace("%i", &round.to_string()),
+                Announcement::Win(winner) => text.replace("%s", players[winner].name),
+                _ => text.clone(),
+            });
+            self.element(element, round_def.position, None, text, &mut draws);
+        }
+        draws
+    }
+    /// Layers of a gauge: the front layer shows the current value, and the middle layer the lagging value
+    fn bar(&self, bar: &BarDef, value: f32, lagged: f32, draws: &mut Vec<HudDraw>) {
+        let visible_x = |value: f32| {
+            let empty = bar.position.0 + bar.range_x.0;
+            let full = empty + value * (bar.range_x.1 - bar.range_x.0);
+            (empty.min(full), empty.max(full))
+        };
+        self.element(&bar.bg0, bar.position, None, None, draws);
+        self.element(&bar.bg1, bar.position, None, None, draws);
+        let first_gauge = draws.len();
+        self.element(&bar.mid, bar.position, None, None, draws);
+        set_visible_x(&mut draws[first_gauge..], visible_x(lagged));
+        let first_gauge = draws.len();
+        self.element(&bar.front, bar.position, None, None, draws);
+        set_visible_x(&mut draws[first_gauge..], visible_x(value));
+    }
+    /// Hit counter of a player: the counter of the second player is mirrored on the right of the screen
+    fn combo(&self, lifebar_def: &LifebarDef, player_index: usize, hits: i32, draws: &mut Vec<HudDraw>) {
+        let combo = &lifebar_def.combo;
+        let first_draw = draws.len();
+        self.element(&combo.counter, combo.position, None, Some(hits.to_string()), draws);
+        self.element(&combo.text, combo.position, None, combo.text.text.as_ref().map(|text| text.replace("%i", &hits.to_string())), draws);
+        if player_index == 1 {
+            for draw in draws[first_draw..].iter_mut() {
+                match draw {
+                    HudDraw::Sprite { position, flip, .. } => {
+                        position.0 = LIFEBAR_SCREEN_SIZE.0 - position.0;
+                        flip.0 = !flip.0;
+                    },
+                    HudDraw::Text { font, position, .. } => {
+                        position.0 = LIFEBAR_SCREEN_SIZE.0 - position.0;
+                        font.alignment = font.alignment.mirrored();
+                    },
+                }
+            }
+        }
+    }
+    /// Icons of the rounds won by a player, or the count of the wins after too many icons
+    fn win_icons(&self, lifebar_def: &LifebarDef, player_index: usize, wins: &[(WinKind, bool)], draws: &mut Vec<HudDraw>) {
+        let win_icon = &lifebar_def.win_icon[player_index];
+        if wins.len() > win_icon.use_icon_up_to {
+            self.element(&win_icon.counter, win_icon.position, None, Some(wins.len().to_string()), draws);
+            return;
+        }
+        for (index, &(kind, perfect)) in wins.iter().enumerate() {
+            let position = (
+                win_icon.position.0 + index as f32 * win_icon.icon_offset.0,
+                win_icon.position.1 + index as f32 * win_icon.icon_offset.1,
+            );
+            if let Some(icon) = win_icon.icon(kind).or_else(|| win_icon.icon(WinKind::Normal)) {
+                self.element(icon, position, None, None, draws);
+            }
+            if perfect {
+                self.element(&win_icon.perfect, position, None, None, draws);
+            }
+        }
+    }
+    /// Sprite and text of an element at the position of its section: the sprite of the current frame of its animation
+    /// or its sprite, from the sprites of the lifebar or of the character of a player, and its text or a given text
+    fn element(&self, element: &LifebarElement, position: (f32, f32), character: Option<usize>, text: Option<String>, draws: &mut Vec<HudDraw>) {
+        let position = (position.0 + element.offset.0, position.1 + element.offset.1);
+        let mirrored = element.facing < 0;
+        let image = |(group, image): (u16, u16)| match character {
+            Some(player) => HudImage::Character { player, group, image },
+            None => HudImage::Lifebar(group, image),
+        };
+        let frame = element.anim.and_then(|action| self.animators.get(&action)?.current_frame());
+        if let Some(frame) = frame {
+            let offset_x = if mirrored { -frame.offset.0 } else { frame.offset.0 };
+            draws.push(HudDraw::Sprite {
+                image: image((frame.group, frame.image)),
+                position: (position.0 + offset_x as f32 * element.scale.0, position.1 + frame.offset.1 as f32 * element.scale.1),
+                flip: (frame.flip.0 != mirrored, frame.flip.1),
+                scale: (frame.scale.0 * element.scale.0, frame.scale.1 * element.scale.1),
+                visible_x: None,
+            });
+        }
+        else if let Some(sprite) = element.sprite {
+            draws.push(HudDraw::Sprite {
+                image: image(sprite),
+                position,
+                flip: (mirrored, false),
+                scale: element.scale,
+                visible_x: None,
+            });
+        }
+        if let (Some(font), Some(text)) = (element.font, text.or_else(|| element.text.clone())) {
+            draws.push(HudDraw::Text { font, text, position });
+        }
+    }
+}
+
+fn set_visible_x(draws: &mut [HudDraw], range: (f32, f32)) {
+    for draw in draws.iter_mut() {
+        if let HudDraw::Sprite { visible_x, .. } = draw {
+            *visible_x = Some(range);
+        }
+    }
+}
+
+/// Element of the `[Round]` section displaying an announcement
+fn announcement_element(lifebar_def: &LifebarDef, announcement: Announcement) -> &LifebarElement {
+    let round_def = &lifebar_def.round;
+    match announcement {
+        Announcement::Round(round) => round_def.round(round),
+        Announcement::Fight => &round_def.fight,
+        Announcement::KO => &round_def.ko,
+        Announcement::DoubleKO => &round_def.double_ko,
+        Announcement::TimeOver => &round_def.time_over,
+        Announcement::Win(_) => &round_def.win,
+        Announcement::Draw => &round_def.draw,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use crate::game::mugen::format::generic_def::Categories;
+    use crate::game::mugen::font::TextAlignment;
+    use crate::game::mugen::lifebar::read_lifebar_def;
+
+    #[test]
+    fn damage_lag() {
+        let mut gauge = LifeGauge::new(1.);
+        gauge.tick(0.8);
+        assert_eq!((0.8, 1.), (gauge.value(), gauge.lagged()));
+        for _ in 0..LAG_DELAY - 1 {
+            gauge.tick(0.8);
+        }
+        assert_eq!(1., gauge.lagged());
+        gauge.tick(0.8);
+        assert_eq!(1. - LAG_SPEED, gauge.lagged());
+        // a new damage stops the draining
+        gauge.tick(0.5);
+        assert_eq!(1. - LAG_SPEED, gauge.lagged());
+        for _ in 0..1000 {
+            gauge.tick(0.5);
+        }
+        assert_eq!(0.5, gauge.lagged());
+        // recovering life moves both values
+        gauge.tick(0.9);
+        assert_eq!((0.9, 0.9), (gauge.value(), gauge.lagged()));
+    }
+
+    #[test]
+    fn lifebar_layout() {
+        let def = b"
+[Lifebar]
+p1.pos = 140,12
+p1.bg0.spr = 10,0
+p1.mid.spr = 12,0
+p1.front.spr = 13,0
+p1.range.x = 0,-100
+
+[Name]
+p2.pos = 319,35
+p2.name.font = 1,0,-1
+
+[Combo]
+pos = 10,80
+counter.font = 2,0,1
+text.font = 1,0,1
+text.text = \"%i Hits\"
+text.offset = 20,0
+displaytime = 10
+
+[Round]
+pos = 160,100
+round.default.font = 1,0,0
+round.default.text = \"Round %i\"
+win.font = 1
+win.text = \"%s Wins\"
+";
+        let lifebar_def = read_lifebar_def(Categories::read_def(Cursor::new(&def[..])));
+        let mut hud = Hud::new(&Animations::new());
+        let player = |life: i32, combo: i32| PlayerHud { life, life_max: 1000, power: 0, power_max: 3000, name: "Kung Fu Man", combo };
+        let mut players = [player(1000, 0), player(1000, 0)];
+        players[0].life = 750;
+        hud.tick(&lifebar_def, &players);
+        let draws = hud.layout(&lifebar_def, &players, 1, None);
+        let sprite = |draw: &HudDraw| match draw {
+            HudDraw::Sprite { image, visible_x, .. } => Some((*image, *visible_x)),
+            HudDraw::Text { .. } => None,
+        };
+        // the damage is still displayed by the middle gauge
+        assert_eq!(Some((HudImage::Lifebar(10, 0), None)), sprite(&draws[0]));
+        assert_eq!(Some((HudImage::Lifebar(12, 0), Some((40., 140.)))), sprite(&draws[1]));
+        assert_eq!(Some((HudImage::Lifebar(13, 0), Some((65., 140.)))), sprite(&draws[2]));
+        let name_font = FontReference { font: 1, bank: 0, alignment: TextAlignment::Right };
+        assert_eq!(HudDraw::Text { font: name_font, text: "Kung Fu Man".to_owned(), position: (319., 35.) }, draws[3]);
+        assert_eq!(4, draws.len());
+        // the combo of the second player is mirrored
+        players[1].combo = 3;
+        hud.tick(&lifebar_def, &players);
+        hud.announce(Announcement::Win(1), &lifebar_def);
+        let texts: Vec<HudDraw> = hud.layout(&lifebar_def, &players, 1, None).into_iter().skip(4).collect();
+        let font = |font: u32, alignment: TextAlignment| FontReference { font, bank: 0, alignment };
+        assert_eq!(vec![
+            HudDraw::Text { font: font(2, TextAlignment::Right), text: "3".to_owned(), position: (310., 80.) },
+            HudDraw::Text { font: font(1, TextAlignment::Right), text: "3 Hits".to_owned(), position: (290., 80.) },
+            HudDraw::Text { font: font(1, TextAlignment::Center), text: "Kung Fu Man Wins".to_owned(), position: (160., 100.) },
+        ], texts);
+    }
+}
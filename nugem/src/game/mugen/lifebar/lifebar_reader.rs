@@ -0,0 +1,71 @@
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use crate::game::mugen::character::air::{read_air_file, Animations};
+use crate::game::mugen::character::file_reader::{FileReader, ReadSeek};
+use crate::game::mugen::font::{read_font, Font, FontError};
+use crate::game::mugen::format::generic_def::Categories;
+use super::{read_lifebar_def, LifebarDef};
+
+/// Name of the lifebar definition file in the common folders
+pub const LIFEBAR_DEF_FILE: &str = "fight.def";
+
+/// Lifebar of a screenpack: its definition, its animations and the files of its sprites and fonts
+pub struct Lifebar {
+    def: LifebarDef,
+    animations: Animations,
+    file_reader: Box<dyn FileReader>,
+    /// Path of the definition file, from the root of the file reader
+    def_path: PathBuf,
+}
+
+impl std::fmt::Debug for Lifebar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct(stringify!(Lifebar))
+            .field(stringify!(def_path), &self.def_path)
+            .field(stringify!(def), &self.def)
+            .finish()
+    }
+}
+
+impl Lifebar {
+    /// Open the lifebar of a definition file of a file reader
+    pub fn open(def_path: &Path, mut file_reader: Box<dyn FileReader>) -> std::io::Result<Lifebar> {
+        let mut def_data = Vec::new();
+        file_reader.read_file(def_path)?.read_to_end(&mut def_data)?;
+        let def = read_lifebar_def(Categories::read_def(Cursor::new(&def_data)));
+        // the actions of the animated elements are in the definition file
+        let animations = read_air_file(Cursor::new(&def_data));
+        Ok(Lifebar {
+            def,
+            animations,
+            file_reader,
+            def_path: def_path.to_path_buf(),
+        })
+    }
+    pub fn def(&self) -> &LifebarDef {
+        &self.def
+    }
+    pub fn animations(&self) -> &Animations {
+        &self.animations
+    }
+    /// Read a file referenced by the definition, from the folder of the definition file
+    pub fn read_file(&mut self, path: &str) -> std::io::Result<Box<dyn ReadSeek + '_>> {
+        let path = path.replace('\\', "/");
+        let def_folder = self.def_path.parent().unwrap_or(Path::new(""));
+        self.file_reader.read_file(&def_folder.join(path))
+    }
+    /// Read the sprite file of the `[Files]` section
+    pub fn read_sprites(&mut self) -> Result<nugem_sff::SpriteFile, nugem_sff::LoadingError> {
+        let sprite_file = self.def.files.sprite_file.clone();
+        nugem_sff::SpriteFile::read(self.read_file(&sprite_file)?, Vec::new())
+    }
+    /// Read a font of the `[Files]` section, `None` if the lifebar has no font with this number
+    pub fn read_font(&mut self, font_number: u32) -> Option<Result<Font, FontError>> {
+        let font_path = self.def.files.fonts.get(&font_number)?.clone();
+        Some(read_font(&font_path, |path| {
+            let mut data = Vec::new();
+            self.read_file(path)?.read_to_end(&mut data)?;
+            Ok(data)
+        }))
+    }
+}
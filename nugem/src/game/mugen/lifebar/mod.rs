@@ -0,0 +1,8 @@
+mod fight_def;
+pub use self::fight_def::*;
+
+mod hud;
+pub use self::hud::*;
+
+mod lifebar_reader;
+pub use self::lifebar_reader::*;
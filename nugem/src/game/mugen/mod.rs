@@ -1,3 +1,15 @@
 pub mod format;
 
 pub mod character;
+
+pub mod stage;
+
+pub mod trigger;
+
+pub mod combat;
+
+pub mod round;
+
+pub mod lifebar;
+
+pub mod font;
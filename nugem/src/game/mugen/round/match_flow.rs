@@ -0,0 +1,448 @@
+use super::MatchRules;
+
+/// Phase of a round, numbered as in the RoundState trigger
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RoundState {
+    /// Waiting before the intros
+    PreIntro,
+    /// Intros of the players and announcements of the round
+    Intro,
+    /// The players have control
+    Fight,
+    /// After a KO or a time over, waiting for the win poses
+    PreOver,
+    /// Win poses
+    Over,
+}
+
+impl RoundState {
+    pub fn number(self) -> i32 {
+        match self {
+            RoundState::PreIntro => 0,
+            RoundState::Intro => 1,
+            RoundState::Fight => 2,
+            RoundState::PreOver => 3,
+            RoundState::Over => 4,
+        }
+    }
+}
+
+/// How a round ended
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RoundEnd {
+    KO,
+    DoubleKO,
+    TimeOver,
+}
+
+/// Result of a round
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RoundResult {
+    /// Index of the winning player, or `None` for a draw
+    pub winner: Option<usize>,
+    pub end: RoundEnd,
+}
+
+/// Message displayed and announced during a match
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Announcement {
+    Round(u32),
+    Fight,
+    KO,
+    DoubleKO,
+    TimeOver,
+    /// Winner of the round
+    Win(usize),
+    Draw,
+}
+
+/// Change of the match that the fight applies to the players
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RoundEvent {
+    /// Reset the players for a new round, without control
+    StartRound(u32),
+    /// Start the intros of the players, in the first round
+    StartIntro,
+    Announce(Announcement),
+    /// Give control to the players
+    StartFight,
+    /// Start the win poses of the winner, and the lose poses after a time over
+    Poses(RoundResult),
+    /// End of the match, with the index of the winning player or `None` for a draw game
+    MatchOver(Option<usize>),
+}
+
+/// State of a player read by the match flow
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FighterStatus {
+    pub life: i32,
+    pub life_max: i32,
+    /// The player is playing its intro
+    pub in_intro: bool,
+}
+
+/// Values of the round triggers for a player
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RoundInfo {
+    pub round_no: i32,
+    pub round_state: RoundState,
+    pub rounds_existed: i32,
+    pub match_over: bool,
+    /// The player won the current round
+    pub win: bool,
+    /// The player lost the current round
+    pub lose: bool,
+    /// The current round is a draw
+    pub draw_game: bool,
+}
+
+impl Default for RoundInfo {
+    fn default() -> Self {
+        RoundInfo {
+            round_no: 1,
+            round_state: RoundState::Fight,
+            rounds_existed: 0,
+            match_over: false,
+            win: false,
+            lose: false,
+            draw_game: false,
+        }
+    }
+}
+
+impl RoundInfo {
+    /// Values of the round triggers for the opponent of the player
+    pub fn opponent(self) -> RoundInfo {
+        RoundInfo {
+            win: self.lose,
+            lose: self.win,
+            ..self
+        }
+    }
+}
+
+/// Rounds of a match between two players, from the intros to the win poses of the last round.
+///
+/// The flow only reads the life of the players and tells the fight what to do with them through the events returned
+/// by each tick.
+#[derive(Clone, Debug)]
+pub struct MatchFlow {
+    rules: MatchRules,
+    round: u32,
+    state: RoundState,
+    /// Ticks since the start of the current round state
+    state_time: i32,
+    /// State time at which both intros ended
+    intro_end: Option<i32>,
+    /// Remaining ticks of the timer
+    timer: Option<i32>,
+    /// Ticks since the start of the slow motion after a KO
+    ko_slow: Option<i32>,
+    /// Rounds won by each player
+    wins: [u32; 2],
+    draws: u32,
+    /// Result of the current round once the fight is over
+    result: Option<RoundResult>,
+    /// Winner of the match once it is decided
+    match_winner: Option<Option<usize>>,
+    match_over: bool,
+}
+
+impl MatchFlow {
+    pub fn new(rules: MatchRules) -> MatchFlow {
+        MatchFlow {
+            rules,
+            round: 1,
+            state: RoundState::PreIntro,
+            state_time: 0,
+            intro_end: None,
+            timer: None,
+            ko_slow: None,
+            wins: [0; 2],
+            draws: 0,
+            result: None,
+            match_winner: None,
+            match_over: false,
+        }
+    }
+    pub fn rules(&self) -> &MatchRules {
+        &self.rules
+    }
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+    pub fn state(&self) -> RoundState {
+        self.state
+    }
+    pub fn wins(&self) -> [u32; 2] {
+        self.wins
+    }
+    pub fn result(&self) -> Option<RoundResult> {
+        self.result
+    }
+    pub fn match_over(&self) -> bool {
+        self.match_over
+    }
+    /// Count displayed by the timer, or `None` without time limit
+    pub fn timer_count(&self) -> Option<i32> {
+        let frames_per_count = self.rules.frames_per_count.max(1);
+        match self.timer {
+            Some(timer) => Some((timer + frames_per_count - 1) / frames_per_count),
+            None => self.rules.round_time,
+        }
+    }
+    /// Check if the players run during the current tick: they only run one tick out of `ko_slow_period` during the slow
+    /// motion after a KO
+    pub fn players_tick(&self) -> bool {
+        self.ko_slow.is_none_or(|elapsed| elapsed % self.rules.ko_slow_period.max(1) == 0)
+    }
+    /// Values of the round triggers for a player
+    pub fn round_info(&self, player: usize) -> RoundInfo {
+        let result = self.result.filter(|_| self.state == RoundState::Over);
+        RoundInfo {
+            round_no: self.round as i32,
+            round_state: self.state,
+            rounds_existed: self.round as i32 - 1,
+            match_over: self.match_winner.is_some(),
+            win: result.is_some_and(|result| result.winner == Some(player)),
+            lose: result.is_some_and(|result| result.winner.is_some_and(|winner| winner != player)),
+            draw_game: result.is_some_and(|result| result.winner.is_none()),
+        }
+    }
+    /// Advance the match by one tick, from the state of the players at the end of the previous tick
+    pub fn tick(&mut self, fighters: &[FighterStatus; 2]) -> Vec<RoundEvent> {
+        let mut events = Vec::new();
+        if let Some(elapsed) = self.ko_slow.as_mut() {
+            *elapsed += 1;
+            if *elapsed >= self.rules.ko_slow_time {
+                self.ko_slow = None;
+            }
+        }
+        match self.state {
+            RoundState::PreIntro => {
+                if self.state_time == 0 {
+                    events.push(RoundEvent::StartRound(self.round));
+                }
+                if self.state_time >= self.rules.start_wait_time {
+                    self.enter(RoundState::Intro);
+                    self.intro_end = None;
+                    if self.round == 1 {
+                        events.push(RoundEvent::StartIntro);
+                    }
+                    return events;
+                }
+            },
+            RoundState::Intro => {
+                if self.intro_end.is_none() && fighters.iter().all(|fighter| !fighter.in_intro) {
+                    self.intro_end = Some(self.state_time);
+                }
+                if let Some(intro_end) = self.intro_end {
+                    let time = self.state_time - intro_end;
+                    if time == self.rules.round_display_time {
+                        events.push(RoundEvent::Announce(Announcement::Round(self.round)));
+                    }
+                    if time == self.rules.fight_display_time {
+                        events.push(RoundEvent::Announce(Announcement::Fight));
+                    }
+                    if time >= self.rules.ctrl_time {
+                        self.enter(RoundState::Fight);
+                        self.timer = self.rules.round_time.map(|time| time * self.rules.frames_per_count.max(1));
+                        events.push(RoundEvent::StartFight);
+                        return events;
+                    }
+                }
+            },
+            RoundState::Fight => {
+                if let Some(timer) = self.timer.as_mut() {
+                    *timer = (*timer - 1).max(0);
+                }
+                if let Some(result) = self.fight_result(fighters) {
+                    self.enter(RoundState::PreOver);
+                    self.result = Some(result);
+                    let announcement = match result.end {
+                        RoundEnd::KO => Announcement::KO,
+                        RoundEnd::DoubleKO => Announcement::DoubleKO,
+                        RoundEnd::TimeOver => Announcement::TimeOver,
+                    };
+                    if result.end != RoundEnd::TimeOver && self.rules.ko_slow_time > 0 {
+                        self.ko_slow = Some(0);
+                    }
+                    events.push(RoundEvent::Announce(announcement));
+                    return events;
+                }
+            },
+            RoundState::PreOver => {
+                if self.state_time >= self.rules.over_wait_time {
+                    if let Some(result) = self.result {
+                        self.record(result);
+                        events.push(RoundEvent::Poses(result));
+                        events.push(RoundEvent::Announce(match result.winner {
+                            Some(winner) => Announcement::Win(winner),
+                            None => Announcement::Draw,
+                        }));
+                    }
+                    self.enter(RoundState::Over);
+                    return events;
+                }
+            },
+            RoundState::Over => {
+                if self.state_time >= self.rules.over_time && !self.match_over {
+                    match self.match_winner {
+                        Some(winner) => {
+                            self.match_over = true;
+                            events.push(RoundEvent::MatchOver(winner));
+                        },
+                        None => {
+                            self.round += 1;
+                            self.result = None;
+                            self.timer = None;
+                            self.enter(RoundState::PreIntro);
+                            events.push(RoundEvent::StartRound(self.round));
+                        },
+                    }
+                }
+            },
+        }
+        self.state_time += 1;
+        events
+    }
+    fn enter(&mut self, state: RoundState) {
+        self.state = state;
+        self.state_time = 0;
+    }
+    /// Result of the fight if it is over: a KO, or the time running out, the player with the highest ratio of life
+    /// winning
+    fn fight_result(&self, fighters: &[FighterStatus; 2]) -> Option<RoundResult> {
+        let knocked_out = fighters.each_ref().map(|fighter| fighter.life <= 0);
+        match knocked_out {
+            [true, true] => return Some(RoundResult { winner: None, end: RoundEnd::DoubleKO }),
+            [true, false] => return Some(RoundResult { winner: Some(1), end: RoundEnd::KO }),
+            [false, true] => return Some(RoundResult { winner: Some(0), end: RoundEnd::KO }),
+            [false, false] => (),
+        }
+        if self.timer != Some(0) {
+            return None;
+        }
+        let [first, second] = fighters.each_ref().map(|fighter| fighter.life as f32 / fighter.life_max.max(1) as f32);
+        let winner = if first > second { Some(0) } else if second > first { Some(1) } else { None };
+        Some(RoundResult { winner, end: RoundEnd::TimeOver })
+    }
+    /// Count the result of a round, and decide the winner of the match: the first player to win enough rounds, or the
+    /// player with the most wins when there have been too many draws
+    fn record(&mut self, result: RoundResult) {
+        match result.winner {
+            Some(winner) => self.wins[winner] += 1,
+            None => self.draws += 1,
+        }
+        let rounds_to_win = self.rules.rounds_to_win.max(1);
+        let too_many_draws = self.rules.max_draw_games >= 0 && self.draws as i32 > self.rules.max_draw_games;
+        if self.wins.iter().any(|&wins| wins >= rounds_to_win) || too_many_draws {
+            let [first, second] = self.wins;
+            self.match_winner = Some(if first > second { Some(0) } else if second > first { Some(1) } else { None });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rules() -> MatchRules {
+        MatchRules {
+            round_time: Some(2),
+            frames_per_count: 10,
+            start_wait_time: 2,
+            round_display_time: 0,
+            fight_display_time: 1,
+            ctrl_time: 2,
+            ko_slow_time: 4,
+            over_wait_time: 6,
+            over_time: 3,
+            ..MatchRules::default()
+        }
+    }
+
+    fn fighters(lives: [i32; 2]) -> [FighterStatus; 2] {
+        lives.map(|life| FighterStatus { life, life_max: 100, in_intro: false })
+    }
+
+    /// Run ticks until an event, returning the events of the tick and the number of ticks run
+    fn run_until(flow: &mut MatchFlow, lives: [i32; 2], event: RoundEvent) -> (Vec<RoundEvent>, i32) {
+        for ticks in 1..1000 {
+            let events = flow.tick(&fighters(lives));
+            if events.contains(&event) {
+                return (events, ticks);
+            }
+        }
+        panic!("{event:?} not reached");
+    }
+
+    #[test]
+    fn round_start() {
+        let mut flow = MatchFlow::new(rules());
+        assert_eq!(vec![RoundEvent::StartRound(1)], flow.tick(&fighters([100, 100])));
+        assert_eq!(0, flow.round_info(0).round_state.number());
+        run_until(&mut flow, [100, 100], RoundEvent::StartIntro);
+        // the announcements wait for the end of the intros
+        let mut in_intro = fighters([100, 100]);
+        in_intro[1].in_intro = true;
+        for _ in 0..5 {
+            assert_eq!(Vec::<RoundEvent>::new(), flow.tick(&in_intro));
+        }
+        assert_eq!(vec![RoundEvent::Announce(Announcement::Round(1))], flow.tick(&fighters([100, 100])));
+        assert_eq!(vec![RoundEvent::Announce(Announcement::Fight)], flow.tick(&fighters([100, 100])));
+        assert_eq!(vec![RoundEvent::StartFight], flow.tick(&fighters([100, 100])));
+        assert_eq!(RoundState::Fight, flow.state());
+        assert_eq!(Some(2), flow.timer_count());
+        flow.tick(&fighters([100, 100]));
+        assert_eq!(Some(2), flow.timer_count());
+    }
+
+    #[test]
+    fn knock_out() {
+        let mut flow = MatchFlow::new(rules());
+        run_until(&mut flow, [100, 100], RoundEvent::StartFight);
+        assert_eq!(vec![RoundEvent::Announce(Announcement::KO)], flow.tick(&fighters([50, 0])));
+        // slow motion
+        let ticking: Vec<bool> = (0..5).map(|_| {
+            flow.tick(&fighters([50, 0]));
+            flow.players_tick()
+        }).collect();
+        assert_eq!(vec![false, true, false, true, true], ticking);
+        let result = RoundResult { winner: Some(0), end: RoundEnd::KO };
+        let (events, _) = run_until(&mut flow, [50, 0], RoundEvent::Poses(result));
+        assert_eq!(vec![RoundEvent::Poses(result), RoundEvent::Announce(Announcement::Win(0))], events);
+        assert!(flow.round_info(0).win && flow.round_info(1).lose);
+        assert_eq!(4, flow.round_info(1).round_state.number());
+        assert_eq!([1, 0], flow.wins());
+        // the second round has no intro
+        run_until(&mut flow, [50, 0], RoundEvent::StartRound(2));
+        assert_eq!(1, flow.round_info(0).rounds_existed);
+        let (_, ticks) = run_until(&mut flow, [100, 100], RoundEvent::StartFight);
+        assert_eq!(5, ticks);
+        run_until(&mut flow, [100, 0], RoundEvent::Announce(Announcement::KO));
+        run_until(&mut flow, [100, 0], RoundEvent::MatchOver(Some(0)));
+        assert!(flow.match_over() && flow.round_info(1).match_over);
+    }
+
+    #[test]
+    fn time_over_and_draws() {
+        let mut flow = MatchFlow::new(rules());
+        run_until(&mut flow, [100, 100], RoundEvent::StartFight);
+        // judged by the ratio of life
+        let (_, ticks) = run_until(&mut flow, [30, 40], RoundEvent::Announce(Announcement::TimeOver));
+        assert_eq!(20, ticks);
+        run_until(&mut flow, [30, 40], RoundEvent::Poses(RoundResult { winner: Some(1), end: RoundEnd::TimeOver }));
+        // a double KO is a draw
+        run_until(&mut flow, [100, 100], RoundEvent::StartFight);
+        run_until(&mut flow, [0, 0], RoundEvent::Announce(Announcement::DoubleKO));
+        run_until(&mut flow, [0, 0], RoundEvent::Announce(Announcement::Draw));
+        assert!(flow.round_info(0).draw_game);
+        assert!(!flow.match_over());
+        // the second draw exceeds the maximum: the player with the most wins wins the match
+        run_until(&mut flow, [100, 100], RoundEvent::StartFight);
+        run_until(&mut flow, [50, 50], RoundEvent::Poses(RoundResult { winner: None, end: RoundEnd::TimeOver }));
+        run_until(&mut flow, [100, 100], RoundEvent::MatchOver(Some(1)));
+        assert_eq!(3, flow.round());
+    }
+}
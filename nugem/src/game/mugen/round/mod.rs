@@ -0,0 +1,5 @@
+mod rules;
+pub use self::rules::*;
+
+mod match_flow;
+pub use self::match_flow::*;
@@ -0,0 +1,47 @@
+/// Settings of a match, with the timing of the rounds in ticks
+#[derive(Clone, PartialEq, Debug)]
+pub struct MatchRules {
+    /// Rounds a player must win to win the match
+    pub rounds_to_win: u32,
+    /// Draw rounds after which the match ends, negative for no limit
+    pub max_draw_games: i32,
+    /// Counts of the timer at the start of a round, or `None` for no time limit
+    pub round_time: Option<i32>,
+    /// Ticks per count of the timer
+    pub frames_per_count: i32,
+    /// Ticks before the intros of the players
+    pub start_wait_time: i32,
+    /// Ticks after the intros before the "Round" announcement
+    pub round_display_time: i32,
+    /// Ticks after the intros before the "Fight" announcement
+    pub fight_display_time: i32,
+    /// Ticks after the intros before the players get control and the fight starts
+    pub ctrl_time: i32,
+    /// Ticks of slow motion after a KO
+    pub ko_slow_time: i32,
+    /// Ticks of the game per tick of the players during the slow motion
+    pub ko_slow_period: i32,
+    /// Ticks after the end of the fight before the win poses
+    pub over_wait_time: i32,
+    /// Ticks of the win poses before the next round
+    pub over_time: i32,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        MatchRules {
+            rounds_to_win: 2,
+            max_draw_games: 1,
+            round_time: Some(99),
+            frames_per_count: 60,
+            start_wait_time: 30,
+            round_display_time: 0,
+            fight_display_time: 60,
+            ctrl_time: 90,
+            ko_slow_time: 60,
+            ko_slow_period: 2,
+            over_wait_time: 45,
+            over_time: 210,
+        }
+    }
+}
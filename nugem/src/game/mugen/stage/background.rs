@@ -0,0 +1,173 @@
+use crate::game::mugen::character::air::BlendMode;
+use super::{number, number_pair, numbers, StageSection};
+
+/// Kind of a background element, from its `type` parameter
+#[derive(Clone, PartialEq, Debug)]
+pub enum BackgroundKind {
+    /// Sprite drawn as is
+    Normal,
+    /// Sprite whose top and bottom edges move at different speeds, for floors seen in perspective
+    Parallax {
+        /// Horizontal scales of the top and bottom edges relative to `delta`
+        x_scale: (f32, f32),
+        /// Widths of the top and bottom edges in pixels, overriding `xscale`
+        width: Option<(f32, f32)>,
+        /// Vertical scale in percent when the camera is at the ground, and its change per camera pixel
+        y_scale_start: f32,
+        y_scale_delta: f32,
+    },
+    /// Animation of the `[Begin Action]` sections of the stage definition
+    Anim {
+        action: u32,
+    },
+}
+
+/// Sine wave added to the position of an element
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct BackgroundSine {
+    pub amplitude: f32,
+    /// Period in ticks, no movement if not positive
+    pub period: f32,
+    /// Phase in ticks
+    pub phase: f32,
+}
+
+impl BackgroundSine {
+    /// Read an `amplitude, period, phase` value
+    pub fn from_values(values: &[f32]) -> BackgroundSine {
+        let value = |index: usize| values.get(index).copied().unwrap_or(0.);
+        BackgroundSine {
+            amplitude: value(0),
+            period: value(1),
+            phase: value(2),
+        }
+    }
+    /// Offset at a tick
+    pub fn offset(&self, time: i32) -> f32 {
+        if self.period <= 0. {
+            return 0.;
+        }
+        self.amplitude * (std::f32::consts::TAU * (time as f32 + self.phase) / self.period).sin()
+    }
+}
+
+/// Background element of a stage, from a `[BG <name>]` section
+#[derive(Clone, PartialEq, Debug)]
+pub struct BackgroundElement {
+    pub name: String,
+    pub kind: BackgroundKind,
+    /// Sprite of the stage sprite file: group, image
+    pub sprite: (u16, u16),
+    /// Identifier of the element for the background controllers
+    pub id: i32,
+    /// 0 behind the players, 1 in front of them
+    pub layer: i32,
+    /// Position of the axis of the sprite from the top center of the screen when the camera is at its origin
+    pub start: (f32, f32),
+    /// Movement of the element per pixel of camera movement: 1 moves with the stage, 0 stays on the screen
+    pub delta: (f32, f32),
+    /// Repetitions of the sprite: 0 drawn once, 1 repeated infinitely, more than 1 drawn that many times
+    pub tile: (i32, i32),
+    /// Pixels between the repeated sprites
+    pub tile_spacing: (f32, f32),
+    /// Movement per tick
+    pub velocity: (f32, f32),
+    pub sin_x: BackgroundSine,
+    pub sin_y: BackgroundSine,
+    /// Transparent pixels of color 0, unless `mask = 0` draws them with the color 0 of the palette
+    pub mask: bool,
+    pub trans: BlendMode,
+    /// Area of the screen where the element is drawn: left, top, right, bottom
+    pub window: Option<[f32; 4]>,
+}
+
+/// Read a background element section, or `None` if its type is invalid
+pub fn read_background_element(name: String, section: &StageSection) -> Option<BackgroundElement> {
+    let kind = match section.get("type").map(|kind| kind.trim().to_lowercase()).as_deref() {
+        None | Some("normal") => BackgroundKind::Normal,
+        Some("parallax") => BackgroundKind::Parallax {
+            x_scale: number_pair(section, "xscale", (1., 1.)),
+            width: section.contains_key("width").then(|| number_pair(section, "width", (0., 0.))),
+            y_scale_start: number(section, "yscalestart", 100.),
+            y_scale_delta: number(section, "yscaledelta", 0.),
+        },
+        Some("anim") => BackgroundKind::Anim {
+            action: u32::try_from(number(section, "actionno", -1.) as i64).ok()?,
+        },
+        Some(_) => None?,
+    };
+    let sprite = number_pair(section, "spriteno", (0., 0.));
+    let tile = number_pair(section, "tile", (0., 0.));
+    let tile_spacing = numbers(section, "tilespacing");
+    // a single tile spacing is used for both axes
+    let tile_spacing = match tile_spacing[..] {
+        [spacing] => (spacing, spacing),
+        _ => number_pair(section, "tilespacing", (0., 0.)),
+    };
+    let window = match numbers(section, "window")[..] {
+        [left, top, right, bottom, ..] => Some([left, top, right, bottom]),
+        _ => None,
+    };
+    let alpha = numbers(section, "alpha");
+    Some(BackgroundElement {
+        name,
+        kind,
+        sprite: (sprite.0 as u16, sprite.1 as u16),
+        id: number(section, "id", 0.) as i32,
+        layer: number(section, "layerno", 0.) as i32,
+        start: number_pair(section, "start", (0., 0.)),
+        delta: number_pair(section, "delta", (1., 1.)),
+        tile: (tile.0 as i32, tile.1 as i32),
+        tile_spacing,
+        velocity: number_pair(section, "velocity", (0., 0.)),
+        sin_x: BackgroundSine::from_values(&numbers(section, "sin.x")),
+        sin_y: BackgroundSine::from_values(&numbers(section, "sin.y")),
+        mask: number(section, "mask", 1.) != 0.,
+        trans: read_background_trans(section.get("trans").map(String::as_str).unwrap_or(""), &alpha),
+        window,
+    })
+}
+
+/// Read the blending of an element: `trans = add`, `add1`, `addalpha` with `alpha = source, destination`, `sub` or
+/// `none`
+fn read_background_trans(trans: &str, alpha: &[f32]) -> BlendMode {
+    let alpha_factor = |index: usize, default: u16| alpha.get(index).map_or(default, |&factor| factor.clamp(0., 256.) as u16);
+    match trans.trim().to_lowercase().as_str() {
+        "add" => BlendMode::Add { source: 256, destination: 256 },
+        "add1" => BlendMode::Add { source: 256, destination: 128 },
+        "addalpha" => BlendMode::Add { source: alpha_factor(0, 256), destination: alpha_factor(1, 0) },
+        "sub" => BlendMode::Subtract,
+        _ => BlendMode::Normal,
+    }
+}
+
+/// Positions of the repetitions of a sprite on one axis, covering the screen range `visible` for an infinite tiling.
+///
+/// `position` is the position of the first sprite and `length` the length of the sprite on the axis.
+pub fn tile_positions(tile: i32, spacing: f32, position: f32, length: f32, visible: (f32, f32)) -> Vec<f32> {
+    let step = length + spacing;
+    match tile {
+        1 if step > 0. => {
+            // the repetition covering the start of the range, then the next ones until the end of the range
+            let first = position + ((visible.0 - position) / step).floor() * step;
+            let count = ((visible.1 - first) / step).ceil().max(1.) as usize;
+            (0..count).map(|i| first + i as f32 * step).collect()
+        },
+        count if count > 1 => (0..count).map(|i| position + i as f32 * step).collect(),
+        _ => vec![position],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tiled_positions() {
+        assert_eq!(vec![5.], tile_positions(0, 2., 5., 10., (-100., 100.)));
+        assert_eq!(vec![5., 17., 29.], tile_positions(3, 2., 5., 10., (-100., 100.)));
+        // infinite tiling in both directions from the position of the sprite
+        assert_eq!(vec![-19., -7., 5., 17.], tile_positions(1, 2., 5., 10., (-10., 20.)));
+        assert_eq!(vec![-7.], tile_positions(1, 2., 5., 10., (-7., 5.)));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::game::mugen::character::air::{Animations, Animator};
+use super::{BackgroundControllerKind, BackgroundElement, BackgroundKind, BackgroundSine, StageDef};
+
+/// Runtime variables of a background element, changed by its velocity and the background controllers
+#[derive(Clone, Debug)]
+pub struct BackgroundElementState {
+    /// Position of the element when the camera is at its origin, without the sine waves
+    pub position: (f32, f32),
+    pub velocity: (f32, f32),
+    pub sin_x: BackgroundSine,
+    pub sin_y: BackgroundSine,
+    pub visible: bool,
+    /// A disabled element is neither updated nor drawn
+    pub enabled: bool,
+    /// Ticks of the sine waves
+    pub time: i32,
+    /// Animation of an animated element
+    pub animator: Option<Animator>,
+}
+
+impl BackgroundElementState {
+    fn new(element: &BackgroundElement, animations: &Animations) -> BackgroundElementState {
+        let animator = match element.kind {
+            BackgroundKind::Anim { action } => animation(action, animations),
+            BackgroundKind::Normal | BackgroundKind::Parallax { .. } => None,
+        };
+        BackgroundElementState {
+            position: element.start,
+            velocity: element.velocity,
+            sin_x: element.sin_x,
+            sin_y: element.sin_y,
+            visible: true,
+            enabled: true,
+            time: 0,
+            animator,
+        }
+    }
+    /// Check if the element is drawn
+    pub fn drawn(&self) -> bool {
+        self.visible && self.enabled
+    }
+    /// Sprite of the element: the sprite of the current frame of an animated element, or the sprite of the element
+    pub fn sprite(&self, element: &BackgroundElement) -> Option<(u16, u16)> {
+        match self.animator.as_ref() {
+            Some(animator) => animator.current_display_info(),
+            None => Some(element.sprite),
+        }
+    }
+}
+
+fn animation(action: u32, animations: &Animations) -> Option<Animator> {
+    match animations.get(&action) {
+        Some(animation) => Some(Animator::new(animation.clone())),
+        None => {
+            log::error!("Background animation {action} not found");
+            None
+        },
+    }
+}
+
+/// Background elements of a stage during a fight
+#[derive(Clone, Debug)]
+pub struct StageBackground {
+    elements: Vec<BackgroundElementState>,
+    /// Ticks of each controller group
+    group_times: Vec<i32>,
+}
+
+impl StageBackground {
+    pub fn new(stage_def: &StageDef, animations: &Animations) -> StageBackground {
+        StageBackground {
+            elements: stage_def.elements.iter().map(|element| BackgroundElementState::new(element, animations)).collect(),
+            group_times: vec![0; stage_def.controller_groups.len()],
+        }
+    }
+    /// States of the elements, in the order of the elements of the stage definition
+    pub fn elements(&self) -> &[BackgroundElementState] {
+        &self.elements
+    }
+    /// Run one tick: the background controllers, then the movement and the animation of the enabled elements
+    pub fn tick(&mut self, stage_def: &StageDef, animations: &Animations) {
+        for (group, group_time) in stage_def.controller_groups.iter().zip(self.group_times.iter_mut()) {
+            for controller in group.controllers.iter().filter(|controller| controller.active(*group_time)) {
+                let controlled = stage_def.elements.iter().zip(self.elements.iter_mut()).filter(|(element, _)| group.controls(controller, element.id));
+                for (element, state) in controlled {
+                    apply_controller(&controller.kind, element, state, animations);
+                }
+            }
+            *group_time += 1;
+            if group.loop_time > 0 && *group_time >= group.loop_time {
+                *group_time = 0;
+            }
+        }
+        for state in self.elements.iter_mut().filter(|state| state.enabled) {
+            state.position.0 += state.velocity.0;
+            state.position.1 += state.velocity.1;
+            state.time += 1;
+            if let Some(animator) = state.animator.as_mut() {
+                animator.tick();
+            }
+        }
+    }
+    /// Position of the axis of the sprite of an element from the top center of the screen, for a camera position in
+    /// stage coordinates
+    pub fn element_position(&self, stage_def: &StageDef, index: usize, camera: (f32, f32)) -> Option<(f32, f32)> {
+        let (element, state) = (stage_def.elements.get(index)?, self.elements.get(index)?);
+        Some((
+            state.position.0 + state.sin_x.offset(state.time) - camera.0 * element.delta.0,
+            state.position.1 + state.sin_y.offset(state.time) - camera.1 * element.delta.1,
+        ))
+    }
+}
+
+fn apply_controller(kind: &BackgroundControllerKind, element: &BackgroundElement, state: &mut BackgroundElementState, animations: &Animations) {
+    fn set(target: &mut (f32, f32), (x, y): (Option<f32>, Option<f32>), add: bool) {
+        for (component, value) in [(&mut target.0, x), (&mut target.1, y)] {
+            if let Some(value) = value {
+                *component = if add { *component + value } else { value };
+            }
+        }
+    }
+    match *kind {
+        BackgroundControllerKind::Null => (),
+        BackgroundControllerKind::Visible(visible) => state.visible = visible,
+        BackgroundControllerKind::Enabled(enabled) => state.enabled = enabled,
+        BackgroundControllerKind::VelSet(x, y) => set(&mut state.velocity, (x, y), false),
+        BackgroundControllerKind::VelAdd(x, y) => set(&mut state.velocity, (x, y), true),
+        BackgroundControllerKind::PosSet(x, y) => set(&mut state.position, (x, y), false),
+        BackgroundControllerKind::PosAdd(x, y) => set(&mut state.position, (x, y), true),
+        BackgroundControllerKind::Anim(action) => {
+            if let BackgroundKind::Anim { .. } = element.kind {
+                state.animator = animation(action, animations);
+            }
+        },
+        BackgroundControllerKind::SinX(sine) => state.sin_x = sine,
+        BackgroundControllerKind::SinY(sine) => state.sin_y = sine,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use crate::game::mugen::format::generic_def::Categories;
+    use crate::game::mugen::stage::read_stage_def;
+
+    fn assert_position(expected: (f32, f32), position: Option<(f32, f32)>) {
+        let position = position.unwrap();
+        assert!((expected.0 - position.0).abs() < 1e-4 && (expected.1 - position.1).abs() < 1e-4, "{expected:?} != {position:?}");
+    }
+
+    #[test]
+    fn background_positions() {
+        let def = b"
+[BGdef]
+spr = stage.sff
+
+[BG sky]
+spriteno = 0, 0
+start = 0, 10
+delta = 0.5, 0.25
+velocity = 1, 0
+id = 1
+
+[BG clouds]
+spriteno = 1, 0
+sin.y = 4, 8, 0
+id = 2
+
+[BGCtrlDef move]
+looptime = 10
+ctrlid = 1
+
+[BGCtrl stop]
+type = VelSet
+x = 0
+time = 3
+
+[BGCtrl hide]
+type = Visible
+value = 0
+time = 4, 5
+ctrlid = 2
+";
+        let stage_def = read_stage_def(Categories::read_def(Cursor::new(&def[..])));
+        let animations = Animations::new();
+        let mut background = StageBackground::new(&stage_def, &animations);
+        // moved by the camera according to the delta
+        assert_position((-50., 5.), background.element_position(&stage_def, 0, (100., 20.)));
+        for _ in 0..2 {
+            background.tick(&stage_def, &animations);
+        }
+        assert_position((2., 10.), background.element_position(&stage_def, 0, (0., 0.)));
+        // a quarter of the period of the sine wave
+        assert_position((0., 4.), background.element_position(&stage_def, 1, (0., 0.)));
+        // stopped at the fourth tick
+        for _ in 0..4 {
+            background.tick(&stage_def, &animations);
+        }
+        assert_position((3., 10.), background.element_position(&stage_def, 0, (0., 0.)));
+        // hidden from the fifth tick
+        assert!(!background.elements()[1].drawn());
+        assert!(background.elements()[0].drawn());
+        assert_eq!(None, background.element_position(&stage_def, 2, (0., 0.)));
+    }
+}
@@ -0,0 +1,100 @@
+use super::{number, numbers, BackgroundSine, StageSection};
+
+/// Action of a background controller on its elements, from its `type` parameter
+#[derive(Clone, PartialEq, Debug)]
+pub enum BackgroundControllerKind {
+    Null,
+    Visible(bool),
+    Enabled(bool),
+    /// Velocity of the elements, the missing components being unchanged
+    VelSet(Option<f32>, Option<f32>),
+    VelAdd(Option<f32>, Option<f32>),
+    /// Position of the elements, the missing components being unchanged
+    PosSet(Option<f32>, Option<f32>),
+    PosAdd(Option<f32>, Option<f32>),
+    /// Action of animated elements
+    Anim(u32),
+    SinX(BackgroundSine),
+    SinY(BackgroundSine),
+}
+
+/// Background controller, from a `[BGCtrl <name>]` section
+#[derive(Clone, PartialEq, Debug)]
+pub struct BackgroundController {
+    pub name: String,
+    pub kind: BackgroundControllerKind,
+    /// First and last ticks of the controller, in the time of its group
+    pub start_time: i32,
+    pub end_time: i32,
+    /// Period of the controller in ticks, no repetition if not positive
+    pub loop_time: i32,
+    /// Identifiers of the controlled elements, or `None` for the elements of the group
+    pub ctrl_ids: Option<Vec<i32>>,
+}
+
+impl BackgroundController {
+    /// Check if the controller runs at a tick of its group
+    pub fn active(&self, group_time: i32) -> bool {
+        let time = if self.loop_time > 0 { group_time % self.loop_time } else { group_time };
+        (self.start_time..=self.end_time).contains(&time)
+    }
+}
+
+/// Background controllers sharing a time, from a `[BGCtrlDef <name>]` section and the `[BGCtrl]` sections after it
+#[derive(Clone, PartialEq, Debug)]
+pub struct BackgroundControllerGroup {
+    pub name: String,
+    /// Period of the time of the group in ticks, no repetition if not positive
+    pub loop_time: i32,
+    /// Identifiers of the elements controlled by default, or all the elements if empty
+    pub ctrl_ids: Vec<i32>,
+    pub controllers: Vec<BackgroundController>,
+}
+
+impl BackgroundControllerGroup {
+    pub fn new(name: String, section: &StageSection) -> BackgroundControllerGroup {
+        BackgroundControllerGroup {
+            name,
+            loop_time: number(section, "looptime", -1.) as i32,
+            ctrl_ids: numbers(section, "ctrlid").into_iter().map(|id| id as i32).collect(),
+            controllers: Vec::new(),
+        }
+    }
+    /// Check if a controller of the group controls an element
+    pub fn controls(&self, controller: &BackgroundController, element_id: i32) -> bool {
+        let ctrl_ids = controller.ctrl_ids.as_deref().unwrap_or(&self.ctrl_ids);
+        ctrl_ids.is_empty() || ctrl_ids.contains(&element_id)
+    }
+}
+
+/// Read a background controller section, or `None` if its type is invalid
+pub fn read_background_controller(name: String, section: &StageSection) -> Option<BackgroundController> {
+    let optional = |key: &str| numbers(section, key).first().copied();
+    let value = |index: usize| numbers(section, "value").get(index).copied();
+    let flag = || value(0).is_none_or(|flag| flag != 0.);
+    // the x and y components are either separate parameters or the components of `value`
+    let (x, y) = (optional("x").or_else(|| value(0)), optional("y").or_else(|| value(1)));
+    let kind = match section.get("type")?.trim().to_lowercase().as_str() {
+        "null" => BackgroundControllerKind::Null,
+        "visible" => BackgroundControllerKind::Visible(flag()),
+        "enabled" => BackgroundControllerKind::Enabled(flag()),
+        "velset" => BackgroundControllerKind::VelSet(x, y),
+        "veladd" => BackgroundControllerKind::VelAdd(x, y),
+        "posset" => BackgroundControllerKind::PosSet(x, y),
+        "posadd" => BackgroundControllerKind::PosAdd(x, y),
+        "anim" => BackgroundControllerKind::Anim(u32::try_from(value(0)? as i64).ok()?),
+        "sinx" => BackgroundControllerKind::SinX(BackgroundSine::from_values(&numbers(section, "value"))),
+        "siny" => BackgroundControllerKind::SinY(BackgroundSine::from_values(&numbers(section, "value"))),
+        _ => None?,
+    };
+    let time = numbers(section, "time");
+    let start_time = time.first().map_or(0, |&time| time as i32);
+    Some(BackgroundController {
+        name,
+        kind,
+        start_time,
+        end_time: time.get(1).map_or(start_time, |&time| time as i32),
+        loop_time: time.get(2).map_or(-1, |&time| time as i32),
+        ctrl_ids: section.contains_key("ctrlid").then(|| numbers(section, "ctrlid").into_iter().map(|id| id as i32).collect()),
+    })
+}
@@ -0,0 +1,219 @@
+use crate::game::mugen::character::state::EnvShake;
+use super::{StageBound, StageCamera, StagePlayerInfo};
+
+/// Player followed by the camera
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CameraTarget {
+    /// Position of the player: x from the center of the stage, y from the ground, upwards negative
+    pub position: (f32, f32),
+    /// The camera follows the player horizontally and vertically
+    pub follow: (bool, bool),
+}
+
+/// Camera of a fight, following the players within the bounds of the stage.
+///
+/// The positions are in the coordinates of the players: x from the center of the stage, y from the ground, upwards
+/// negative.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FightCamera {
+    /// Position of the center of the screen horizontally, and vertical movement of the screen from the ground view
+    position: (f32, f32),
+    /// Size of the screen
+    screen_size: (f32, f32),
+    /// Shake of the screen, and the ticks since its start
+    shake: Option<(EnvShake, i32)>,
+}
+
+impl FightCamera {
+    pub fn new(camera: &StageCamera, screen_size: (f32, f32)) -> FightCamera {
+        FightCamera {
+            position: camera.start,
+            screen_size,
+            shake: None,
+        }
+    }
+    pub fn position(&self) -> (f32, f32) {
+        self.position
+    }
+    pub fn screen_size(&self) -> (f32, f32) {
+        self.screen_size
+    }
+    /// Position of the screen, with the vertical offset of the shake
+    pub fn view_position(&self) -> (f32, f32) {
+        let shake_offset = self.shake.map_or(0., |(shake, elapsed)| shake.offset(elapsed));
+        (self.position.0, self.position.1 + shake_offset)
+    }
+    /// Horizontal positions of the left and right edges of the screen
+    pub fn screen_edges(&self) -> (f32, f32) {
+        let half_width = self.screen_size.0 / 2.;
+        (self.position.0 - half_width, self.position.0 + half_width)
+    }
+    /// Move the camera to follow the players.
+    ///
+    /// The camera moves horizontally when a player is closer than `tension` to an edge of the screen, and centers the
+    /// players when they are too far apart to keep them both away from the edges. It moves up with the highest player
+    /// above `floortension`, by `verticalfollow` of its height.
+    pub fn follow(&mut self, camera: &StageCamera, targets: &[CameraTarget]) {
+        let followed_x = targets.iter().filter(|target| target.follow.0).map(|target| target.position.0);
+        let extremes = followed_x.fold(None, |extremes: Option<(f32, f32)>, x| Some(extremes.map_or((x, x), |(min, max)| (min.min(x), max.max(x)))));
+        if let Some((min_x, max_x)) = extremes {
+            let half_width = self.screen_size.0 / 2.;
+            let tension = camera.tension.clamp(0., half_width);
+            let x = self.position.0;
+            self.position.0 = if max_x - min_x > 2. * (half_width - tension) {
+                (min_x + max_x) / 2.
+            }
+            else if min_x < x - half_width + tension {
+                min_x + half_width - tension
+            }
+            else if max_x > x + half_width - tension {
+                max_x - half_width + tension
+            }
+            else {
+                x
+            };
+        }
+        let highest_y = targets.iter().filter(|target| target.follow.1).map(|target| target.position.1).reduce(f32::min);
+        if let Some(highest_y) = highest_y {
+            self.position.1 = (highest_y + camera.floor_tension).min(0.) * camera.vertical_follow;
+        }
+        self.position = (
+            clamp_bounds(self.position.0, camera.bound_left, camera.bound_right),
+            clamp_bounds(self.position.1, camera.bound_high, camera.bound_low),
+        );
+    }
+    /// Horizontal position of a player kept in the stage, and on the screen if it is bound to the screen
+    pub fn bound_player_x(&self, x: f32, bound_to_screen: bool, bound: &StageBound, player_info: &StagePlayerInfo) -> f32 {
+        let x = clamp_bounds(x, player_info.left_bound, player_info.right_bound);
+        if !bound_to_screen {
+            return x;
+        }
+        let (left, right) = self.screen_edges();
+        clamp_bounds(x, left + bound.screen_left, right - bound.screen_right)
+    }
+    /// Start a shake of the screen, replacing the current one
+    pub fn shake(&mut self, shake: EnvShake) {
+        self.shake = Some((shake, 0));
+    }
+    /// Advance the shake of the screen by one tick
+    pub fn tick(&mut self) {
+        if let Some((shake, elapsed)) = self.shake.as_mut() {
+            *elapsed += 1;
+            if *elapsed >= shake.time {
+                self.shake = None;
+            }
+        }
+    }
+}
+
+/// Horizontal positions of two players pushed apart so that their widths do not overlap.
+///
+/// The widths are the extents of the players to their left and right, and the limits the ranges of positions allowed
+/// for the players, like the edges of the screen. A player stopped by a limit pushes the other player by the remaining
+/// distance.
+pub fn push_players(positions: [f32; 2], widths: [(f32, f32); 2], limits: [(f32, f32); 2]) -> [f32; 2] {
+    let (left, right) = if positions[0] <= positions[1] { (0, 1) } else { (1, 0) };
+    let overlap = (positions[left] + widths[left].1) - (positions[right] - widths[right].0);
+    if overlap <= 0. {
+        return positions;
+    }
+    let mut pushed = positions;
+    pushed[left] = clamp_bounds(positions[left] - overlap / 2., limits[left].0, limits[left].1);
+    pushed[right] = clamp_bounds(positions[right] + overlap - (positions[left] - pushed[left]), limits[right].0, limits[right].1);
+    // the left player takes the distance the right player could not move
+    let remaining = overlap - (positions[left] - pushed[left]) - (pushed[right] - positions[right]);
+    pushed[left] = clamp_bounds(pushed[left] - remaining, limits[left].0, limits[left].1);
+    pushed
+}
+
+/// Clamp a value between two bounds, the lower bound winning if they are inverted
+fn clamp_bounds(value: f32, low: f32, high: f32) -> f32 {
+    value.min(high).max(low)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stage_camera() -> StageCamera {
+        StageCamera {
+            bound_left: -100.,
+            bound_right: 100.,
+            bound_high: -40.,
+            bound_low: 0.,
+            tension: 50.,
+            vertical_follow: 0.5,
+            floor_tension: 20.,
+            ..StageCamera::default()
+        }
+    }
+
+    fn targets(positions: [(f32, f32); 2]) -> [CameraTarget; 2] {
+        positions.map(|position| CameraTarget { position, follow: (true, true) })
+    }
+
+    #[test]
+    fn follow_players() {
+        let stage_camera = stage_camera();
+        let mut camera = FightCamera::new(&stage_camera, (320., 240.));
+        // within the tension area: no movement
+        camera.follow(&stage_camera, &targets([(-100., 0.), (100., 0.)]));
+        assert_eq!((0., 0.), camera.position());
+        // a player in the tension area pushes the camera
+        camera.follow(&stage_camera, &targets([(-50., 0.), (130., 0.)]));
+        assert_eq!((20., 0.), camera.position());
+        // the camera stays in the stage bounds
+        camera.follow(&stage_camera, &targets([(150., 0.), (250., 0.)]));
+        assert_eq!((100., 0.), camera.position());
+        // the players too far apart are centered
+        camera.follow(&stage_camera, &targets([(-200., 0.), (40., 0.)]));
+        assert_eq!((-80., 0.), camera.position());
+        // a player not followed horizontally is ignored
+        let mut unfollowed = targets([(-200., 0.), (40., 0.)]);
+        unfollowed[0].follow.0 = false;
+        camera.follow(&stage_camera, &unfollowed);
+        assert_eq!((-70., 0.), camera.position());
+        // the camera moves up with the highest player above the floor tension, within the high bound
+        camera.follow(&stage_camera, &targets([(-70., -60.), (-70., 0.)]));
+        assert_eq!((-70., -20.), camera.position());
+        camera.follow(&stage_camera, &targets([(-70., -200.), (-70., 0.)]));
+        assert_eq!((-70., -40.), camera.position());
+    }
+
+    #[test]
+    fn bound_players() {
+        let stage_camera = stage_camera();
+        let camera = FightCamera::new(&stage_camera, (320., 240.));
+        let bound = StageBound::default();
+        let player_info = StagePlayerInfo { left_bound: -150., right_bound: 1000., ..StagePlayerInfo::default() };
+        assert_eq!(-145., camera.bound_player_x(-170., true, &bound, &player_info));
+        assert_eq!(145., camera.bound_player_x(170., true, &bound, &player_info));
+        // ScreenBound: only the stage bounds apply
+        assert_eq!(170., camera.bound_player_x(170., false, &bound, &player_info));
+        assert_eq!(-150., camera.bound_player_x(-170., false, &bound, &player_info));
+    }
+
+    #[test]
+    fn pushed_players() {
+        let limits = [(-145., 145.); 2];
+        let widths = [(15., 16.), (16., 15.)];
+        assert_eq!([-20., 20.], push_players([-20., 20.], widths, limits));
+        // overlapping by 12
+        assert_eq!([-16., 16.], push_players([-10., 10.], widths, limits));
+        // the player at the edge of the screen pushes the other one by the whole overlap
+        assert_eq!([-145., -113.], push_players([-145., -120.], widths, limits));
+        assert_eq!([113., 145.], push_players([120., 145.], widths, limits));
+        // crossed players
+        assert_eq!([16., -16.], push_players([10., -10.], [(16., 15.), (15., 16.)], limits));
+    }
+
+    #[test]
+    fn screen_shake() {
+        let mut camera = FightCamera::new(&stage_camera(), (320., 240.));
+        camera.shake(EnvShake { time: 2, frequency: 90., amplitude: -4., phase: 90. });
+        assert_eq!(-4., camera.view_position().1);
+        camera.tick();
+        camera.tick();
+        assert_eq!((0., 0.), camera.view_position());
+    }
+}
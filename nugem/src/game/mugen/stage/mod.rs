@@ -0,0 +1,17 @@
+mod stage_def;
+pub use self::stage_def::*;
+
+mod background;
+pub use self::background::*;
+
+mod bg_ctrl;
+pub use self::bg_ctrl::*;
+
+mod background_state;
+pub use self::background_state::*;
+
+mod stage_reader;
+pub use self::stage_reader::*;
+
+mod camera;
+pub use self::camera::*;
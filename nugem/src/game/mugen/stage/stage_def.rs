@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use crate::game::mugen::format::generic_def::{Categories, DefLine};
+use super::{read_background_controller, read_background_element, BackgroundControllerGroup, BackgroundElement};
+
+/// `[Info]` section of a stage
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct StageInfo {
+    pub name: String,
+    pub display_name: String,
+    pub author: String,
+}
+
+/// `[Camera]` section: the limits and the movement of the camera, in stage coordinates
+#[derive(Clone, PartialEq, Debug)]
+pub struct StageCamera {
+    pub start: (f32, f32),
+    /// Leftmost and rightmost camera positions
+    pub bound_left: f32,
+    pub bound_right: f32,
+    /// Highest and lowest camera positions, upwards negative
+    pub bound_high: f32,
+    pub bound_low: f32,
+    /// Distance from the edges of the screen at which a player moves the camera horizontally
+    pub tension: f32,
+    /// Fraction of the height of the highest player followed by the camera
+    pub vertical_follow: f32,
+    /// Height of a player above which the camera moves up
+    pub floor_tension: f32,
+}
+
+impl Default for StageCamera {
+    fn default() -> Self {
+        StageCamera {
+            start: (0., 0.),
+            bound_left: 0.,
+            bound_right: 0.,
+            bound_high: 0.,
+            bound_low: 0.,
+            tension: 50.,
+            vertical_follow: 0.,
+            floor_tension: 0.,
+        }
+    }
+}
+
+/// `[PlayerInfo]` section: the starting positions of the players and the limits of their movement
+#[derive(Clone, PartialEq, Debug)]
+pub struct StagePlayerInfo {
+    pub p1_start: (f32, f32),
+    /// 1 facing right, -1 facing left
+    pub p1_facing: i32,
+    pub p2_start: (f32, f32),
+    pub p2_facing: i32,
+    pub left_bound: f32,
+    pub right_bound: f32,
+}
+
+impl Default for StagePlayerInfo {
+    fn default() -> Self {
+        StagePlayerInfo {
+            p1_start: (-70., 0.),
+            p1_facing: 1,
+            p2_start: (70., 0.),
+            p2_facing: -1,
+            left_bound: -1000.,
+            right_bound: 1000.,
+        }
+    }
+}
+
+/// `[Bound]` section: minimum distance of the players from the edges of the screen
+#[derive(Clone, PartialEq, Debug)]
+pub struct StageBound {
+    pub screen_left: f32,
+    pub screen_right: f32,
+}
+
+impl Default for StageBound {
+    fn default() -> Self {
+        StageBound {
+            screen_left: 15.,
+            screen_right: 15.,
+        }
+    }
+}
+
+/// `[StageInfo]` section: the placement of the ground and the coordinate system of the stage
+#[derive(Clone, PartialEq, Debug)]
+pub struct StageSettings {
+    /// Vertical position of the ground from the top of the screen
+    pub z_offset: f32,
+    /// Turn the players to face each other
+    pub auto_turn: bool,
+    /// Reset the background at the start of each round
+    pub reset_bg: bool,
+    /// Size of the screen in the coordinates of the stage
+    pub local_coord: (u32, u32),
+    /// Scale of the players in the stage
+    pub scale: (f32, f32),
+}
+
+impl Default for StageSettings {
+    fn default() -> Self {
+        StageSettings {
+            z_offset: 200.,
+            auto_turn: true,
+            reset_bg: true,
+            local_coord: (320, 240),
+            scale: (1., 1.),
+        }
+    }
+}
+
+/// `[Shadow]` section: the shadows of the players on the ground
+#[derive(Clone, PartialEq, Debug)]
+pub struct StageShadow {
+    /// Darkness from 0 to 256
+    pub intensity: i32,
+    pub color: (i32, i32, i32),
+    /// Vertical scale of the shadows
+    pub y_scale: f32,
+    /// Heights of the players above the ground at which the shadows start and finish fading out
+    pub fade_range: Option<(f32, f32)>,
+}
+
+impl Default for StageShadow {
+    fn default() -> Self {
+        StageShadow {
+            intensity: 128,
+            color: (0, 0, 0),
+            y_scale: 0.4,
+            fade_range: None,
+        }
+    }
+}
+
+/// `[Reflection]` section: the reflections of the players on the ground
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct StageReflection {
+    /// Opacity from 0 to 256, 0 without reflections
+    pub intensity: i32,
+}
+
+/// `[Music]` section
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct StageMusic {
+    pub bg_music: Option<String>,
+    /// Volume offset of the music
+    pub bg_volume: i32,
+}
+
+impl StageMusic {
+    /// Volume multiplier of the music: the offset goes from -255 for silence to 255 for twice the volume
+    pub fn volume(&self) -> f32 {
+        (1. + self.bg_volume as f32 / 255.).clamp(0., 2.)
+    }
+}
+
+/// `[BGdef]` section: the sprite file of the background
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct BackgroundDef {
+    pub sprite_file: String,
+    /// Clear the screen in magenta before drawing the background, to find the holes
+    pub debug_bg: bool,
+}
+
+/// Definition of a stage, from its DEF file
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct StageDef {
+    pub info: StageInfo,
+    pub camera: StageCamera,
+    pub player_info: StagePlayerInfo,
+    pub bound: StageBound,
+    pub stage_info: StageSettings,
+    pub shadow: StageShadow,
+    pub reflection: StageReflection,
+    pub music: StageMusic,
+    pub background_def: BackgroundDef,
+    /// Background elements in drawing order
+    pub elements: Vec<BackgroundElement>,
+    /// Background controllers, in the order of their definitions
+    pub controller_groups: Vec<BackgroundControllerGroup>,
+}
+
+/// Values of a section of the stage definition, with lowercase keys
+pub type StageSection = HashMap<String, String>;
+
+/// Read the sections of a stage DEF file. The `[BG <name>]` sections after `[BGdef]` are the background elements, and the
+/// `[BGCtrl <name>]` sections are the controllers of the last `[BGCtrlDef <name>]` section.
+pub fn read_stage_def<R: std::io::Read>(categories: Categories<R>) -> StageDef {
+    let mut stage_def = StageDef::default();
+    for (line_number, category) in categories {
+        let name = category.name().trim().to_owned();
+        let lowercase_name = name.to_lowercase();
+        let section: StageSection = category.into_lines().into_iter()
+            .filter_map(|(_, line)| match line {
+                DefLine::KeyValue(key, value) => Some((key.to_lowercase(), value)),
+                DefLine::Simple(_) => None,
+            })
+            .collect();
+        match lowercase_name.as_str() {
+            "info" => stage_def.info = read_info(&section),
+            "camera" => stage_def.camera = read_camera(&section),
+            "playerinfo" => stage_def.player_info = read_player_info(&section),
+            "bound" => stage_def.bound = read_bound(&section),
+            "stageinfo" => stage_def.stage_info = read_stage_settings(&section),
+            "shadow" => stage_def.shadow = read_shadow(&section),
+            "reflection" => stage_def.reflection = StageReflection { intensity: number(&section, "intensity", 0.) as i32 },
+            "music" => stage_def.music = StageMusic {
+                bg_music: section.get("bgmusic").filter(|music| !music.is_empty()).cloned(),
+                bg_volume: number(&section, "bgvolume", 0.) as i32,
+            },
+            "bgdef" => stage_def.background_def = BackgroundDef {
+                sprite_file: section.get("spr").cloned().unwrap_or_default(),
+                debug_bg: number(&section, "debugbg", 0.) != 0.,
+            },
+            _ => {
+                // the names of the sections are separated from their kind by a space, such as [BG sky]
+                let (kind, element_name) = name.split_once(char::is_whitespace).unwrap_or((&name, ""));
+                let element_name = element_name.trim().to_owned();
+                match kind.to_lowercase().as_str() {
+                    "bg" => match read_background_element(element_name, &section) {
+                        Some(element) => stage_def.elements.push(element),
+                        None => log::error!("Invalid background element {name} at line {line_number}"),
+                    },
+                    "bgctrldef" => stage_def.controller_groups.push(BackgroundControllerGroup::new(element_name, &section)),
+                    "bgctrl" => match (stage_def.controller_groups.last_mut(), read_background_controller(element_name, &section)) {
+                        (Some(group), Some(controller)) => group.controllers.push(controller),
+                        (None, _) => log::error!("Background controller {name} at line {line_number} outside of a BGCtrlDef section"),
+                        (_, None) => log::error!("Invalid background controller {name} at line {line_number}"),
+                    },
+                    // the [Begin Action] sections of the animated elements are read as an AIR file
+                    _ => (),
+                }
+            },
+        }
+    }
+    stage_def
+}
+
+fn read_info(section: &StageSection) -> StageInfo {
+    let name = section.get("name").cloned().unwrap_or_default();
+    StageInfo {
+        display_name: section.get("displayname").cloned().unwrap_or_else(|| name.clone()),
+        name,
+        author: section.get("author").cloned().unwrap_or_default(),
+    }
+}
+
+fn read_camera(section: &StageSection) -> StageCamera {
+    let default = StageCamera::default();
+    StageCamera {
+        start: (number(section, "startx", default.start.0), number(section, "starty", default.start.1)),
+        bound_left: number(section, "boundleft", default.bound_left),
+        bound_right: number(section, "boundright", default.bound_right),
+        bound_high: number(section, "boundhigh", default.bound_high),
+        bound_low: number(section, "boundlow", default.bound_low),
+        tension: number(section, "tension", default.tension),
+        vertical_follow: number(section, "verticalfollow", default.vertical_follow),
+        floor_tension: number(section, "floortension", default.floor_tension),
+    }
+}
+
+fn read_player_info(section: &StageSection) -> StagePlayerInfo {
+    let default = StagePlayerInfo::default();
+    StagePlayerInfo {
+        p1_start: (number(section, "p1startx", default.p1_start.0), number(section, "p1starty", default.p1_start.1)),
+        p1_facing: number(section, "p1facing", default.p1_facing as f32) as i32,
+        p2_start: (number(section, "p2startx", default.p2_start.0), number(section, "p2starty", default.p2_start.1)),
+        p2_facing: number(section, "p2facing", default.p2_facing as f32) as i32,
+        left_bound: number(section, "leftbound", default.left_bound),
+        right_bound: number(section, "rightbound", default.right_bound),
+    }
+}
+
+fn read_bound(section: &StageSection) -> StageBound {
+    let default = StageBound::default();
+    StageBound {
+        screen_left: number(section, "screenleft", default.screen_left),
+        screen_right: number(section, "screenright", default.screen_right),
+    }
+}
+
+fn read_stage_settings(section: &StageSection) -> StageSettings {
+    let default = StageSettings::default();
+    let local_coord = numbers(section, "localcoord");
+    StageSettings {
+        z_offset: number(section, "zoffset", default.z_offset),
+        auto_turn: number(section, "autoturn", 1.) != 0.,
+        reset_bg: number(section, "resetbg", 1.) != 0.,
+        local_coord: match local_coord[..] {
+            [width, height, ..] if width > 0. && height > 0. => (width as u32, height as u32),
+            _ => default.local_coord,
+        },
+        scale: (number(section, "xscale", default.scale.0), number(section, "yscale", default.scale.1)),
+    }
+}
+
+fn read_shadow(section: &StageSection) -> StageShadow {
+    let default = StageShadow::default();
+    let color = numbers(section, "color");
+    let color_component = |index: usize| color.get(index).map_or(0, |&component| component as i32);
+    StageShadow {
+        intensity: number(section, "intensity", default.intensity as f32) as i32,
+        color: (color_component(0), color_component(1), color_component(2)),
+        y_scale: number(section, "yscale", default.y_scale),
+        fade_range: match numbers(section, "fade.range")[..] {
+            [start, end, ..] => Some((start, end)),
+            _ => None,
+        },
+    }
+}
+
+/// Comma-separated numbers of a value, the invalid numbers being 0
+pub fn parse_numbers(text: &str) -> Vec<f32> {
+    text.split(',').map(|number| number.trim().parse().unwrap_or(0.)).collect()
+}
+
+/// Numbers of a value of a section, empty if the key is missing
+pub fn numbers(section: &StageSection, key: &str) -> Vec<f32> {
+    section.get(key).map(|text| parse_numbers(text)).unwrap_or_default()
+}
+
+/// First number of a value of a section
+pub fn number(section: &StageSection, key: &str, default: f32) -> f32 {
+    numbers(section, key).first().copied().unwrap_or(default)
+}
+
+/// First two numbers of a value of a section, the missing numbers taking their default value
+pub fn number_pair(section: &StageSection, key: &str, default: (f32, f32)) -> (f32, f32) {
+    let values = numbers(section, key);
+    (values.first().copied().unwrap_or(default.0), values.get(1).copied().unwrap_or(default.1))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use crate::game::mugen::character::air::BlendMode;
+    use crate::game::mugen::stage::{BackgroundControllerKind, BackgroundKind, BackgroundSine};
+
+    #[test]
+    fn read_stage_sections() {
+        let def = b"
+[Info]
+name = \"Test Stage\"
+author = \"Someone\"
+
+[Camera]
+startx = 0
+starty = 0
+boundleft = -95
+boundright = 95
+boundhigh = -25
+verticalfollow = .2
+floortension = 0
+tension = 50
+
+[PlayerInfo]
+p1startx = -80
+p2startx = 80
+leftbound = -1000
+rightbound = 1000
+
+[Bound]
+screenleft = 15
+screenright = 15
+
+[StageInfo]
+zoffset = 210
+autoturn = 1
+resetBG = 0
+localcoord = 640, 480
+
+[Shadow]
+intensity = 96
+color = 10, 20, 30
+yscale = .3
+fade.range = 0, -60
+
+[Reflection]
+intensity = 64
+
+[Music]
+bgmusic = sound/kfm.mp3
+bgvolume = 10
+
+[BGdef]
+spr = stages\\test.sff
+
+[BG Floor]
+type = parallax
+spriteno = 0, 1
+start = 0, 170
+delta = 1, 1
+xscale = 1, 1.5
+yscalestart = 100
+yscaledelta = 1.2
+mask = 0
+
+[BG Fire]
+type = anim
+actionno = 10
+layerno = 1
+tile = 1, 0
+tilespacing = 4
+sin.x = 2, 30
+trans = addalpha
+alpha = 128, 128
+window = 0, 0, 319, 100
+id = 3
+
+[BG Bad]
+type = unknown
+
+[Begin Action 10]
+0, 0, 0, 0, 5
+
+[BGCtrlDef Fire]
+looptime = 120
+ctrlid = 3
+
+[BGCtrl Flicker]
+type = Enabled
+time = 0, 29, 60
+value = 0
+
+[BGCtrl Push]
+type = PosAdd
+x = -2
+time = 10
+ctrlid = 1, 2
+";
+        let stage_def = read_stage_def(Categories::read_def(Cursor::new(&def[..])));
+        assert_eq!("Test Stage", stage_def.info.name);
+        assert_eq!("Test Stage", stage_def.info.display_name);
+        assert_eq!((-95., 95.), (stage_def.camera.bound_left, stage_def.camera.bound_right));
+        assert_eq!((-25., 0.), (stage_def.camera.bound_high, stage_def.camera.bound_low));
+        assert_eq!(0.2, stage_def.camera.vertical_follow);
+        assert_eq!((-80., 0.), stage_def.player_info.p1_start);
+        assert_eq!(-1, stage_def.player_info.p2_facing);
+        assert_eq!(210., stage_def.stage_info.z_offset);
+        assert!(!stage_def.stage_info.reset_bg);
+        assert_eq!((640, 480), stage_def.stage_info.local_coord);
+        assert_eq!(StageShadow { intensity: 96, color: (10, 20, 30), y_scale: 0.3, fade_range: Some((0., -60.)) }, stage_def.shadow);
+        assert_eq!(64, stage_def.reflection.intensity);
+        assert_eq!(Some("sound/kfm.mp3"), stage_def.music.bg_music.as_deref());
+        assert_eq!("stages\\test.sff", stage_def.background_def.sprite_file);
+        // the element of unknown type is ignored
+        assert_eq!(2, stage_def.elements.len());
+        let floor = &stage_def.elements[0];
+        assert_eq!("Floor", floor.name);
+        assert_eq!(BackgroundKind::Parallax { x_scale: (1., 1.5), width: None, y_scale_start: 100., y_scale_delta: 1.2 }, floor.kind);
+        assert_eq!(((0, 1), (0., 170.), 0, false), (floor.sprite, floor.start, floor.layer, floor.mask));
+        let fire = &stage_def.elements[1];
+        assert_eq!(BackgroundKind::Anim { action: 10 }, fire.kind);
+        assert_eq!(((1, 0), (4., 4.)), (fire.tile, fire.tile_spacing));
+        assert_eq!(BackgroundSine { amplitude: 2., period: 30., phase: 0. }, fire.sin_x);
+        assert_eq!(BlendMode::Add { source: 128, destination: 128 }, fire.trans);
+        assert_eq!(Some([0., 0., 319., 100.]), fire.window);
+        assert_eq!((3, 1, true), (fire.id, fire.layer, fire.mask));
+        let group = &stage_def.controller_groups[0];
+        assert_eq!((120, vec![3]), (group.loop_time, group.ctrl_ids.clone()));
+        assert_eq!(2, group.controllers.len());
+        let flicker = &group.controllers[0];
+        assert_eq!(BackgroundControllerKind::Enabled(false), flicker.kind);
+        assert_eq!((0, 29, 60), (flicker.start_time, flicker.end_time, flicker.loop_time));
+        // repeated every 60 ticks
+        assert!(flicker.active(29) && !flicker.active(30) && flicker.active(65));
+        let push = &group.controllers[1];
+        assert_eq!(BackgroundControllerKind::PosAdd(Some(-2.), None), push.kind);
+        assert!(group.controls(push, 2) && !group.controls(push, 3) && group.controls(flicker, 3));
+    }
+}
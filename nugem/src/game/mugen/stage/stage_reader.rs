@@ -0,0 +1,164 @@
+use std::ffi::OsStr;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use crate::game::mugen::character::air::{read_air_file, Animations};
+use crate::game::mugen::character::file_reader::{fs::FileReaderFs, FileReader};
+use crate::game::mugen::format::generic_def::Categories;
+use super::{read_stage_def, StageDef};
+
+/// Stage of a fight: its definition, the animations of its animated elements and the files of its sprites
+pub struct Stage {
+    def: StageDef,
+    animations: Animations,
+    file_reader: Box<dyn FileReader>,
+    /// Path of the definition file, from the root of the file reader
+    def_path: PathBuf,
+}
+
+impl std::fmt::Debug for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct(stringify!(Stage))
+            .field(stringify!(def_path), &self.def_path)
+            .field(stringify!(def), &self.def)
+            .finish()
+    }
+}
+
+impl Stage {
+    /// Open the stage of a definition file of a file reader
+    pub fn open(def_path: &Path, mut file_reader: Box<dyn FileReader>) -> std::io::Result<Stage> {
+        let mut def_data = Vec::new();
+        file_reader.read_file(def_path)?.read_to_end(&mut def_data)?;
+        let def = read_stage_def(Categories::read_def(Cursor::new(&def_data)));
+        // the actions of the animated elements are in the definition file
+        let animations = read_air_file(Cursor::new(&def_data));
+        Ok(Stage {
+            def,
+            animations,
+            file_reader,
+            def_path: def_path.to_path_buf(),
+        })
+    }
+    pub fn def(&self) -> &StageDef {
+        &self.def
+    }
+    pub fn animations(&self) -> &Animations {
+        &self.animations
+    }
+    pub fn name(&self) -> &str {
+        &self.def.info.name
+    }
+    /// Read the sprite file of the `[BGdef]` section.
+    ///
+    /// The path of the sprite file is usually relative to the MUGEN folder, such as `stages/kfm.sff`: it is looked up
+    /// from the folder of the definition file, then by its file name in that folder.
+    pub fn read_sprites(&mut self) -> Result<nugem_sff::SpriteFile, nugem_sff::LoadingError> {
+        let sprite_file = self.def.background_def.sprite_file.clone();
+        nugem_sff::SpriteFile::read(Cursor::new(self.read_stage_file(&sprite_file)?), Vec::new())
+    }
+    /// Read and decode the background music of the `[Music]` section, `None` for a stage without music.
+    ///
+    /// The music file is looked up like the sprite file. Only the WAV files are supported.
+    pub fn read_music(&mut self) -> Result<Option<nugem_snd::Sound>, nugem_snd::DecodingError> {
+        let Some(music_file) = self.def.music.bg_music.clone() else {
+            return Ok(None);
+        };
+        nugem_snd::decode_wav(&self.read_stage_file(&music_file)?).map(Some)
+    }
+    /// Read a file of the stage from the folder of the definition file, or by its file name in that folder
+    fn read_stage_file(&mut self, file: &str) -> std::io::Result<Vec<u8>> {
+        let file = file.replace('\\', "/");
+        let file = Path::new(&file);
+        let def_folder = self.def_path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut data = Vec::new();
+        if let Ok(mut file_data) = self.file_reader.read_file(&def_folder.join(file)) {
+            file_data.read_to_end(&mut data)?;
+            return Ok(data);
+        }
+        self.file_reader.read_file(&def_folder.join(file.file_name().unwrap_or_default()))?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Open the stages of the definition files of a folder, in the order of their names
+pub fn read_directory_stages(directory_path: &Path) -> impl Iterator<Item = Stage> {
+    let mut def_paths: Vec<PathBuf> = std::fs::read_dir(directory_path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case(OsStr::new("def"))))
+        .collect();
+    def_paths.sort();
+    let directory_path = directory_path.to_path_buf();
+    def_paths.into_iter().filter_map(move |def_path| {
+        let file_name = PathBuf::from(def_path.file_name()?);
+        match Stage::open(&file_name, Box::new(FileReaderFs::new(directory_path.clone()))) {
+            Ok(stage) => Some(stage),
+            Err(err) => {
+                log::error!("Failed to read stage {0}: {err}", def_path.display());
+                None
+            },
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::game::mugen::character::file_reader::ReadSeek;
+
+    struct FileReaderMemory(HashMap<PathBuf, Vec<u8>>);
+
+    impl FileReader for FileReaderMemory {
+        fn read_file<'a>(&'a mut self, path: &Path) -> std::io::Result<Box<dyn ReadSeek + 'a>> {
+            self.0.get(path)
+                .map(|data| Box::new(Cursor::new(data)) as Box<dyn ReadSeek>)
+                .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))
+        }
+        fn file_names<'a>(&'a mut self) -> std::io::Result<Box<dyn Iterator<Item = PathBuf> + 'a>> {
+            Ok(Box::new(self.0.keys().cloned()))
+        }
+    }
+
+    /// WAV data of a mono sound with 8 bits PCM samples
+    fn wav_data(sample_rate: u32, samples: &[u8]) -> Vec<u8> {
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(36 + samples.len() as u32).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        for value in [1u16, 1] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&sample_rate.to_le_bytes());
+        data.extend_from_slice(&sample_rate.to_le_bytes());
+        for value in [1u16, 8] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+        data.extend_from_slice(samples);
+        data
+    }
+
+    fn stage(music: &str, files: Vec<(&str, Vec<u8>)>) -> Stage {
+        let mut files: HashMap<PathBuf, Vec<u8>> = files.into_iter().map(|(path, data)| (PathBuf::from(path), data)).collect();
+        files.insert(PathBuf::from("stages/test.def"), format!("[Music]\nbgmusic = {music}\nbgvolume = -51\n").into_bytes());
+        Stage::open(Path::new("stages/test.def"), Box::new(FileReaderMemory(files))).unwrap()
+    }
+
+    #[test]
+    fn read_music() {
+        // the path from the MUGEN folder is looked up by its file name in the folder of the stage
+        let mut wav_stage = stage("sound\\theme.wav", vec![("stages/theme.wav", wav_data(22050, &[128, 255, 0]))]);
+        let music = wav_stage.read_music().unwrap().unwrap();
+        assert_eq!((22050, 3), (music.sample_rate(), music.frame_count()));
+        assert_eq!(0.8, wav_stage.def().music.volume());
+        assert!(stage("", Vec::new()).read_music().unwrap().is_none());
+        let mp3_music = stage("theme.mp3", vec![("stages/theme.mp3", b"ID3".to_vec())]).read_music();
+        assert!(matches!(mp3_music, Err(nugem_snd::DecodingError::NoWaveHeader)));
+        let missing_music = stage("missing.wav", Vec::new()).read_music();
+        assert!(matches!(missing_music, Err(nugem_snd::DecodingError::IoError(_))));
+    }
+}
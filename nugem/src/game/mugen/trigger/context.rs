@@ -0,0 +1,30 @@
+use super::{Redirection, SymbolTrigger, Trigger, Value};
+
+/// Game state read by trigger expressions, for a given player.
+///
+/// Every method returns `None` when the value is not available: the expression reading it then has no value, and
+/// its trigger is false.
+pub trait TriggerContext {
+    /// Value of a trigger, with its argument if it has one
+    fn trigger(&self, trigger: Trigger, argument: Option<i32>) -> Option<Value>;
+    /// Check if a command is active
+    fn command(&self, _name: &str) -> Option<bool> {
+        None
+    }
+    /// Symbol or name of a symbol trigger, such as `"S"` for the state type of a standing player
+    fn symbol(&self, _trigger: SymbolTrigger) -> Option<String> {
+        None
+    }
+    /// Character constant, by lowercase name such as `"velocity.walk.fwd.x"`
+    fn constant(&self, _name: &str) -> Option<Value> {
+        None
+    }
+    /// Information about the last hit received, by lowercase name such as `"xvel"`
+    fn hit_var(&self, _name: &str) -> Option<Value> {
+        None
+    }
+    /// Context of the player targeted by a redirection
+    fn redirect(&self, _redirection: Redirection, _argument: Option<i32>) -> Option<Box<dyn TriggerContext + '_>> {
+        None
+    }
+}
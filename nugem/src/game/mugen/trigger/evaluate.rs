@@ -0,0 +1,278 @@
+use super::{BinaryOperator, Expression, Function, TriggerContext, UnaryOperator, Value};
+
+impl Expression {
+    /// Evaluate the expression. Returns `None` if it has no value, for example when dividing by 0 or when reading a
+    /// value that is not available.
+    pub fn evaluate(&self, context: &dyn TriggerContext) -> Option<Value> {
+        match self {
+            Expression::Int(i) => Some(Value::Int(*i)),
+            Expression::Float(f) => Some(Value::Float(*f)),
+            Expression::Unary(operator, operand) => unary(*operator, operand.evaluate(context)?),
+            Expression::Binary(operator, left, right) => binary(*operator, left, right, context),
+            Expression::Interval { value, negated, low, low_inclusive, high, high_inclusive } => {
+                let value = value.evaluate(context)?.as_float();
+                let low = low.evaluate(context)?.as_float();
+                let high = high.evaluate(context)?.as_float();
+                let above_low = if *low_inclusive { value >= low } else { value > low };
+                let below_high = if *high_inclusive { value <= high } else { value < high };
+                Some(Value::from((above_low && below_high) != *negated))
+            },
+            Expression::Trigger(trigger, argument) => {
+                let argument = match argument {
+                    Some(argument) => Some(argument.evaluate(context)?.as_int()),
+                    None => None,
+                };
+                context.trigger(*trigger, argument)
+            },
+            Expression::Command(name) => context.command(name).map(Value::from),
+            Expression::Symbol(trigger, symbol) => context.symbol(*trigger).map(|value| Value::from(value.eq_ignore_ascii_case(symbol))),
+            Expression::Const(name) => context.constant(name),
+            Expression::GetHitVar(name) => context.hit_var(name),
+            Expression::Function(function, arguments) => self::function(*function, arguments, context),
+            Expression::Redirect(redirection, argument, redirected) => {
+                let argument = match argument {
+                    Some(argument) => Some(argument.evaluate(context)?.as_int()),
+                    None => None,
+                };
+                redirected.evaluate(context.redirect(*redirection, argument)?.as_ref())
+            },
+        }
+    }
+
+    /// Evaluate the expression as a condition: an expression without a value is false.
+    pub fn is_true(&self, context: &dyn TriggerContext) -> bool {
+        self.evaluate(context).map(Value::is_true).unwrap_or(false)
+    }
+}
+
+fn unary(operator: UnaryOperator, value: Value) -> Option<Value> {
+    match (operator, value) {
+        (UnaryOperator::LogicalNot, value) => Some(Value::from(!value.is_true())),
+        (UnaryOperator::BitwiseNot, Value::Int(i)) => Some(Value::Int(!i)),
+        (UnaryOperator::BitwiseNot, Value::Float(_)) => None,
+        (UnaryOperator::Negate, Value::Int(i)) => Some(Value::Int(i.wrapping_neg())),
+        (UnaryOperator::Negate, Value::Float(f)) => Some(Value::Float(-f)),
+    }
+}
+
+fn binary(operator: BinaryOperator, left: &Expression, right: &Expression, context: &dyn TriggerContext) -> Option<Value> {
+    // logical operators are short-circuited
+    match operator {
+        BinaryOperator::LogicalAnd => return Some(Value::from(left.evaluate(context)?.is_true() && right.evaluate(context)?.is_true())),
+        BinaryOperator::LogicalOr => return Some(Value::from(left.evaluate(context)?.is_true() || right.evaluate(context)?.is_true())),
+        _ => (),
+    }
+    let left = left.evaluate(context)?;
+    let right = right.evaluate(context)?;
+    match (left, right) {
+        (Value::Int(a), Value::Int(b)) => int_binary(operator, a, b),
+        (a, b) => float_binary(operator, a.as_float(), b.as_float()),
+    }
+}
+
+fn int_binary(operator: BinaryOperator, a: i32, b: i32) -> Option<Value> {
+    let value = match operator {
+        BinaryOperator::LogicalXor => Value::from((a != 0) != (b != 0)),
+        BinaryOperator::BitwiseOr => Value::Int(a | b),
+        BinaryOperator::BitwiseXor => Value::Int(a ^ b),
+        BinaryOperator::BitwiseAnd => Value::Int(a & b),
+        BinaryOperator::Add => Value::Int(a.wrapping_add(b)),
+        BinaryOperator::Subtract => Value::Int(a.wrapping_sub(b)),
+        BinaryOperator::Multiply => Value::Int(a.wrapping_mul(b)),
+        BinaryOperator::Divide => Value::Int(a.checked_div(b)?),
+        BinaryOperator::Modulo => Value::Int(a.checked_rem(b)?),
+        // negative powers of integers are floats
+        BinaryOperator::Power => match u32::try_from(b) {
+            Ok(exponent) => Value::Int(a.wrapping_pow(exponent)),
+            Err(_) => Value::Float((a as f32).powi(b)),
+        },
+        comparison => Value::from(compare(comparison, a, b)?),
+    };
+    Some(value)
+}
+
+fn float_binary(operator: BinaryOperator, a: f32, b: f32) -> Option<Value> {
+    let value = match operator {
+        BinaryOperator::LogicalXor => Value::from((a != 0.) != (b != 0.)),
+        // bitwise operators and modulo are only defined for integers
+        BinaryOperator::BitwiseOr | BinaryOperator::BitwiseXor | BinaryOperator::BitwiseAnd | BinaryOperator::Modulo => None?,
+        BinaryOperator::Add => Value::Float(a + b),
+        BinaryOperator::Subtract => Value::Float(a - b),
+        BinaryOperator::Multiply => Value::Float(a * b),
+        BinaryOperator::Divide if b == 0. => None?,
+        BinaryOperator::Divide => Value::Float(a / b),
+        BinaryOperator::Power => Value::Float(a.powf(b)),
+        comparison => Value::from(compare(comparison, a, b)?),
+    };
+    Some(value)
+}
+
+fn compare<T: PartialOrd>(operator: BinaryOperator, a: T, b: T) -> Option<bool> {
+    match operator {
+        BinaryOperator::Equal => Some(a == b),
+        BinaryOperator::NotEqual => Some(a != b),
+        BinaryOperator::Less => Some(a < b),
+        BinaryOperator::LessOrEqual => Some(a <= b),
+        BinaryOperator::Greater => Some(a > b),
+        BinaryOperator::GreaterOrEqual => Some(a >= b),
+        _ => None,
+    }
+}
+
+fn function(function: Function, arguments: &[Expression], context: &dyn TriggerContext) -> Option<Value> {
+    let float_argument = |index: usize| arguments.get(index).and_then(|a| a.evaluate(context)).map(Value::as_float);
+    let float = |f: fn(f32) -> f32| float_argument(0).map(f).filter(|f| f.is_finite()).map(Value::Float);
+    match function {
+        Function::Abs => match arguments.first()?.evaluate(context)? {
+            Value::Int(i) => Some(Value::Int(i.wrapping_abs())),
+            Value::Float(f) => Some(Value::Float(f.abs())),
+        },
+        Function::Ceil => float_argument(0).map(|f| Value::Int(f.ceil() as i32)),
+        Function::Floor => float_argument(0).map(|f| Value::Int(f.floor() as i32)),
+        Function::Acos => float(f32::acos),
+        Function::Asin => float(f32::asin),
+        Function::Atan => float(f32::atan),
+        Function::Cos => float(f32::cos),
+        Function::Exp => float(f32::exp),
+        Function::Ln => float(f32::ln),
+        Function::Sin => float(f32::sin),
+        Function::Tan => float(f32::tan),
+        Function::Log => {
+            let value = float_argument(1)?.log(float_argument(0)?);
+            value.is_finite().then_some(Value::Float(value))
+        },
+        Function::IfElse => {
+            let condition = arguments.first()?.evaluate(context)?;
+            let if_true = arguments.get(1)?.evaluate(context)?;
+            let if_false = arguments.get(2)?.evaluate(context)?;
+            Some(if condition.is_true() { if_true } else { if_false })
+        },
+        Function::Cond => {
+            let index = if arguments.first()?.evaluate(context)?.is_true() { 1 } else { 2 };
+            arguments.get(index)?.evaluate(context)
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::game::mugen::trigger::{parse_expression, Axis, Redirection, SymbolTrigger, Trigger};
+
+    /// Character context for the tests, with an optional opponent
+    #[derive(Clone, Default)]
+    struct MockContext {
+        triggers: HashMap<(Trigger, Option<i32>), Value>,
+        commands: Vec<&'static str>,
+        state_type: &'static str,
+        constants: HashMap<&'static str, Value>,
+        opponent: Option<Box<MockContext>>,
+    }
+
+    impl TriggerContext for MockContext {
+        fn trigger(&self, trigger: Trigger, argument: Option<i32>) -> Option<Value> {
+            self.triggers.get(&(trigger, argument)).copied()
+        }
+        fn command(&self, name: &str) -> Option<bool> {
+            Some(self.commands.contains(&name))
+        }
+        fn symbol(&self, trigger: SymbolTrigger) -> Option<String> {
+            match trigger {
+                SymbolTrigger::StateType => Some(self.state_type.to_owned()),
+                _ => None,
+            }
+        }
+        fn constant(&self, name: &str) -> Option<Value> {
+            self.constants.get(name).copied()
+        }
+        fn redirect(&self, redirection: Redirection, argument: Option<i32>) -> Option<Box<dyn TriggerContext + '_>> {
+            match (redirection, argument) {
+                (Redirection::P2, None) | (Redirection::EnemyNear, None | Some(0)) => {
+                    self.opponent.clone().map(|opponent| opponent as Box<dyn TriggerContext>)
+                },
+                _ => None,
+            }
+        }
+    }
+
+    fn context() -> MockContext {
+        let opponent = MockContext {
+            triggers: HashMap::from([((Trigger::Life, None), Value::Int(250))]),
+            state_type: "A",
+            ..MockContext::default()
+        };
+        MockContext {
+            triggers: HashMap::from([
+                ((Trigger::Time, None), Value::Int(12)),
+                ((Trigger::StateNo, None), Value::Int(200)),
+                ((Trigger::Var, Some(3)), Value::Int(7)),
+                ((Trigger::FVar, Some(0)), Value::Float(1.5)),
+                ((Trigger::Vel(Axis::Y), None), Value::Float(-4.5)),
+                ((Trigger::AnimElemTime, Some(2)), Value::Int(0)),
+                ((Trigger::AnimElemTime, Some(1)), Value::Int(5)),
+            ]),
+            commands: vec!["holdfwd", "a"],
+            state_type: "S",
+            constants: HashMap::from([("velocity.walk.fwd.x", Value::Float(2.4))]),
+            opponent: Some(Box::new(opponent)),
+        }
+    }
+
+    fn evaluate(expression: &str) -> Option<Value> {
+        parse_expression(expression).unwrap().evaluate(&context())
+    }
+
+    #[test]
+    fn evaluate_arithmetic() {
+        assert_eq!(Some(Value::Int(19)), evaluate("1 + 2 * 3 ** 2"));
+        assert_eq!(Some(Value::Int(3)), evaluate("7 / 2"));
+        assert_eq!(Some(Value::Float(3.5)), evaluate("7 / 2.0"));
+        assert_eq!(Some(Value::Int(1)), evaluate("7 % 3"));
+        assert_eq!(Some(Value::Int(-8)), evaluate("-2 ** 3"));
+        assert_eq!(Some(Value::Float(0.25)), evaluate("2 ** -2"));
+        assert_eq!(Some(Value::Int(6)), evaluate("~(-7)"));
+        assert_eq!(Some(Value::Int(4)), evaluate("12 & 6"));
+        assert_eq!(Some(Value::Int(1)), evaluate("1 ^^ 0"));
+        assert_eq!(Some(Value::Int(0)), evaluate("1 ^^ 2"));
+        assert_eq!(None, evaluate("1 / 0"));
+        assert_eq!(None, evaluate("1.5 % 2"));
+        assert_eq!(Some(Value::Int(-3)), evaluate("floor(-2.5)"));
+        assert_eq!(Some(Value::Int(5)), evaluate("abs(-5)"));
+        assert_eq!(Some(Value::Int(2)), evaluate("ifelse(0, 1, 2)"));
+        // the branch not taken by Cond is not evaluated
+        assert_eq!(Some(Value::Int(1)), evaluate("cond(1, 1, 1 / 0)"));
+        assert_eq!(None, evaluate("ifelse(1, 1, 1 / 0)"));
+    }
+
+    #[test]
+    fn evaluate_conditions() {
+        assert_eq!(Some(Value::Int(1)), evaluate("Time = [10, 12]"));
+        assert_eq!(Some(Value::Int(0)), evaluate("Time = [10, 12)"));
+        assert_eq!(Some(Value::Int(1)), evaluate("Time != (12, 20]"));
+        assert_eq!(Some(Value::Int(1)), evaluate("StateNo = 200 && var(3) > 5 && fvar(0) = 1.5"));
+        assert_eq!(Some(Value::Int(1)), evaluate("Vel Y < 0 || 1 / 0"));
+        // missing values make the whole condition false, unless short-circuited
+        assert_eq!(None, evaluate("var(4) = 0"));
+        assert_eq!(Some(Value::Int(0)), evaluate("0 && var(4) = 0"));
+        let context = context();
+        assert!(!parse_expression("var(4) = 0 || 1").unwrap().is_true(&context));
+        assert!(parse_expression("command = \"holdfwd\" && command != \"holdback\"").unwrap().is_true(&context));
+        assert!(parse_expression("statetype = s").unwrap().is_true(&context));
+        assert!(parse_expression("AnimElem = 2").unwrap().is_true(&context));
+        assert!(!parse_expression("AnimElem = 1").unwrap().is_true(&context));
+        assert!(parse_expression("AnimElem = 1, >= 5").unwrap().is_true(&context));
+        assert_eq!(Some(Value::Float(4.8)), evaluate("const(velocity.walk.fwd.x) * 2"));
+    }
+
+    #[test]
+    fn evaluate_redirections() {
+        assert_eq!(Some(Value::Int(250)), evaluate("p2, life"));
+        assert_eq!(Some(Value::Int(250)), evaluate("P2Life"));
+        assert_eq!(Some(Value::Int(251)), evaluate("enemynear(0), life + 1"));
+        assert_eq!(Some(Value::Int(1)), evaluate("p2statetype = A"));
+        // no second opponent
+        assert_eq!(None, evaluate("enemynear(1), life"));
+        assert_eq!(None, evaluate("parent, life"));
+    }
+}
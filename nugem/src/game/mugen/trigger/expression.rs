@@ -0,0 +1,242 @@
+use thiserror::Error;
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum TriggerError {
+    #[error("Invalid characters in expression: \"{0}\"")]
+    InvalidCharacters(String),
+    #[error("Unexpected end of expression")]
+    UnexpectedEnd,
+    #[error("Unexpected {0} in expression")]
+    UnexpectedToken(String),
+    #[error("Unknown trigger \"{0}\"")]
+    UnknownTrigger(String),
+    #[error("Trigger \"{0}\" must be compared with = or !=")]
+    MissingComparison(String),
+    #[error("Trigger \"{name}\" expects {expected} arguments, found {found}")]
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// Result of an expression: MUGEN expressions are either integers or floats.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub enum Value {
+    Int(i32),
+    Float(f32),
+}
+
+impl Value {
+    /// Integer value, truncating floats
+    pub fn as_int(self) -> i32 {
+        match self {
+            Value::Int(i) => i,
+            Value::Float(f) => f as i32,
+        }
+    }
+    pub fn as_float(self) -> f32 {
+        match self {
+            Value::Int(i) => i as f32,
+            Value::Float(f) => f,
+        }
+    }
+    pub fn is_true(self) -> bool {
+        match self {
+            Value::Int(i) => i != 0,
+            Value::Float(f) => f != 0.,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Int(b as i32)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum UnaryOperator {
+    /// `!`
+    LogicalNot,
+    /// `~`
+    BitwiseNot,
+    /// `-`
+    Negate,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum BinaryOperator {
+    LogicalOr,
+    LogicalXor,
+    LogicalAnd,
+    BitwiseOr,
+    BitwiseXor,
+    BitwiseAnd,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+/// Trigger reading a numeric value from the game state.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Trigger {
+    Time,
+    StateNo,
+    PrevStateNo,
+    Anim,
+    AnimTime,
+    /// Time since the start of the animation element given as argument
+    AnimElemTime,
+    /// Animation element displayed at the time offset given as argument
+    AnimElemNo,
+    AnimExist,
+    SelfAnimExist,
+    Ctrl,
+    Alive,
+    Life,
+    LifeMax,
+    Power,
+    PowerMax,
+    Facing,
+    Var,
+    FVar,
+    SysVar,
+    SysFVar,
+    Vel(Axis),
+    Pos(Axis),
+    ScreenPos(Axis),
+    HitVel(Axis),
+    P2Dist(Axis),
+    P2BodyDist(Axis),
+    ParentDist(Axis),
+    RootDist(Axis),
+    FrontEdgeDist,
+    BackEdgeDist,
+    FrontEdgeBodyDist,
+    BackEdgeBodyDist,
+    MoveContact,
+    MoveHit,
+    MoveGuarded,
+    MoveReversed,
+    HitCount,
+    UniqHitCount,
+    HitShakeOver,
+    HitOver,
+    HitFall,
+    HitPauseTime,
+    CanRecover,
+    InGuardDist,
+    NumEnemy,
+    NumPartner,
+    NumHelper,
+    NumTarget,
+    NumExplod,
+    NumProj,
+    IsHelper,
+    Id,
+    TeamSide,
+    PalNo,
+    RoundNo,
+    RoundState,
+    RoundsExisted,
+    MatchOver,
+    MatchNo,
+    GameTime,
+    Win,
+    Lose,
+    DrawGame,
+    Random,
+}
+
+/// Trigger whose value is a symbol or a name, compared with `=` or `!=`: `StateType = A`, `Name = "Kung Fu Man"`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum SymbolTrigger {
+    StateType,
+    MoveType,
+    Name,
+    AuthorName,
+}
+
+/// Mathematical functions and conditionals, computed without the game state.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Function {
+    Abs,
+    Acos,
+    Asin,
+    Atan,
+    Ceil,
+    Cos,
+    Exp,
+    Floor,
+    Ln,
+    Log,
+    Sin,
+    Tan,
+    /// `IfElse(condition, a, b)`: both values are evaluated
+    IfElse,
+    /// `Cond(condition, a, b)`: only the selected value is evaluated
+    Cond,
+}
+
+/// Player whose state is read by a redirected trigger: `p2, Life`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Redirection {
+    Parent,
+    Root,
+    Partner,
+    /// Helper with the given ID, or any helper
+    Helper,
+    /// Target with the given ID, or any target
+    Target,
+    /// N-th opponent
+    Enemy,
+    /// N-th nearest opponent
+    EnemyNear,
+    PlayerId,
+    /// Nearest opponent, as for the `P2` triggers
+    P2,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Expression {
+    Int(i32),
+    Float(f32),
+    Unary(UnaryOperator, Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+    /// `value = [low, high)`, or `value != [low, high)` if negated
+    Interval {
+        value: Box<Expression>,
+        negated: bool,
+        low: Box<Expression>,
+        low_inclusive: bool,
+        high: Box<Expression>,
+        high_inclusive: bool,
+    },
+    Trigger(Trigger, Option<Box<Expression>>),
+    /// `Command = "name"`: true if the command is active
+    Command(String),
+    /// Comparison of a symbol trigger, case-insensitive
+    Symbol(SymbolTrigger, String),
+    /// `Const(name)`: constant of the character
+    Const(String),
+    /// `GetHitVar(name)`: information about the last hit received
+    GetHitVar(String),
+    Function(Function, Vec<Expression>),
+    Redirect(Redirection, Option<Box<Expression>>, Box<Expression>),
+}
@@ -0,0 +1,161 @@
+use nom::{IResult, branch::alt, bytes::complete::{tag, take_while}, character::complete::{char, digit0, digit1, multispace0, satisfy}, combinator::{map, map_res, recognize, value}, multi::many0, sequence::{delimited, pair, preceded}, Finish};
+use super::TriggerError;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Token {
+    Int(i32),
+    Float(f32),
+    /// Quoted text, without the quotes
+    Text(String),
+    Identifier(String),
+    LeftParenthesis,
+    RightParenthesis,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    LogicalAnd,
+    LogicalOr,
+    LogicalXor,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    LogicalNot,
+    BitwiseNot,
+}
+
+/// Split a trigger expression into tokens.
+pub fn tokenize(expression: &str) -> Result<Vec<Token>, TriggerError> {
+    let (remaining, tokens) = many0(preceded(multispace0, parse_token))(expression)
+        .finish()
+        .map_err(|e: nom::error::Error<&str>| TriggerError::InvalidCharacters(e.input.to_owned()))?;
+    let remaining = remaining.trim_start();
+    if remaining.is_empty() {
+        Ok(tokens)
+    }
+    else {
+        Err(TriggerError::InvalidCharacters(remaining.to_owned()))
+    }
+}
+
+fn parse_token(input: &str) -> IResult<&str, Token> {
+    alt((
+        parse_number,
+        parse_text,
+        parse_identifier,
+        parse_operator,
+    ))
+    (input)
+}
+
+fn parse_number(input: &str) -> IResult<&str, Token> {
+    alt((
+        // decimal numbers: "1.5", "1." or ".5"
+        map_res(
+            recognize(alt((
+                recognize(pair(digit1, pair(char('.'), digit0))),
+                recognize(pair(char('.'), digit1)),
+            ))),
+            |s: &str| s.parse().map(Token::Float)
+        ),
+        map_res(digit1, |s: &str| s.parse().map(Token::Int)),
+    ))
+    (input)
+}
+
+fn parse_text(input: &str) -> IResult<&str, Token> {
+    map(
+        delimited(char('"'), take_while(|c| c != '"'), char('"')),
+        |s: &str| Token::Text(s.to_owned())
+    )
+    (input)
+}
+
+fn parse_identifier(input: &str) -> IResult<&str, Token> {
+    // dots are allowed for constant names such as "velocity.walk.fwd.x"
+    map(
+        recognize(pair(
+            satisfy(|c| c.is_ascii_alphabetic() || c == '_'),
+            take_while(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.'),
+        )),
+        |s: &str| Token::Identifier(s.to_owned())
+    )
+    (input)
+}
+
+fn parse_operator(input: &str) -> IResult<&str, Token> {
+    alt((
+        alt((
+            value(Token::Power, tag("**")),
+            value(Token::LogicalAnd, tag("&&")),
+            value(Token::LogicalOr, tag("||")),
+            value(Token::LogicalXor, tag("^^")),
+            value(Token::NotEqual, tag("!=")),
+            value(Token::LessOrEqual, tag("<=")),
+            value(Token::GreaterOrEqual, tag(">=")),
+        )),
+        alt((
+            value(Token::LeftParenthesis, char('(')),
+            value(Token::RightParenthesis, char(')')),
+            value(Token::LeftBracket, char('[')),
+            value(Token::RightBracket, char(']')),
+            value(Token::Comma, char(',')),
+            value(Token::Plus, char('+')),
+            value(Token::Minus, char('-')),
+            value(Token::Multiply, char('*')),
+            value(Token::Divide, char('/')),
+            value(Token::Modulo, char('%')),
+            value(Token::Equal, char('=')),
+            value(Token::Less, char('<')),
+            value(Token::Greater, char('>')),
+            value(Token::BitwiseAnd, char('&')),
+            value(Token::BitwiseOr, char('|')),
+            value(Token::BitwiseXor, char('^')),
+            value(Token::LogicalNot, char('!')),
+            value(Token::BitwiseNot, char('~')),
+        )),
+    ))
+    (input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tokenize_expression() {
+        assert_eq!(
+            Ok(vec![
+                Token::Identifier("Vel".to_owned()),
+                Token::Identifier("Y".to_owned()),
+                Token::GreaterOrEqual,
+                Token::Minus,
+                Token::Float(0.5),
+                Token::LogicalAnd,
+                Token::Identifier("command".to_owned()),
+                Token::NotEqual,
+                Token::Text("holdfwd".to_owned()),
+                Token::LogicalOr,
+                Token::Identifier("const".to_owned()),
+                Token::LeftParenthesis,
+                Token::Identifier("velocity.walk.fwd.x".to_owned()),
+                Token::RightParenthesis,
+                Token::Power,
+                Token::Int(2),
+            ]),
+            tokenize("Vel Y>=-.5&& command != \"holdfwd\" || const(velocity.walk.fwd.x)**2 ")
+        );
+        assert!(tokenize("var(1) = @").is_err());
+    }
+}
@@ -0,0 +1,12 @@
+mod expression;
+pub use self::expression::*;
+
+mod lexer;
+
+mod parser;
+pub use self::parser::*;
+
+mod context;
+pub use self::context::*;
+
+mod evaluate;
@@ -0,0 +1,578 @@
+use super::lexer::{tokenize, Token};
+use super::{Axis, BinaryOperator, Expression, Function, Redirection, SymbolTrigger, Trigger, TriggerError, UnaryOperator};
+
+/// Parse a trigger expression, such as the value of `trigger1 = ...`.
+pub fn parse_expression(expression: &str) -> Result<Expression, TriggerError> {
+    let mut parser = Parser::new(expression)?;
+    let expression = parser.expression()?;
+    parser.expect_end()?;
+    Ok(expression)
+}
+
+/// Parse comma-separated expressions, such as the value of `pausetime = 10, 12`.
+pub fn parse_expression_list(expression: &str) -> Result<Vec<Expression>, TriggerError> {
+    let mut parser = Parser::new(expression)?;
+    let mut expressions = vec![parser.expression()?];
+    while parser.accept(&Token::Comma) {
+        expressions.push(parser.expression()?);
+    }
+    parser.expect_end()?;
+    Ok(expressions)
+}
+
+/// Binary operator precedence levels, from the lowest to the highest
+const LOGICAL_OR_LEVEL: usize = 0;
+const EQUALITY_LEVEL: usize = 6;
+const ADDITIVE_LEVEL: usize = 8;
+const POWER_LEVEL: usize = 10;
+
+fn binary_operator(token: &Token) -> Option<(BinaryOperator, usize)> {
+    let operator = match token {
+        Token::LogicalOr => (BinaryOperator::LogicalOr, LOGICAL_OR_LEVEL),
+        Token::LogicalXor => (BinaryOperator::LogicalXor, 1),
+        Token::LogicalAnd => (BinaryOperator::LogicalAnd, 2),
+        Token::BitwiseOr => (BinaryOperator::BitwiseOr, 3),
+        Token::BitwiseXor => (BinaryOperator::BitwiseXor, 4),
+        Token::BitwiseAnd => (BinaryOperator::BitwiseAnd, 5),
+        Token::Equal => (BinaryOperator::Equal, EQUALITY_LEVEL),
+        Token::NotEqual => (BinaryOperator::NotEqual, EQUALITY_LEVEL),
+        Token::Less => (BinaryOperator::Less, 7),
+        Token::LessOrEqual => (BinaryOperator::LessOrEqual, 7),
+        Token::Greater => (BinaryOperator::Greater, 7),
+        Token::GreaterOrEqual => (BinaryOperator::GreaterOrEqual, 7),
+        Token::Plus => (BinaryOperator::Add, ADDITIVE_LEVEL),
+        Token::Minus => (BinaryOperator::Subtract, ADDITIVE_LEVEL),
+        Token::Multiply => (BinaryOperator::Multiply, 9),
+        Token::Divide => (BinaryOperator::Divide, 9),
+        Token::Modulo => (BinaryOperator::Modulo, 9),
+        Token::Power => (BinaryOperator::Power, POWER_LEVEL),
+        _ => None?,
+    };
+    Some(operator)
+}
+
+fn comparison_operator(token: &Token) -> Option<BinaryOperator> {
+    match binary_operator(token) {
+        Some((operator, level)) if level == EQUALITY_LEVEL || level == EQUALITY_LEVEL + 1 => Some(operator),
+        _ => None,
+    }
+}
+
+/// How a trigger name is followed in an expression
+enum TriggerSyntax {
+    Simple(Trigger),
+    /// `Vel X`
+    Axis(fn(Axis) -> Trigger),
+    /// `Var(1)`
+    Argument(Trigger),
+    /// `NumHelper` or `NumHelper(1200)`
+    OptionalArgument(Trigger),
+}
+
+fn trigger_syntax(name: &str) -> Option<TriggerSyntax> {
+    use TriggerSyntax::*;
+    let syntax = match name {
+        "time" => Simple(Trigger::Time),
+        "stateno" => Simple(Trigger::StateNo),
+        "prevstateno" => Simple(Trigger::PrevStateNo),
+        "anim" => Simple(Trigger::Anim),
+        "animtime" => Simple(Trigger::AnimTime),
+        "animelemtime" => Argument(Trigger::AnimElemTime),
+        "animelemno" => Argument(Trigger::AnimElemNo),
+        "animexist" => Argument(Trigger::AnimExist),
+        "selfanimexist" => Argument(Trigger::SelfAnimExist),
+        "ctrl" => Simple(Trigger::Ctrl),
+        "alive" => Simple(Trigger::Alive),
+        "life" => Simple(Trigger::Life),
+        "lifemax" => Simple(Trigger::LifeMax),
+        "power" => Simple(Trigger::Power),
+        "powermax" => Simple(Trigger::PowerMax),
+        "facing" => Simple(Trigger::Facing),
+        "var" => Argument(Trigger::Var),
+        "fvar" => Argument(Trigger::FVar),
+        "sysvar" => Argument(Trigger::SysVar),
+        "sysfvar" => Argument(Trigger::SysFVar),
+        "vel" => Axis(Trigger::Vel),
+        "pos" => Axis(Trigger::Pos),
+        "screenpos" => Axis(Trigger::ScreenPos),
+        "hitvel" => Axis(Trigger::HitVel),
+        "p2dist" => Axis(Trigger::P2Dist),
+        "p2bodydist" => Axis(Trigger::P2BodyDist),
+        "parentdist" => Axis(Trigger::ParentDist),
+        "rootdist" => Axis(Trigger::RootDist),
+        "frontedgedist" => Simple(Trigger::FrontEdgeDist),
+        "backedgedist" => Simple(Trigger::BackEdgeDist),
+        "frontedgebodydist" => Simple(Trigger::FrontEdgeBodyDist),
+        "backedgebodydist" => Simple(Trigger::BackEdgeBodyDist),
+        "movecontact" => Simple(Trigger::MoveContact),
+        "movehit" => Simple(Trigger::MoveHit),
+        "moveguarded" => Simple(Trigger::MoveGuarded),
+        "movereversed" => Simple(Trigger::MoveReversed),
+        "hitcount" => Simple(Trigger::HitCount),
+        "uniqhitcount" => Simple(Trigger::UniqHitCount),
+        "hitshakeover" => Simple(Trigger::HitShakeOver),
+        "hitover" => Simple(Trigger::HitOver),
+        "hitfall" => Simple(Trigger::HitFall),
+        "hitpausetime" => Simple(Trigger::HitPauseTime),
+        "canrecover" => Simple(Trigger::CanRecover),
+        "inguarddist" => Simple(Trigger::InGuardDist),
+        "numenemy" => Simple(Trigger::NumEnemy),
+        "numpartner" => Simple(Trigger::NumPartner),
+        "numhelper" => OptionalArgument(Trigger::NumHelper),
+        "numtarget" => OptionalArgument(Trigger::NumTarget),
+        "numexplod" => OptionalArgument(Trigger::NumExplod),
+        "numproj" => Simple(Trigger::NumProj),
+        "ishelper" => OptionalArgument(Trigger::IsHelper),
+        "id" => Simple(Trigger::Id),
+        "teamside" => Simple(Trigger::TeamSide),
+        "palno" => Simple(Trigger::PalNo),
+        "roundno" => Simple(Trigger::RoundNo),
+        "roundstate" => Simple(Trigger::RoundState),
+        "roundsexisted" => Simple(Trigger::RoundsExisted),
+        "matchover" => Simple(Trigger::MatchOver),
+        "matchno" => Simple(Trigger::MatchNo),
+        "gametime" => Simple(Trigger::GameTime),
+        "win" => Simple(Trigger::Win),
+        "lose" => Simple(Trigger::Lose),
+        "drawgame" => Simple(Trigger::DrawGame),
+        "random" => Simple(Trigger::Random),
+        _ => None?,
+    };
+    Some(syntax)
+}
+
+fn function(name: &str) -> Option<(Function, usize)> {
+    let function = match name {
+        "abs" => (Function::Abs, 1),
+        "acos" => (Function::Acos, 1),
+        "asin" => (Function::Asin, 1),
+        "atan" => (Function::Atan, 1),
+        "ceil" => (Function::Ceil, 1),
+        "cos" => (Function::Cos, 1),
+        "exp" => (Function::Exp, 1),
+        "floor" => (Function::Floor, 1),
+        "ln" => (Function::Ln, 1),
+        "log" => (Function::Log, 2),
+        "sin" => (Function::Sin, 1),
+        "tan" => (Function::Tan, 1),
+        "ifelse" => (Function::IfElse, 3),
+        "cond" => (Function::Cond, 3),
+        _ => None?,
+    };
+    Some(function)
+}
+
+/// Redirection keyword, and whether it accepts an argument
+fn redirection(name: &str) -> Option<(Redirection, bool)> {
+    let redirection = match name {
+        "parent" => (Redirection::Parent, false),
+        "root" => (Redirection::Root, false),
+        "partner" => (Redirection::Partner, false),
+        "helper" => (Redirection::Helper, true),
+        "target" => (Redirection::Target, true),
+        "enemy" => (Redirection::Enemy, true),
+        "enemynear" => (Redirection::EnemyNear, true),
+        "playerid" => (Redirection::PlayerId, true),
+        "p2" => (Redirection::P2, false),
+        _ => None?,
+    };
+    Some(redirection)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn new(expression: &str) -> Result<Parser, TriggerError> {
+        Ok(Parser {
+            tokens: tokenize(expression)?,
+            position: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, TriggerError> {
+        let token = self.tokens.get(self.position).cloned().ok_or(TriggerError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn accept(&mut self, token: &Token) -> bool {
+        let accepted = self.peek() == Some(token);
+        if accepted {
+            self.position += 1;
+        }
+        accepted
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), TriggerError> {
+        match self.next()? {
+            next_token if &next_token == token => Ok(()),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    fn expect_end(&self) -> Result<(), TriggerError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(unexpected(token)),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expression, TriggerError> {
+        self.binary(LOGICAL_OR_LEVEL)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expression, TriggerError> {
+        if level > POWER_LEVEL {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        loop {
+            let operator = match self.peek().and_then(binary_operator) {
+                Some((operator, operator_level)) if operator_level == level => operator,
+                _ => break,
+            };
+            self.position += 1;
+            if level == EQUALITY_LEVEL {
+                if let Some((low_inclusive, low, high, high_inclusive)) = self.interval()? {
+                    left = Expression::Interval {
+                        value: Box::new(left),
+                        negated: operator == BinaryOperator::NotEqual,
+                        low: Box::new(low),
+                        low_inclusive,
+                        high: Box::new(high),
+                        high_inclusive,
+                    };
+                    continue;
+                }
+            }
+            let right = self.binary(level + 1)?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    /// Parse an interval such as `[1, 5)` after an equality operator, if there is one
+    fn interval(&mut self) -> Result<Option<(bool, Expression, Expression, bool)>, TriggerError> {
+        let low_inclusive = match self.peek() {
+            Some(Token::LeftBracket) => true,
+            Some(Token::LeftParenthesis) => false,
+            _ => return Ok(None),
+        };
+        let start_position = self.position;
+        let interval = (|| {
+            self.position += 1;
+            let low = self.expression()?;
+            self.expect(&Token::Comma)?;
+            let high = self.expression()?;
+            let high_inclusive = match self.next()? {
+                Token::RightBracket => true,
+                Token::RightParenthesis => false,
+                other => Err(unexpected(&other))?,
+            };
+            Ok((low_inclusive, low, high, high_inclusive))
+        })();
+        match interval {
+            Ok(interval) => Ok(Some(interval)),
+            // not an interval: parenthesized expression
+            Err(_) if !low_inclusive => {
+                self.position = start_position;
+                Ok(None)
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, TriggerError> {
+        let operator = match self.peek() {
+            Some(Token::LogicalNot) => UnaryOperator::LogicalNot,
+            Some(Token::BitwiseNot) => UnaryOperator::BitwiseNot,
+            Some(Token::Minus) => UnaryOperator::Negate,
+            Some(Token::Plus) => {
+                self.position += 1;
+                return self.unary();
+            },
+            _ => return self.primary(),
+        };
+        self.position += 1;
+        let operand = self.unary()?;
+        let expression = match (operator, operand) {
+            (UnaryOperator::Negate, Expression::Int(i)) => Expression::Int(i.wrapping_neg()),
+            (UnaryOperator::Negate, Expression::Float(f)) => Expression::Float(-f),
+            (operator, operand) => Expression::Unary(operator, Box::new(operand)),
+        };
+        Ok(expression)
+    }
+
+    fn primary(&mut self) -> Result<Expression, TriggerError> {
+        match self.next()? {
+            Token::Int(i) => Ok(Expression::Int(i)),
+            Token::Float(f) => Ok(Expression::Float(f)),
+            Token::LeftParenthesis => {
+                let expression = self.expression()?;
+                self.expect(&Token::RightParenthesis)?;
+                Ok(expression)
+            },
+            Token::Identifier(name) => self.named(&name.to_lowercase()),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    /// Parse the expression starting with the given lowercase identifier
+    fn named(&mut self, name: &str) -> Result<Expression, TriggerError> {
+        if let Some((redirection, has_argument)) = redirection(name) {
+            let argument = match has_argument && self.peek() == Some(&Token::LeftParenthesis) {
+                true => Some(Box::new(self.argument()?)),
+                false => None,
+            };
+            self.expect(&Token::Comma)?;
+            let redirected = self.primary()?;
+            return Ok(Expression::Redirect(redirection, argument, Box::new(redirected)));
+        }
+        if let Some(syntax) = trigger_syntax(name) {
+            let expression = match syntax {
+                TriggerSyntax::Simple(trigger) => Expression::Trigger(trigger, None),
+                TriggerSyntax::Axis(trigger) => Expression::Trigger(trigger(self.axis()?), None),
+                TriggerSyntax::Argument(trigger) => Expression::Trigger(trigger, Some(Box::new(self.argument()?))),
+                TriggerSyntax::OptionalArgument(trigger) => {
+                    let argument = match self.peek() == Some(&Token::LeftParenthesis) {
+                        true => Some(Box::new(self.argument()?)),
+                        false => None,
+                    };
+                    Expression::Trigger(trigger, argument)
+                },
+            };
+            return Ok(expression);
+        }
+        if let Some((function, argument_count)) = function(name) {
+            let arguments = self.arguments()?;
+            if arguments.len() != argument_count {
+                Err(TriggerError::ArgumentCount { name: name.to_owned(), expected: argument_count, found: arguments.len() })?;
+            }
+            return Ok(Expression::Function(function, arguments));
+        }
+        match name {
+            "pi" => Ok(Expression::Float(std::f32::consts::PI)),
+            "e" => Ok(Expression::Float(std::f32::consts::E)),
+            "command" => self.compared(name, |parser| match parser.next()? {
+                Token::Text(command) => Ok(Expression::Command(command)),
+                other => Err(unexpected(&other)),
+            }),
+            "statetype" | "movetype" => {
+                let trigger = if name == "statetype" { SymbolTrigger::StateType } else { SymbolTrigger::MoveType };
+                self.compared(name, |parser| match parser.next()? {
+                    Token::Identifier(symbol) => Ok(Expression::Symbol(trigger, symbol)),
+                    other => Err(unexpected(&other)),
+                })
+            },
+            "name" | "p1name" | "authorname" => {
+                let trigger = if name == "authorname" { SymbolTrigger::AuthorName } else { SymbolTrigger::Name };
+                self.compared(name, |parser| match parser.next()? {
+                    Token::Text(text) => Ok(Expression::Symbol(trigger, text)),
+                    other => Err(unexpected(&other)),
+                })
+            },
+            "p2life" | "p2stateno" | "p2statetype" | "p2movetype" | "p2name" => {
+                let redirected = self.named(&name[2..])?;
+                Ok(Expression::Redirect(Redirection::P2, None, Box::new(redirected)))
+            },
+            "animelem" => self.anim_elem(),
+            "const" | "gethitvar" => {
+                self.expect(&Token::LeftParenthesis)?;
+                let parameter_name = match self.next()? {
+                    Token::Identifier(parameter_name) => parameter_name.to_lowercase(),
+                    other => Err(unexpected(&other))?,
+                };
+                self.expect(&Token::RightParenthesis)?;
+                match name {
+                    "const" => Ok(Expression::Const(parameter_name)),
+                    _ => Ok(Expression::GetHitVar(parameter_name)),
+                }
+            },
+            _ => Err(TriggerError::UnknownTrigger(name.to_owned())),
+        }
+    }
+
+    /// Parse `= value` or `!= value` after a trigger that can only be compared
+    fn compared<F>(&mut self, name: &str, value: F) -> Result<Expression, TriggerError>
+        where F: FnOnce(&mut Parser) -> Result<Expression, TriggerError>
+    {
+        let negated = match self.peek() {
+            Some(Token::Equal) => false,
+            Some(Token::NotEqual) => true,
+            _ => Err(TriggerError::MissingComparison(name.to_owned()))?,
+        };
+        self.position += 1;
+        let expression = value(self)?;
+        match negated {
+            true => Ok(Expression::Unary(UnaryOperator::LogicalNot, Box::new(expression))),
+            false => Ok(expression),
+        }
+    }
+
+    /// `AnimElem = n`: true when the animation element starts, or `AnimElem = n, >= t`: comparison of the time since the element started
+    fn anim_elem(&mut self) -> Result<Expression, TriggerError> {
+        self.expect(&Token::Equal)?;
+        let element = self.binary(ADDITIVE_LEVEL)?;
+        let element_time = Expression::Trigger(Trigger::AnimElemTime, Some(Box::new(element)));
+        let comparison = match self.tokens.get(self.position..self.position + 2) {
+            Some([Token::Comma, operator]) => comparison_operator(operator),
+            _ => None,
+        };
+        match comparison {
+            Some(operator) => {
+                self.position += 2;
+                let time = self.binary(ADDITIVE_LEVEL)?;
+                Ok(Expression::Binary(operator, Box::new(element_time), Box::new(time)))
+            },
+            None => Ok(Expression::Binary(BinaryOperator::Equal, Box::new(element_time), Box::new(Expression::Int(0)))),
+        }
+    }
+
+    fn axis(&mut self) -> Result<Axis, TriggerError> {
+        match self.next()? {
+            Token::Identifier(axis) if axis.eq_ignore_ascii_case("x") => Ok(Axis::X),
+            Token::Identifier(axis) if axis.eq_ignore_ascii_case("y") => Ok(Axis::Y),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    /// Single parenthesized argument
+    fn argument(&mut self) -> Result<Expression, TriggerError> {
+        self.expect(&Token::LeftParenthesis)?;
+        let argument = self.expression()?;
+        self.expect(&Token::RightParenthesis)?;
+        Ok(argument)
+    }
+
+    /// Parenthesized comma-separated arguments
+    fn arguments(&mut self) -> Result<Vec<Expression>, TriggerError> {
+        self.expect(&Token::LeftParenthesis)?;
+        let mut arguments = vec![self.expression()?];
+        while self.accept(&Token::Comma) {
+            arguments.push(self.expression()?);
+        }
+        self.expect(&Token::RightParenthesis)?;
+        Ok(arguments)
+    }
+}
+
+fn unexpected(token: &Token) -> TriggerError {
+    TriggerError::UnexpectedToken(format!("{token:?}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn int(i: i32) -> Box<Expression> {
+        Box::new(Expression::Int(i))
+    }
+
+    #[test]
+    fn parse_precedence() {
+        // 1 + 2 * 3 ** 2 = 19 || 0
+        assert_eq!(
+            Ok(Expression::Binary(BinaryOperator::LogicalOr,
+                Box::new(Expression::Binary(BinaryOperator::Equal,
+                    Box::new(Expression::Binary(BinaryOperator::Add,
+                        int(1),
+                        Box::new(Expression::Binary(BinaryOperator::Multiply,
+                            int(2),
+                            Box::new(Expression::Binary(BinaryOperator::Power, int(3), int(2))),
+                        )),
+                    )),
+                    int(19),
+                )),
+                int(0),
+            )),
+            parse_expression("1 + 2 * 3 ** 2 = 19 || 0")
+        );
+        assert_eq!(
+            Ok(Expression::Binary(BinaryOperator::Subtract, Box::new(Expression::Binary(BinaryOperator::Subtract, int(5), int(2))), int(1))),
+            parse_expression("5 - 2 - 1")
+        );
+    }
+
+    #[test]
+    fn parse_intervals() {
+        assert_eq!(
+            Ok(Expression::Interval {
+                value: Box::new(Expression::Trigger(Trigger::Time, None)),
+                negated: true,
+                low: int(1),
+                low_inclusive: true,
+                high: int(5),
+                high_inclusive: false,
+            }),
+            parse_expression("Time != [1, 5)")
+        );
+        // a parenthesized expression is not an interval
+        assert_eq!(
+            Ok(Expression::Binary(BinaryOperator::Equal,
+                Box::new(Expression::Trigger(Trigger::Time, None)),
+                Box::new(Expression::Binary(BinaryOperator::Multiply, Box::new(Expression::Binary(BinaryOperator::Add, int(1), int(2))), int(3))),
+            )),
+            parse_expression("time = (1 + 2) * 3")
+        );
+    }
+
+    #[test]
+    fn parse_triggers() {
+        assert_eq!(
+            Ok(Expression::Binary(BinaryOperator::LogicalAnd,
+                Box::new(Expression::Command("holdfwd".to_owned())),
+                Box::new(Expression::Unary(UnaryOperator::LogicalNot, Box::new(Expression::Symbol(SymbolTrigger::StateType, "A".to_owned())))),
+            )),
+            parse_expression("command = \"holdfwd\" && StateType != A")
+        );
+        assert_eq!(
+            Ok(Expression::Binary(BinaryOperator::Less, Box::new(Expression::Trigger(Trigger::Vel(Axis::Y), None)), Box::new(Expression::Float(-0.5)))),
+            parse_expression("Vel Y < -0.5")
+        );
+        assert_eq!(
+            Ok(Expression::Redirect(Redirection::Helper, Some(int(1200)), Box::new(Expression::Trigger(Trigger::Var, Some(int(3)))))),
+            parse_expression("helper(1200), var(3)")
+        );
+        assert_eq!(
+            Ok(Expression::Redirect(Redirection::P2, None, Box::new(Expression::Trigger(Trigger::Life, None)))),
+            parse_expression("P2Life")
+        );
+        assert_eq!(
+            Ok(Expression::Binary(BinaryOperator::GreaterOrEqual, Box::new(Expression::Trigger(Trigger::AnimElemTime, Some(int(2)))), int(3))),
+            parse_expression("AnimElem = 2, >= 3")
+        );
+        assert_eq!(
+            Ok(Expression::Function(Function::IfElse, vec![
+                Expression::Binary(BinaryOperator::Equal, Box::new(Expression::Trigger(Trigger::AnimElemTime, Some(int(2)))), int(0)),
+                Expression::Const("velocity.walk.fwd.x".to_owned()),
+                Expression::Int(0),
+            ])),
+            parse_expression("ifelse(AnimElem = 2, const(velocity.walk.fwd.x), 0)")
+        );
+        assert_eq!(
+            Ok(Expression::Redirect(Redirection::EnemyNear, Some(int(1)), Box::new(Expression::Trigger(Trigger::Life, None)))),
+            parse_expression("enemynear(1), life")
+        );
+        assert_eq!(
+            Ok(Expression::Binary(BinaryOperator::Greater, Box::new(Expression::Trigger(Trigger::ParentDist(Axis::X), None)), int(10))),
+            parse_expression("ParentDist X > 10")
+        );
+        assert_eq!(Ok(vec![Expression::Int(10), Expression::Int(12)]), parse_expression_list("10, 12"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Err(TriggerError::UnknownTrigger("foo".to_owned())), parse_expression("foo = 1"));
+        assert_eq!(Err(TriggerError::UnexpectedEnd), parse_expression("1 +"));
+        assert_eq!(Err(TriggerError::MissingComparison("command".to_owned())), parse_expression("command"));
+        assert!(matches!(parse_expression("abs(1, 2)"), Err(TriggerError::ArgumentCount { .. })));
+        assert!(matches!(parse_expression("time = 1)"), Err(TriggerError::UnexpectedToken(_))));
+    }
+}
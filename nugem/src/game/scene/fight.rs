@@ -1,72 +1,353 @@
 use super::Scene;
-use crate::game::mugen::character::{Character, command};
-use crate::game::mugen::character::air;
-use crate::game::graphics::{self, surface::BitmapSurfaceRenderer};
+use crate::game::mugen::character::{Character, Constants, command};
+use crate::game::mugen::character::{air, state};
+use crate::game::mugen::character::file_reader::{fs::FileReaderFs, FileReader};
+use crate::game::mugen::combat;
+use crate::game::mugen::round;
+use crate::game::mugen::lifebar;
+use crate::game::mugen::font;
+use crate::game::mugen::stage;
+use crate::game::graphics::{self, surface::{BitmapSurfaceRenderer, IndexedSurfaceRenderer}};
+use crate::game::audio;
 use crate::game::Config;
 use crate::game::events;
-use crate::game::input::{self, DirectionState, DirectionalMotion, Directional};
-use std::collections::{BTreeMap, HashMap};
+use crate::game::input;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use nugem_sff::bitmap::BitmapPixel;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use log::error;
 
 const SCREEN_DIMENSIONS: (u32, u32) = (800, 600);
 
+/// Maximum number of game ticks run in a single update: if the game is running late, the remaining time is dropped
+const MAX_TICKS_PER_UPDATE: u32 = 10;
+
+/// Distance of the players from the center of the stage at the start of the fight
+const START_DISTANCE_FROM_CENTER: f32 = 70.;
+
+/// Scale of the characters on the screen
+const DISPLAY_SCALE: f32 = 2.;
+
+/// Size of the big face of the characters on the screen
+const BIG_FACE_SIZE: f32 = 175.;
+
+/// Vertical screen coordinate of the ground
+const GROUND_SCREEN_Y: f32 = 500.;
+
+/// Distance from the center of the screen at which the sounds are only played on one side
+const SOUND_PAN_DISTANCE: f32 = SCREEN_DIMENSIONS.0 as f32 / DISPLAY_SCALE / 2.;
+
+/// Width of the screen in the coordinates of the characters, scaled by `DISPLAY_SCALE`
+const LOCAL_SCREEN_WIDTH: f32 = 320.;
+
+/// Size of the screen in the coordinates of the characters, followed by the camera
+const CAMERA_SCREEN_SIZE: (f32, f32) = (SCREEN_DIMENSIONS.0 as f32 / DISPLAY_SCALE, SCREEN_DIMENSIONS.1 as f32 / DISPLAY_SCALE);
+
+/// Drawing order of the background elements behind the players and in front of them
+const BACK_LAYER_PRIORITY: i32 = i32::MIN;
+const FRONT_LAYER_PRIORITY: i32 = i32::MAX;
+
+/// Scale of the lifebar on the screen
+const HUD_SCALE: f32 = SCREEN_DIMENSIONS.0 as f32 / lifebar::LIFEBAR_SCREEN_SIZE.0;
+
+/// Drawing order of the lifebar, over the stage and the players
+const HUD_PRIORITY: i32 = i32::MAX;
+
+/// Intro states of the characters, played at the start of the first round
+const INTRO_STATE: i32 = 190;
+const INTRO_STATES: [i32; 2] = [190, 191];
+
+/// Win pose state of the characters, and the alternative win poses selected by holding the a, b or c button
+const WIN_POSE_STATE: i32 = 180;
+const ALTERNATIVE_WIN_POSE_STATES: [i32; 3] = [181, 182, 183];
+
+/// States of the characters losing and drawing a round by time over
+const TIME_OVER_LOSE_STATE: i32 = 170;
+const TIME_OVER_DRAW_STATE: i32 = 175;
+
+/// Sound file shared by the characters, for the hit sounds
+const COMMON_SOUND_FILE: &str = "fight.snd";
+
+/// Animations and sprites of the common hit sparks
+const FIGHT_FX_ANIMATION_FILE: &str = "fightfx.air";
+const FIGHT_FX_SPRITE_FILE: &str = "fightfx.sff";
+
+/// Drawing order of the hit sparks, over the players
+const SPARK_PRIORITY: i32 = i32::MAX - 1;
+
+/// Owner of the channels of the stage music in the mixer, after the players
+const MUSIC_OWNER: usize = 2;
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 struct ImageKey {
     group: u16,
     image: u16,
 }
 
+/// Glyph of a character in a color bank of a font of the lifebar
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+struct GlyphKey {
+    font: u32,
+    bank: i32,
+    character: char,
+}
+
+/// Sprites of the lifebar in the texture atlas: its images, and the glyphs of its fonts in the banks of its texts
+#[derive(Default)]
+struct HudSprites {
+    images: HashMap<ImageKey, LoadedSprite>,
+    glyphs: HashMap<GlyphKey, LoadedSprite>,
+}
+
+/// Rows of the palette texture with the selected palettes of the players, the palettes of the sprites with their own
+/// palette coming after them
+const PLAYER_PALETTE_ROWS: u32 = 2;
+
+/// Sprite in the texture atlas
+struct LoadedSprite {
+    pub id: usize,
+    /// Axis of the sprite: x, y from its top left corner
+    pub axis: (i16, i16),
+}
+
 struct Player {
     pub character_id: usize,
-    pub image_keys: HashMap<ImageKey, usize>,
-    pub current_animation: usize,
-    pub animator: Option<air::Animator>,
+    pub image_keys: HashMap<ImageKey, LoadedSprite>,
+    pub state: state::PlayerState,
+    pub state_machine: state::StateMachine,
+    /// Current state of the input device of the player
+    pub input_state: input::State,
+    pub command_recognizer: Option<command::CommandRecognizer>,
+    pub displayed_image: Option<ImageKey>,
+    /// Sprite drawn in the sprite stack
+    pub displayed_sprite: Option<graphics::sprites::SpriteInstance>,
     pub big_face: Option<usize>,
-    pub small_face: Option<usize>,
+    pub small_face: Option<LoadedSprite>,
     pub sprite_id: usize,
+    /// Palette of the sprite file applied to the sprites of the player
+    pub palette_index: usize,
+    /// Palette uploaded to the palette texture
+    pub displayed_palette: Option<usize>,
 }
 
 struct FightData {
     pub texture_atlas: graphics::sprites::SpriteTextureAtlas,
+    pub palette_texture: graphics::sprites::PaletteTexture,
     pub sprite_stack: graphics::sprites::SpriteStack,
+    /// Sprites of the stage in the texture atlas, with and without mask
+    pub stage_sprites: HashMap<(ImageKey, bool), LoadedSprite>,
+    /// Sprites of the sprite stack drawing the tiles of each background element
+    pub background_sprite_ids: Vec<Vec<usize>>,
+    pub hud_sprites: HudSprites,
+    /// Sprites of the sprite stack drawing the lifebar, the unused ones being hidden
+    pub hud_sprite_ids: Vec<usize>,
+    /// Sprites of the common hit sparks in the texture atlas
+    pub fight_fx_sprites: HashMap<ImageKey, LoadedSprite>,
+    /// Sprites of the sprite stack drawing the hit sparks, the unused ones being hidden
+    pub spark_sprite_ids: Vec<usize>,
+}
+
+/// Common hit sparks
+struct FightFxData {
+    pub sff_data: nugem_sff::SpriteFile,
+    pub animations: air::Animations,
+}
+
+/// Hit spark playing its animation once where a hit landed
+struct Spark {
+    /// Player whose animations and sprites draw the spark, `None` for a common spark
+    owner: Option<usize>,
+    animator: air::Animator,
+    /// Position in the coordinates of the players
+    position: (f32, f32),
+    /// Facing of the attacker
+    facing_sign: f32,
+}
+
+struct LifebarData {
+    pub lifebar: lifebar::Lifebar,
+    pub sff_data: nugem_sff::SpriteFile,
+    /// Fonts of the `[Files]` section by number
+    pub fonts: BTreeMap<u32, font::Font>,
+    pub hud: lifebar::Hud,
+}
+
+struct StageData {
+    pub stage: stage::Stage,
+    pub sff_data: nugem_sff::SpriteFile,
+    pub background: stage::StageBackground,
+    /// Background music, played in a loop from the first round
+    pub music: Option<Arc<nugem_snd::Sound>>,
+    /// Limits of the camera and of the players, in the coordinates of the players
+    pub bounds: FightBounds,
+}
+
+/// Parameters of a stage moving the camera and limiting the movement of the players
+#[derive(Clone, Default)]
+struct FightBounds {
+    pub camera: stage::StageCamera,
+    pub bound: stage::StageBound,
+    pub player_info: stage::StagePlayerInfo,
+}
+
+impl FightBounds {
+    /// Parameters of a stage converted from the coordinates of the stage to the coordinates of the players
+    fn new(stage_def: &stage::StageDef) -> FightBounds {
+        let scale = LOCAL_SCREEN_WIDTH / stage_def.stage_info.local_coord.0.max(1) as f32;
+        let camera = &stage_def.camera;
+        FightBounds {
+            camera: stage::StageCamera {
+                start: (camera.start.0 * scale, camera.start.1 * scale),
+                bound_left: camera.bound_left * scale,
+                bound_right: camera.bound_right * scale,
+                bound_high: camera.bound_high * scale,
+                bound_low: camera.bound_low * scale,
+                tension: camera.tension * scale,
+                vertical_follow: camera.vertical_follow,
+                floor_tension: camera.floor_tension * scale,
+            },
+            bound: stage::StageBound {
+                screen_left: stage_def.bound.screen_left * scale,
+                screen_right: stage_def.bound.screen_right * scale,
+            },
+            player_info: stage::StagePlayerInfo {
+                left_bound: stage_def.player_info.left_bound * scale,
+                right_bound: stage_def.player_info.right_bound * scale,
+                ..stage_def.player_info.clone()
+            },
+        }
+    }
 }
 
 struct CharaData {
-    // TODO
-    pub _character: Character,
+    pub character: Character,
     pub sff_data: nugem_sff::SpriteFile,
-    pub animations: Vec<air::Animation>,
-    // TODO use commands
-    pub _commands: command::CommandConfiguration,
+    pub animations: air::Animations,
+    pub commands: command::CommandConfiguration,
+    pub states: state::States,
+    pub constants: Constants,
+    pub sounds: audio::SoundBank,
 }
 
 pub struct Fight {
     characters: Vec<CharaData>,
+    stage: Option<StageData>,
+    lifebar: Option<LifebarData>,
+    fight_fx: Option<FightFxData>,
+    sparks: Vec<Spark>,
+    camera: stage::FightCamera,
     loaded_data: Option<FightData>,
     players: [Player; 2],
+    match_flow: round::MatchFlow,
+    tick_duration: Duration,
+    last_update: Option<Instant>,
+    tick_time_accumulator: Duration,
+    mixer: audio::Mixer,
+    audio_output: Box<dyn audio::AudioOutput>,
+    common_sounds: audio::SoundBank,
+    /// Ticks since the start of the match
+    game_time: i32,
+    random: state::RandomGenerator,
 }
 
 impl Player {
-    pub fn new(character_id: usize) -> Player {
+    pub fn new(character_id: usize, facing: state::Facing) -> Player {
         Player {
             character_id,
             image_keys: HashMap::new(),
-            current_animation: 0,
-            animator: None,
+            state: state::PlayerState::new(facing),
+            state_machine: state::StateMachine::new(),
+            input_state: input::State::new(),
+            command_recognizer: None,
+            displayed_image: None,
+            displayed_sprite: None,
             big_face: None,
             small_face: None,
             sprite_id: 0,
+            palette_index: 0,
+            displayed_palette: None,
+        }
+    }
+    /// Reset the player to the initial state of its character
+    /// Palette of the sprite file applied to the sprites of the player: the selected one, unless a RemapPal controller
+    /// replaced it with a palette of the sprite file
+    fn shown_palette(&self, sff_data: &nugem_sff::SpriteFile) -> usize {
+        self.state.remapped_palette
+            .and_then(|(group, item)| palette_number_index(sff_data, group, item))
+            .unwrap_or(self.palette_index)
+    }
+    fn reset(&mut self, chara_data: &CharaData) {
+        self.state = state::PlayerState::new(self.state.facing);
+        self.state.position.0 = -START_DISTANCE_FROM_CENTER * self.state.facing_sign();
+        self.state.life_max = chara_data.constants.life();
+        self.state.life = self.state.life_max;
+        self.state.power_max = chara_data.constants.power();
+        self.state_machine = state::StateMachine::new();
+        self.command_recognizer = Some(command::CommandRecognizer::new(&chara_data.commands, self.state.facing));
+        self.state_machine.enter_state(&mut self.state, 0, &chara_data.states, &chara_data.animations, None);
+        if self.state.animation_number().is_none() {
+            // display the first animation if the initial state does not set one
+            if let Some(&first_animation) = chara_data.animations.keys().next() {
+                self.state.change_animation(first_animation, &chara_data.animations);
+            }
+        }
+    }
+    fn current_image(&self) -> Option<ImageKey> {
+        self.state.animator()
+            .and_then(air::Animator::current_display_info)
+            .map(|(group, image)| ImageKey { group, image })
+    }
+    /// Sprite of the player drawn with the transforms of the current animation frame relative to the camera, in front of
+    /// the sprites with a lower sprite priority
+    fn sprite_instance(&self, sprite: &LoadedSprite, camera: (f32, f32)) -> graphics::sprites::SpriteInstance {
+        let default_frame = air::AnimationFrame::new(0, 0, (0, 0), None);
+        let frame = self.state.animator().and_then(air::Animator::current_frame).unwrap_or(&default_frame);
+        let position = (self.state.position.0 - camera.0, self.state.position.1 - camera.1);
+        let mut instance = sprite_instance(sprite, position, self.state.facing_sign(), frame);
+        instance.priority = self.state.sprite_priority;
+        if let Some(trans) = self.state.trans {
+            instance.blend = sprite_blend(trans);
+        }
+        if let Some(pal_fx) = self.state.pal_fx.as_ref() {
+            instance.color_effect = color_effect(pal_fx);
+        }
+        instance
+    }
+    /// Sprite of the current animation frame, hidden if the frame has no sprite in the texture atlas
+    fn current_sprite_instance(&self, camera: (f32, f32)) -> graphics::sprites::SpriteInstance {
+        match self.current_image().as_ref().and_then(|key| self.image_keys.get(key)) {
+            Some(sprite) => self.sprite_instance(sprite, camera),
+            None => hidden_sprite(),
         }
     }
 }
 
 impl Fight {
-    pub fn new(_: &Config) -> Fight {
-        let players = [Player::new(0), Player::new(1)];
+    pub fn new(config: &Config) -> Fight {
+        let players = [Player::new(0, state::Facing::Right), Player::new(1, state::Facing::Left)];
+        let audio_output = audio::open_output(config.audio_output());
+        let mut mixer = audio::Mixer::new(audio_output.sample_rate(), config.ticks_per_second());
+        mixer.set_volumes(config.volumes());
         Fight {
             characters: Vec::new(),
+            stage: None,
+            lifebar: None,
+            fight_fx: None,
+            sparks: Vec::new(),
+            camera: stage::FightCamera::new(&stage::StageCamera::default(), CAMERA_SCREEN_SIZE),
             loaded_data: None,
             players,
+            match_flow: round::MatchFlow::new(config.match_rules(round::MatchRules::default())),
+            tick_duration: Duration::from_secs(1) / config.ticks_per_second().max(1),
+            last_update: None,
+            tick_time_accumulator: Duration::ZERO,
+            mixer,
+            audio_output,
+            common_sounds: audio::SoundBank::default(),
+            game_time: 0,
+            random: state::RandomGenerator::new(SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |time| time.subsec_nanos())),
         }
     }
     pub fn loaded(&self) -> bool {
@@ -75,92 +356,467 @@ impl Fight {
     pub fn unload(&mut self) {
         self.loaded_data = None;
     }
-    pub fn load(&mut self, state: &graphics::State) {
+    /// Build the texture atlas with every sprite of the animations of the characters, the stage and the lifebar
+    pub fn load(&mut self, state: &graphics::State) -> Result<(), graphics::Error> {
         let mut sprite_atlas_builder = graphics::sprites::SpriteTextureAtlasBuilder::new();
-        for player in self.players.iter_mut() {
+        let mut sprite_palettes = SpritePalettes::default();
+        for (player_number, player) in self.players.iter_mut().enumerate() {
             let chara_data = self.characters.get(player.character_id).expect(&format!("Failed to load character {0}", player.character_id));
-            let palette_index = 0;
-            let render_sff_sprite = |group_index, image_index, palette_index| -> Result<_, nugem_sff::RenderingError<<BitmapSurfaceRenderer as nugem_sff::bitmap::BitmapRenderer>::Error>> {
-                let renderer: BitmapSurfaceRenderer = chara_data.sff_data.render_sprite((), group_index, image_index, palette_index)?;
-                Ok(renderer.take())
+            let palette_index = player.palette_index;
+            let mut add_sprite = |group_index, image_index| {
+                add_atlas_sprite(&mut sprite_atlas_builder, &mut sprite_palettes, &chara_data.sff_data, (group_index, image_index), player_number as u32, palette_index)
             };
-            player.animator = Some(air::Animator::new(chara_data.animations[player.current_animation].clone()));
-            player.small_face = render_sff_sprite(9000, 0, palette_index).map(|s| sprite_atlas_builder.add_surface(s)).ok();
-            player.big_face = render_sff_sprite(9000, 1, palette_index).map(|s| sprite_atlas_builder.add_surface(s)).ok();
-            {
-                player.image_keys.clear();
-                let animator = player.animator.as_ref().unwrap();
-                for step in animator.animation().steps() {
-                    for frame in step.frames() {
-                        let key = ImageKey {
-                            group: frame.group,
-                            image: frame.image,
-                        };
-                        if player.image_keys.get(&key) == None {
-                            match chara_data.sff_data.render_sprite::<BitmapSurfaceRenderer>((), key.group, key.image, palette_index) {
-                                Ok(renderer) => {
-                                    let image_id = sprite_atlas_builder.add_surface(renderer.take());
-                                    player.image_keys.insert(key, image_id);
-                                },
-                                Err(err) => {
-                                    error!("Unable to render sprite from group {0}, image {1}, palette {2}: {err}", key.group, key.image, palette_index);
-                                },
+            player.small_face = add_sprite(9000, 0).ok();
+            player.big_face = add_sprite(9000, 1).map(|sprite| sprite.id).ok();
+            // the sprites of all the animations, so that changing the animation only changes the drawn sprite
+            player.image_keys.clear();
+            for key in animation_image_keys(&chara_data.animations) {
+                match add_sprite(key.group, key.image) {
+                    Ok(sprite) => {
+                        player.image_keys.insert(key, sprite);
+                    },
+                    Err(err) => {
+                        error!("Unable to render sprite from group {0}, image {1}, palette {2}: {err}", key.group, key.image, palette_index);
+                    },
+                }
+            }
+        }
+        let mut stage_sprites = HashMap::new();
+        if let Some(stage_data) = self.stage.as_ref() {
+            for (key, masked) in stage_image_keys(&stage_data.stage) {
+                match add_stage_sprite(&mut sprite_atlas_builder, &mut sprite_palettes, &stage_data.sff_data, &key, masked) {
+                    Ok(sprite) => {
+                        stage_sprites.insert((key, masked), sprite);
+                    },
+                    Err(err) => error!("Unable to render stage sprite from group {0}, image {1}: {err}", key.group, key.image),
+                }
+            }
+        }
+        let mut fight_fx_sprites = HashMap::new();
+        if let Some(fight_fx) = self.fight_fx.as_ref() {
+            for key in animation_image_keys(&fight_fx.animations) {
+                match fight_fx.sff_data.render_sprite::<BitmapSurfaceRenderer>((), key.group, key.image, 0) {
+                    Ok(rendered) => {
+                        let id = sprite_atlas_builder.add_surface(rendered.surface.take());
+                        fight_fx_sprites.insert(key, LoadedSprite { id, axis: rendered.axis });
+                    },
+                    Err(err) => error!("Unable to render spark sprite from group {0}, image {1}: {err}", key.group, key.image),
+                }
+            }
+        }
+        let mut hud_sprites = HudSprites::default();
+        if let Some(lifebar_data) = self.lifebar.as_ref() {
+            for key in lifebar_image_keys(&lifebar_data.lifebar) {
+                match lifebar_data.sff_data.render_sprite::<BitmapSurfaceRenderer>((), key.group, key.image, 0) {
+                    Ok(rendered) => {
+                        let id = sprite_atlas_builder.add_surface(rendered.surface.take());
+                        hud_sprites.images.insert(key, LoadedSprite { id, axis: rendered.axis });
+                    },
+                    Err(err) => error!("Unable to render lifebar sprite from group {0}, image {1}: {err}", key.group, key.image),
+                }
+            }
+            for (font_number, bank) in lifebar_font_banks(&lifebar_data.lifebar) {
+                let Some(font) = lifebar_data.fonts.get(&font_number) else {
+                    continue;
+                };
+                for character in font.characters() {
+                    match font.render_glyph::<BitmapSurfaceRenderer>((), character, bank) {
+                        Ok(Some(rendered)) => {
+                            let surface = rendered.surface.take();
+                            if surface.width() > 0 && surface.height() > 0 {
+                                let id = sprite_atlas_builder.add_surface(surface);
+                                hud_sprites.glyphs.insert(GlyphKey { font: font_number, bank, character }, LoadedSprite { id, axis: rendered.axis });
                             }
-                        }
+                        },
+                        Ok(None) => (),
+                        Err(err) => error!("Unable to render the glyph {character:?} of the lifebar font {font_number}: {err}"),
                     }
                 }
             }
         }
-        let texture_atlas = sprite_atlas_builder.build(state).unwrap();
+        let texture_atlas = sprite_atlas_builder.build(state)?;
+        let palette_texture = graphics::sprites::PaletteTexture::new(state.device(), PLAYER_PALETTE_ROWS + sprite_palettes.rows.len() as u32);
+        for (player_number, player) in self.players.iter_mut().enumerate() {
+            let sff_data = &self.characters[player.character_id].sff_data;
+            let palette_index = player.shown_palette(sff_data);
+            palette_texture.set_palette(state.queue(), player_number as u32, &sff_data.palette_colors(palette_index).unwrap_or_default());
+            player.displayed_palette = Some(palette_index);
+        }
+        for (palette, &row) in sprite_palettes.rows.iter() {
+            palette_texture.set_palette(state.queue(), row, palette);
+        }
         let mut sprite_stack_drawer = graphics::sprites::SpriteStack::new(state.device(), state.surface_configuration().format, SCREEN_DIMENSIONS);
-        sprite_stack_drawer.set_texture_atlas(&texture_atlas, state.device());
+        sprite_stack_drawer.set_texture_atlas(&texture_atlas, &palette_texture, state.device());
+        let camera = self.camera.view_position();
+        let background_sprite_ids = match self.stage.as_ref() {
+            Some(stage_data) => (0..stage_data.stage.def().elements.len())
+                .map(|element_index| {
+                    background_instances(stage_data, element_index, camera, &stage_sprites, &texture_atlas)
+                        .into_iter()
+                        .map(|instance| sprite_stack_drawer.push_sprite(instance))
+                        .collect()
+                })
+                .collect(),
+            None => Vec::new(),
+        };
         for (player_number, player) in self.players.iter_mut().enumerate() {
-            if let Some(animator) = player.animator.as_mut() {
-                if let Some((group, image)) = animator.current_display_info() {
-                    let key = ImageKey { group, image };
-                    if let Some(&sprite_id) = player.image_keys.get(&key) {
-                        let (w, h) = texture_atlas.dimensions(sprite_id).unwrap();
-                        if let Some(big_face) = player.big_face.clone() {
-                            sprite_stack_drawer.push_sprite(big_face, (50 + player_number * 300) as u32, 400, 175, 175);
-                        }
-                        player.sprite_id = sprite_stack_drawer.push_sprite(sprite_id, (50 + player_number * 300) as u32, 200, w * 2, h * 2);
-                    }
-                }
+            if let Some(big_face) = player.big_face {
+                let (w, h) = texture_atlas.dimensions(big_face).unwrap_or_default();
+                let mut face = graphics::sprites::SpriteInstance::new(big_face, ((50 + player_number * 300) as f32, 400.));
+                face.scale = (BIG_FACE_SIZE / w.max(1) as f32, BIG_FACE_SIZE / h.max(1) as f32);
+                sprite_stack_drawer.push_sprite(face);
             }
+            player.displayed_image = player.current_image();
+            let instance = player.current_sprite_instance(camera);
+            player.sprite_id = sprite_stack_drawer.push_sprite(instance.clone());
+            player.displayed_sprite = Some(instance);
         }
+        let hud_sprite_ids = self.hud_instances(&hud_sprites, &texture_atlas)
+            .into_iter()
+            .map(|instance| sprite_stack_drawer.push_sprite(instance))
+            .collect();
         self.loaded_data = Some(FightData {
             texture_atlas,
+            palette_texture,
             sprite_stack: sprite_stack_drawer,
+            stage_sprites,
+            background_sprite_ids,
+            hud_sprites,
+            hud_sprite_ids,
+            fight_fx_sprites,
+            spark_sprite_ids: Vec::new(),
         });
+        Ok(())
+    }
+    /// Start a new match with the same rules
+    fn restart_match(&mut self) {
+        self.match_flow = round::MatchFlow::new(self.match_flow.rules().clone());
+        if let Some(lifebar_data) = self.lifebar.as_mut() {
+            lifebar_data.hud.clear_wins();
+        }
+    }
+    /// Sprites drawing the lifebar
+    fn hud_instances(&self, hud_sprites: &HudSprites, texture_atlas: &graphics::sprites::SpriteTextureAtlas) -> Vec<graphics::sprites::SpriteInstance> {
+        let Some(lifebar_data) = self.lifebar.as_ref() else {
+            return Vec::new();
+        };
+        let draws = lifebar_data.hud.layout(lifebar_data.lifebar.def(), &player_huds(&self.players, &self.characters), self.match_flow.round(), self.match_flow.timer_count());
+        draws.iter().flat_map(|draw| hud_draw_instances(draw, hud_sprites, &lifebar_data.fonts, &self.players, texture_atlas)).collect()
+    }
+    /// Run the game ticks for the time elapsed since the last update
+    fn run_ticks(&mut self) {
+        let now = Instant::now();
+        if let Some(last_update) = self.last_update {
+            self.tick_time_accumulator += now - last_update;
+        }
+        self.last_update = Some(now);
+        let mut tick_count = 0;
+        while self.tick_time_accumulator >= self.tick_duration {
+            if tick_count >= MAX_TICKS_PER_UPDATE {
+                self.tick_time_accumulator = Duration::ZERO;
+                break;
+            }
+            self.tick_time_accumulator -= self.tick_duration;
+            self.tick();
+            tick_count += 1;
+        }
+    }
+    fn tick(&mut self) {
+        let fighters = self.players.each_ref().map(|player| round::FighterStatus {
+            life: player.state.life,
+            life_max: player.state.life_max,
+            in_intro: INTRO_STATES.contains(&player.state.state_number),
+        });
+        for event in self.match_flow.tick(&fighters) {
+            self.apply_round_event(event);
+        }
+        if self.match_flow.players_tick() {
+            self.tick_players();
+        }
+        if let Some(lifebar_data) = self.lifebar.as_mut() {
+            lifebar_data.hud.tick(lifebar_data.lifebar.def(), &player_huds(&self.players, &self.characters));
+        }
+        if let Some(stage_data) = self.stage.as_mut() {
+            stage_data.background.tick(stage_data.stage.def(), stage_data.stage.animations());
+        }
+        for spark in self.sparks.iter_mut() {
+            spark.animator.tick();
+        }
+        // the sparks are removed at the end of their animation
+        self.sparks.retain(|spark| !spark.animator.has_looped());
+        self.move_camera();
+        self.play_sounds();
+        if let Err(err) = self.mixer.tick(self.audio_output.as_mut()) {
+            log::error!("Failed to write the sounds to the audio output: {err}");
+        }
+        self.game_time += 1;
+    }
+    /// Run one tick of the state machines of the players, then resolve their attacks
+    fn tick_players(&mut self) {
+        for player_index in 0..self.players.len() {
+            let [first_player, second_player] = &mut self.players;
+            let (player, opponent) = match player_index {
+                0 => (first_player, &*second_player),
+                _ => (second_player, &*first_player),
+            };
+            let chara_data = &self.characters[player.character_id];
+            if let Some(command_recognizer) = player.command_recognizer.as_mut() {
+                command_recognizer.tick(&chara_data.commands, &player.input_state, player.state.facing, player.state.can_turn());
+            }
+            let opponent_data = &self.characters[opponent.character_id];
+            let environment = state::TriggerEnvironment {
+                commands: player.command_recognizer.as_ref().map(command::CommandRecognizer::active_commands).unwrap_or_default(),
+                constants: &chara_data.constants,
+                info: player_info(player_index, player, &self.characters),
+                opponent: Some((&opponent.state, &opponent_data.animations, &opponent_data.constants, player_info(1 - player_index, opponent, &self.characters))),
+                round: self.match_flow.round_info(player_index),
+                game_time: self.game_time,
+                screen: trigger_screen(&self.camera),
+                random: &self.random,
+            };
+            player.state_machine.tick(&mut player.state, &chara_data.states, &chara_data.animations, &environment);
+            if self.match_flow.state() != round::RoundState::Fight {
+                // the players only get control during the fight
+                player.state.ctrl = false;
+            }
+        }
+        self.resolve_hits();
     }
-    fn wheel_selection(current: usize, max: usize, move_by: isize) -> usize {
-        if move_by > 0 {
-            (current + move_by.abs() as usize) % max
+    /// Apply a change of the match to the players
+    fn apply_round_event(&mut self, event: round::RoundEvent) {
+        match event {
+            round::RoundEvent::StartRound(round_number) => {
+                log::debug!("Round {round_number}");
+                for player in self.players.iter_mut() {
+                    player.reset(&self.characters[player.character_id]);
+                    player.state.ctrl = false;
+                }
+                self.sparks.clear();
+                if let Some(stage_data) = self.stage.as_mut() {
+                    if round_number == 1 || stage_data.stage.def().stage_info.reset_bg {
+                        stage_data.background = stage::StageBackground::new(stage_data.stage.def(), stage_data.stage.animations());
+                    }
+                    if round_number == 1 {
+                        self.mixer.stop_category(audio::SoundCategory::Music);
+                        if let Some(music) = stage_data.music.clone() {
+                            self.mixer.play(music, audio::PlayParameters {
+                                owner: MUSIC_OWNER,
+                                volume: stage_data.stage.def().music.volume(),
+                                looping: true,
+                                category: audio::SoundCategory::Music,
+                                ..Default::default()
+                            });
+                        }
+                    }
+                }
+                let bounds = self.stage.as_ref().map(|stage_data| stage_data.bounds.clone()).unwrap_or_default();
+                self.camera = stage::FightCamera::new(&bounds.camera, CAMERA_SCREEN_SIZE);
+                if let Some(lifebar_data) = self.lifebar.as_mut() {
+                    lifebar_data.hud.reset_gauges(&player_huds(&self.players, &self.characters));
+                }
+            },
+            round::RoundEvent::StartIntro => {
+                for player_index in 0..self.players.len() {
+                    self.enter_state(player_index, &[INTRO_STATE]);
+                }
+            },
+            round::RoundEvent::Announce(announcement) => {
+                log::debug!("{announcement:?}");
+                if let Some(lifebar_data) = self.lifebar.as_mut() {
+                    lifebar_data.hud.announce(announcement, lifebar_data.lifebar.def());
+                }
+            },
+            round::RoundEvent::StartFight => {
+                for player in self.players.iter_mut() {
+                    player.state.ctrl = true;
+                }
+            },
+            round::RoundEvent::Poses(result) => {
+                if let (Some(winner), Some(lifebar_data)) = (result.winner, self.lifebar.as_mut()) {
+                    let kind = match result.end {
+                        round::RoundEnd::TimeOver => lifebar::WinKind::Time,
+                        round::RoundEnd::KO | round::RoundEnd::DoubleKO => lifebar::WinKind::Normal,
+                    };
+                    let winner_state = &self.players[winner].state;
+                    lifebar_data.hud.add_win(winner, kind, winner_state.life >= winner_state.life_max);
+                }
+                for player_index in 0..self.players.len() {
+                    match result.winner {
+                        Some(winner) if winner == player_index => {
+                            let input_state = &self.players[player_index].input_state;
+                            let held = [input_state.a, input_state.b, input_state.c].map(|button| button == input::ButtonState::Down);
+                            let alternative = held.iter().position(|&held| held).map(|index| ALTERNATIVE_WIN_POSE_STATES[index]);
+                            self.enter_state(player_index, &[alternative.unwrap_or(WIN_POSE_STATE), WIN_POSE_STATE]);
+                        },
+                        Some(_) if result.end == round::RoundEnd::TimeOver => self.enter_state(player_index, &[TIME_OVER_LOSE_STATE]),
+                        None if result.end == round::RoundEnd::TimeOver => self.enter_state(player_index, &[TIME_OVER_DRAW_STATE]),
+                        _ => (),
+                    }
+                }
+            },
+            round::RoundEvent::MatchOver(winner) => {
+                match winner {
+                    Some(winner) => log::info!("Player {0} wins the match", winner + 1),
+                    None => log::info!("Draw game"),
+                }
+                self.restart_match();
+            },
         }
-        else if move_by < 0 {
-            (current as isize - move_by.abs() + max as isize) as usize % max
+    }
+    /// Put a player in the first of some states that its character has
+    fn enter_state(&mut self, player_index: usize, state_numbers: &[i32]) {
+        let opponent_x = self.players[1 - player_index].state.position.0;
+        let player = &mut self.players[player_index];
+        let chara_data = &self.characters[player.character_id];
+        if let Some(&state_number) = state_numbers.iter().find(|n| chara_data.states.contains_key(n)) {
+            player.state_machine.enter_state(&mut player.state, state_number, &chara_data.states, &chara_data.animations, Some(opponent_x));
         }
-        else {
-            current
+    }
+    /// Resolve the attacks of the players and put them in the states requested by the hits
+    fn resolve_hits(&mut self) {
+        let holding_back = self.players.each_ref().map(|player| {
+            player.command_recognizer.as_ref().map(|recognizer| recognizer.active_commands().iter().any(|command| command == "holdback")).unwrap_or(false)
+        });
+        let [first_player, second_player] = &mut self.players;
+        let results = combat::resolve_hits([&mut first_player.state, &mut second_player.state], holding_back);
+        for result in results {
+            let attacker = &self.players[result.attacker];
+            let attacker_animations = &self.characters[attacker.character_id].animations;
+            let spark_reference = result.spark.unwrap_or(state::SparkReference {
+                own: false,
+                number: self.characters[attacker.character_id].constants.spark_number(result.guarded),
+            });
+            let spark = new_spark(spark_reference, result.attacker, attacker_animations, self.fight_fx.as_ref().map(|fight_fx| &fight_fx.animations), result.spark_position, attacker.state.facing_sign());
+            self.sparks.extend(spark);
+            if let Some(sound) = result.sound {
+                self.players[result.attacker].state.sound_commands.push(state::SoundCommand::Play {
+                    sound,
+                    channel: None,
+                    low_priority: false,
+                    volume: 1.,
+                    frequency_multiplier: 1.,
+                    looping: false,
+                    pan: state::SoundPan::Relative(0.),
+                });
+            }
+            let state_changes = [(result.attacker, result.attacker_state), (result.defender, Some(result.defender_state))];
+            for (player_index, state_number) in state_changes {
+                if let Some(state_number) = state_number {
+                    self.enter_state(player_index, &[state_number]);
+                }
+            }
         }
     }
-    fn change_animation(&mut self, by: isize) {
-        let player = &mut self.players[0];
-        let animation_count = self.characters[player.character_id].animations.len();
-        player.current_animation = Self::wheel_selection(player.current_animation, animation_count, by);
-        self.unload();
+    /// Move the camera to follow the players, then keep the players apart and within the stage and the screen
+    fn move_camera(&mut self) {
+        let bounds = self.stage.as_ref().map(|stage_data| stage_data.bounds.clone()).unwrap_or_default();
+        self.camera.tick();
+        for player in self.players.iter_mut() {
+            if let Some(env_shake) = player.state.env_shake.take() {
+                self.camera.shake(env_shake);
+            }
+        }
+        let targets = self.players.each_ref().map(|player| stage::CameraTarget {
+            position: player.state.position,
+            follow: player.state.camera_follow,
+        });
+        self.camera.follow(&bounds.camera, &targets);
+        for player in self.players.iter_mut() {
+            player.state.position.0 = self.camera.bound_player_x(player.state.position.0, player.state.screen_bound, &bounds.bound, &bounds.player_info);
+        }
+        // the players on the ground push each other, without leaving the screen
+        if self.players.iter().all(|player| player.state.position.1 >= 0.) {
+            let widths = self.players.each_ref().map(|player| {
+                let constants = &self.characters[player.character_id].constants;
+                match player.state.facing {
+                    state::Facing::Right => (constants.ground_back(), constants.ground_front()),
+                    state::Facing::Left => (constants.ground_front(), constants.ground_back()),
+                }
+            });
+            let limits = self.players.each_ref().map(|player| {
+                let x_limit = |x| self.camera.bound_player_x(x, player.state.screen_bound, &bounds.bound, &bounds.player_info);
+                (x_limit(f32::NEG_INFINITY), x_limit(f32::INFINITY))
+            });
+            let positions = stage::push_players(self.players.each_ref().map(|player| player.state.position.0), widths, limits);
+            for (player, x) in self.players.iter_mut().zip(positions) {
+                player.state.position.0 = x;
+            }
+        }
     }
-    fn change_character(&mut self, by: isize) {
-        let character_count = self.characters.len();
-        let player = &mut self.players[0];
-        player.current_animation = 0;
-        player.character_id = Self::wheel_selection(player.character_id, character_count, by);
-        self.unload();
+    /// Send the sound actions requested by the players to the mixer, each player using its own channels
+    fn play_sounds(&mut self) {
+        let camera_x = self.camera.position().0;
+        for (player_index, player) in self.players.iter_mut().enumerate() {
+            let sound_commands = std::mem::take(&mut player.state.sound_commands);
+            let stage_pan = |pan| {
+                let x = match pan {
+                    state::SoundPan::Relative(offset) => player.state.position.0 + offset * player.state.facing_sign(),
+                    state::SoundPan::Absolute(x) => x,
+                };
+                (x - camera_x) / SOUND_PAN_DISTANCE
+            };
+            for sound_command in sound_commands {
+                match sound_command {
+                    state::SoundCommand::Play { sound, channel, low_priority, volume, frequency_multiplier, looping, pan } => {
+                        let sound_bank = match sound.own {
+                            true => &mut self.characters[player.character_id].sounds,
+                            false => &mut self.common_sounds,
+                        };
+                        let Some(decoded_sound) = sound_bank.sound(sound.group, sound.number) else {
+                            continue;
+                        };
+                        self.mixer.play(decoded_sound, audio::PlayParameters {
+                            owner: player_index,
+                            channel,
+                            low_priority,
+                            volume,
+                            pan: stage_pan(pan),
+                            frequency_multiplier,
+                            looping,
+                            category: audio::SoundCategory::Effect,
+                        });
+                    },
+                    state::SoundCommand::Stop { channel } => self.mixer.stop(player_index, channel),
+                    state::SoundCommand::Pan { channel, pan } => self.mixer.set_pan(player_index, channel, stage_pan(pan)),
+                }
+            }
+        }
     }
 }
 
 impl Scene for Fight {    
     fn load(&mut self, graphics_state: &graphics::State, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+        let mut common_file_readers: Vec<Box<dyn FileReader>> = config.common_paths()
+            .into_iter()
+            .map(|common_path| Box::new(FileReaderFs::new(common_path)) as Box<dyn FileReader>)
+            .collect();
+        self.common_sounds = read_common_sounds(&mut common_file_readers);
+        self.fight_fx = read_fight_fx(&mut common_file_readers);
+        self.stage = config.stage_paths()
+            .iter()
+            .flat_map(|stage_path| stage::read_directory_stages(stage_path))
+            .find_map(read_stage_data);
+        let bounds = self.stage.as_ref().map(|stage_data| stage_data.bounds.clone()).unwrap_or_default();
+        self.camera = stage::FightCamera::new(&bounds.camera, CAMERA_SCREEN_SIZE);
+        if self.stage.is_none() {
+            log::debug!("No stage found in the stage directories");
+        }
+        self.lifebar = config.common_paths().into_iter().find_map(read_lifebar_data);
+        let rules = match self.lifebar.as_ref() {
+            Some(lifebar_data) => {
+                let lifebar_def = lifebar_data.lifebar.def();
+                let mut rules = lifebar_def.round.match_rules(round::MatchRules::default());
+                if let Some(frames_per_count) = lifebar_def.time.frames_per_count {
+                    rules.frames_per_count = frames_per_count;
+                }
+                rules
+            },
+            None => {
+                log::debug!("No lifebar found in the common directories");
+                round::MatchRules::default()
+            },
+        };
+        self.match_flow = round::MatchFlow::new(config.match_rules(rules));
         let characters_iterator = config.data_paths()
             .iter()
             .flat_map(|data_path| { crate::game::mugen::character::directory_reader::read_directory_characters(data_path) })
@@ -180,21 +836,35 @@ impl Scene for Fight {
                     }
                 };
                 
-                let animations : Vec<_> = {
-                    // first use a btreemap for the animations to be in order
-                    character
-                        .read_animations()
-                        .into_iter()
-                        .collect::<BTreeMap<u32, air::Animation>>()
-                        .into_iter()
-                        .map(|(_, animation)| { animation })
-                        .collect()
+                let states = match character.read_states(&mut common_file_readers) {
+                    Ok(cns_states) => {
+                        // the states of the CMD file come first
+                        let mut states = commands.states.clone();
+                        state::add_states(&mut states, cns_states);
+                        states
+                    },
+                    Err(err) => {
+                        log::error!("Error loading state data for {0}: {1}", character.name(), err);
+                        None?
+                    }
                 };
+                let constants = character.read_constants().unwrap_or_else(|err| {
+                    log::error!("Error loading constants for {0}: {1}", character.name(), err);
+                    Constants::default()
+                });
+                let sounds = character.read_sounds().unwrap_or_else(|err| {
+                    log::error!("Error loading sound data for {0}: {1}", character.name(), err);
+                    nugem_snd::SoundFile::default()
+                });
+                let animations = character.read_animations();
                 Some(CharaData {
-                    _character: character,
+                    character,
                     sff_data,
                     animations,
-                    _commands: commands,
+                    commands,
+                    states,
+                    constants,
+                    sounds: audio::SoundBank::new(sounds),
                 })
             })
             ;
@@ -203,7 +873,12 @@ impl Scene for Fight {
         if self.characters.is_empty() {
             Err("No characters found in data directories.")?
         }
-        self.load(graphics_state);
+        for player in self.players.iter_mut() {
+            player.character_id %= self.characters.len();
+            player.reset(&self.characters[player.character_id]);
+        }
+        self.restart_match();
+        self.load(graphics_state)?;
         Ok(())
     }
 
@@ -212,21 +887,17 @@ impl Scene for Fight {
         if input_event.partial_state.back == Some(input::ButtonState::Down) {
             return Some(events::Event::Quit);
         }
-        if let Some(motion) = input_event.partial_state.directional {
-            match motion {
-                DirectionalMotion::Vertical(DirectionState::Plus) | DirectionalMotion::FullDirection(Directional::Up) => self.change_character(1),
-                DirectionalMotion::Vertical(DirectionState::Minus) | DirectionalMotion::FullDirection(Directional::Down) => self.change_character(-1),
-                DirectionalMotion::Horizontal(DirectionState::Plus) | DirectionalMotion::FullDirection(Directional::Forward) => self.change_animation(1),
-                DirectionalMotion::Horizontal(DirectionState::Minus) | DirectionalMotion::FullDirection(Directional::Backward) => self.change_animation(-1),
-                _ => (),
-            }
-        }
+        self.players[0].input_state = input_event.device.state().clone();
         None
     }
 
     fn update(&mut self, graphics_state: &graphics::State, _: &Config, _: events::EventLoopSender) -> bool {
+        self.run_ticks();
         if !self.loaded() {
-            self.load(graphics_state);
+            if let Err(err) = self.load(graphics_state) {
+                log::error!("Failed to load the sprites of the fight: {err}");
+                return false;
+            }
         }
         true
     }
@@ -234,25 +905,49 @@ impl Scene for Fight {
     fn display(&mut self, graphics_state: &graphics::State) {
         if let Ok(output) = graphics_state.surface().get_current_texture() {
             let surface_texture_view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let hud_instances = self.loaded_data.as_ref()
+                .map(|loaded_data| self.hud_instances(&loaded_data.hud_sprites, &loaded_data.texture_atlas))
+                .unwrap_or_default();
             if let Some(loaded_data) = self.loaded_data.as_mut() {
+                let camera = self.camera.view_position();
                 for i in 0..self.players.len() {
                     let player = &mut self.players[i];
-                    if let Some(animator) = player.animator.as_mut() {
-                        if animator.tick() {
-                            if let Some((group, image)) = animator.current_display_info() {
-                                // change frame
-                                let key = ImageKey {
-                                    group,
-                                    image,
-                                };
-                                if let Some(&sprite_id) = player.image_keys.get(&key) {
-                                    let (w, h) = loaded_data.texture_atlas.dimensions(sprite_id).unwrap();
-                                    loaded_data.sprite_stack.update_sprite(player.sprite_id, sprite_id, (50 + i * 300) as u32, 200, w * 2, h * 2);
-                                }
-                            }
+                    let instance = player.current_sprite_instance(camera);
+                    if player.displayed_sprite.as_ref() != Some(&instance) {
+                        // change frame, animation, move or transform
+                        loaded_data.sprite_stack.update_sprite(player.sprite_id, instance.clone());
+                        player.displayed_sprite = Some(instance);
+                    }
+                    player.displayed_image = player.current_image();
+                    let sff_data = &self.characters[player.character_id].sff_data;
+                    let palette_index = player.shown_palette(sff_data);
+                    if player.displayed_palette != Some(palette_index) {
+                        // only the 256 colors of the palette of the player change, after a RemapPal for example
+                        let palette = sff_data.palette_colors(palette_index).unwrap_or_default();
+                        loaded_data.palette_texture.set_palette(graphics_state.queue(), i as u32, &palette);
+                        player.displayed_palette = Some(palette_index);
+                    }
+                }
+                if let Some(stage_data) = self.stage.as_ref() {
+                    for (element_index, sprite_ids) in loaded_data.background_sprite_ids.iter().enumerate() {
+                        // the number of tiles is set when loading the stage, the sprites of missing tiles being hidden
+                        let mut instances = background_instances(stage_data, element_index, camera, &loaded_data.stage_sprites, &loaded_data.texture_atlas).into_iter();
+                        for &sprite_id in sprite_ids.iter() {
+                            loaded_data.sprite_stack.update_sprite(sprite_id, instances.next().unwrap_or_else(hidden_sprite));
                         }
                     }
                 }
+                let spark_instances: Vec<graphics::sprites::SpriteInstance> = self.sparks.iter()
+                    .filter_map(|spark| {
+                        let sprites = match spark.owner {
+                            Some(player_index) => &self.players[player_index].image_keys,
+                            None => &loaded_data.fight_fx_sprites,
+                        };
+                        spark_instance(spark, sprites, camera)
+                    })
+                    .collect();
+                update_sprite_pool(&mut loaded_data.sprite_stack, &mut loaded_data.spark_sprite_ids, spark_instances);
+                update_sprite_pool(&mut loaded_data.sprite_stack, &mut loaded_data.hud_sprite_ids, hud_instances);
                 loaded_data.sprite_stack.apply_changes(&loaded_data.texture_atlas, graphics_state.device(), graphics_state.queue());
                 loaded_data.sprite_stack.render(&surface_texture_view, graphics_state.device(), graphics_state.queue());
             }
@@ -260,3 +955,682 @@ impl Scene for Fight {
         }
     }
 }
+
+/// Rows of the palette texture for the sprites with their own palette, the sprites with the same colors sharing a row
+#[derive(Default)]
+struct SpritePalettes {
+    rows: HashMap<Vec<BitmapPixel>, u32>,
+}
+
+impl SpritePalettes {
+    fn row(&mut self, palette: Vec<BitmapPixel>) -> u32 {
+        let next_row = PLAYER_PALETTE_ROWS + self.rows.len() as u32;
+        *self.rows.entry(palette).or_insert(next_row)
+    }
+}
+
+/// Index of the palette of a sprite file with the given group and item numbers, the palettes 1,1 to 1,12 of SFF v1 files
+/// being the palette files of the character
+fn palette_number_index(sff_data: &nugem_sff::SpriteFile, group: i32, item: i32) -> Option<usize> {
+    match sff_data {
+        nugem_sff::SpriteFile::V1(_) => (group == 1 && item >= 1)
+            .then(|| item as usize - 1)
+            .filter(|&index| index < sff_data.palette_count()),
+        nugem_sff::SpriteFile::V2(_) => sff_data.palette_index(u16::try_from(group).ok()?, u16::try_from(item).ok()?),
+    }
+}
+
+/// Add a sprite of a player to the texture atlas.
+///
+/// The palette indices of the sprite are drawn with the palette of the player, or with a palette of their own if the
+/// sprite does not use the selected palette. The sprites without palette indices, like true color PNG sprites, are added
+/// with their colors.
+fn add_atlas_sprite(
+    sprite_atlas_builder: &mut graphics::sprites::SpriteTextureAtlasBuilder,
+    sprite_palettes: &mut SpritePalettes,
+    sff_data: &nugem_sff::SpriteFile,
+    (group_index, image_index): (u16, u16),
+    player_number: u32,
+    palette_index: usize,
+) -> Result<LoadedSprite, nugem_sff::RenderingError<graphics::surface::RendererError>> {
+    let indexed = sff_data.render_sprite_indexed::<IndexedSurfaceRenderer>((), group_index, image_index);
+    let palette_row = match sff_data.uses_selected_palette(group_index, image_index) {
+        true => Some(player_number),
+        false => sff_data.sprite_palette(group_index, image_index, palette_index).map(|palette| sprite_palettes.row(palette)),
+    };
+    match (indexed, palette_row) {
+        (Ok(rendered), Some(palette_row)) => {
+            let id = sprite_atlas_builder.add_indexed_surface(rendered.surface.take(), palette_row);
+            Ok(LoadedSprite { id, axis: rendered.axis })
+        },
+        _ => {
+            let rendered = sff_data.render_sprite::<BitmapSurfaceRenderer>((), group_index, image_index, palette_index)?;
+            let id = sprite_atlas_builder.add_surface(rendered.surface.take());
+            Ok(LoadedSprite { id, axis: rendered.axis })
+        },
+    }
+}
+
+/// Sprite of a player on the screen, drawn with the transforms of an animation frame.
+///
+/// The axis of the sprite is drawn at the position of the player, moved by the offset of the animation frame towards the
+/// front of the player. The sprite is mirrored when the player faces left, and when the frame is flipped.
+fn sprite_instance(sprite: &LoadedSprite, position: (f32, f32), facing_sign: f32, frame: &air::AnimationFrame) -> graphics::sprites::SpriteInstance {
+    let axis_x = position.0 + frame.offset.0 as f32 * facing_sign;
+    let axis_y = position.1 + frame.offset.1 as f32;
+    let facing_left = facing_sign < 0.;
+    graphics::sprites::SpriteInstance {
+        atlas_index: sprite.id,
+        position: (SCREEN_DIMENSIONS.0 as f32 / 2. + axis_x * DISPLAY_SCALE, GROUND_SCREEN_Y + axis_y * DISPLAY_SCALE),
+        axis: (sprite.axis.0 as f32, sprite.axis.1 as f32),
+        flip: (frame.flip.0 != facing_left, frame.flip.1),
+        scale: (frame.scale.0 * DISPLAY_SCALE, frame.scale.1 * DISPLAY_SCALE),
+        // mirroring the sprite mirrors its rotation
+        angle: frame.angle * facing_sign,
+        priority: 0,
+        blend: sprite_blend(frame.blend),
+        color_effect: graphics::sprites::ColorEffect::IDENTITY,
+        crop: graphics::sprites::SpriteInstance::UNCROPPED,
+    }
+}
+
+/// Blending of the sprites drawn with a blend mode of an animation frame or of a Trans controller
+fn sprite_blend(blend: air::BlendMode) -> graphics::sprites::SpriteBlend {
+    match blend {
+        air::BlendMode::Normal => graphics::sprites::SpriteBlend::Normal,
+        air::BlendMode::Add { source, destination } => graphics::sprites::SpriteBlend::Add {
+            source: source as f32 / 256.,
+            destination: destination as f32 / 256.,
+        },
+        air::BlendMode::Subtract => graphics::sprites::SpriteBlend::Subtract,
+    }
+}
+
+/// Color effect of the sprites at the current tick of a PalFX controller
+fn color_effect(pal_fx: &state::PalFx) -> graphics::sprites::ColorEffect {
+    graphics::sprites::ColorEffect {
+        add: pal_fx.current_add().map(|add| add as f32 / 255.),
+        multiply: pal_fx.mul.map(|mul| mul as f32 / 256.),
+        color: pal_fx.color as f32 / 256.,
+        invert: pal_fx.invert_all,
+    }
+}
+
+/// States of the players displayed by the lifebar. The combo of a player is the hit count of the opponent, until the
+/// opponent recovers.
+fn player_huds<'a>(players: &[Player; 2], characters: &'a [CharaData]) -> [lifebar::PlayerHud<'a>; 2] {
+    let [first_player, second_player] = players;
+    [(first_player, second_player), (second_player, first_player)].map(|(player, opponent)| lifebar::PlayerHud {
+        life: player.state.life,
+        life_max: player.state.life_max,
+        power: player.state.power,
+        power_max: player.state.power_max,
+        name: characters[player.character_id].character.display_name(),
+        combo: match opponent.state.move_type {
+            state::MoveType::BeingHit => opponent.state.get_hit.hit_count,
+            _ => 0,
+        },
+    })
+}
+
+/// Read the lifebar of a common folder, with its sprites
+fn read_lifebar_data(common_path: std::path::PathBuf) -> Option<LifebarData> {
+    let file_reader = Box::new(FileReaderFs::new(common_path));
+    let mut lifebar = lifebar::Lifebar::open(Path::new(lifebar::LIFEBAR_DEF_FILE), file_reader).ok()?;
+    let sff_data = match lifebar.read_sprites() {
+        Ok(sff_data) => sff_data,
+        Err(err) => {
+            log::error!("Error loading sprite data for the lifebar: {err}");
+            None?
+        }
+    };
+    let font_numbers: Vec<u32> = lifebar.def().files.fonts.keys().copied().collect();
+    let mut fonts = BTreeMap::new();
+    for font_number in font_numbers {
+        match lifebar.read_font(font_number) {
+            Some(Ok(font)) => {
+                fonts.insert(font_number, font);
+            },
+            Some(Err(err)) => log::error!("Error loading the font {font_number} of the lifebar: {err}"),
+            None => (),
+        }
+    }
+    let hud = lifebar::Hud::new(lifebar.animations());
+    Some(LifebarData {
+        lifebar,
+        sff_data,
+        fonts,
+        hud,
+    })
+}
+
+/// Sprites of the lifebar: the sprites of its elements and the frames of its actions
+fn lifebar_image_keys(lifebar: &lifebar::Lifebar) -> Vec<ImageKey> {
+    let element_sprites = lifebar.def().elements().into_iter().filter_map(|element| element.sprite);
+    let frame_sprites = lifebar.animations().values().flat_map(air::Animation::frames).map(|frame| (frame.group, frame.image));
+    let mut keys: Vec<ImageKey> = element_sprites.chain(frame_sprites).map(|(group, image)| ImageKey { group, image }).collect();
+    keys.sort_by_key(|key| (key.group, key.image));
+    keys.dedup();
+    keys
+}
+
+/// Fonts and color banks of the texts of the lifebar
+fn lifebar_font_banks(lifebar: &lifebar::Lifebar) -> Vec<(u32, i32)> {
+    let mut font_banks: Vec<(u32, i32)> = lifebar.def().elements().into_iter()
+        .filter_map(|element| element.font)
+        .map(|font| (font.font, font.bank))
+        .collect();
+    font_banks.sort();
+    font_banks.dedup();
+    font_banks
+}
+
+/// Sprites drawing a sprite or a text of the lifebar, scaled from the lifebar screen. The faces of the players are
+/// their small portraits.
+fn hud_draw_instances(
+    draw: &lifebar::HudDraw,
+    hud_sprites: &HudSprites,
+    fonts: &BTreeMap<u32, font::Font>,
+    players: &[Player; 2],
+    texture_atlas: &graphics::sprites::SpriteTextureAtlas,
+) -> Vec<graphics::sprites::SpriteInstance> {
+    match draw {
+        lifebar::HudDraw::Sprite { .. } => hud_sprite_instance(draw, &hud_sprites.images, players, texture_atlas).into_iter().collect(),
+        lifebar::HudDraw::Text { font, text, position } => {
+            let Some(text_font) = fonts.get(&font.font) else {
+                return Vec::new();
+            };
+            text_font.layout(text, *position, font.alignment).into_iter()
+                .filter_map(|quad| {
+                    let glyph = hud_sprites.glyphs.get(&GlyphKey { font: font.font, bank: font.bank, character: quad.character })?;
+                    Some(graphics::sprites::SpriteInstance {
+                        scale: (HUD_SCALE, HUD_SCALE),
+                        priority: HUD_PRIORITY,
+                        ..graphics::sprites::SpriteInstance::new(glyph.id, (quad.position.0 * HUD_SCALE, quad.position.1 * HUD_SCALE))
+                    })
+                })
+                .collect()
+        },
+    }
+}
+
+fn hud_sprite_instance(
+    draw: &lifebar::HudDraw,
+    hud_images: &HashMap<ImageKey, LoadedSprite>,
+    players: &[Player; 2],
+    texture_atlas: &graphics::sprites::SpriteTextureAtlas,
+) -> Option<graphics::sprites::SpriteInstance> {
+    match *draw {
+        lifebar::HudDraw::Sprite { image, position, flip, scale, visible_x } => {
+            let sprite = match image {
+                lifebar::HudImage::Lifebar(group, image) => hud_images.get(&ImageKey { group, image })?,
+                lifebar::HudImage::Character { player, group: 9000, image: 0 } => players[player].small_face.as_ref()?,
+                lifebar::HudImage::Character { .. } => None?,
+            };
+            let axis = (sprite.axis.0 as f32, sprite.axis.1 as f32);
+            let width = texture_atlas.dimensions(sprite.id).map_or(0, |(width, _)| width) as f32;
+            Some(graphics::sprites::SpriteInstance {
+                axis,
+                flip,
+                scale: (scale.0 * HUD_SCALE, scale.1 * HUD_SCALE),
+                priority: HUD_PRIORITY,
+                crop: match visible_x {
+                    Some(visible_x) => horizontal_crop(visible_x, position.0, axis.0, width, scale.0, flip.0),
+                    None => graphics::sprites::SpriteInstance::UNCROPPED,
+                },
+                ..graphics::sprites::SpriteInstance::new(sprite.id, (position.0 * HUD_SCALE, position.1 * HUD_SCALE))
+            })
+        },
+        lifebar::HudDraw::Text { .. } => None,
+    }
+}
+
+/// Crop of a sprite only visible in a horizontal range of the screen, from the position of its axis
+fn horizontal_crop((left, right): (f32, f32), position_x: f32, axis_x: f32, width: f32, scale_x: f32, flipped: bool) -> (f32, f32, f32, f32) {
+    if width <= 0. || scale_x == 0. {
+        return graphics::sprites::SpriteInstance::UNCROPPED;
+    }
+    let sign = if flipped { -1. } else { 1. };
+    // fraction of the width of the image drawn at a horizontal position
+    let fraction = |x: f32| (((x - position_x) * sign / scale_x + axis_x) / width).clamp(0., 1.);
+    let (left, right) = (fraction(left), fraction(right));
+    (left.min(right), 0., left.max(right), 1.)
+}
+
+/// Information about a player read by the triggers
+fn player_info<'a>(player_index: usize, player: &Player, characters: &'a [CharaData]) -> state::PlayerInfo<'a> {
+    let character = &characters[player.character_id].character;
+    state::PlayerInfo {
+        name: character.name(),
+        author_name: character.author_name(),
+        team_side: player_index as i32 + 1,
+        palette_number: player.palette_index as i32 + 1,
+    }
+}
+
+/// Top left corner and size of the screen in the coordinates of the players, read by the triggers
+fn trigger_screen(camera: &stage::FightCamera) -> ((f32, f32), (f32, f32)) {
+    let (left, _) = camera.screen_edges();
+    ((left, camera.position().1 - GROUND_SCREEN_Y / DISPLAY_SCALE), camera.screen_size())
+}
+
+/// Read the sprites of a stage and start its background
+fn read_stage_data(mut stage: stage::Stage) -> Option<StageData> {
+    let sff_data = match stage.read_sprites() {
+        Ok(sff_data) => sff_data,
+        Err(err) => {
+            log::error!("Error loading sprite data for stage {0}: {1}", stage.name(), err);
+            None?
+        }
+    };
+    let music = stage.read_music().unwrap_or_else(|err| {
+        log::error!("Error loading music for stage {0}: {1}", stage.name(), err);
+        None
+    });
+    let background = stage::StageBackground::new(stage.def(), stage.animations());
+    let bounds = FightBounds::new(stage.def());
+    Some(StageData {
+        stage,
+        sff_data,
+        background,
+        music: music.map(Arc::new),
+        bounds,
+    })
+}
+
+/// Sprites of the frames of all the animations
+fn animation_image_keys(animations: &air::Animations) -> Vec<ImageKey> {
+    let mut keys: Vec<ImageKey> = animations.values()
+        .flat_map(air::Animation::frames)
+        .map(|frame| ImageKey { group: frame.group, image: frame.image })
+        .collect();
+    keys.sort_by_key(|key| (key.group, key.image));
+    keys.dedup();
+    keys
+}
+
+/// Spark of a hit, with its animation from the animations of the attacker or from the common sparks
+fn new_spark(
+    spark: state::SparkReference,
+    attacker: usize,
+    attacker_animations: &air::Animations,
+    fight_fx_animations: Option<&air::Animations>,
+    position: (f32, f32),
+    facing_sign: f32,
+) -> Option<Spark> {
+    let (owner, animations) = match spark.own {
+        true => (Some(attacker), attacker_animations),
+        false => (None, fight_fx_animations?),
+    };
+    let animation = animations.get(&u32::try_from(spark.number).ok()?)?;
+    Some(Spark {
+        owner,
+        animator: air::Animator::new(animation.clone()),
+        position,
+        facing_sign,
+    })
+}
+
+/// Sprite of the current frame of a spark relative to the camera
+fn spark_instance(spark: &Spark, sprites: &HashMap<ImageKey, LoadedSprite>, camera: (f32, f32)) -> Option<graphics::sprites::SpriteInstance> {
+    let (group, image) = spark.animator.current_display_info()?;
+    let sprite = sprites.get(&ImageKey { group, image })?;
+    let position = (spark.position.0 - camera.0, spark.position.1 - camera.1);
+    let mut instance = sprite_instance(sprite, position, spark.facing_sign, spark.animator.current_frame()?);
+    instance.priority = SPARK_PRIORITY;
+    Some(instance)
+}
+
+/// Draw some sprites with sprites of the sprite stack, adding sprites to the stack when there are more sprites to draw
+/// and hiding the unused ones
+fn update_sprite_pool(sprite_stack: &mut graphics::sprites::SpriteStack, sprite_ids: &mut Vec<usize>, instances: Vec<graphics::sprites::SpriteInstance>) {
+    let instance_count = instances.len();
+    for (index, instance) in instances.into_iter().enumerate() {
+        match sprite_ids.get(index) {
+            Some(&sprite_id) => sprite_stack.update_sprite(sprite_id, instance),
+            None => sprite_ids.push(sprite_stack.push_sprite(instance)),
+        }
+    }
+    for &sprite_id in sprite_ids.iter().skip(instance_count) {
+        sprite_stack.update_sprite(sprite_id, hidden_sprite());
+    }
+}
+
+/// Sprite scaled to nothing, keeping its place in the sprite stack
+fn hidden_sprite() -> graphics::sprites::SpriteInstance {
+    graphics::sprites::SpriteInstance { scale: (0., 0.), ..graphics::sprites::SpriteInstance::new(0, (0., 0.)) }
+}
+
+/// Sprites of the background elements of a stage: the sprites of the elements and the frames of the actions of the stage
+fn stage_image_keys(stage: &stage::Stage) -> Vec<(ImageKey, bool)> {
+    let elements = &stage.def().elements;
+    let element_sprites = elements.iter()
+        .filter(|element| !matches!(element.kind, stage::BackgroundKind::Anim { .. }))
+        .map(|element| (element.sprite, element.mask));
+    // the Anim background controllers can change the action of an animated element to any action of the stage
+    let animation_masks: BTreeSet<bool> = elements.iter()
+        .filter(|element| matches!(element.kind, stage::BackgroundKind::Anim { .. }))
+        .map(|element| element.mask)
+        .collect();
+    let frame_sprites = stage.animations().values()
+        .flat_map(air::Animation::frames)
+        .flat_map(|frame| animation_masks.iter().map(move |&mask| ((frame.group, frame.image), mask)));
+    let mut keys: Vec<(ImageKey, bool)> = element_sprites.chain(frame_sprites).map(|((group, image), mask)| (ImageKey { group, image }, mask)).collect();
+    keys.sort_by_key(|(key, mask)| (key.group, key.image, *mask));
+    keys.dedup();
+    keys
+}
+
+/// Add a sprite of the stage to the texture atlas.
+///
+/// The stage sprites are drawn with their colors. The sprites without mask are drawn with the color 0 of their palette
+/// instead of transparent pixels, with a palette of their own.
+fn add_stage_sprite(
+    sprite_atlas_builder: &mut graphics::sprites::SpriteTextureAtlasBuilder,
+    sprite_palettes: &mut SpritePalettes,
+    sff_data: &nugem_sff::SpriteFile,
+    key: &ImageKey,
+    masked: bool,
+) -> Result<LoadedSprite, nugem_sff::RenderingError<graphics::surface::RendererError>> {
+    let mask_color = sff_data.sprite_mask_color(key.group, key.image, 0).filter(|_| !masked);
+    let palette = mask_color.and_then(|mask_color| {
+        let mut palette = sff_data.sprite_palette(key.group, key.image, 0)?;
+        palette[0] = mask_color;
+        Some(palette)
+    });
+    if let Some(palette) = palette {
+        if let Ok(rendered) = sff_data.render_sprite_indexed::<IndexedSurfaceRenderer>((), key.group, key.image) {
+            let id = sprite_atlas_builder.add_indexed_surface(rendered.surface.take(), sprite_palettes.row(palette));
+            return Ok(LoadedSprite { id, axis: rendered.axis });
+        }
+    }
+    let rendered = sff_data.render_sprite::<BitmapSurfaceRenderer>((), key.group, key.image, 0)?;
+    let id = sprite_atlas_builder.add_surface(rendered.surface.take());
+    Ok(LoadedSprite { id, axis: rendered.axis })
+}
+
+/// Sprites drawing the tiles of a background element, from its position for a camera position in the coordinates of the
+/// players.
+///
+/// The stage is scaled so that its ground is at the ground of the players. The parallax elements are drawn row by row,
+/// each row being scaled and moved with the camera between the scales of the top and bottom edges. The sprites are
+/// cropped to the window of the element, and the hidden elements are scaled to nothing so that their sprites stay in
+/// the stack. The number of sprites does not change with the camera, the unused ones being hidden.
+fn background_instances(
+    stage_data: &StageData,
+    element_index: usize,
+    camera: (f32, f32),
+    stage_sprites: &HashMap<(ImageKey, bool), LoadedSprite>,
+    texture_atlas: &graphics::sprites::SpriteTextureAtlas,
+) -> Vec<graphics::sprites::SpriteInstance> {
+    let stage_def = stage_data.stage.def();
+    let element = &stage_def.elements[element_index];
+    let element_state = &stage_data.background.elements()[element_index];
+    let sprite = element_state.sprite(element).and_then(|(group, image)| stage_sprites.get(&(ImageKey { group, image }, element.mask)));
+    let scale = DISPLAY_SCALE * LOCAL_SCREEN_WIDTH / stage_def.stage_info.local_coord.0.max(1) as f32;
+    let stage_camera = (camera.0 * DISPLAY_SCALE / scale, camera.1 * DISPLAY_SCALE / scale);
+    let (Some(sprite), Some(position)) = (sprite, stage_data.background.element_position(stage_def, element_index, stage_camera)) else {
+        return Vec::new();
+    };
+    let size = texture_atlas.dimensions(sprite.id).unwrap_or((0, 0));
+    let (width, height) = (size.0 as f32, size.1 as f32);
+    let half_screen_width = SCREEN_DIMENSIONS.0 as f32 / 2. / scale;
+    let visible_height = (GROUND_SCREEN_Y / scale, (SCREEN_DIMENSIONS.1 as f32 - GROUND_SCREEN_Y) / scale);
+    let axis = (sprite.axis.0 as f32, sprite.axis.1 as f32);
+    let ground = stage_def.stage_info.z_offset;
+    let drawn_scale = if element_state.drawn() { scale } else { 0. };
+    let tile_instance = |x: f32, y: f32| graphics::sprites::SpriteInstance {
+        priority: if element.layer > 0 { FRONT_LAYER_PRIORITY } else { BACK_LAYER_PRIORITY },
+        blend: sprite_blend(element.trans),
+        ..graphics::sprites::SpriteInstance::new(sprite.id, (SCREEN_DIMENSIONS.0 as f32 / 2. + x * scale, GROUND_SCREEN_Y + y * scale))
+    };
+    let (mut instances, capacity) = match element.kind {
+        stage::BackgroundKind::Parallax { x_scale, width: edge_widths, y_scale_start, y_scale_delta } => {
+            let edge_scales = match edge_widths {
+                Some((top, bottom)) if width > 0. => (top / width, bottom / width),
+                _ => x_scale,
+            };
+            // the vertical scale is in percent, and changes with the camera moving up
+            let y_scale = ((y_scale_start - stage_camera.1 * y_scale_delta) / 100.).max(0.);
+            let top = position.1 - axis.1 * y_scale - ground;
+            // the parallax elements are not repeated infinitely on the vertical axis
+            let y_positions: Vec<f32> = (0..element.tile.1.max(1)).map(|index| top + index as f32 * (height + element.tile_spacing.1) * y_scale).collect();
+            let mut instances = Vec::new();
+            let mut capacity = 0;
+            for row in 0..size.1 {
+                let (row_scale, row_x) = parallax_row(edge_scales, row, size.1, (position.0, axis.0), stage_camera.0 * element.delta.0);
+                let spacing = element.tile_spacing.0 * row_scale;
+                let x_positions = stage::tile_positions(element.tile.0, spacing, row_x, width * row_scale, (-half_screen_width, half_screen_width));
+                capacity += tile_capacity(element.tile.0, width * row_scale + spacing, 2. * half_screen_width) * y_positions.len();
+                for &y in y_positions.iter() {
+                    instances.extend(x_positions.iter().map(|&x| graphics::sprites::SpriteInstance {
+                        scale: (row_scale * drawn_scale, y_scale * drawn_scale),
+                        crop: (0., row as f32 / height, 1., (row + 1) as f32 / height),
+                        ..tile_instance(x, y)
+                    }));
+                }
+            }
+            (instances, capacity)
+        },
+        _ => {
+            // the tiles are placed by the top left corner of their sprite
+            let x_positions = stage::tile_positions(element.tile.0, element.tile_spacing.0, position.0 - axis.0, width, (-half_screen_width, half_screen_width));
+            let y_positions = stage::tile_positions(element.tile.1, element.tile_spacing.1, position.1 - axis.1 - ground, height, (-visible_height.0, visible_height.1));
+            let capacity = tile_capacity(element.tile.0, width + element.tile_spacing.0, 2. * half_screen_width)
+                * tile_capacity(element.tile.1, height + element.tile_spacing.1, visible_height.0 + visible_height.1);
+            let instances = y_positions.iter()
+                .flat_map(|&y| x_positions.iter().map(move |&x| (x, y)))
+                .map(|(x, y)| graphics::sprites::SpriteInstance {
+                    axis,
+                    scale: (drawn_scale, drawn_scale),
+                    ..tile_instance(x + axis.0, y + axis.1)
+                })
+                .collect();
+            (instances, capacity)
+        },
+    };
+    if let Some([left, top, right, bottom]) = element.window {
+        // the window is fixed on the screen, in the coordinates of the stage from its top left corner
+        let screen_x = |x: f32| SCREEN_DIMENSIONS.0 as f32 / 2. + (x - stage_def.stage_info.local_coord.0 as f32 / 2.) * scale;
+        let screen_y = |y: f32| GROUND_SCREEN_Y + (y - ground) * scale;
+        let window = [screen_x(left), screen_y(top), screen_x(right + 1.), screen_y(bottom + 1.)];
+        for instance in instances.iter_mut() {
+            instance.crop = window_crop(instance, size, window);
+        }
+    }
+    if instances.len() < capacity {
+        instances.resize(capacity, hidden_sprite());
+    }
+    instances
+}
+
+/// Horizontal scale of a row of a parallax sprite, between the scales of its top and bottom edges, and the position of
+/// the left of the row.
+///
+/// The row is scaled around the axis of the sprite, at the position of the element moved by `camera_movement`. The rows
+/// move with the camera in proportion to their scale.
+fn parallax_row(edge_scales: (f32, f32), row: u32, height: u32, (position_x, axis_x): (f32, f32), camera_movement: f32) -> (f32, f32) {
+    let fraction = (row as f32 + 0.5) / height as f32;
+    let row_scale = edge_scales.0 + (edge_scales.1 - edge_scales.0) * fraction;
+    (row_scale, position_x - camera_movement * (row_scale - 1.) - axis_x * row_scale)
+}
+
+/// Number of sprites drawing the repetitions of a sprite on one axis, enough for any position of an infinite tiling of
+/// the screen range of the given length
+fn tile_capacity(tile: i32, step: f32, visible_length: f32) -> usize {
+    match tile {
+        1 if step > 0. => (visible_length / step).floor() as usize + 2,
+        count if count > 1 => count as usize,
+        _ => 1,
+    }
+}
+
+/// Crop of an unrotated sprite visible in a window of the screen: left, top, right, bottom
+fn window_crop(instance: &graphics::sprites::SpriteInstance, (width, height): (u32, u32), [left, top, right, bottom]: [f32; 4]) -> (f32, f32, f32, f32) {
+    let (x_start, _, x_end, _) = horizontal_crop((left, right), instance.position.0, instance.axis.0, width as f32, instance.scale.0, instance.flip.0);
+    // the vertical crop is computed like the horizontal one
+    let (y_start, _, y_end, _) = horizontal_crop((top, bottom), instance.position.1, instance.axis.1, height as f32, instance.scale.1, instance.flip.1);
+    let crop = instance.crop;
+    let (crop_left, crop_top) = (crop.0.max(x_start), crop.1.max(y_start));
+    (crop_left, crop_top, crop.2.min(x_end).max(crop_left), crop.3.min(y_end).max(crop_top))
+}
+
+/// Read the common sound file with the first common file reader that has it
+fn read_common_sounds(common_file_readers: &mut [Box<dyn FileReader>]) -> audio::SoundBank {
+    for file_reader in common_file_readers.iter_mut() {
+        if let Ok(sound_file) = file_reader.read_file(Path::new(COMMON_SOUND_FILE)) {
+            match nugem_snd::SoundFile::read(sound_file) {
+                Ok(sound_file) => return audio::SoundBank::new(sound_file),
+                Err(err) => log::error!("Error loading common sound data: {err}"),
+            }
+        }
+    }
+    audio::SoundBank::default()
+}
+
+/// Read the animations and the sprites of the common hit sparks from the first common folder with them
+fn read_fight_fx(common_file_readers: &mut [Box<dyn FileReader>]) -> Option<FightFxData> {
+    for file_reader in common_file_readers.iter_mut() {
+        let Ok(animation_file) = file_reader.read_file(Path::new(FIGHT_FX_ANIMATION_FILE)) else {
+            continue;
+        };
+        let animations = air::read_air_file(animation_file);
+        let sff_data = file_reader.read_file(Path::new(FIGHT_FX_SPRITE_FILE))
+            .map_err(nugem_sff::LoadingError::from)
+            .and_then(|sprite_file| nugem_sff::SpriteFile::read(sprite_file, Vec::new()));
+        match sff_data {
+            Ok(sff_data) => return Some(FightFxData { sff_data, animations }),
+            Err(err) => log::error!("Error loading the common hit sparks: {err}"),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sprite_placement() {
+        // axis at the bottom center of a 40x80 sprite
+        let sprite = LoadedSprite { id: 0, axis: (20, 80) };
+        let screen = |x: f32, y: f32| [SCREEN_DIMENSIONS.0 as f32 / 2. + x * DISPLAY_SCALE, GROUND_SCREEN_Y + y * DISPLAY_SCALE];
+        let top_left = |instance: graphics::sprites::SpriteInstance| instance.corners((40, 80))[0];
+        let mut frame = air::AnimationFrame::new(0, 0, (0, 0), None);
+        assert_eq!(screen(-20., -80.), top_left(sprite_instance(&sprite, (0., 0.), 1., &frame)));
+        frame.offset = (5, -5);
+        assert_eq!(screen(25., -95.), top_left(sprite_instance(&sprite, (40., -10.), 1., &frame)));
+        // the frame offset is towards the front of the player, and the sprite is mirrored around its axis
+        assert_eq!(screen(55., -95.), top_left(sprite_instance(&sprite, (40., -10.), -1., &frame)));
+        // flipping the frame when facing left draws the sprite unmirrored
+        frame.flip = (true, false);
+        assert_eq!(screen(15., -95.), top_left(sprite_instance(&sprite, (40., -10.), -1., &frame)));
+        // negative axis: the origin is outside of the image
+        let sprite = LoadedSprite { id: 0, axis: (-10, -30) };
+        let frame = air::AnimationFrame::new(0, 0, (0, 0), None);
+        assert_eq!(screen(10., 30.), top_left(sprite_instance(&sprite, (0., 0.), 1., &frame)));
+    }
+
+    #[test]
+    fn hit_sparks() {
+        // sparks of 2 frames of 1 tick
+        let animation = |group| air::Animation::new(vec![air::AnimationSteps::new(Vec::new(), vec![
+            air::AnimationFrame::new(group, 0, (0, 0), Some(1)),
+            air::AnimationFrame::new(group, 1, (0, 0), Some(1)),
+        ])], None);
+        let fight_fx_animations: air::Animations = [(2, animation(8000))].into_iter().collect();
+        let attacker_animations: air::Animations = [(2, animation(7000))].into_iter().collect();
+        let reference = |own, number| state::SparkReference { own, number };
+        let spark = |reference, fight_fx| new_spark(reference, 1, &attacker_animations, fight_fx, (30., -65.), -1.);
+        let common_spark = spark(reference(false, 2), Some(&fight_fx_animations)).unwrap();
+        assert_eq!((None, Some((8000, 0))), (common_spark.owner, common_spark.animator.current_display_info()));
+        let own_spark = spark(reference(true, 2), None).unwrap();
+        assert_eq!((Some(1), Some((7000, 0))), (own_spark.owner, own_spark.animator.current_display_info()));
+        assert!(spark(reference(false, 2), None).is_none());
+        assert!(spark(reference(false, -1), Some(&fight_fx_animations)).is_none());
+        // drawn over the players at the position of the hit
+        let sprites: HashMap<ImageKey, LoadedSprite> = [(ImageKey { group: 8000, image: 0 }, LoadedSprite { id: 3, axis: (0, 0) })].into_iter().collect();
+        let instance = spark_instance(&common_spark, &sprites, (10., 0.)).unwrap();
+        assert_eq!((3, SPARK_PRIORITY, (true, false)), (instance.atlas_index, instance.priority, instance.flip));
+        assert_eq!((SCREEN_DIMENSIONS.0 as f32 / 2. + 20. * DISPLAY_SCALE, GROUND_SCREEN_Y - 65. * DISPLAY_SCALE), instance.position);
+        // the second frame has no sprite
+        let mut animator = common_spark.animator;
+        animator.tick();
+        assert!(spark_instance(&Spark { animator: animator.clone(), ..common_spark }, &sprites, (10., 0.)).is_none());
+        animator.tick();
+        assert!(animator.has_looped());
+    }
+
+    #[test]
+    fn remap_palette() {
+        use nugem_sff::writer::{Compression, IndexedImage, SpriteContent, SpriteModel};
+        let mut model = SpriteModel::new();
+        model.add_palette(1, 1, vec![[0, 0, 0], [255, 255, 255]]);
+        model.add_palette(1, 3, vec![[0, 0, 0], [255, 0, 0]]);
+        model.add_sprite(0, 0, (0, 0), SpriteContent::Image(IndexedImage { width: 1, height: 1, pixels: vec![1] }), 0);
+        let mut data = Vec::new();
+        model.write_v2(&mut data, Compression::RLE8).unwrap();
+        let sff_data = nugem_sff::SpriteFile::read(std::io::Cursor::new(data), []).unwrap();
+        let cns = b"
+[Statedef 0]
+[State 0, remap]
+type = RemapPal
+trigger1 = Time = 0
+dest = 1, 3
+
+[State 0, unknown palette]
+type = RemapPal
+trigger1 = Time = 1
+dest = 1, 2
+
+[State 0, back to the selected palette]
+type = RemapPal
+trigger1 = Time = 2
+source = 1, 1
+dest = 1, 1
+";
+        let states = state::read_cns_file(std::io::Cursor::new(cns), "test");
+        let animations = air::Animations::new();
+        let environment = state::TriggerEnvironment::default();
+        let mut player = Player::new(0, state::Facing::Right);
+        player.state_machine.enter_state(&mut player.state, 0, &states, &animations, None);
+        assert_eq!(0, player.shown_palette(&sff_data));
+        player.state_machine.tick(&mut player.state, &states, &animations, &environment);
+        assert_eq!(1, player.shown_palette(&sff_data));
+        // a missing palette keeps the selected one
+        player.state_machine.tick(&mut player.state, &states, &animations, &environment);
+        assert_eq!((Some((1, 2)), 0), (player.state.remapped_palette, player.shown_palette(&sff_data)));
+        player.palette_index = 1;
+        player.state_machine.tick(&mut player.state, &states, &animations, &environment);
+        assert_eq!((None, 1), (player.state.remapped_palette, player.shown_palette(&sff_data)));
+    }
+
+    #[test]
+    fn background_geometry() {
+        // rows of a 4 pixels high floor, from a scale of 1 at the top to 2 at the bottom, with its axis 10 pixels right
+        // of its left
+        assert_eq!((1.125, -21.25), parallax_row((1., 2.), 0, 4, (-10., 10.), 0.));
+        assert_eq!((1.875, -28.75), parallax_row((1., 2.), 3, 4, (-10., 10.), 0.));
+        // the bottom rows move more than the top rows with the camera
+        assert_eq!((1.875, -116.25), parallax_row((1., 2.), 3, 4, (-10., 10.), 100.));
+        // enough tiles for any position of a 30 pixels range with 12 pixels between the tiles
+        assert_eq!((4, 3, 1), (tile_capacity(1, 12., 30.), tile_capacity(3, 12., 30.), tile_capacity(0, 12., 30.)));
+        // a 20x40 sprite with its axis at its bottom center, scaled by 2, in a window ending 10 pixels right of its axis
+        // and 20 pixels above it
+        let sprite = graphics::sprites::SpriteInstance { axis: (10., 40.), scale: (2., 2.), ..graphics::sprites::SpriteInstance::new(0, (100., 200.)) };
+        assert_eq!((0., 0., 0.75, 0.75), window_crop(&sprite, (20, 40), [0., 0., 110., 180.]));
+        // the rows of the parallax sprites stay in their crop
+        let row = graphics::sprites::SpriteInstance { crop: (0., 0.5, 1., 0.625), ..sprite };
+        assert_eq!((0., 0.5, 0.75, 0.625), window_crop(&row, (20, 40), [0., 0., 110., 180.]));
+        // out of the window
+        assert_eq!((0., 0., 0., 0.), window_crop(&sprite, (20, 40), [0., 0., 50., 20.]));
+    }
+
+    #[test]
+    fn gauge_crop() {
+        // a 100 pixels wide gauge with its axis at its right end, visible on its right quarter
+        assert_eq!((0.75, 0., 1., 1.), horizontal_crop((115., 140.), 140., 100., 100., 1., false));
+        // mirrored: the right of the screen shows the left of the image
+        assert_eq!((0., 0., 0.25, 1.), horizontal_crop((115., 140.), 40., 100., 100., 1., true));
+    }
+}
@@ -0,0 +1,55 @@
+use std::io;
+use super::{BitmapPixel, BitmapRenderer, IndexedBitmapRenderer};
+
+/// Palette indices of a sprite image being decoded, the pixels beyond the size of the image being dropped
+pub(crate) struct IndexBuffer {
+    indices: Vec<u8>,
+    pixel_count: usize,
+}
+
+impl IndexBuffer {
+    pub fn new(width: u64, height: u64) -> IndexBuffer {
+        let pixel_count = (width * height) as usize;
+        IndexBuffer {
+            indices: Vec::with_capacity(pixel_count),
+            pixel_count,
+        }
+    }
+    pub fn push_run(&mut self, index: u8, count: u64) {
+        let count = (count as usize).min(self.pixel_count - self.indices.len());
+        self.indices.extend(std::iter::repeat_n(index, count));
+    }
+    /// Copy pixels decoded before, the copied pixels being repeated if there are more of them than the offset
+    pub fn copy(&mut self, offset: u64, count: u64) -> Result<(), io::Error> {
+        let offset = offset as usize;
+        if offset == 0 || offset > self.indices.len() {
+            Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid copy offset {offset} at pixel {}", self.indices.len())))?;
+        }
+        let count = (count as usize).min(self.pixel_count - self.indices.len());
+        for _ in 0..count {
+            self.indices.push(self.indices[self.indices.len() - offset]);
+        }
+        Ok(())
+    }
+    /// Decoded indices, the missing pixels being transparent
+    pub fn into_indices(mut self) -> Vec<u8> {
+        self.indices.resize(self.pixel_count, 0);
+        self.indices
+    }
+}
+
+/// Render the colors of palette indices
+pub(crate) fn render_colors<R: BitmapRenderer>(surface_renderer: &mut R, indices: &[u8], color: impl Fn(u8) -> BitmapPixel) -> Result<(), R::Error> {
+    for run in indices.chunk_by(|a, b| a == b) {
+        R::render_pixels(surface_renderer, color(run[0]), run.len() as u64)?;
+    }
+    Ok(())
+}
+
+/// Render palette indices on an indexed surface
+pub(crate) fn render_indices<R: IndexedBitmapRenderer>(surface_renderer: &mut R, indices: &[u8]) -> Result<(), R::Error> {
+    for run in indices.chunk_by(|a, b| a == b) {
+        R::render_indices(surface_renderer, run[0], run.len() as u64)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,12 @@
+use std::io;
+use std::num::IntErrorKind;
+
+/// Renderer of the palette indices of a sprite, for its colors to be applied later
+pub trait IndexedBitmapRenderer: Sized {
+    type Error: From<io::Error> + From<IntErrorKind>;
+    type Initializer;
+    /// Initializes a surface of palette indices with a given `width` and `height`
+    fn initialize_surface(initializer: Self::Initializer, width: u64, height: u64) -> Result<Self, Self::Error>;
+    /// Renders pixels of a palette index, the index 0 being transparent
+    fn render_indices(&mut self, index: u8, count: u64) -> Result<(), Self::Error>;
+}
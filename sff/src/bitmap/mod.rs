@@ -1,5 +1,14 @@
 mod bitmap_renderer;
 pub use self::bitmap_renderer::*;
 
+mod indexed_bitmap_renderer;
+pub use self::indexed_bitmap_renderer::*;
+
 mod bitmap_pixel;
 pub use self::bitmap_pixel::*;
+
+mod index_buffer;
+pub(crate) use self::index_buffer::*;
+
+#[cfg(test)]
+pub(crate) mod test_renderer;
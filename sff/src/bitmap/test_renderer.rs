@@ -0,0 +1,83 @@
+use std::io;
+use std::num::IntErrorKind;
+use super::{BitmapPixel, BitmapRenderer, IndexedBitmapRenderer};
+
+/// Renderer keeping the pixels in memory, to check the rendered sprites
+#[derive(Debug)]
+pub struct TestRenderer {
+    pub pixels: Vec<BitmapPixel>,
+    cursor: u64,
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum TestRendererError {
+    IoError(io::Error),
+    NumKindError(IntErrorKind),
+}
+
+impl From<io::Error> for TestRendererError {
+    fn from(err: io::Error) -> Self {
+        Self::IoError(err)
+    }
+}
+
+impl From<IntErrorKind> for TestRendererError {
+    fn from(err: IntErrorKind) -> Self {
+        Self::NumKindError(err)
+    }
+}
+
+impl BitmapRenderer for TestRenderer {
+    type Error = TestRendererError;
+    type Initializer = ();
+    fn initialize_surface(_initializer: Self::Initializer, width: u64, height: u64) -> Result<Self, Self::Error> {
+        Ok(TestRenderer {
+            pixels: vec![BitmapPixel::new(0, 0, 0, 0); (width * height) as usize],
+            cursor: 0,
+        })
+    }
+    fn render_pixels(&mut self, pixel: BitmapPixel, count: u64) -> Result<(), Self::Error> {
+        let end = (self.cursor + count).min(self.pixels.len() as u64);
+        self.pixels[self.cursor as usize..end as usize].fill(pixel);
+        self.cursor = end;
+        Ok(())
+    }
+    fn surface_pixel_count(&mut self) -> Result<u64, Self::Error> {
+        Ok(self.pixels.len() as u64)
+    }
+    fn get_pixel(&mut self, pixel_index: u64) -> Result<Option<BitmapPixel>, Self::Error> {
+        Ok(self.pixels.get(pixel_index as usize).copied())
+    }
+}
+
+impl io::Seek for TestRenderer {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.cursor = match pos {
+            io::SeekFrom::Start(i) => i,
+            io::SeekFrom::End(i) => (self.pixels.len() as i64 + i) as u64,
+            io::SeekFrom::Current(i) => (self.cursor as i64 + i) as u64,
+        };
+        Ok(self.cursor)
+    }
+}
+
+/// Renderer keeping the palette indices in memory, to check the rendered sprites
+#[derive(Debug)]
+pub struct TestIndexedRenderer {
+    pub indices: Vec<u8>,
+}
+
+impl IndexedBitmapRenderer for TestIndexedRenderer {
+    type Error = TestRendererError;
+    type Initializer = ();
+    fn initialize_surface(_initializer: Self::Initializer, width: u64, height: u64) -> Result<Self, Self::Error> {
+        Ok(TestIndexedRenderer {
+            indices: Vec::with_capacity((width * height) as usize),
+        })
+    }
+    fn render_indices(&mut self, index: u8, count: u64) -> Result<(), Self::Error> {
+        self.indices.extend(std::iter::repeat_n(index, count as usize));
+        Ok(())
+    }
+}
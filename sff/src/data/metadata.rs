@@ -0,0 +1,40 @@
+use crate::v2;
+
+/// Format of the image data of a sprite
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpriteFormat {
+    /// PCX image of a SFF v1 file
+    Pcx,
+    V2(v2::ImageFormat),
+}
+
+/// Information about a sprite, read without decoding its image.
+///
+/// The size, format, color depth and palette of a linked sprite are the ones of the sprite with its image.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SpriteMetadata {
+    pub group: u16,
+    pub image: u16,
+    /// Index of the sprite in the file
+    pub index: usize,
+    /// Size of the image: width, height
+    pub size: (u16, u16),
+    /// Axis of the sprite: x, y from the top left corner of the image
+    pub axis: (i16, i16),
+    /// Index of the sprite with the image of a linked sprite, `None` if the sprite has its own image
+    pub linked_index: Option<usize>,
+    pub format: SpriteFormat,
+    /// Bits per pixel
+    pub color_depth: u8,
+    /// Palette of a SFF v2 sprite, SFF v1 sprites using the palette at the end of their image or an external palette
+    pub palette_index: Option<usize>,
+}
+
+/// Information about a palette of a sprite file
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PaletteMetadata {
+    pub index: usize,
+    /// Group and item numbers of a SFF v2 palette, the external palettes of SFF v1 files having none
+    pub id: Option<(u16, u16)>,
+    pub color_count: usize,
+}
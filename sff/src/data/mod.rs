@@ -1,13 +1,24 @@
 use std::io::{Read, Seek};
 
-use crate::bitmap::BitmapRenderer;
+use crate::bitmap::{BitmapPixel, BitmapRenderer, IndexedBitmapRenderer};
 use crate::{LoadingError, RenderingError};
 
 pub mod v1;
 pub mod v2;
 
 mod reader;
-use self::reader::*;
+pub(crate) use self::reader::*;
+
+mod metadata;
+pub use self::metadata::*;
+
+/// Image of a sprite, and the position of the sprite origin in it
+#[derive(Debug)]
+pub struct RenderedSprite<R> {
+    pub surface: R,
+    /// Axis of the sprite: x, y from the top left corner of the image
+    pub axis: (i16, i16),
+}
 
 #[derive(Debug)]
 pub enum SpriteFile {
@@ -24,21 +35,176 @@ impl SpriteFile {
         }
     }
 
+    pub fn sprite_count(&self) -> usize {
+        match self {
+            SpriteFile::V1(data) => data.sprites().len(),
+            SpriteFile::V2(data) => data.sprites().len(),
+        }
+    }
+
+    /// Numbers of the sprite groups, in ascending order
+    pub fn groups(&self) -> Vec<u16> {
+        match self {
+            SpriteFile::V1(data) => data.groups().keys().copied().collect(),
+            SpriteFile::V2(data) => data.groups().keys().copied().collect(),
+        }
+    }
+
+    /// Numbers of the images of a sprite group, in ascending order
+    pub fn images(&self, group_index: u16) -> Vec<u16> {
+        let images = match self {
+            SpriteFile::V1(data) => data.groups().get(&group_index).map(|group| &group.0),
+            SpriteFile::V2(data) => data.groups().get(&group_index).map(|group| &group.0),
+        };
+        images.map(|images| images.keys().copied().collect()).unwrap_or_default()
+    }
+
+    /// Information about a sprite, without decoding its image
+    pub fn sprite(&self, group_index: u16, image_index: u16) -> Option<SpriteMetadata> {
+        match self {
+            SpriteFile::V1(data) => {
+                let index = *data.groups().get(&group_index)?.0.get(&image_index)?;
+                let sprite = &data.sprites()[index];
+                let image_sprite_index = data.linked_sprite(index);
+                let image_sprite = image_sprite_index.map(|image_sprite_index| &data.sprites()[image_sprite_index]);
+                Some(SpriteMetadata {
+                    group: group_index,
+                    image: image_index,
+                    index,
+                    size: image_sprite.and_then(v1::Sprite::image_size).unwrap_or((0, 0)),
+                    axis: sprite.axis,
+                    linked_index: match sprite.data.is_empty() {
+                        true => Some(image_sprite_index.unwrap_or(sprite.linked_index as usize)),
+                        false => None,
+                    },
+                    format: SpriteFormat::Pcx,
+                    color_depth: image_sprite.and_then(v1::Sprite::color_depth).unwrap_or(0),
+                    palette_index: None,
+                })
+            },
+            SpriteFile::V2(data) => {
+                let index = *data.groups().get(&group_index)?.0.get(&image_index)?;
+                let sprite = &data.sprites()[index];
+                let image_sprite_index = data.linked_sprite_index(index);
+                let image_sprite = &data.sprites()[image_sprite_index];
+                Some(SpriteMetadata {
+                    group: group_index,
+                    image: image_index,
+                    index,
+                    size: image_sprite.size,
+                    axis: sprite.axis,
+                    linked_index: (image_sprite_index != index).then_some(image_sprite_index),
+                    format: SpriteFormat::V2(image_sprite.format),
+                    color_depth: image_sprite.color_depth,
+                    palette_index: Some(image_sprite.palette_index as usize),
+                })
+            },
+        }
+    }
+
+    /// Information about all the sprites, ordered by group and image numbers
+    pub fn sprites(&self) -> Vec<SpriteMetadata> {
+        self.groups().into_iter()
+            .flat_map(|group_index| self.images(group_index).into_iter().map(move |image_index| (group_index, image_index)))
+            .filter_map(|(group_index, image_index)| self.sprite(group_index, image_index))
+            .collect()
+    }
+
+    /// Information about the palettes: the palettes of a SFF v2 file, or the external palettes of a SFF v1 file
+    pub fn palettes(&self) -> Vec<PaletteMetadata> {
+        match self {
+            SpriteFile::V1(data) => (0..data.palette_count()).map(|index| PaletteMetadata {
+                index,
+                id: None,
+                color_count: v1::PALETTE_COLOR_COUNT,
+            }).collect(),
+            SpriteFile::V2(data) => data.palettes().iter().enumerate().map(|(index, palette)| PaletteMetadata {
+                index,
+                id: Some((palette.group, palette.item)),
+                color_count: palette.colors as usize,
+            }).collect(),
+        }
+    }
+
+    /// Index of the SFF v2 palette with the given group and item numbers
+    pub fn palette_index(&self, group: u16, item: u16) -> Option<usize> {
+        match self {
+            SpriteFile::V1(_) => None,
+            SpriteFile::V2(data) => data.palettes().iter().position(|palette| palette.group == group && palette.item == item),
+        }
+    }
+
     pub fn render_sprite<R: BitmapRenderer>(
         &self,
         renderer_params: R::Initializer,
         group_index: u16,
         image_index: u16,
         palette_index: usize,
-    ) -> Result<R, RenderingError<<R as BitmapRenderer>::Error>> {
+    ) -> Result<RenderedSprite<R>, RenderingError<<R as BitmapRenderer>::Error>> {
         use SpriteFile::*;
-        match self {
+        let surface = match self {
             V1(data) => {
                 data.render_sprite(renderer_params, group_index, image_index, palette_index)
             }
             V2(data) => {
                 data.render_sprite(renderer_params, group_index, image_index, palette_index)
             }
+        }?;
+        // the sprite exists since it was rendered
+        let axis = self.sprite(group_index, image_index).map(|sprite| sprite.axis).unwrap_or_default();
+        Ok(RenderedSprite { surface, axis })
+    }
+
+    /// Render the palette indices of a sprite image, to apply a palette on them later
+    ///
+    /// The true color PNG sprites of SFF v2 files have no palette indices, and fail to render.
+    pub fn render_sprite_indexed<R: IndexedBitmapRenderer>(
+        &self,
+        renderer_params: R::Initializer,
+        group_index: u16,
+        image_index: u16,
+    ) -> Result<RenderedSprite<R>, RenderingError<<R as IndexedBitmapRenderer>::Error>> {
+        use SpriteFile::*;
+        let surface = match self {
+            V1(data) => data.render_sprite_indexed(renderer_params, group_index, image_index),
+            V2(data) => data.render_sprite_indexed(renderer_params, group_index, image_index),
+        }?;
+        let axis = self.sprite(group_index, image_index).map(|sprite| sprite.axis).unwrap_or_default();
+        Ok(RenderedSprite { surface, axis })
+    }
+
+    /// The 256 colors applied to the palette indices of a sprite when it is rendered with the given palette,
+    /// the color 0 being transparent
+    pub fn sprite_palette(&self, group_index: u16, image_index: u16, palette_index: usize) -> Option<Vec<BitmapPixel>> {
+        match self {
+            SpriteFile::V1(data) => data.sprite_palette(group_index, image_index, palette_index),
+            SpriteFile::V2(data) => data.sprite_palette(group_index, image_index, palette_index),
+        }
+    }
+
+    /// Opaque color of the index 0 in the palette applied to a sprite, drawn instead of transparent pixels by the
+    /// sprites without mask
+    pub fn sprite_mask_color(&self, group_index: u16, image_index: u16, palette_index: usize) -> Option<BitmapPixel> {
+        match self {
+            SpriteFile::V1(data) => data.sprite_mask_color(group_index, image_index, palette_index),
+            SpriteFile::V2(data) => data.sprite_mask_color(group_index, image_index, palette_index),
+        }
+    }
+
+    /// Check if a sprite is rendered with the palette given to render it,
+    /// instead of the palette of its own data for SFF v1 files, or the palette it forces for SFF v2 files
+    pub fn uses_selected_palette(&self, group_index: u16, image_index: u16) -> bool {
+        match self {
+            SpriteFile::V1(data) => data.uses_selected_palette(group_index, image_index),
+            SpriteFile::V2(data) => data.uses_selected_palette(group_index, image_index),
+        }
+    }
+
+    /// The 256 colors of a palette, the color 0 being transparent
+    pub fn palette_colors(&self, palette_index: usize) -> Option<Vec<BitmapPixel>> {
+        match self {
+            SpriteFile::V1(data) => data.palette_colors(palette_index),
+            SpriteFile::V2(data) => data.palette_colors(palette_index),
         }
     }
 
@@ -75,4 +241,96 @@ pub(crate) trait SffData {
         image_index: u16,
         palette_index: usize,
     ) -> Result<R, RenderingError<R::Error>>;
+    fn render_sprite_indexed<R: IndexedBitmapRenderer>(
+        &self,
+        renderer_params: R::Initializer,
+        group_index: u16,
+        image_index: u16,
+    ) -> Result<R, RenderingError<R::Error>>;
+    fn sprite_palette(&self, group_index: u16, image_index: u16, palette_index: usize) -> Option<Vec<BitmapPixel>>;
+    fn sprite_mask_color(&self, group_index: u16, image_index: u16, palette_index: usize) -> Option<BitmapPixel>;
+    fn uses_selected_palette(&self, group_index: u16, image_index: u16) -> bool;
+    fn palette_colors(&self, palette_index: usize) -> Option<Vec<BitmapPixel>>;
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use super::*;
+    use crate::bitmap::test_renderer::{TestIndexedRenderer, TestRenderer};
+    use crate::writer::{Compression, IndexedImage, SpriteContent, SpriteModel};
+
+    fn sprite_files() -> (SpriteFile, SpriteFile) {
+        let mut model = SpriteModel::new();
+        model.add_palette(1, 1, vec![[0, 0, 0], [255, 255, 255]]);
+        model.add_palette(1, 3, vec![[0, 0, 0], [255, 0, 0]]);
+        let image = SpriteContent::Image(IndexedImage { width: 3, height: 2, pixels: vec![0, 1, 1, 1, 1, 0] });
+        model.add_sprite(5, 1, (1, 2), image, 1);
+        model.add_sprite(0, 0, (3, 4), SpriteContent::Linked(0), 1);
+        model.add_sprite(5, 0, (-3, 7), SpriteContent::Image(IndexedImage { width: 1, height: 1, pixels: vec![1] }), 0);
+        let mut v1_data = Vec::new();
+        model.write_v1(&mut v1_data).unwrap();
+        let mut v2_data = Vec::new();
+        model.write_v2(&mut v2_data, Compression::RLE8).unwrap();
+        (SpriteFile::read(Cursor::new(v1_data), []).unwrap(), SpriteFile::read(Cursor::new(v2_data), []).unwrap())
+    }
+
+    #[test]
+    fn sprite_metadata() {
+        let (v1_file, v2_file) = sprite_files();
+        for sprite_file in [&v1_file, &v2_file] {
+            assert_eq!(3, sprite_file.sprite_count());
+            assert_eq!(vec![0, 5], sprite_file.groups());
+            assert_eq!(vec![0, 1], sprite_file.images(5));
+            assert!(sprite_file.images(1).is_empty());
+            assert!(sprite_file.sprite(5, 2).is_none());
+            let sprites = sprite_file.sprites();
+            assert_eq!(vec![(0, 0), (5, 0), (5, 1)], sprites.iter().map(|sprite| (sprite.group, sprite.image)).collect::<Vec<_>>());
+            let linked = &sprites[0];
+            assert_eq!((1, (3, 4), (3, 2), Some(0), 8), (linked.index, linked.axis, linked.size, linked.linked_index, linked.color_depth));
+            assert_eq!(((1, 2), None), (sprites[2].axis, sprites[2].linked_index));
+            assert_eq!(((1, 1), (-3, 7)), (sprites[1].size, sprites[1].axis));
+        }
+        assert_eq!(SpriteFormat::Pcx, v1_file.sprite(5, 1).unwrap().format);
+        assert_eq!(None, v1_file.sprite(0, 0).unwrap().palette_index);
+        assert!(v1_file.palettes().is_empty());
+        let linked = v2_file.sprite(0, 0).unwrap();
+        assert_eq!((SpriteFormat::V2(v2::ImageFormat::RLE8), Some(1)), (linked.format, linked.palette_index));
+        assert_eq!(vec![
+            PaletteMetadata { index: 0, id: Some((1, 1)), color_count: 2 },
+            PaletteMetadata { index: 1, id: Some((1, 3)), color_count: 2 },
+        ], v2_file.palettes());
+        assert_eq!(Some(1), v2_file.palette_index(1, 3));
+        assert_eq!(None, v2_file.palette_index(1, 2));
+        let rendered = v2_file.render_sprite::<TestRenderer>((), 0, 0, 0).unwrap();
+        assert_eq!(((3, 4), 6), (rendered.axis, rendered.surface.pixels.len()));
+    }
+
+    #[test]
+    fn indexed_sprites() {
+        let (v1_file, v2_file) = sprite_files();
+        for sprite_file in [&v1_file, &v2_file] {
+            let rendered = sprite_file.render_sprite_indexed::<TestIndexedRenderer>((), 0, 0).unwrap();
+            assert_eq!(((3, 4), vec![0, 1, 1, 1, 1, 0]), (rendered.axis, rendered.surface.indices));
+        }
+        // the v1 sprites use the palette at the end of their data
+        assert!(!v1_file.uses_selected_palette(5, 1));
+        assert!(v1_file.sprite_palette(5, 1, 0).is_none());
+        // the v2 sprites get the same colors with their palette indices as when they are rendered
+        assert!(v2_file.uses_selected_palette(5, 0));
+        assert!(!v2_file.uses_selected_palette(0, 0));
+        for (group, image) in [(5, 0), (0, 0)] {
+            let indices = v2_file.render_sprite_indexed::<TestIndexedRenderer>((), group, image).unwrap().surface.indices;
+            for palette_index in 0..2 {
+                let palette = v2_file.sprite_palette(group, image, palette_index).unwrap();
+                let colors = indices.iter().map(|&index| palette[index as usize]).collect::<Vec<_>>();
+                assert_eq!(v2_file.render_sprite::<TestRenderer>((), group, image, palette_index).unwrap().surface.pixels, colors);
+            }
+        }
+        assert_eq!(v2_file.palette_colors(1), v2_file.sprite_palette(0, 0, 0));
+        // the color 0 of the palettes is black
+        assert_eq!(Some(BitmapPixel::new(0, 0, 0, 255)), v2_file.sprite_mask_color(5, 0, 1));
+        assert!(v1_file.sprite_mask_color(5, 1, 0).is_none());
+        assert!(v2_file.palette_colors(2).is_none());
+    }
 }
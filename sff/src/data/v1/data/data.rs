@@ -1,17 +1,17 @@
 use std::collections::BTreeMap;
 use std::io::Cursor;
 use std::borrow::Cow;
-use crate::bitmap::BitmapRenderer;
+use crate::bitmap::{BitmapPixel, BitmapRenderer, IndexedBitmapRenderer};
 use crate::v1::RenderingError;
 
 use super::pcx;
-use super::{Color, Palette, PALETTE_COLOR_COUNT};
+use super::{Color, Palette};
 use crate::SffData;
 
 #[derive(Debug)]
 pub struct Sprite {
     /// Image axis coordinates: X, Y
-    pub axis: (u16, u16),
+    pub axis: (i16, i16),
     /// Index of the previous copy of the sprites (only for linked sprites: those that have no data)
     pub linked_index: u16,
     /// True if it uses the same palette as the previous sprite
@@ -20,6 +20,17 @@ pub struct Sprite {
     pub data: Vec<u8>,
 }
 
+impl Sprite {
+    /// Size of the PCX image of the sprite, read from its header: width, height
+    pub fn image_size(&self) -> Option<(u16, u16)> {
+        pcx::read_pcx_size(&self.data)
+    }
+    /// Bits per pixel of the PCX image of the sprite, read from its header
+    pub fn color_depth(&self) -> Option<u8> {
+        pcx::read_pcx_color_depth(&self.data)
+    }
+}
+
 #[derive(Debug)]
 pub struct Group(pub BTreeMap<u16, usize>);
 
@@ -41,29 +52,66 @@ impl Data {
             shared_palette,
         }
     }
-    fn sprite_palette<'a>(&self, sprite_index: usize, general_palette: &'a Palette) -> Cow<'a, Palette> {
-        let mut result = Cow::Borrowed(general_palette);
+    pub fn sprites(&self) -> &[Sprite] {
+        &self.sprites
+    }
+    pub fn groups(&self) -> &BTreeMap<u16, Group> {
+        &self.groups
+    }
+    /// Index of the sprite with the image of a sprite, following the links of the sprites without data
+    pub fn linked_sprite(&self, sprite_index: usize) -> Option<usize> {
+        let mut current_index = sprite_index;
+        // a chain of links longer than the number of sprites has a cycle
+        for _ in 0..self.sprites.len() {
+            let sprite = self.sprites.get(current_index)?;
+            if !sprite.data.is_empty() {
+                return Some(current_index);
+            }
+            current_index = sprite.linked_index as usize;
+        }
+        None
+    }
+    /// Index of the sprite with the palette at the end of its data used by a sprite, `None` if the sprite uses the general palette
+    fn palette_sprite(&self, sprite_index: usize) -> Option<usize> {
         for i in 0..sprite_index+1 {
             let sprite = &self.sprites[sprite_index - i];
             if self.shared_palette && sprite.uses_shared_palette {
                 break;
             }
-            else {
-                if (sprite.data.len() > 768) && (sprite.data[sprite.data.len() - 768 - 1] == 0x0C)  {
-                    // read the palette data from the sprite data
-                    let palette_data_index = sprite.data.len() - 768;
-                    for j in 0..PALETTE_COLOR_COUNT {
-                        let r = sprite.data[palette_data_index + j * 3];
-                        let g = sprite.data[palette_data_index + j * 3 + 1];
-                        let b = sprite.data[palette_data_index + j * 3 + 2];
-                        result.to_mut().colors[j] = Color::Rgb(r, g, b);
-                    }
-                    break;
-                }
+            else if (sprite.data.len() > 768) && (sprite.data[sprite.data.len() - 768 - 1] == 0x0C) {
+                return Some(sprite_index - i);
             }
         }
+        None
+    }
+    fn sprite_palette<'a>(&self, sprite_index: usize, general_palette: &'a Palette) -> Cow<'a, Palette> {
+        let mut result = Cow::Borrowed(general_palette);
+        if let Some(palette) = self.palette_sprite(sprite_index).and_then(|palette_sprite_index| pcx::read_pcx_palette(&self.sprites[palette_sprite_index].data)) {
+            // the palette data at the end of the sprite data
+            result = Cow::Owned(palette);
+        }
         result
     }
+    /// The 256 colors used to render a sprite with the palette indices of its image, the index 0 being transparent
+    pub fn sprite_index_palette(&self, sprite_index: usize, general_palette: &Palette) -> Vec<BitmapPixel> {
+        let palette = self.sprite_palette(sprite_index, general_palette);
+        (0..=u8::MAX).map(|color_index| pcx::palette_pixel(&palette, color_index)).collect()
+    }
+    /// Check if a sprite is rendered with the general palette, instead of the palette at the end of the data of a sprite
+    pub fn uses_general_palette(&self, sprite_index: usize) -> bool {
+        self.palette_sprite(sprite_index).is_none()
+    }
+    /// Index of the sprite with the image of the sprite with the given group and image numbers
+    fn image_sprite_index<T>(&self, group_index: u16, image_index: u16) -> Result<usize, RenderingError<T>> {
+        let sprite_group = self.groups.get(&group_index).ok_or_else(|| RenderingError::InvalidSpriteGroupNumber { invalid_index: group_index, sprite_group_count: self.groups.len() })?;
+        let specified_real_index = *sprite_group.0.get(&image_index).ok_or_else(|| RenderingError::InvalidImageNumber { invalid_index: group_index, image_count: sprite_group.0.len() })?;
+        self.linked_sprite(specified_real_index).ok_or_else(|| RenderingError::InvalidLinkedSpriteNumber { invalid_index: self.sprites[specified_real_index].linked_index, sprite_count: self.sprites.len() })
+    }
+    pub fn render_sprite_indexed_surface<R: IndexedBitmapRenderer>(&self, renderer_params: R::Initializer, group_index: u16, image_index: u16) -> Result<R, RenderingError<R::Error>> {
+        let real_index = self.image_sprite_index(group_index, image_index)?;
+        let cursor = Cursor::new(&self.sprites[real_index].data[..]);
+        pcx::read_pcx_indexed_surface(cursor, renderer_params)
+    }
     fn render_sprite_linked_index_surface<R: BitmapRenderer>(&self, renderer_params: R::Initializer, linked_index: u16, palette: &Palette) -> Result<R, RenderingError<R::Error>> {
         // if the specified sprite uses a linked index
         let actual_index = linked_index as usize;
@@ -110,4 +158,27 @@ impl SffData for Data {
         let palette = &self.palettes.get(palette_index).ok_or(RenderingError::PaletteNotFound(palette_index))?;
         self.render_sprite_surface(renderer_params, group_index, image_index, palette).map_err(Into::into)
     }
+    fn render_sprite_indexed<R: IndexedBitmapRenderer>(&self, renderer_params: R::Initializer, group_index: u16, image_index: u16) -> Result<R, crate::RenderingError<R::Error>> {
+        self.render_sprite_indexed_surface(renderer_params, group_index, image_index).map_err(Into::into)
+    }
+    fn sprite_palette(&self, group_index: u16, image_index: u16, palette_index: usize) -> Option<Vec<BitmapPixel>> {
+        let palette = self.palettes.get(palette_index)?;
+        let real_index = self.image_sprite_index::<()>(group_index, image_index).ok()?;
+        Some(self.sprite_index_palette(real_index, palette))
+    }
+    fn sprite_mask_color(&self, group_index: u16, image_index: u16, palette_index: usize) -> Option<BitmapPixel> {
+        let palette = self.palettes.get(palette_index)?;
+        let real_index = self.image_sprite_index::<()>(group_index, image_index).ok()?;
+        match self.sprite_palette(real_index, palette).colors[0] {
+            Color::Rgb(r, g, b) => Some(BitmapPixel::new(r, g, b, u8::MAX)),
+            Color::Transparent => None,
+        }
+    }
+    fn uses_selected_palette(&self, group_index: u16, image_index: u16) -> bool {
+        self.image_sprite_index::<()>(group_index, image_index).is_ok_and(|real_index| self.uses_general_palette(real_index))
+    }
+    fn palette_colors(&self, palette_index: usize) -> Option<Vec<BitmapPixel>> {
+        let palette = self.palettes.get(palette_index)?;
+        Some((0..=u8::MAX).map(|color_index| pcx::palette_pixel(palette, color_index)).collect())
+    }
 }
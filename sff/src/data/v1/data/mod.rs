@@ -2,6 +2,7 @@ mod palette;
 pub use self::palette::{Color, Palette, PALETTE_COLOR_COUNT};
 
 mod pcx;
+pub use self::pcx::{palette_pixel, read_pcx_image, read_pcx_palette, PcxImage};
 
 mod data;
 pub use self::data::{Data, Group, Sprite};
\ No newline at end of file
@@ -1,10 +1,88 @@
-use crate::{bitmap::BitmapRenderer, v1::RenderingError};
+use crate::bitmap::{render_colors, render_indices, BitmapPixel, BitmapRenderer, IndexBuffer, IndexedBitmapRenderer};
+use crate::v1::RenderingError;
 
-use super::{Palette, Color};
-use std::io::{Read, Seek, SeekFrom};
+use super::{Palette, Color, PALETTE_COLOR_COUNT};
+use std::io::{Cursor, Read, Seek, SeekFrom};
 use byteorder::{LittleEndian, ReadBytesExt};
 
-pub fn read_pcx_surface<T: Read + Seek, R: BitmapRenderer>(mut reader: T, renderer_params: R::Initializer, palette: &Palette) -> Result<R, RenderingError<R::Error>> {
+/// Size of the 256 colors palette at the end of the PCX data
+const PCX_PALETTE_SIZE: usize = PALETTE_COLOR_COUNT * 3;
+
+/// Image size from the PCX header: width, height
+pub fn read_pcx_size(data: &[u8]) -> Option<(u16, u16)> {
+    let header_u16 = |index: usize| Some(u16::from_le_bytes(data.get(index..index + 2)?.try_into().ok()?));
+    let (x_min, y_min, x_max, y_max) = (header_u16(4)?, header_u16(6)?, header_u16(8)?, header_u16(10)?);
+    Some((x_max.checked_sub(x_min)? + 1, y_max.checked_sub(y_min)? + 1))
+}
+
+/// Bits per pixel from the PCX header: bits per pixel of each color plane, times the number of planes
+pub fn read_pcx_color_depth(data: &[u8]) -> Option<u8> {
+    let bits_per_plane = *data.get(3)?;
+    let bit_planes = *data.get(65)?;
+    bits_per_plane.checked_mul(bit_planes)
+}
+
+/// Color of a palette index, the index 0 being transparent
+pub fn palette_pixel(palette: &Palette, color_index: u8) -> BitmapPixel {
+    if color_index > 0 {
+        palette.colors[color_index as usize].into()
+    }
+    else {
+        Color::Transparent.into()
+    }
+}
+
+/// Palette of a PCX image, stored after a marker at the end of its data
+pub fn read_pcx_palette(data: &[u8]) -> Option<Palette> {
+    let marker_index = data.len().checked_sub(PCX_PALETTE_SIZE + 1)?;
+    if data[marker_index] != 0x0C {
+        return None;
+    }
+    let palette_data = &data[marker_index + 1..];
+    let mut palette = Palette { colors: [Color::Transparent; PALETTE_COLOR_COUNT] };
+    for (color, rgb) in palette.colors.iter_mut().zip(palette_data.chunks_exact(3)) {
+        *color = Color::Rgb(rgb[0], rgb[1], rgb[2]);
+    }
+    Some(palette)
+}
+
+/// Size and palette indices of a PCX image, with its palette if it has one
+#[derive(Clone, Debug)]
+pub struct PcxImage {
+    pub size: (u16, u16),
+    pub indices: Vec<u8>,
+    pub palette: Option<Palette>,
+}
+
+/// Decode a PCX image outside of a sprite file, such as the image of a font
+pub fn read_pcx_image<E>(data: &[u8]) -> Result<PcxImage, RenderingError<E>> {
+    let (size, indices) = read_pcx_indices(Cursor::new(data))?;
+    Ok(PcxImage {
+        size,
+        indices,
+        palette: read_pcx_palette(data),
+    })
+}
+
+pub fn read_pcx_surface<T: Read + Seek, R: BitmapRenderer>(reader: T, renderer_params: R::Initializer, palette: &Palette) -> Result<R, RenderingError<R::Error>> {
+    let ((width, height), indices) = read_pcx_indices(reader)?;
+    let mut surface_renderer = R::initialize_surface(renderer_params, width as u64, height as u64).map_err(RenderingError::renderer_error)?;
+    render_colors(&mut surface_renderer, &indices, |color_index| palette_pixel(palette, color_index)).map_err(RenderingError::renderer_error)?;
+    Ok(surface_renderer)
+}
+
+pub fn read_pcx_indexed_surface<T: Read + Seek, R: IndexedBitmapRenderer>(reader: T, renderer_params: R::Initializer) -> Result<R, RenderingError<R::Error>> {
+    let ((width, height), indices) = read_pcx_indices(reader)?;
+    let mut surface_renderer = R::initialize_surface(renderer_params, width as u64, height as u64).map_err(RenderingError::renderer_error)?;
+    render_indices(&mut surface_renderer, &indices).map_err(RenderingError::renderer_error)?;
+    Ok(surface_renderer)
+}
+
+/// Size of a PCX image, and its palette indices
+type PcxIndices = ((u16, u16), Vec<u8>);
+
+/// Decode the palette indices of a PCX image
+fn read_pcx_indices<T: Read + Seek, E>(mut reader: T) -> Result<PcxIndices, RenderingError<E>> {
     // PCX file format:
     // reading the PCX header
     // byte 0: manufacturer, must be 0x0A
@@ -81,7 +159,7 @@ pub fn read_pcx_surface<T: Read + Seek, R: BitmapRenderer>(mut reader: T, render
     reader.seek(SeekFrom::Start(128))?;
     let scanline_length = bit_planes * bytes_per_plane;
     let line_padding = scanline_length * 8 / bits_per_pixel - width;
-    let mut surface_renderer = R::initialize_surface(renderer_params, width as u64, height as u64).map_err(RenderingError::renderer_error)?;
+    let mut indices = IndexBuffer::new(width as u64, height as u64);
     // finished reading the header, now reading the pixel data
     {
         let max_pixel = (width as u64) * (height as u64);
@@ -97,10 +175,10 @@ pub fn read_pcx_surface<T: Read + Seek, R: BitmapRenderer>(mut reader: T, render
                         if (first_byte & 0xC0) == 0xC0 {
                             let run_length = first_byte & 0x3F;
                             let second_byte = reader.read_u8()?;
-                            (run_length as u64, second_byte as usize, true, false)
+                            (run_length as u64, second_byte, true, false)
                         }
                         else {
-                            (1, first_byte as usize, false, false)
+                            (1, first_byte, false, false)
                         }
                     }
                     else {
@@ -124,15 +202,7 @@ pub fn read_pcx_surface<T: Read + Seek, R: BitmapRenderer>(mut reader: T, render
                 if pixel_index + (run_length as u64) > max_pixel {
                     run_length = (max_pixel - pixel_index) as u64;
                 }
-                let color = {
-                    if color_index > 0 {
-                        palette.colors[color_index].clone()
-                    }
-                    else {
-                        Color::Transparent
-                    }
-                };
-                R::render_pixels(&mut surface_renderer, color.into(), run_length).map_err(RenderingError::renderer_error)?;
+                indices.push_run(color_index, run_length);
                 pixel_index += run_length;
             }
         }
@@ -140,10 +210,9 @@ pub fn read_pcx_surface<T: Read + Seek, R: BitmapRenderer>(mut reader: T, render
             // raw pixels
             for _ in 0..max_pixel {
                 let color_byte = reader.read_u8()?;
-                let pixel = palette.colors[color_byte as usize].into();
-                R::render_single_pixel(&mut surface_renderer, pixel).map_err(RenderingError::renderer_error)?;
+                indices.push_run(color_byte, 1);
             }
         }
     }
-    Ok(surface_renderer)
+    Ok(((width, height), indices.into_indices()))
 }
@@ -2,7 +2,7 @@ use thiserror::Error;
 use std::io;
 
 mod data;
-pub use self::data::{Data, Group, Sprite, Color, Palette};
+pub use self::data::{Data, Group, Sprite, Color, Palette, PALETTE_COLOR_COUNT, palette_pixel, read_pcx_image, read_pcx_palette, PcxImage};
 
 mod sff;
 pub use self::sff::read_sff;
@@ -38,8 +38,8 @@ pub fn read_sff<T: Read + Seek>(mut reader: T) -> Result<(Vec<Sprite>, BTreeMap<
         reader.seek(SeekFrom::Start(next_subfile_offset as u64))?;
         next_subfile_offset = reader.read_u32::<LittleEndian>()?;
         let data_size = reader.read_u32::<LittleEndian>()?;
-        let axis_x = reader.read_u16::<LittleEndian>()?;
-        let axis_y = reader.read_u16::<LittleEndian>()?;
+        let axis_x = reader.read_i16::<LittleEndian>()?;
+        let axis_y = reader.read_i16::<LittleEndian>()?;
         let group_index = reader.read_u16::<LittleEndian>()?;
         let image_index = reader.read_u16::<LittleEndian>()?;
         let linked_index = reader.read_u16::<LittleEndian>()?;
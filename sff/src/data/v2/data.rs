@@ -1,15 +1,22 @@
 use std::collections::BTreeMap;
 use crate::SffData;
-use crate::bitmap::{BitmapPixel, BitmapRenderer};
+use crate::bitmap::{render_colors, render_indices, BitmapPixel, BitmapRenderer, IndexBuffer, IndexedBitmapRenderer};
 use super::RenderingError;
+use super::png::{png_indices, render_png};
 
-#[derive(Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ImageFormat {
     Raw,
     Invalid(u8),
     RLE8,
     RLE5,
-    LZ5
+    LZ5,
+    /// PNG with indexed colors, using the palette of the sprite
+    Png8,
+    /// PNG with RGB colors
+    Png24,
+    /// PNG with RGBA colors
+    Png32,
 }
 
 #[derive(Debug)]
@@ -17,7 +24,7 @@ pub struct SpriteInfo {
     /// dimension of the sprite: width, height
     pub size: (u16, u16),
     /// axis translation of the sprite: x, y
-    pub axis: (u16, u16),
+    pub axis: (i16, i16),
     /// linked index to the actual sprite image
     pub linked_index: u16,
     /// image format
@@ -39,6 +46,8 @@ pub struct GroupInfo(pub BTreeMap<u16, usize>);
 
 #[derive(Debug)]
 pub struct PaletteInfo {
+    pub group: u16,
+    pub item: u16,
     /// Number of colors
     pub colors: u16,
     pub linked_index: u16,
@@ -65,14 +74,39 @@ impl Data {
             tdata,
         }
     }
-    pub fn linked_sprite(&self, sprite_number: usize) -> &SpriteInfo {
-        let sprite = &self.sprites[sprite_number];
-        if (sprite.linked_index > 0) && ((sprite.linked_index as usize) < self.sprites.len()) {
-            self.linked_sprite(sprite.linked_index as usize)
-        }
-        else {
-            &sprite
+    pub fn sprites(&self) -> &[SpriteInfo] {
+        &self.sprites
+    }
+    pub fn groups(&self) -> &BTreeMap<u16, GroupInfo> {
+        &self.groups
+    }
+    pub fn palettes(&self) -> &[PaletteInfo] {
+        &self.palettes
+    }
+    /// Index of the sprite with the image of a sprite, following the links of the sprites without data
+    pub fn linked_sprite_index(&self, sprite_number: usize) -> usize {
+        let mut current_number = sprite_number;
+        // a chain of links longer than the number of sprites has a cycle
+        for _ in 0..self.sprites.len() {
+            let sprite = &self.sprites[current_number];
+            // linked sprites have no data of their own
+            let linked_index = sprite.linked_index as usize;
+            if sprite.data_length == 0 && linked_index != current_number && linked_index < self.sprites.len() {
+                current_number = linked_index;
+            }
+            else {
+                break;
+            }
         }
+        current_number
+    }
+    pub fn linked_sprite(&self, sprite_number: usize) -> &SpriteInfo {
+        &self.sprites[self.linked_sprite_index(sprite_number)]
+    }
+    fn group_sprite_index<T>(&self, group_index: u16, image_index: u16) -> Result<usize, RenderingError<T>> {
+        let group = self.groups.get(&group_index).ok_or_else(|| RenderingError::InvalidSpriteGroupNumber { invalid_index: group_index, sprite_group_count: self.groups.len() })?;
+        let sprite_index = *group.0.get(&image_index).ok_or_else(|| RenderingError::InvalidSpriteNumber { invalid_index: group_index, sprite_count: self.groups.len() })?;
+        Ok(sprite_index)
     }
     pub fn linked_palette(&self, palette_number: usize) -> &PaletteInfo {
         let palette = &self.palettes[palette_number];
@@ -83,221 +117,255 @@ impl Data {
             &palette
         }
     }
+    /// Palette used to render a sprite: the palette of the sprite if it forces one, or the given palette
+    fn sprite_palette_info(&self, sprite_info: &SpriteInfo, palette_number: usize) -> &PaletteInfo {
+        // if the sprite indicates a palette number other than 0, it's trying to force that one
+        if sprite_info.palette_index > 0 {
+            self.linked_palette(sprite_info.palette_index as usize)
+        }
+        else {
+            self.linked_palette(palette_number)
+        }
+    }
     pub fn sprite_index_surface<R: BitmapRenderer>(&self, renderer_params: R::Initializer, sprite_number: usize, palette_number: usize) -> Result<R, RenderingError<R::Error>> {
         let displayed_sprite = self.linked_sprite(sprite_number);
-        let palette_used = {
-            // if the sprite indicates a palette number other than 0, it's trying to force that one
-            if displayed_sprite.palette_index > 0 {
-                self.linked_palette(displayed_sprite.palette_index as usize)
+        let palette_used = self.sprite_palette_info(displayed_sprite, palette_number);
+        self.sprite_surface(renderer_params, displayed_sprite, palette_used)
+    }
+    /// Color of a palette index, the index 0 and the indices out of the palette data being transparent
+    fn palette_color(&self, palette_info: &PaletteInfo, color: u8) -> BitmapPixel {
+        // 4 bytes per color: 3 bytes for the 8-bit RGB values and an unused one last
+        let color_index = (palette_info.ldata_offset as usize) + (color as usize) * 4;
+        match self.ldata.get(color_index..color_index + 4) {
+            Some(color_array) if color > 0 && u16::from(color) < palette_info.colors => BitmapPixel::new(color_array[0], color_array[1], color_array[2], u8::MAX),
+            _ => BitmapPixel::new(0, 0, 0, 0),
+        }
+    }
+    /// The 256 colors of the palette used to render a sprite with the palette indices of its image
+    pub fn sprite_index_palette(&self, sprite_number: usize, palette_number: usize) -> Vec<BitmapPixel> {
+        let displayed_sprite = self.linked_sprite(sprite_number);
+        let palette_info = self.sprite_palette_info(displayed_sprite, palette_number);
+        (0..=u8::MAX).map(|color| self.palette_color(palette_info, color)).collect()
+    }
+    /// Check if a sprite is rendered with the palette given to render it, instead of a palette it forces
+    pub fn uses_given_palette(&self, sprite_number: usize) -> bool {
+        self.linked_sprite(sprite_number).palette_index == 0
+    }
+    /// The 256 colors of a palette
+    pub fn palette_index_colors(&self, palette_number: usize) -> Vec<BitmapPixel> {
+        let palette_info = self.linked_palette(palette_number);
+        (0..=u8::MAX).map(|color| self.palette_color(palette_info, color)).collect()
+    }
+    fn sprite_data(&self, sprite_info: &SpriteInfo) -> &[u8] {
+        let data_block = {
+            if sprite_info.uses_tdata {
+                &self.tdata
             }
             else {
-                self.linked_palette(palette_number)
+                &self.ldata
             }
         };
-        self.sprite_surface(renderer_params, displayed_sprite, palette_used)
+        // the first 4 bytes are the size of uncompressed data, so skip them
+        &data_block[((sprite_info.data_offset as usize) + 4)..((sprite_info.data_offset + sprite_info.data_length) as usize)]
     }
     pub fn sprite_surface<R: BitmapRenderer>(&self, renderer_params: R::Initializer, sprite_info: &SpriteInfo, palette_info: &PaletteInfo) -> Result<R, RenderingError<R::Error>> {
         let width = sprite_info.size.0 as u64;
         let height = sprite_info.size.1 as u64;
         let mut surface_renderer = R::initialize_surface(renderer_params, width, height).map_err(RenderingError::renderer_error)?;
-        let colored_pixel = |color: u8| {
-            if color > 0 {
-                // 4 bytes per color: 3 bytes for the 8-bit RGB values and an unused one last
-                let color_index = (palette_info.ldata_offset as usize) + (color as usize) * 4;
-                let color_array = &self.ldata[color_index..color_index + 4];
-                let (r, g, b) = (color_array[0], color_array[1], color_array[2]);
-                BitmapPixel::new(r, g, b, u8::max_value())
-            }
-            else {
-                BitmapPixel::new(0, 0, 0, 0)
+        let colored_pixel = |color: u8| self.palette_color(palette_info, color);
+        let sprite_data = self.sprite_data(sprite_info);
+        match sprite_info.format {
+            ImageFormat::Png8 | ImageFormat::Png24 | ImageFormat::Png32 => {
+                // indexed PNG sprites use the palette of the sprite file, like their palette indices
+                render_png(&mut surface_renderer, sprite_data, width, height, colored_pixel)?;
+            },
+            _ => {
+                let indices = decode_indices(&sprite_info.format, sprite_data, width, height)?;
+                render_colors(&mut surface_renderer, &indices, colored_pixel).map_err(RenderingError::renderer_error)?;
+            },
+        }
+        Ok(surface_renderer)
+    }
+    /// Render the palette indices of a sprite, which fails for the PNG sprites without palette
+    pub fn sprite_index_indexed_surface<R: IndexedBitmapRenderer>(&self, renderer_params: R::Initializer, sprite_number: usize) -> Result<R, RenderingError<R::Error>> {
+        let sprite_info = self.linked_sprite(sprite_number);
+        let width = sprite_info.size.0 as u64;
+        let height = sprite_info.size.1 as u64;
+        let sprite_data = self.sprite_data(sprite_info);
+        let indices = decode_indices(&sprite_info.format, sprite_data, width, height)?;
+        let mut surface_renderer = R::initialize_surface(renderer_params, width, height).map_err(RenderingError::renderer_error)?;
+        render_indices(&mut surface_renderer, &indices).map_err(RenderingError::renderer_error)?;
+        Ok(surface_renderer)
+    }
+}
+
+/// Decode the palette indices of a sprite image, the PNG images without palette having none
+fn decode_indices<T>(format: &ImageFormat, sprite_data: &[u8], width: u64, height: u64) -> Result<Vec<u8>, RenderingError<T>> {
+    let mut indices = IndexBuffer::new(width, height);
+    match format {
+        ImageFormat::Raw => {
+            // raw, uncompressed image
+            for color in sprite_data.iter() {
+                indices.push_run(*color, 1);
             }
-        };
-        let sprite_data = {
-            let data_block = {
-                if sprite_info.uses_tdata {
-                    &self.tdata
+        },
+        ImageFormat::RLE8 => {
+            // Run-Length Encoding with an 8-bit-per-pixel pixmap
+            let mut data_index = 0;
+            while data_index < sprite_data.len() {
+                let first_byte = sprite_data[data_index];
+                if (first_byte & 0xC0) == 0x40 {
+                    // RLE control packet
+                    let run_length = first_byte & 0x3F;
+                    data_index += 1;
+                    let color_byte = sprite_data[data_index];
+                    // output the color for the run length
+                    indices.push_run(color_byte, run_length as u64);
                 }
                 else {
-                    &self.ldata
+                    // output the raw pixel
+                    indices.push_run(first_byte, 1);
                 }
-            };
-            // the first 4 bytes are the size of uncompressed data, so skip them
-            &data_block[((sprite_info.data_offset as usize) + 4)..((sprite_info.data_offset + sprite_info.data_length) as usize)]
-        };
-        match sprite_info.format {
-            ImageFormat::Raw => {
-                // raw, uncompressed image
-                for color in sprite_data.iter() {
-                    R::render_single_pixel(&mut surface_renderer, colored_pixel(*color)).map_err(RenderingError::renderer_error)?;
-                }
-            },
-            ImageFormat::RLE8 => {
-                // Run-Length Encoding with an 8-bit-per-pixel pixmap
-                let mut data_index = 0;
-                while data_index < sprite_data.len() {
-                    let first_byte = sprite_data[data_index];
-                    if (first_byte & 0xC0) == 0x40 {
-                        // RLE control packet
-                        let run_length = first_byte & 0x3F;
-                        data_index += 1;
-                        let color_byte = sprite_data[data_index];
-                        // output the color for the run length
-                        R::render_pixels(&mut surface_renderer, colored_pixel(color_byte), run_length as u64).map_err(RenderingError::renderer_error)?;
-                    }
-                    else {
-                        // output the raw pixel
-                        R::render_single_pixel(&mut surface_renderer, colored_pixel(first_byte)).map_err(RenderingError::renderer_error)?;
-                    }
+                data_index += 1;
+            }
+        },
+        ImageFormat::RLE5 => {
+            // Run-Length Encoding with a 5-bit-per-pixel pixmap
+            let mut data_index = 0;
+            while data_index < sprite_data.len() {
+                let data_length = {
+                    let run_length = sprite_data[data_index];
                     data_index += 1;
-                }
-            },
-            ImageFormat::RLE5 => {
-                // Run-Length Encoding with a 5-bit-per-pixel pixmap
-                let mut data_index = 0;
-                while data_index < sprite_data.len() {
-                    let data_length = {
-                        let run_length = sprite_data[data_index];
-                        data_index += 1;
-                        let (color, data_length) = {
-                            let data_length_byte = sprite_data[data_index];
-                            let color = {
-                                if (data_length_byte & 0x80) > 0 {
-                                    // testing the color bit
-                                    data_index += 1;
-                                    sprite_data[data_index]
-                                }
-                                else {
-                                    // if no color bit: transparency
-                                    0
-                                }
-                            };
-                            // the actual data length is the rest of the byte (without the color bit)
-                            (color, data_length_byte & 0x7F)
+                    let (color, data_length) = {
+                        let data_length_byte = sprite_data[data_index];
+                        let color = {
+                            if (data_length_byte & 0x80) > 0 {
+                                // testing the color bit
+                                data_index += 1;
+                                sprite_data[data_index]
+                            }
+                            else {
+                                // if no color bit: transparency
+                                0
+                            }
                         };
-                        // output the bytes
-                        R::render_pixels(&mut surface_renderer, colored_pixel(color), run_length as u64).map_err(RenderingError::renderer_error)?;
-                        data_length
+                        // the actual data length is the rest of the byte (without the color bit)
+                        (color, data_length_byte & 0x7F)
                     };
-                    // reprocess the output according to the data length
-                    for _ in 0..data_length {
-                        data_index += 1;
-                        let (color, run_length) = {
-                            let data_byte = sprite_data[data_index];
-                            (data_byte & 0x1F, data_byte >> 5)
-                        };
-                        R::render_pixels(&mut surface_renderer, colored_pixel(color), run_length as u64).map_err(RenderingError::renderer_error)?;
-                    }
+                    // output the bytes: the run length is the number of pixels minus one
+                    indices.push_run(color, run_length as u64 + 1);
+                    data_length
+                };
+                // reprocess the output according to the data length
+                for _ in 0..data_length {
                     data_index += 1;
+                    let (color, run_length) = {
+                        let data_byte = sprite_data[data_index];
+                        (data_byte & 0x1F, data_byte >> 5)
+                    };
+                    indices.push_run(color, run_length as u64 + 1);
                 }
-            },
-            ImageFormat::LZ5 => {
-                // LZ5 compression
-                // see the documentation on LZ5 compression: https://web.archive.org/web/20141230125932/http://elecbyte.com/wiki/index.php/LZ5
-                let mut short_lz_packets : u32 = 1;
-                let mut recycled_bits : u8 = 0;
-                let mut data_index = 0;
-                let max_pixels = R::surface_pixel_count(&mut surface_renderer).map_err(RenderingError::renderer_error)?;
-                'data_loop: while data_index < sprite_data.len() {
-                    let control_packet = sprite_data[data_index];
-                    for packet_index in 0..8 {
-                        data_index += 1;
-                        // break if we are above the data size
-                        if data_index >= sprite_data.len() {
-                            break 'data_loop;
-                        }
-                        let lz_packet = {
-                            // flag for the type of this packet: n-th bit of the control packet byte
-                            // if the flag is 0, then it is a Run-Length Encoding packet
-                            // if the flag is 1, then it is a LZ packet
-                            let flag = control_packet & (1 << packet_index);
-                            flag > 0
-                        };
-                        if lz_packet {
-                            // LZ packet, short or long
-                            let lz_packet_byte = sprite_data[data_index];
-                            let mut copy_length = (lz_packet_byte & 0x3F) as u64; // bits 0-5
-                            let mut offset;
-                            if copy_length > 0 {
-                                // short LZ packet if initial copy length is not null
-                                copy_length += 1; // see the docs
-                                // Recycled bits work:
-                                // bits 6-7: recycled bits of short LZ packet 4k + 1
-                                // bits 4-5: recycled bits of short LZ packet 4k + 2
-                                // bits 2-3: recycled bits of short LZ packet 4k + 3
-                                // bits 0-1: recycled bits of short LZ packet 4k + 4
-                                let new_recycled_bits = (lz_packet_byte & 0xC0) >> 6; // top 2 bits
-                                recycled_bits = new_recycled_bits | (recycled_bits << 2);
-                                if short_lz_packets % 4 == 0 {
-                                    // use the recycled bits then
-                                    offset = (recycled_bits as u64) + 1;
-                                    recycled_bits = 0;
-                                }
-                                else {
-                                    data_index += 1;
-                                    offset = (sprite_data[data_index] as u64) + 1;
-                                }
-                                short_lz_packets += 1;
+                data_index += 1;
+            }
+        },
+        ImageFormat::LZ5 => {
+            // LZ5 compression
+            // see the documentation on LZ5 compression: https://web.archive.org/web/20141230125932/http://elecbyte.com/wiki/index.php/LZ5
+            let mut short_lz_packets : u32 = 1;
+            let mut recycled_bits : u8 = 0;
+            let mut data_index = 0;
+            'data_loop: while data_index < sprite_data.len() {
+                let control_packet = sprite_data[data_index];
+                for packet_index in 0..8 {
+                    data_index += 1;
+                    // break if we are above the data size
+                    if data_index >= sprite_data.len() {
+                        break 'data_loop;
+                    }
+                    let lz_packet = {
+                        // flag for the type of this packet: n-th bit of the control packet byte
+                        // if the flag is 0, then it is a Run-Length Encoding packet
+                        // if the flag is 1, then it is a LZ packet
+                        let flag = control_packet & (1 << packet_index);
+                        flag > 0
+                    };
+                    if lz_packet {
+                        // LZ packet, short or long
+                        let lz_packet_byte = sprite_data[data_index];
+                        let mut copy_length = (lz_packet_byte & 0x3F) as u64; // bits 0-5
+                        let mut offset;
+                        if copy_length > 0 {
+                            // short LZ packet if initial copy length is not null
+                            copy_length += 1; // see the docs
+                            // Recycled bits work:
+                            // bits 6-7: recycled bits of short LZ packet 4k + 1
+                            // bits 4-5: recycled bits of short LZ packet 4k + 2
+                            // bits 2-3: recycled bits of short LZ packet 4k + 3
+                            // bits 0-1: recycled bits of short LZ packet 4k + 4
+                            let new_recycled_bits = (lz_packet_byte & 0xC0) >> 6; // top 2 bits
+                            recycled_bits = new_recycled_bits | (recycled_bits << 2);
+                            if short_lz_packets % 4 == 0 {
+                                // use the recycled bits then
+                                offset = (recycled_bits as u64) + 1;
+                                recycled_bits = 0;
                             }
                             else {
-                                // long LZ packet
-                                // since the 0x3F-masked bits are null, there are only the 0xC0-masked bits left
-                                // take the highest 2 bits of the 10-bit offset
-                                offset = 1 + ((sprite_data[data_index] as u64) << 2);
                                 data_index += 1;
-                                // take the lowest 8 bits of the 10-bit offset, plus one
-                                offset = offset | (1 + (sprite_data[data_index] as u64));
-                                data_index += 1;
-                                // value range of the copy length for a long LZ packet: 8 to 263
-                                copy_length = (sprite_data[data_index] as u64) + 3;
-                            }
-                            // to be safer: check if the pixel index is over the surface limit
-                            let start_pixel_index = surface_renderer.stream_position()?;
-                            if start_pixel_index + copy_length > max_pixels {
-                                copy_length = max_pixels.saturating_sub(start_pixel_index);
-                            }
-                            // actually write the surface
-                            // Credits to the Nomen developper:
-                            // if the copy length is greater than the offset, then the copy pointer must go back to the beginning of the source when it reaches the full length
-                            // so that is why the offset has a factor here
-                            if offset > 0 {
-                                while copy_length > 0 {
-                                    let current_copy_length = copy_length.min(offset);
-                                    R::copy_pixels_offset(&mut surface_renderer, current_copy_length, offset).map_err(RenderingError::renderer_error)?;
-                                    copy_length = copy_length.saturating_sub(current_copy_length);
-                                }
-                            }
-                            else {
-                                // the offset should never be null so this case should not be reachable
-                                Err(RenderingError::NullCopyLength)?;
+                                offset = (sprite_data[data_index] as u64) + 1;
                             }
+                            short_lz_packets += 1;
+                        }
+                        else {
+                            // long LZ packet
+                            // since the 0x3F-masked bits are null, there are only the 0xC0-masked bits left
+                            // take the highest 2 bits of the 10-bit offset
+                            offset = (sprite_data[data_index] as u64) << 2;
+                            data_index += 1;
+                            // take the lowest 8 bits of the 10-bit offset, plus one
+                            offset = (offset | (sprite_data[data_index] as u64)) + 1;
+                            data_index += 1;
+                            // value range of the copy length for a long LZ packet: 8 to 263
+                            copy_length = (sprite_data[data_index] as u64) + 3;
+                        }
+                        // Credits to the Nomen developper:
+                        // if the copy length is greater than the offset, then the copy pointer must go back to the beginning of the source when it reaches the full length
+                        if offset > 0 {
+                            indices.copy(offset, copy_length)?;
                         }
                         else {
-                            // RLE packet, short or long
-                            let (color, run_length) = {
-                                let rle_byte = sprite_data[data_index];
-                                let color = rle_byte & 0x1F;
-                                let mut run_length = (rle_byte & 0xE0) as u64;
-                                if run_length > 0 {
-                                    // short RLE byte
-                                    run_length = run_length >> 5;
-                                }
-                                else {
-                                    // long RLE byte
-                                    data_index += 1;
-                                    run_length = 8 + (sprite_data[data_index] as u64);
-                                }
-                                (color, run_length)
-                            };
-                            R::render_pixels(&mut surface_renderer, colored_pixel(color), run_length).map_err(RenderingError::renderer_error)?;
+                            // the offset should never be null so this case should not be reachable
+                            Err(RenderingError::NullCopyLength)?;
                         }
                     }
-                    data_index += 1;
+                    else {
+                        // RLE packet, short or long
+                        let (color, run_length) = {
+                            let rle_byte = sprite_data[data_index];
+                            let color = rle_byte & 0x1F;
+                            let mut run_length = (rle_byte & 0xE0) as u64;
+                            if run_length > 0 {
+                                // short RLE byte
+                                run_length = run_length >> 5;
+                            }
+                            else {
+                                // long RLE byte
+                                data_index += 1;
+                                run_length = 8 + (sprite_data[data_index] as u64);
+                            }
+                            (color, run_length)
+                        };
+                        indices.push_run(color, run_length);
+                    }
                 }
-            },
-            ImageFormat::Invalid(n) => Err(RenderingError::InvalidImageFormat(n))?,
-        }
-        Ok(surface_renderer)
+                data_index += 1;
+            }
+        },
+        ImageFormat::Png8 | ImageFormat::Png24 | ImageFormat::Png32 => {
+            return png_indices(sprite_data, width, height)?.ok_or(RenderingError::NoPaletteIndices);
+        },
+        ImageFormat::Invalid(n) => Err(RenderingError::InvalidImageFormat(*n))?,
     }
+    Ok(indices.into_indices())
 }
 
 impl SffData for Data {
@@ -308,8 +376,109 @@ impl SffData for Data {
         self.palettes.len()
     }
     fn render_sprite<R: BitmapRenderer>(&self, renderer_params: R::Initializer, group_index: u16, image_index: u16, palette_index: usize) -> Result<R, crate::RenderingError<R::Error>> {
-        let group = self.groups.get(&group_index).ok_or_else(|| RenderingError::InvalidSpriteGroupNumber { invalid_index: group_index, sprite_group_count: self.groups.len() })?;
-        let sprite_index = *group.0.get(&image_index).ok_or_else(|| RenderingError::InvalidSpriteNumber { invalid_index: group_index, sprite_count: self.groups.len() })?;
+        let sprite_index = self.group_sprite_index(group_index, image_index)?;
         self.sprite_index_surface(renderer_params, sprite_index, palette_index).map_err(Into::into)
     }
+    fn render_sprite_indexed<R: IndexedBitmapRenderer>(&self, renderer_params: R::Initializer, group_index: u16, image_index: u16) -> Result<R, crate::RenderingError<R::Error>> {
+        let sprite_index = self.group_sprite_index(group_index, image_index)?;
+        self.sprite_index_indexed_surface(renderer_params, sprite_index).map_err(Into::into)
+    }
+    fn sprite_palette(&self, group_index: u16, image_index: u16, palette_index: usize) -> Option<Vec<BitmapPixel>> {
+        let sprite_index = self.group_sprite_index::<()>(group_index, image_index).ok()?;
+        (palette_index < self.palettes.len()).then(|| self.sprite_index_palette(sprite_index, palette_index))
+    }
+    fn sprite_mask_color(&self, group_index: u16, image_index: u16, palette_index: usize) -> Option<BitmapPixel> {
+        let sprite_index = self.group_sprite_index::<()>(group_index, image_index).ok()?;
+        if palette_index >= self.palettes.len() {
+            return None;
+        }
+        let palette_info = self.sprite_palette_info(self.linked_sprite(sprite_index), palette_index);
+        let color_index = palette_info.ldata_offset as usize;
+        let color = self.ldata.get(color_index..color_index + 3).filter(|_| palette_info.colors > 0)?;
+        Some(BitmapPixel::new(color[0], color[1], color[2], u8::MAX))
+    }
+    fn uses_selected_palette(&self, group_index: u16, image_index: u16) -> bool {
+        self.group_sprite_index::<()>(group_index, image_index).is_ok_and(|sprite_index| self.uses_given_palette(sprite_index))
+    }
+    fn palette_colors(&self, palette_index: usize) -> Option<Vec<BitmapPixel>> {
+        (palette_index < self.palettes.len()).then(|| self.palette_index_colors(palette_index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bitmap::test_renderer::{TestIndexedRenderer, TestRenderer};
+
+    const INDEXED8_PNG: &[u8] = include_bytes!("../../../fixtures/indexed8.png");
+    const INDEXED2_PNG: &[u8] = include_bytes!("../../../fixtures/indexed2.png");
+    const RGB24_PNG: &[u8] = include_bytes!("../../../fixtures/rgb24.png");
+    const RGBA32_PNG: &[u8] = include_bytes!("../../../fixtures/rgba32.png");
+
+    /// Sprite data with a palette of 3 colors, and the PNG sprites with their formats
+    fn png_data(sprites: &[(ImageFormat, (u16, u16), &[u8])]) -> Data {
+        // white, yellow and cyan for the colors 1 to 3
+        let mut ldata = vec![0, 0, 0, 0, 255, 255, 255, 0, 255, 255, 0, 0, 0, 255, 255, 0];
+        let palettes = vec![PaletteInfo { group: 1, item: 1, colors: 3, linked_index: 0, ldata_offset: 0, ldata_length: 12 }];
+        let mut sprite_infos = Vec::new();
+        for (format, size, png) in sprites {
+            let data_offset = ldata.len() as u32;
+            ldata.extend_from_slice(&(png.len() as u32).to_le_bytes());
+            ldata.extend_from_slice(png);
+            sprite_infos.push(SpriteInfo {
+                size: *size,
+                axis: (0, 0),
+                linked_index: 0,
+                format: match format {
+                    ImageFormat::Png8 => ImageFormat::Png8,
+                    ImageFormat::Png24 => ImageFormat::Png24,
+                    _ => ImageFormat::Png32,
+                },
+                color_depth: 8,
+                data_offset,
+                data_length: png.len() as u32 + 4,
+                palette_index: 0,
+                uses_tdata: false,
+            });
+        }
+        let groups = BTreeMap::from([(0, GroupInfo((0..sprites.len()).map(|index| (index as u16, index)).collect()))]);
+        Data::new(sprite_infos, groups, palettes, ldata, Vec::new())
+    }
+
+    fn render(data: &Data, image: u16) -> Vec<BitmapPixel> {
+        data.render_sprite::<TestRenderer>((), 0, image, 0).unwrap().pixels
+    }
+
+    #[test]
+    fn render_indexed_png() {
+        let data = png_data(&[(ImageFormat::Png8, (3, 2), INDEXED8_PNG), (ImageFormat::Png8, (3, 2), INDEXED2_PNG)]);
+        let transparent = BitmapPixel::new(0, 0, 0, 0);
+        let white = BitmapPixel::new(255, 255, 255, 255);
+        let yellow = BitmapPixel::new(255, 255, 0, 255);
+        // the color 3 is not in the palette of the sprite file: transparent, like with the other formats
+        let expected = vec![transparent, white, yellow, transparent, yellow, white];
+        assert_eq!(expected, render(&data, 0));
+        assert_eq!(expected, render(&data, 1));
+        // the PNG images bigger than the sprites are cropped
+        let data = png_data(&[(ImageFormat::Png8, (2, 1), INDEXED8_PNG)]);
+        assert_eq!(vec![transparent, white], render(&data, 0));
+        let indices = data.render_sprite_indexed::<TestIndexedRenderer>((), 0, 0).unwrap().indices;
+        assert_eq!(vec![0, 1], indices);
+    }
+
+    #[test]
+    fn render_true_color_png() {
+        let data = png_data(&[(ImageFormat::Png24, (2, 2), RGB24_PNG), (ImageFormat::Png32, (2, 2), RGBA32_PNG)]);
+        assert_eq!(vec![
+            BitmapPixel::new(255, 0, 0, 255), BitmapPixel::new(0, 255, 0, 255),
+            BitmapPixel::new(0, 0, 255, 255), BitmapPixel::new(10, 20, 30, 255),
+        ], render(&data, 0));
+        assert_eq!(vec![
+            BitmapPixel::new(255, 0, 0, 255), BitmapPixel::new(0, 255, 0, 128),
+            BitmapPixel::new(0, 0, 255, 0), BitmapPixel::new(10, 20, 30, 40),
+        ], render(&data, 1));
+        // invalid PNG data
+        let data = png_data(&[(ImageFormat::Png32, (2, 2), &RGBA32_PNG[..20])]);
+        assert!(data.render_sprite::<TestRenderer>((), 0, 0, 0).is_err());
+    }
 }
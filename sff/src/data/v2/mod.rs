@@ -8,12 +8,18 @@ pub use self::data::{Data, GroupInfo, ImageFormat, PaletteInfo, SpriteInfo};
 mod sff;
 pub use self::sff::read_sff;
 
+mod png;
+
 #[derive(Debug, Error)]
 pub enum RenderingError<R> {
     #[error("Renderer error")]
     RendererError(R),
     #[error("Invalid SFFv2 sprite data")]
     IoError(#[from] io::Error),
+    #[error("Invalid PNG sprite data")]
+    PngError(#[from] ::png::DecodingError),
+    #[error("The PNG sprite has no palette indices")]
+    NoPaletteIndices,
     #[error("Null copy length")]
     NullCopyLength,
     #[error("Invalid image format {0}")]
@@ -0,0 +1,70 @@
+use std::io::SeekFrom;
+use png::{ColorType, Decoder, Transformations};
+use crate::bitmap::{BitmapPixel, BitmapRenderer, IndexBuffer};
+use super::RenderingError;
+
+/// Render PNG sprite data on a surface of the given size, the pixels out of the surface being dropped.
+///
+/// The pixels of indexed images get their colors from `indexed_pixel`, the palette of the PNG data being ignored like
+/// when rendering the palette indices. The pixels of the other images are rendered with their own colors.
+pub fn render_png<R: BitmapRenderer>(surface_renderer: &mut R, png_data: &[u8], surface_width: u64, surface_height: u64, indexed_pixel: impl Fn(u8) -> BitmapPixel) -> Result<(), RenderingError<R::Error>> {
+    let indexed = {
+        let reader = Decoder::new(png_data).read_info()?;
+        reader.info().color_type == ColorType::Indexed
+    };
+    let mut decoder = Decoder::new(png_data);
+    if !indexed {
+        // 8-bit RGB, RGBA, grayscale or grayscale with alpha
+        decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+    }
+    let mut reader = decoder.read_info()?;
+    let mut image_data = vec![0; reader.output_buffer_size()];
+    let output_info = reader.next_frame(&mut image_data)?;
+    let width = (output_info.width as u64).min(surface_width) as usize;
+    let height = (output_info.height as u64).min(surface_height) as usize;
+    let bit_depth = output_info.bit_depth as usize;
+    for (y, line) in image_data.chunks_exact(output_info.line_size).take(height).enumerate() {
+        surface_renderer.seek(SeekFrom::Start(y as u64 * surface_width))?;
+        for x in 0..width {
+            let pixel = match output_info.color_type {
+                ColorType::Indexed => indexed_pixel(unpack_index(line, x, bit_depth)),
+                ColorType::Rgb => BitmapPixel::new(line[x * 3], line[x * 3 + 1], line[x * 3 + 2], u8::MAX),
+                ColorType::Rgba => BitmapPixel::new(line[x * 4], line[x * 4 + 1], line[x * 4 + 2], line[x * 4 + 3]),
+                ColorType::Grayscale => BitmapPixel::new(line[x], line[x], line[x], u8::MAX),
+                ColorType::GrayscaleAlpha => BitmapPixel::new(line[x * 2], line[x * 2], line[x * 2], line[x * 2 + 1]),
+            };
+            R::render_single_pixel(surface_renderer, pixel).map_err(RenderingError::renderer_error)?;
+        }
+    }
+    Ok(())
+}
+
+/// Palette index of a pixel in a line of an indexed PNG image
+fn unpack_index(line: &[u8], x: usize, bit_depth: usize) -> u8 {
+    // indices of less than 8 bits are packed, from the highest bits
+    let bit_index = x * bit_depth;
+    let shift = 8 - bit_depth - bit_index % 8;
+    (line[bit_index / 8] >> shift) & ((1u16 << bit_depth) - 1) as u8
+}
+
+/// Palette indices of PNG sprite data for a surface of the given size, or `None` if the image is not indexed
+pub fn png_indices(png_data: &[u8], surface_width: u64, surface_height: u64) -> Result<Option<Vec<u8>>, png::DecodingError> {
+    let mut reader = Decoder::new(png_data).read_info()?;
+    if reader.info().color_type != ColorType::Indexed {
+        return Ok(None);
+    }
+    let mut image_data = vec![0; reader.output_buffer_size()];
+    let output_info = reader.next_frame(&mut image_data)?;
+    let mut indices = IndexBuffer::new(surface_width, surface_height);
+    let width = (output_info.width as u64).min(surface_width) as usize;
+    let height = (output_info.height as u64).min(surface_height) as usize;
+    let bit_depth = output_info.bit_depth as usize;
+    for line in image_data.chunks_exact(output_info.line_size).take(height) {
+        for x in 0..width {
+            indices.push_run(unpack_index(line, x, bit_depth), 1);
+        }
+        // the pixels out of the PNG image are transparent
+        indices.push_run(0, surface_width - width as u64);
+    }
+    Ok(Some(indices.into_indices()))
+}
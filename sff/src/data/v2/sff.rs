@@ -9,14 +9,6 @@ struct RawSpriteInfo {
     sprite_info: SpriteInfo,
 }
 
-struct RawPaletteInfo {
-    #[allow(dead_code)]
-    group: u16,
-    #[allow(dead_code)]
-    item: u16,
-    palette_info: PaletteInfo,
-}
-
 pub fn read_sff<T: Read + Seek>(mut reader: T) -> Result<Data, Error> {
     // 8 reserved bytes
     reader.seek(SeekFrom::Current(8))?;
@@ -62,10 +54,10 @@ pub fn read_sff<T: Read + Seek>(mut reader: T) -> Result<Data, Error> {
             let item = reader.read_u16::<LittleEndian>()?;
             let width = reader.read_u16::<LittleEndian>()?;
             let height = reader.read_u16::<LittleEndian>()?;
-            let axis_x = reader.read_u16::<LittleEndian>()?;
-            let axis_y = reader.read_u16::<LittleEndian>()?;
+            let axis_x = reader.read_i16::<LittleEndian>()?;
+            let axis_y = reader.read_i16::<LittleEndian>()?;
             let linked_index = reader.read_u16::<LittleEndian>()?;
-            // format: 0 -> raw, 1 -> invalid, 2 -> RLE8, 3 -> RLE5, 4 -> LZ5
+            // format: 0 -> raw, 1 -> invalid, 2 -> RLE8, 3 -> RLE5, 4 -> LZ5, 10 -> PNG8, 11 -> PNG24, 12 -> PNG32
             let format = {
                 let format_byte = reader.read_u8()?;
                 match format_byte {
@@ -73,6 +65,9 @@ pub fn read_sff<T: Read + Seek>(mut reader: T) -> Result<Data, Error> {
                     2 => ImageFormat::RLE8,
                     3 => ImageFormat::RLE5,
                     4 => ImageFormat::LZ5,
+                    10 => ImageFormat::Png8,
+                    11 => ImageFormat::Png24,
+                    12 => ImageFormat::Png32,
                     n => ImageFormat::Invalid(n),
                 }
             };
@@ -102,7 +97,7 @@ pub fn read_sff<T: Read + Seek>(mut reader: T) -> Result<Data, Error> {
         sprites
     };
     // reading the palettes data
-    let raw_palettes = {
+    let palettes = {
         let mut palettes = Vec::new();
         reader.seek(SeekFrom::Start(first_palette_offset as u64))?;
         for _ in 0..(palette_number as usize) {
@@ -113,16 +108,13 @@ pub fn read_sff<T: Read + Seek>(mut reader: T) -> Result<Data, Error> {
             let linked_index = reader.read_u16::<LittleEndian>()?;
             let ldata_offset = reader.read_u32::<LittleEndian>()?;
             let ldata_length = reader.read_u32::<LittleEndian>()?;
-            let palette_info = PaletteInfo {
+            palettes.push(PaletteInfo {
+                group,
+                item,
                 colors,
                 linked_index,
                 ldata_offset,
                 ldata_length,
-            };
-            palettes.push(RawPaletteInfo {
-                group,
-                item,
-                palette_info
             });
         }
         palettes
@@ -137,12 +129,5 @@ pub fn read_sff<T: Read + Seek>(mut reader: T) -> Result<Data, Error> {
         }
         (groups_map, sprites)
     };
-    let palettes = {
-        let mut palettes = Vec::new();
-        for raw_palette in raw_palettes {
-            palettes.push(raw_palette.palette_info);
-        }
-        palettes
-    };
     Ok(Data::new(sprites, groups, palettes, ldata, tdata))
 }
@@ -1,4 +1,5 @@
 use std::io;
+use std::num::IntErrorKind;
 use thiserror::Error;
 
 use crate::{v1, v2};
@@ -19,4 +20,55 @@ pub enum RenderingError<T> {
     SffV1Error(#[from] v1::RenderingError<T>),
     #[error(transparent)]
     SffV2Error(#[from] v2::RenderingError<T>),
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Error)]
+pub enum WritingError {
+    #[error("Error writing sprite data")]
+    IoError(#[from] io::Error),
+    #[error("Sprite {sprite_index} is linked to the invalid sprite {linked_index} ({sprite_count} sprites available)")]
+    InvalidLinkedSprite {
+        sprite_index: usize,
+        linked_index: usize,
+        sprite_count: usize,
+    },
+    #[error("Sprite {0} is in a cycle of linked sprites")]
+    LinkCycle(usize),
+    #[error("Sprite {sprite_index} has {pixel_count} pixels instead of {width} x {height}")]
+    InvalidPixelCount {
+        sprite_index: usize,
+        width: u16,
+        height: u16,
+        pixel_count: usize,
+    },
+    #[error("Sprite {sprite_index} uses the palette {palette_index} ({palette_count} palettes available)")]
+    PaletteNotFound {
+        sprite_index: usize,
+        palette_index: usize,
+        palette_count: usize,
+    },
+    #[error("{0} sprites, more than the 65536 sprites with a 16-bit index")]
+    TooManySprites(usize),
+    #[error("{0} palettes, more than the 65536 palettes with a 16-bit index")]
+    TooManyPalettes(usize),
+    #[error("Palette {palette_index} has {color_count} colors, more than 256")]
+    TooManyColors {
+        palette_index: usize,
+        color_count: usize,
+    },
+}
+
+/// Error of the decoding of the palette indices of a sprite into the image of a `SpriteModel`
+#[derive(Debug, Error)]
+pub enum ImageDecodingError {
+    #[error("Error decoding sprite image")]
+    IoError(#[from] io::Error),
+    #[error("Invalid sprite image size: {0:?}")]
+    InvalidSize(IntErrorKind),
+}
+
+impl From<IntErrorKind> for ImageDecodingError {
+    fn from(err: IntErrorKind) -> Self {
+        ImageDecodingError::InvalidSize(err)
+    }
+}
@@ -5,3 +5,5 @@ mod error;
 pub use self::error::*;
 
 pub mod bitmap;
+
+pub mod writer;
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+/// Compression of the images in a SFF v2 file
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compression {
+    Raw,
+    RLE8,
+    RLE5,
+    /// LZ5 only encodes colors below 32: the images with other colors are compressed with RLE8
+    LZ5,
+}
+
+impl Compression {
+    /// Format byte of the sprite node
+    pub(crate) fn format_byte(self) -> u8 {
+        match self {
+            Compression::Raw => 0,
+            Compression::RLE8 => 2,
+            Compression::RLE5 => 3,
+            Compression::LZ5 => 4,
+        }
+    }
+}
+
+/// Number of colors of the RLE packets of LZ5 and of the short packets of RLE5
+const FIVE_BIT_COLORS: u8 = 32;
+
+/// Color depth of an image compressed with a compression: 5 bits if every color fits in the packets of RLE5 and LZ5
+pub(crate) fn color_depth(pixels: &[u8], compression: Compression) -> u8 {
+    match compression {
+        Compression::RLE5 | Compression::LZ5 if pixels.iter().all(|&color| color < FIVE_BIT_COLORS) => 5,
+        _ => 8,
+    }
+}
+
+/// Compress the pixels of an image, and return the compression that was used
+pub(crate) fn compress(pixels: &[u8], compression: Compression) -> (Compression, Vec<u8>) {
+    match compression {
+        Compression::Raw => (compression, pixels.to_vec()),
+        Compression::RLE8 => (compression, encode_rle8(pixels)),
+        Compression::RLE5 => (compression, encode_rle5(pixels)),
+        Compression::LZ5 if pixels.iter().all(|&color| color < FIVE_BIT_COLORS) => (compression, encode_lz5(pixels)),
+        Compression::LZ5 => (Compression::RLE8, encode_rle8(pixels)),
+    }
+}
+
+/// Runs of the same color: color and length
+fn runs(pixels: &[u8]) -> Vec<(u8, usize)> {
+    let mut runs: Vec<(u8, usize)> = Vec::new();
+    for &color in pixels {
+        match runs.last_mut() {
+            Some((run_color, length)) if *run_color == color => *length += 1,
+            _ => runs.push((color, 1)),
+        }
+    }
+    runs
+}
+
+/// RLE8: a byte `0b01xxxxxx` is followed by a color repeated `xxxxxx` times, other bytes are single pixels
+fn encode_rle8(pixels: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for (color, mut length) in runs(pixels) {
+        while length > 0 {
+            let run_length = length.min(0x3F);
+            if run_length == 1 && (color & 0xC0) != 0x40 {
+                data.push(color);
+            }
+            else {
+                data.extend_from_slice(&[0x40 | run_length as u8, color]);
+            }
+            length -= run_length;
+        }
+    }
+    data
+}
+
+/// RLE5: packets of a run of up to 256 pixels of any color, followed by up to 127 runs of up to 8 pixels of colors
+/// below 32, each one in a single byte
+fn encode_rle5(pixels: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let runs = runs(pixels);
+    let mut run_index = 0;
+    let mut remaining_length = runs.first().map(|run| run.1).unwrap_or(0);
+    while run_index < runs.len() {
+        let color = runs[run_index].0;
+        let head_length = remaining_length.min(256);
+        remaining_length -= head_length;
+        if remaining_length == 0 {
+            run_index += 1;
+            remaining_length = runs.get(run_index).map(|run| run.1).unwrap_or(0);
+        }
+        // short runs following the first run
+        let mut short_runs = Vec::new();
+        while run_index < runs.len() && short_runs.len() < 0x7F && runs[run_index].0 < FIVE_BIT_COLORS && remaining_length <= 8 {
+            short_runs.push(((remaining_length - 1) as u8) << 5 | runs[run_index].0);
+            run_index += 1;
+            remaining_length = runs.get(run_index).map(|run| run.1).unwrap_or(0);
+        }
+        // the color 0 is the default color of the first run
+        let color_bit = if color != 0 { 0x80 } else { 0 };
+        data.push((head_length - 1) as u8);
+        data.push(color_bit | short_runs.len() as u8);
+        if color != 0 {
+            data.push(color);
+        }
+        data.extend_from_slice(&short_runs);
+    }
+    data
+}
+
+/// Packet of LZ5 compressed data
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Lz5Packet {
+    /// Color below 32 repeated from 1 to 263 times
+    Run { color: u8, length: usize },
+    /// Copy of previous pixels, from 1 to 1024 pixels before, of 2 to 258 pixels
+    Copy { offset: usize, length: usize },
+}
+
+const LZ5_MAX_RUN_LENGTH: usize = 263;
+const LZ5_MAX_OFFSET: usize = 1024;
+const LZ5_MAX_COPY_LENGTH: usize = 258;
+/// Maximum offset and length of the short copy packets
+const LZ5_SHORT_MAX_OFFSET: usize = 256;
+const LZ5_SHORT_MAX_COPY_LENGTH: usize = 64;
+/// Number of previous positions with the same first pixels tested to find a copy
+const LZ5_MAX_CANDIDATES: usize = 32;
+
+/// Split the pixels into run and copy packets, choosing the longest packet at each position
+fn lz5_packets(pixels: &[u8]) -> Vec<Lz5Packet> {
+    let mut packets = Vec::new();
+    // positions of the previous pairs of pixels, the most recent last
+    let mut pair_positions: HashMap<(u8, u8), Vec<usize>> = HashMap::new();
+    let mut position = 0;
+    while position < pixels.len() {
+        let color = pixels[position];
+        let run_length = pixels[position..].iter().take(LZ5_MAX_RUN_LENGTH).take_while(|&&c| c == color).count();
+        let mut copy = None;
+        if position + 1 < pixels.len() {
+            let candidates = pair_positions.get(&(pixels[position], pixels[position + 1])).map(Vec::as_slice).unwrap_or_default();
+            for &candidate in candidates.iter().rev().take(LZ5_MAX_CANDIDATES) {
+                let offset = position - candidate;
+                if offset > LZ5_MAX_OFFSET {
+                    break;
+                }
+                // the copy can overlap the copied pixels
+                let length = (0..LZ5_MAX_COPY_LENGTH.min(pixels.len() - position))
+                    .take_while(|&i| pixels[candidate + i] == pixels[position + i])
+                    .count();
+                // long copies need at least 3 pixels
+                let valid = length >= 3 || (length == 2 && offset <= LZ5_SHORT_MAX_OFFSET);
+                if valid && copy.map(|(_, best_length)| length > best_length).unwrap_or(true) {
+                    copy = Some((offset, length));
+                }
+            }
+        }
+        let packet = match copy {
+            Some((offset, length)) if length > run_length => Lz5Packet::Copy { offset, length },
+            _ => Lz5Packet::Run { color, length: run_length },
+        };
+        let length = match packet {
+            Lz5Packet::Run { length, .. } | Lz5Packet::Copy { length, .. } => length,
+        };
+        for pair_position in position..position + length {
+            if pair_position + 1 < pixels.len() {
+                pair_positions.entry((pixels[pair_position], pixels[pair_position + 1])).or_default().push(pair_position);
+            }
+        }
+        packets.push(packet);
+        position += length;
+    }
+    packets
+}
+
+/// LZ5: groups of 8 packets, each group starting with a byte of flags telling which packets are copies.
+///
+/// Every fourth short copy packet has no offset byte: its offset is made of the 2 highest bits of the 4 short copy
+/// packets ending with it.
+fn encode_lz5(pixels: &[u8]) -> Vec<u8> {
+    let packets = lz5_packets(pixels);
+    // offsets of the fourth short copy packets, split in the previous short copy packets
+    let short_copy_offsets: Vec<usize> = packets.iter()
+        .filter_map(|packet| match *packet {
+            Lz5Packet::Copy { offset, length } if is_short_copy(offset, length) => Some(offset),
+            _ => None,
+        })
+        .collect();
+    let mut data = Vec::new();
+    let mut short_copy_count = 0;
+    for packet_group in packets.chunks(8) {
+        let flags_index = data.len();
+        data.push(0);
+        for (packet_index, packet) in packet_group.iter().enumerate() {
+            match *packet {
+                Lz5Packet::Run { color, length } if length < 8 => data.push((length as u8) << 5 | color),
+                Lz5Packet::Run { color, length } => data.extend_from_slice(&[color, (length - 8) as u8]),
+                Lz5Packet::Copy { offset, length } => {
+                    data[flags_index] |= 1 << packet_index;
+                    if is_short_copy(offset, length) {
+                        // offset of the fourth packet of the current group of 4 short copy packets
+                        let group_start = short_copy_count / 4 * 4;
+                        let recycled_offset = short_copy_offsets.get(group_start + 3).map(|offset| offset - 1).unwrap_or(0);
+                        let position_in_group = short_copy_count % 4;
+                        let recycled_bits = ((recycled_offset >> (6 - position_in_group * 2)) & 0x03) as u8;
+                        data.push(recycled_bits << 6 | (length - 1) as u8);
+                        if position_in_group != 3 {
+                            data.push((offset - 1) as u8);
+                        }
+                        short_copy_count += 1;
+                    }
+                    else {
+                        let offset = offset - 1;
+                        data.extend_from_slice(&[((offset >> 2) & 0xC0) as u8, offset as u8, (length - 3) as u8]);
+                    }
+                },
+            }
+        }
+    }
+    data
+}
+
+fn is_short_copy(offset: usize, length: usize) -> bool {
+    offset <= LZ5_SHORT_MAX_OFFSET && length <= LZ5_SHORT_MAX_COPY_LENGTH
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lz5_copy_packets() {
+        let pixels: Vec<u8> = [1, 2, 3, 4].repeat(20).into_iter().chain([5; 300]).collect();
+        let packets = lz5_packets(&pixels);
+        assert_eq!(&[
+            Lz5Packet::Run { color: 1, length: 1 },
+            Lz5Packet::Run { color: 2, length: 1 },
+            Lz5Packet::Run { color: 3, length: 1 },
+            Lz5Packet::Run { color: 4, length: 1 },
+            Lz5Packet::Copy { offset: 4, length: 76 },
+            Lz5Packet::Run { color: 5, length: 263 },
+            Lz5Packet::Run { color: 5, length: 37 },
+        ], &packets[..]);
+    }
+}
@@ -0,0 +1,10 @@
+//! Writing of sprite files from an in-memory model of their sprites and palettes.
+
+mod model;
+pub use self::model::*;
+
+mod compression;
+pub use self::compression::Compression;
+
+mod v1;
+mod v2;
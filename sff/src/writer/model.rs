@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::num::IntErrorKind;
+use crate::bitmap::IndexedBitmapRenderer;
+use crate::{ImageDecodingError, RenderingError, SpriteFile, WritingError};
+use super::{v1, v2, Compression};
+
+/// Image of a sprite: 8-bit palette indices, row by row, the color 0 being transparent.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct IndexedImage {
+    pub width: u16,
+    pub height: u16,
+    pub pixels: Vec<u8>,
+}
+
+impl IndexedBitmapRenderer for IndexedImage {
+    type Error = ImageDecodingError;
+    type Initializer = ();
+    fn initialize_surface(_initializer: Self::Initializer, width: u64, height: u64) -> Result<Self, Self::Error> {
+        let size = |length: u64| u16::try_from(length).map_err(|_| IntErrorKind::PosOverflow);
+        Ok(IndexedImage {
+            width: size(width)?,
+            height: size(height)?,
+            pixels: Vec::with_capacity((width * height) as usize),
+        })
+    }
+    fn render_indices(&mut self, index: u8, count: u64) -> Result<(), Self::Error> {
+        self.pixels.extend(std::iter::repeat_n(index, count as usize));
+        Ok(())
+    }
+}
+
+/// Image of a sprite, or the sprite it shares its image with
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SpriteContent {
+    Image(IndexedImage),
+    /// Index of the sprite in the model
+    Linked(usize),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SpriteEntry {
+    pub group: u16,
+    pub image: u16,
+    pub axis: (i16, i16),
+    pub content: SpriteContent,
+    /// Index of the palette in the model, the linked sprites using the palette of the sprite with their image
+    pub palette_index: usize,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PaletteEntry {
+    pub group: u16,
+    pub item: u16,
+    /// RGB colors, 256 at most
+    pub colors: Vec<[u8; 3]>,
+}
+
+/// Sprites and palettes to write in a sprite file.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct SpriteModel {
+    pub sprites: Vec<SpriteEntry>,
+    pub palettes: Vec<PaletteEntry>,
+}
+
+impl SpriteModel {
+    pub fn new() -> SpriteModel {
+        SpriteModel::default()
+    }
+    /// Model of the sprites and palettes of a sprite file, the sprites being ordered by group and image numbers.
+    ///
+    /// The palettes of a SFF v1 file are its external palettes, used by the sprites without their own palette, followed
+    /// by the palettes at the end of the PCX data of the other sprites. The true color PNG sprites of SFF v2 files have
+    /// no palette indices and fail to be imported.
+    pub fn from_sprite_file(sprite_file: &SpriteFile) -> Result<SpriteModel, RenderingError<ImageDecodingError>> {
+        let mut model = SpriteModel::new();
+        for (palette_index, palette) in sprite_file.palettes().into_iter().enumerate() {
+            let (group, item) = palette.id.unwrap_or((1, palette_index as u16 + 1));
+            let color_count = match sprite_file {
+                SpriteFile::V1(_) => palette.color_count,
+                SpriteFile::V2(data) => data.linked_palette(palette_index).colors as usize,
+            };
+            let colors = sprite_file.palette_colors(palette_index).unwrap_or_default();
+            model.add_palette(group, item, colors.iter().take(color_count).map(|color| [color.r(), color.g(), color.b()]).collect());
+        }
+        let sprites = sprite_file.sprites();
+        let model_indices: HashMap<usize, usize> = sprites.iter().enumerate().map(|(model_index, sprite)| (sprite.index, model_index)).collect();
+        let mut own_palettes: HashMap<Vec<[u8; 3]>, usize> = HashMap::new();
+        for sprite in sprites.iter() {
+            // the sprites linked to a sprite without group and image numbers get a copy of its image
+            let content = match sprite.linked_index.and_then(|linked_index| model_indices.get(&linked_index)) {
+                Some(&model_index) => SpriteContent::Linked(model_index),
+                None => {
+                    let mut image = sprite_file.render_sprite_indexed::<IndexedImage>((), sprite.group, sprite.image)?.surface;
+                    image.pixels.resize(image.width as usize * image.height as usize, 0);
+                    SpriteContent::Image(image)
+                },
+            };
+            let palette_index = match sprite_file {
+                SpriteFile::V1(data) => match data.linked_sprite(sprite.index) {
+                    Some(image_index) if !data.uses_general_palette(image_index) => {
+                        let general_palette = crate::v1::Palette { colors: [crate::v1::Color::Transparent; crate::v1::PALETTE_COLOR_COUNT] };
+                        let colors = data.sprite_index_palette(image_index, &general_palette).iter().map(|color| [color.r(), color.g(), color.b()]).collect();
+                        *own_palettes.entry(colors).or_insert_with_key(|colors| model.add_palette(1, model.palettes.len() as u16 + 1, colors.clone()))
+                    },
+                    _ => 0,
+                },
+                SpriteFile::V2(_) => sprite.palette_index.unwrap_or(0),
+            };
+            model.add_sprite(sprite.group, sprite.image, sprite.axis, content, palette_index);
+        }
+        Ok(model)
+    }
+    /// Add a palette and return its index
+    pub fn add_palette(&mut self, group: u16, item: u16, colors: Vec<[u8; 3]>) -> usize {
+        self.palettes.push(PaletteEntry {
+            group,
+            item,
+            colors,
+        });
+        self.palettes.len() - 1
+    }
+    /// Replace the colors of a palette, and return false if there is no palette with that index
+    pub fn replace_palette(&mut self, palette_index: usize, colors: Vec<[u8; 3]>) -> bool {
+        match self.palettes.get_mut(palette_index) {
+            Some(palette) => {
+                palette.colors = colors;
+                true
+            },
+            None => false,
+        }
+    }
+    /// Add a sprite and return its index
+    pub fn add_sprite(&mut self, group: u16, image: u16, axis: (i16, i16), content: SpriteContent, palette_index: usize) -> usize {
+        self.sprites.push(SpriteEntry {
+            group,
+            image,
+            axis,
+            content,
+            palette_index,
+        });
+        self.sprites.len() - 1
+    }
+    /// Link the sprites with the same image and palette as a previous sprite to that sprite, and return the number of
+    /// sprites that were linked
+    pub fn deduplicate(&mut self) -> usize {
+        let mut first_sprites: HashMap<(&IndexedImage, usize), usize> = HashMap::new();
+        let mut links = Vec::new();
+        for (sprite_index, sprite) in self.sprites.iter().enumerate() {
+            if let SpriteContent::Image(image) = &sprite.content {
+                let first_sprite = *first_sprites.entry((image, sprite.palette_index)).or_insert(sprite_index);
+                if first_sprite != sprite_index {
+                    links.push((sprite_index, first_sprite));
+                }
+            }
+        }
+        for &(sprite_index, linked_index) in links.iter() {
+            self.sprites[sprite_index].content = SpriteContent::Linked(linked_index);
+        }
+        links.len()
+    }
+    /// Index of the sprite with the image of a sprite, following the links
+    pub fn image_sprite_index(&self, sprite_index: usize) -> Result<usize, WritingError> {
+        let mut current_index = sprite_index;
+        // a chain of links longer than the number of sprites has a cycle
+        for _ in 0..=self.sprites.len() {
+            match &self.sprites[current_index].content {
+                SpriteContent::Image(_) => return Ok(current_index),
+                SpriteContent::Linked(linked_index) if *linked_index < self.sprites.len() => current_index = *linked_index,
+                SpriteContent::Linked(linked_index) => return Err(WritingError::InvalidLinkedSprite {
+                    sprite_index: current_index,
+                    linked_index: *linked_index,
+                    sprite_count: self.sprites.len(),
+                }),
+            }
+        }
+        Err(WritingError::LinkCycle(sprite_index))
+    }
+    /// Check the links, images and palettes of the sprites before writing them
+    pub fn validate(&self) -> Result<(), WritingError> {
+        // the sprite files link the sprites and palettes with 16-bit indices
+        const MAX_INDEX_COUNT: usize = u16::MAX as usize + 1;
+        if self.sprites.len() > MAX_INDEX_COUNT {
+            return Err(WritingError::TooManySprites(self.sprites.len()));
+        }
+        if self.palettes.len() > MAX_INDEX_COUNT {
+            return Err(WritingError::TooManyPalettes(self.palettes.len()));
+        }
+        for (palette_index, palette) in self.palettes.iter().enumerate() {
+            if palette.colors.len() > 256 {
+                return Err(WritingError::TooManyColors { palette_index, color_count: palette.colors.len() });
+            }
+        }
+        for (sprite_index, sprite) in self.sprites.iter().enumerate() {
+            self.image_sprite_index(sprite_index)?;
+            if let SpriteContent::Image(image) = &sprite.content {
+                if image.pixels.len() != image.width as usize * image.height as usize {
+                    return Err(WritingError::InvalidPixelCount {
+                        sprite_index,
+                        width: image.width,
+                        height: image.height,
+                        pixel_count: image.pixels.len(),
+                    });
+                }
+            }
+            if sprite.palette_index >= self.palettes.len() {
+                return Err(WritingError::PaletteNotFound {
+                    sprite_index,
+                    palette_index: sprite.palette_index,
+                    palette_count: self.palettes.len(),
+                });
+            }
+        }
+        Ok(())
+    }
+    /// Write a SFF v1 file, with PCX images including their palette
+    pub fn write_v1<W: Write>(&self, writer: W) -> Result<(), WritingError> {
+        self.validate()?;
+        v1::write_sff(self, writer)
+    }
+    /// Write a SFF v2 file with the given compression of the images
+    pub fn write_v2<W: Write>(&self, writer: W, compression: Compression) -> Result<(), WritingError> {
+        self.validate()?;
+        v2::write_sff(self, writer, compression)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use super::*;
+    use crate::bitmap::BitmapPixel;
+    use crate::bitmap::test_renderer::TestRenderer;
+    use crate::{v1, v2, SpriteFile, SpriteFormat};
+
+    fn image(width: u16, height: u16, pixel: impl Fn(u16, u16) -> u8) -> SpriteContent {
+        let pixels = (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| pixel(x, y)).collect();
+        SpriteContent::Image(IndexedImage { width, height, pixels })
+    }
+
+    fn test_model() -> SpriteModel {
+        let mut model = SpriteModel::new();
+        let palette = model.add_palette(1, 1, (0..=255).map(|i| [i, 255 - i, i / 2]).collect());
+        let other_palette = model.add_palette(1, 2, (0..32).map(|i| [i * 8, 0, 255]).collect());
+        // odd width with colors below 32
+        let small = image(5, 3, |x, y| ((x + y) % 3 * 7) as u8);
+        model.add_sprite(0, 0, (2, 3), small.clone(), palette);
+        // long runs
+        model.add_sprite(0, 1, (0, 0), image(100, 4, |x, y| if y < 3 { 1 } else { (x / 10) as u8 }), palette);
+        // repeated patterns
+        model.add_sprite(1, 0, (10, 20), image(64, 8, |x, y| ((x * 3 + y) % 7) as u8 + (x / 32) as u8 * 10), palette);
+        // colors above 32, including the RLE markers of RLE8 and PCX
+        model.add_sprite(1, 1, (0, 0), image(9, 5, |x, y| [0, 200, 0x41, 0xC3, 31, 255, 64, 64, 0][((x + y * 2) % 9) as usize]), palette);
+        model.add_sprite(2, 0, (5, 5), SpriteContent::Linked(2), palette);
+        model.add_sprite(2, 1, (0, 0), small, other_palette);
+        model
+    }
+
+    /// Pixels of the sprites of the model, with the colors of their palette
+    fn expected_pixels(model: &SpriteModel) -> Vec<Vec<BitmapPixel>> {
+        (0..model.sprites.len()).map(|sprite_index| {
+            let image_sprite = &model.sprites[model.image_sprite_index(sprite_index).unwrap()];
+            let SpriteContent::Image(image) = &image_sprite.content else { unreachable!() };
+            let colors = &model.palettes[image_sprite.palette_index].colors;
+            image.pixels.iter().map(|&color| match color {
+                0 => BitmapPixel::new(0, 0, 0, 0),
+                _ => BitmapPixel::new(colors[color as usize][0], colors[color as usize][1], colors[color as usize][2], 255),
+            }).collect()
+        }).collect()
+    }
+
+    /// Read the written sprite file and render its sprites
+    fn rendered_pixels(model: &SpriteModel, data: Vec<u8>) -> Vec<Vec<BitmapPixel>> {
+        let palettes = vec![v1::Palette { colors: [v1::Color::Transparent; v1::PALETTE_COLOR_COUNT] }];
+        let sprite_file = SpriteFile::read(Cursor::new(data), palettes).unwrap();
+        model.sprites.iter()
+            .map(|sprite| sprite_file.render_sprite::<TestRenderer>((), sprite.group, sprite.image, 0).unwrap().surface.pixels)
+            .collect()
+    }
+
+    #[test]
+    fn write_and_read() {
+        let mut model = test_model();
+        let expected = expected_pixels(&model);
+        for compression in [Compression::Raw, Compression::RLE8, Compression::RLE5, Compression::LZ5] {
+            let mut data = Vec::new();
+            model.write_v2(&mut data, compression).unwrap();
+            assert_eq!(expected, rendered_pixels(&model, data), "{compression:?}");
+        }
+        let mut data = Vec::new();
+        model.write_v1(&mut data).unwrap();
+        assert_eq!(expected, rendered_pixels(&model, data));
+        // the last sprite has the same image as the first one, but not the same palette
+        model.add_sprite(3, 0, (0, 0), model.sprites[1].content.clone(), 0);
+        let mut data = Vec::new();
+        model.write_v2(&mut data, Compression::Raw).unwrap();
+        assert_eq!(1, model.deduplicate());
+        assert_eq!(SpriteContent::Linked(1), model.sprites[6].content);
+        let mut deduplicated_data = Vec::new();
+        model.write_v2(&mut deduplicated_data, Compression::Raw).unwrap();
+        assert!(deduplicated_data.len() < data.len());
+        assert_eq!(expected_pixels(&model), rendered_pixels(&model, deduplicated_data));
+    }
+
+    /// External palettes of a SFF v1 file with the colors of the palettes of a model
+    fn external_palettes(model: &SpriteModel) -> Vec<v1::Palette> {
+        model.palettes.iter().map(|palette| v1::Palette {
+            colors: std::array::from_fn(|color_index| {
+                let [r, g, b] = palette.colors.get(color_index).copied().unwrap_or([0; 3]);
+                v1::Color::Rgb(r, g, b)
+            }),
+        }).collect()
+    }
+
+    fn render(sprite_file: &SpriteFile, group: u16, image: u16, palette_index: usize) -> Vec<BitmapPixel> {
+        sprite_file.render_sprite::<TestRenderer>((), group, image, palette_index).unwrap().surface.pixels
+    }
+
+    /// Pixels of every sprite of a sprite file rendered with every palette
+    fn rendered_sprites(sprite_file: &SpriteFile, palette_count: usize) -> Vec<Vec<BitmapPixel>> {
+        sprite_file.sprites().iter()
+            .flat_map(|sprite| (0..palette_count).map(|palette_index| render(sprite_file, sprite.group, sprite.image, palette_index)))
+            .collect()
+    }
+
+    #[test]
+    fn sprite_file_round_trip() {
+        let source_model = test_model();
+        let source_palettes = external_palettes(&source_model);
+        // LZ5 compression, the images with colors above 32 being compressed with RLE8
+        let mut v2_data = Vec::new();
+        source_model.write_v2(&mut v2_data, Compression::LZ5).unwrap();
+        // the first sprite uses the external palettes: shared palette type in the header, and shared palette flag of the sprite
+        let mut v1_data = Vec::new();
+        source_model.write_v1(&mut v1_data).unwrap();
+        v1_data[32] = 1;
+        v1_data[512 + 18] = 1;
+        for source_data in [v2_data, v1_data] {
+            let source = SpriteFile::read(Cursor::new(source_data), source_palettes.clone()).unwrap();
+            let palette_count = source.palette_count();
+            let model = SpriteModel::from_sprite_file(&source).unwrap();
+            let expected = rendered_sprites(&source, palette_count);
+            for compression in [Compression::Raw, Compression::RLE8, Compression::RLE5, Compression::LZ5] {
+                let mut data = Vec::new();
+                model.write_v2(&mut data, compression).unwrap();
+                let written = SpriteFile::read(Cursor::new(data), []).unwrap();
+                assert_eq!(expected, rendered_sprites(&written, palette_count), "{compression:?}");
+                if compression == Compression::LZ5 {
+                    assert_eq!(SpriteFormat::V2(v2::ImageFormat::LZ5), written.sprite(0, 1).unwrap().format);
+                    assert_eq!(SpriteFormat::V2(v2::ImageFormat::RLE8), written.sprite(1, 1).unwrap().format);
+                }
+                // the colors of the LZ5 and RLE5 images below 32 are written on 5 bits
+                let five_bits = matches!(compression, Compression::RLE5 | Compression::LZ5);
+                assert_eq!(if five_bits { 5 } else { 8 }, written.sprite(0, 1).unwrap().color_depth, "{compression:?}");
+                assert_eq!(8, written.sprite(1, 1).unwrap().color_depth, "{compression:?}");
+            }
+            // the SFF v1 files written have the palette of each sprite in its PCX data: the sprites using the selected
+            // palette are only rendered the same with the first palette
+            let mut data = Vec::new();
+            model.write_v1(&mut data).unwrap();
+            let written = SpriteFile::read(Cursor::new(data), external_palettes(&model)).unwrap();
+            for sprite in source.sprites() {
+                let palette_count = if source.uses_selected_palette(sprite.group, sprite.image) { 1 } else { palette_count };
+                for palette_index in 0..palette_count {
+                    assert_eq!(render(&source, sprite.group, sprite.image, palette_index), render(&written, sprite.group, sprite.image, palette_index));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_model() {
+        let mut model = test_model();
+        model.sprites[4].content = SpriteContent::Linked(4);
+        assert!(matches!(model.write_v2(Vec::new(), Compression::Raw), Err(WritingError::LinkCycle(4))));
+        model.sprites[4].content = SpriteContent::Linked(10);
+        assert!(matches!(model.validate(), Err(WritingError::InvalidLinkedSprite { sprite_index: 4, linked_index: 10, .. })));
+        model.sprites[4].content = image(2, 2, |_, _| 1);
+        model.sprites[4].palette_index = 2;
+        assert!(matches!(model.write_v1(Vec::new()), Err(WritingError::PaletteNotFound { sprite_index: 4, .. })));
+        model.sprites[4].palette_index = 0;
+        if let SpriteContent::Image(image) = &mut model.sprites[4].content {
+            image.pixels.pop();
+        }
+        assert!(matches!(model.validate(), Err(WritingError::InvalidPixelCount { sprite_index: 4, pixel_count: 3, .. })));
+        // the indices of the sprites and palettes are written on 16 bits
+        let mut model = test_model();
+        model.sprites.resize(u16::MAX as usize + 2, model.sprites[0].clone());
+        assert!(matches!(model.write_v2(Vec::new(), Compression::Raw), Err(WritingError::TooManySprites(65537))));
+        let mut model = test_model();
+        model.palettes.resize(u16::MAX as usize + 2, model.palettes[0].clone());
+        assert!(matches!(model.validate(), Err(WritingError::TooManyPalettes(65537))));
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+use byteorder::{LittleEndian, WriteBytesExt};
+use crate::{v1, SffData, WritingError, SIGNATURE_BYTES};
+use super::{IndexedImage, PaletteEntry, SpriteContent, SpriteModel};
+
+const HEADER_SIZE: u32 = 512;
+const SUBHEADER_SIZE: u32 = 32;
+const PCX_HEADER_SIZE: usize = 128;
+/// Marker before the 256 colors of the palette at the end of the PCX data
+const PCX_PALETTE_MARKER: u8 = 0x0C;
+
+pub fn write_sff<W: Write>(model: &SpriteModel, mut writer: W) -> Result<(), WritingError> {
+    // the linked sprites have no data
+    let mut subfiles = Vec::new();
+    for (sprite_index, sprite) in model.sprites.iter().enumerate() {
+        let image_index = model.image_sprite_index(sprite_index)?;
+        let subfile = match &sprite.content {
+            SpriteContent::Image(image) => (0, encode_pcx(image, &model.palettes[sprite.palette_index])),
+            SpriteContent::Linked(_) => (image_index as u16, Vec::new()),
+        };
+        subfiles.push(subfile);
+    }
+    let group_count = model.sprites.iter().map(|sprite| sprite.group).collect::<BTreeSet<_>>().len();
+    // header
+    writer.write_all(SIGNATURE_BYTES)?;
+    writer.write_all(v1::Data::version_bytes())?;
+    writer.write_u32::<LittleEndian>(group_count as u32)?;
+    writer.write_u32::<LittleEndian>(model.sprites.len() as u32)?;
+    writer.write_u32::<LittleEndian>(HEADER_SIZE)?;
+    writer.write_u32::<LittleEndian>(SUBHEADER_SIZE)?;
+    // palette type: each sprite has its own palette
+    writer.write_u8(0)?;
+    // 33 bytes written so far
+    writer.write_all(&[0; HEADER_SIZE as usize - 33])?;
+    let mut subfile_offset = HEADER_SIZE;
+    for (subfile_index, (sprite, (linked_index, data))) in model.sprites.iter().zip(subfiles.iter()).enumerate() {
+        let next_subfile_offset = match subfile_index + 1 < subfiles.len() {
+            true => subfile_offset + SUBHEADER_SIZE + data.len() as u32,
+            false => 0,
+        };
+        writer.write_u32::<LittleEndian>(next_subfile_offset)?;
+        writer.write_u32::<LittleEndian>(data.len() as u32)?;
+        writer.write_i16::<LittleEndian>(sprite.axis.0)?;
+        writer.write_i16::<LittleEndian>(sprite.axis.1)?;
+        writer.write_u16::<LittleEndian>(sprite.group)?;
+        writer.write_u16::<LittleEndian>(sprite.image)?;
+        writer.write_u16::<LittleEndian>(*linked_index)?;
+        // shared palette byte, then 13 blank bytes
+        writer.write_all(&[0; 14])?;
+        writer.write_all(data)?;
+        subfile_offset = next_subfile_offset;
+    }
+    Ok(())
+}
+
+/// Encode an image as a run-length encoded 8-bit PCX image, with the palette at its end
+fn encode_pcx(image: &IndexedImage, palette: &PaletteEntry) -> Vec<u8> {
+    // the lines have an even number of bytes
+    let bytes_per_line = image.width + image.width % 2;
+    let mut data = vec![0; PCX_HEADER_SIZE];
+    // manufacturer, version 3.0, run-length encoding, 8 bits per pixel
+    data[0..4].copy_from_slice(&[0x0A, 5, 1, 8]);
+    // x min and y min are 0, then x max and y max
+    data[8..10].copy_from_slice(&image.width.saturating_sub(1).to_le_bytes());
+    data[10..12].copy_from_slice(&image.height.saturating_sub(1).to_le_bytes());
+    // 1 color plane
+    data[65] = 1;
+    data[66..68].copy_from_slice(&bytes_per_line.to_le_bytes());
+    // palette type: color
+    data[68] = 1;
+    if image.width > 0 {
+        for line in image.pixels.chunks(image.width as usize) {
+            // the runs do not continue on the next line
+            let mut pixel_index = 0;
+            while pixel_index < line.len() {
+                let color = line[pixel_index];
+                let run_length = line[pixel_index..].iter().take(0x3F).take_while(|&&c| c == color).count();
+                if run_length == 1 && (color & 0xC0) != 0xC0 {
+                    data.push(color);
+                }
+                else {
+                    data.extend_from_slice(&[0xC0 | run_length as u8, color]);
+                }
+                pixel_index += run_length;
+            }
+            if bytes_per_line > image.width {
+                data.push(0);
+            }
+        }
+    }
+    data.push(PCX_PALETTE_MARKER);
+    for color_index in 0..v1::PALETTE_COLOR_COUNT {
+        data.extend_from_slice(palette.colors.get(color_index).unwrap_or(&[0; 3]));
+    }
+    data
+}
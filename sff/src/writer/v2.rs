@@ -0,0 +1,118 @@
+use std::io::Write;
+use byteorder::{LittleEndian, WriteBytesExt};
+use crate::{v2, SffData, WritingError, SIGNATURE_BYTES};
+use super::compression::{color_depth, compress};
+use super::{Compression, SpriteContent, SpriteModel};
+
+const HEADER_SIZE: u32 = 512;
+const SPRITE_NODE_SIZE: u32 = 28;
+const PALETTE_NODE_SIZE: u32 = 16;
+
+/// Node of a sprite, pointing to its data in the ldata block
+#[derive(Clone, Copy)]
+struct SpriteNode {
+    size: (u16, u16),
+    linked_index: u16,
+    format: u8,
+    color_depth: u8,
+    data_offset: u32,
+    data_length: u32,
+    palette_index: u16,
+}
+
+pub fn write_sff<W: Write>(model: &SpriteModel, mut writer: W, compression: Compression) -> Result<(), WritingError> {
+    // ldata: the palettes, 4 bytes per color, then the sprite images
+    let mut ldata = Vec::new();
+    let mut palette_ranges = Vec::new();
+    for palette in model.palettes.iter() {
+        let offset = ldata.len() as u32;
+        for color in palette.colors.iter() {
+            ldata.extend_from_slice(color);
+            ldata.push(0);
+        }
+        palette_ranges.push((offset, ldata.len() as u32 - offset));
+    }
+    let mut image_nodes = Vec::new();
+    for sprite in model.sprites.iter() {
+        let node = match &sprite.content {
+            SpriteContent::Image(image) => {
+                let (compression, data) = compress(&image.pixels, compression);
+                let data_offset = ldata.len() as u32;
+                // the image data starts with its uncompressed size
+                ldata.write_u32::<LittleEndian>(image.pixels.len() as u32)?;
+                ldata.extend_from_slice(&data);
+                Some(SpriteNode {
+                    size: (image.width, image.height),
+                    linked_index: 0,
+                    format: compression.format_byte(),
+                    color_depth: color_depth(&image.pixels, compression),
+                    data_offset,
+                    data_length: ldata.len() as u32 - data_offset,
+                    palette_index: sprite.palette_index as u16,
+                })
+            },
+            SpriteContent::Linked(_) => None,
+        };
+        image_nodes.push(node);
+    }
+    // the linked sprites have no data, and the size, format and palette of their image
+    let mut sprite_nodes = Vec::new();
+    for sprite_index in 0..model.sprites.len() {
+        let image_index = model.image_sprite_index(sprite_index)?;
+        let mut node = image_nodes[image_index].expect("the links end at a sprite with an image");
+        if image_index != sprite_index {
+            node.linked_index = image_index as u16;
+            node.data_offset = 0;
+            node.data_length = 0;
+        }
+        sprite_nodes.push(node);
+    }
+    let first_sprite_offset = HEADER_SIZE;
+    let first_palette_offset = first_sprite_offset + SPRITE_NODE_SIZE * sprite_nodes.len() as u32;
+    let ldata_offset = first_palette_offset + PALETTE_NODE_SIZE * model.palettes.len() as u32;
+    // header
+    writer.write_all(SIGNATURE_BYTES)?;
+    writer.write_all(v2::Data::version_bytes())?;
+    writer.write_all(&[0; 8])?;
+    // compatibility version
+    writer.write_all(v2::Data::version_bytes())?;
+    writer.write_all(&[0; 8])?;
+    writer.write_u32::<LittleEndian>(first_sprite_offset)?;
+    writer.write_u32::<LittleEndian>(sprite_nodes.len() as u32)?;
+    writer.write_u32::<LittleEndian>(first_palette_offset)?;
+    writer.write_u32::<LittleEndian>(model.palettes.len() as u32)?;
+    writer.write_u32::<LittleEndian>(ldata_offset)?;
+    writer.write_u32::<LittleEndian>(ldata.len() as u32)?;
+    // empty tdata block after the ldata block
+    writer.write_u32::<LittleEndian>(ldata_offset + ldata.len() as u32)?;
+    writer.write_u32::<LittleEndian>(0)?;
+    // 68 bytes written so far
+    writer.write_all(&[0; HEADER_SIZE as usize - 68])?;
+    for (sprite, node) in model.sprites.iter().zip(sprite_nodes.iter()) {
+        writer.write_u16::<LittleEndian>(sprite.group)?;
+        writer.write_u16::<LittleEndian>(sprite.image)?;
+        writer.write_u16::<LittleEndian>(node.size.0)?;
+        writer.write_u16::<LittleEndian>(node.size.1)?;
+        writer.write_i16::<LittleEndian>(sprite.axis.0)?;
+        writer.write_i16::<LittleEndian>(sprite.axis.1)?;
+        writer.write_u16::<LittleEndian>(node.linked_index)?;
+        writer.write_u8(node.format)?;
+        writer.write_u8(node.color_depth)?;
+        writer.write_u32::<LittleEndian>(node.data_offset)?;
+        writer.write_u32::<LittleEndian>(node.data_length)?;
+        writer.write_u16::<LittleEndian>(node.palette_index)?;
+        // flags: the data is in the ldata block
+        writer.write_u16::<LittleEndian>(0)?;
+    }
+    for (palette, (offset, length)) in model.palettes.iter().zip(palette_ranges) {
+        writer.write_u16::<LittleEndian>(palette.group)?;
+        writer.write_u16::<LittleEndian>(palette.item)?;
+        writer.write_u16::<LittleEndian>(palette.colors.len() as u16)?;
+        // linked palette index
+        writer.write_u16::<LittleEndian>(0)?;
+        writer.write_u32::<LittleEndian>(offset)?;
+        writer.write_u32::<LittleEndian>(length)?;
+    }
+    writer.write_all(&ldata)?;
+    Ok(())
+}
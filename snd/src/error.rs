@@ -0,0 +1,31 @@
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LoadingError {
+    #[error("Missing signature at start of file")]
+    NoSignature,
+    #[error("Error loading sound data")]
+    IoError(#[from] io::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum DecodingError {
+    #[error("Sound {0}, {1} not found")]
+    NotFound(u32, u32),
+    #[error("Missing RIFF WAVE header")]
+    NoWaveHeader,
+    #[error("Missing {0} chunk in WAV data")]
+    MissingChunk(&'static str),
+    #[error("Unsupported WAV audio format {0}, only PCM is supported")]
+    UnsupportedFormat(u16),
+    #[error("Unsupported WAV sample size of {0} bits")]
+    UnsupportedBitsPerSample(u16),
+    #[error("Invalid WAV format: {channels} channels at {sample_rate} Hz")]
+    InvalidFormat {
+        channels: u16,
+        sample_rate: u32,
+    },
+    #[error("Error decoding sound data")]
+    IoError(#[from] io::Error),
+}
@@ -0,0 +1,11 @@
+mod sound_file;
+pub use self::sound_file::*;
+
+mod sound;
+pub use self::sound::*;
+
+mod wav;
+pub use self::wav::*;
+
+mod error;
+pub use self::error::*;
@@ -0,0 +1,83 @@
+/// Decoded sound: interleaved samples between -1 and 1.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Sound {
+    sample_rate: u32,
+    channels: u16,
+    samples: Vec<f32>,
+}
+
+impl Sound {
+    /// Sound from interleaved samples. The number of samples must be a multiple of the number of channels.
+    pub fn new(sample_rate: u32, channels: u16, samples: Vec<f32>) -> Sound {
+        assert!(channels > 0 && samples.len().is_multiple_of(channels as usize), "Samples do not match {channels} channels");
+        Sound {
+            sample_rate,
+            channels,
+            samples,
+        }
+    }
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+    /// Interleaved samples of all the channels
+    pub fn samples(&self) -> &[f32] {
+        &self.samples[..]
+    }
+    /// Number of samples per channel
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+    /// Samples of all the channels at a given frame
+    pub fn frame(&self, index: usize) -> Option<&[f32]> {
+        let channels = self.channels as usize;
+        self.samples.get(index * channels..(index + 1) * channels)
+    }
+    /// Duration in seconds
+    pub fn duration(&self) -> f64 {
+        self.frame_count() as f64 / self.sample_rate as f64
+    }
+    /// Convert the sound to another sample rate, with linear interpolation
+    pub fn resampled(&self, sample_rate: u32) -> Sound {
+        if sample_rate == self.sample_rate || self.samples.is_empty() {
+            return Sound::new(sample_rate, self.channels, self.samples.clone());
+        }
+        let channels = self.channels as usize;
+        let source_frame_count = self.frame_count();
+        let frame_count = ((source_frame_count as u64 * sample_rate as u64) / self.sample_rate as u64).max(1) as usize;
+        let step = self.sample_rate as f64 / sample_rate as f64;
+        let mut samples = Vec::with_capacity(frame_count * channels);
+        for frame in 0..frame_count {
+            let position = frame as f64 * step;
+            let index = (position as usize).min(source_frame_count - 1);
+            let next_index = (index + 1).min(source_frame_count - 1);
+            let fraction = (position - index as f64) as f32;
+            for channel in 0..channels {
+                let sample = self.samples[index * channels + channel];
+                let next_sample = self.samples[next_index * channels + channel];
+                samples.push(sample + (next_sample - sample) * fraction);
+            }
+        }
+        Sound::new(sample_rate, self.channels, samples)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resample() {
+        let sound = Sound::new(100, 2, vec![0., 1., 0.5, -1., 1., 0.]);
+        let upsampled = sound.resampled(200);
+        assert_eq!(6, upsampled.frame_count());
+        assert_eq!(Some(&[0.25, 0.][..]), upsampled.frame(1));
+        assert_eq!(Some(&[1., 0.][..]), upsampled.frame(5));
+        let downsampled = sound.resampled(50);
+        assert_eq!(1, downsampled.frame_count());
+        assert_eq!(Some(&[0., 1.][..]), downsampled.frame(0));
+        assert_eq!(sound, sound.resampled(100));
+    }
+}
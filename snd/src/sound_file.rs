@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom};
+use byteorder::{LittleEndian, ReadBytesExt};
+use crate::{decode_wav, DecodingError, LoadingError, Sound};
+
+pub const SIGNATURE_BYTES: &[u8; 12] = b"ElecbyteSnd\0";
+
+/// Sounds of a SND file, indexed by group and sound number.
+///
+/// The sounds are kept as WAV data and decoded when requested.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SoundFile {
+    sounds: BTreeMap<(u32, u32), Vec<u8>>,
+}
+
+impl SoundFile {
+    pub fn read<T: Read + Seek>(mut reader: T) -> Result<SoundFile, LoadingError> {
+        let mut signature = [0; SIGNATURE_BYTES.len()];
+        reader.read_exact(&mut signature)?;
+        if &signature != SIGNATURE_BYTES {
+            return Err(LoadingError::NoSignature);
+        }
+        // get the total stream size
+        let stream_size = {
+            let stream_pos = reader.stream_position()?;
+            let stream_size = reader.seek(SeekFrom::End(0))?;
+            reader.seek(SeekFrom::Start(stream_pos))?;
+            stream_size
+        };
+        // read the version
+        reader.read_u32::<LittleEndian>()?;
+        let sound_count = reader.read_u32::<LittleEndian>()?;
+        let mut next_subfile_offset = reader.read_u32::<LittleEndian>()?;
+        let mut sounds = BTreeMap::new();
+        let mut read_count = 0;
+        // the offset of the last subfile is 0 in some files
+        while read_count < sound_count && next_subfile_offset != 0 && (next_subfile_offset as u64) < stream_size {
+            reader.seek(SeekFrom::Start(next_subfile_offset as u64))?;
+            next_subfile_offset = reader.read_u32::<LittleEndian>()?;
+            let data_size = reader.read_u32::<LittleEndian>()?;
+            let group = reader.read_u32::<LittleEndian>()?;
+            let number = reader.read_u32::<LittleEndian>()?;
+            let mut data = vec![0; data_size as usize];
+            reader.read_exact(&mut data[..])?;
+            // the first sound with a given number is used
+            sounds.entry((group, number)).or_insert(data);
+            read_count += 1;
+        }
+        Ok(SoundFile {
+            sounds,
+        })
+    }
+    pub fn len(&self) -> usize {
+        self.sounds.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.sounds.is_empty()
+    }
+    /// Group and number of all the sounds
+    pub fn keys(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.sounds.keys().copied()
+    }
+    pub fn contains(&self, group: u32, number: u32) -> bool {
+        self.sounds.contains_key(&(group, number))
+    }
+    /// Decode a sound of the file
+    pub fn sound(&self, group: u32, number: u32) -> Result<Sound, DecodingError> {
+        let data = self.sounds.get(&(group, number)).ok_or(DecodingError::NotFound(group, number))?;
+        decode_wav(data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use crate::wav::test::wav_data;
+
+    /// SND file with the header and blank bytes of 512 bytes
+    fn snd_data(sounds: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(SIGNATURE_BYTES);
+        data.extend_from_slice(&[0, 1, 0, 0]);
+        data.extend_from_slice(&(sounds.len() as u32).to_le_bytes());
+        data.extend_from_slice(&512u32.to_le_bytes());
+        data.resize(512, 0);
+        for (index, (group, number, wav)) in sounds.iter().enumerate() {
+            let next_offset = if index + 1 < sounds.len() { data.len() + 16 + wav.len() } else { 0 };
+            data.extend_from_slice(&(next_offset as u32).to_le_bytes());
+            data.extend_from_slice(&(wav.len() as u32).to_le_bytes());
+            data.extend_from_slice(&group.to_le_bytes());
+            data.extend_from_slice(&number.to_le_bytes());
+            data.extend_from_slice(wav);
+        }
+        data
+    }
+
+    #[test]
+    fn read_sounds() {
+        let data = snd_data(&[
+            (0, 0, wav_data(1, 11025, 8, &[128, 192])),
+            (5, 2, wav_data(2, 22050, 16, &[0, 0x40, 0, 0xc0])),
+            (5, 2, wav_data(1, 11025, 8, &[0])),
+        ]);
+        let sound_file = SoundFile::read(Cursor::new(data)).unwrap();
+        assert_eq!(vec![(0, 0), (5, 2)], sound_file.keys().collect::<Vec<_>>());
+        assert_eq!(&[0., 0.5], sound_file.sound(0, 0).unwrap().samples());
+        let sound = sound_file.sound(5, 2).unwrap();
+        assert_eq!((22050, 2), (sound.sample_rate(), sound.channels()));
+        assert_eq!(&[0.5, -0.5], sound.samples());
+        assert!(matches!(sound_file.sound(1, 0), Err(DecodingError::NotFound(1, 0))));
+        assert!(matches!(SoundFile::read(Cursor::new(b"ElecbyteSpr\0")), Err(LoadingError::NoSignature)));
+    }
+}
@@ -0,0 +1,176 @@
+use std::io::{Cursor, Read};
+use byteorder::{LittleEndian, ReadBytesExt};
+use crate::{DecodingError, Sound};
+
+/// `audio_format` of the `fmt ` chunk for PCM data
+const PCM_FORMAT: u16 = 1;
+
+/// `audio_format` of the `fmt ` chunk for formats described by an extension
+const EXTENSIBLE_FORMAT: u16 = 0xFFFE;
+
+/// End of the SubFormat GUID of the extension, after the `audio_format` of the data
+const SUB_FORMAT_GUID_SUFFIX: [u8; 14] = [0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71];
+
+struct WavFormat {
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+}
+
+/// Decode WAV data with PCM samples of 8 or 16 bits.
+pub fn decode_wav(data: &[u8]) -> Result<Sound, DecodingError> {
+    let mut reader = Cursor::new(data);
+    let mut riff_header = [0; 12];
+    reader.read_exact(&mut riff_header).map_err(|_| DecodingError::NoWaveHeader)?;
+    if &riff_header[0..4] != b"RIFF" || &riff_header[8..12] != b"WAVE" {
+        return Err(DecodingError::NoWaveHeader);
+    }
+    let mut format = None;
+    let mut samples = None;
+    // the chunks, until the end of the data
+    while samples.is_none() {
+        let mut chunk_id = [0; 4];
+        if reader.read_exact(&mut chunk_id).is_err() {
+            break;
+        }
+        let chunk_size = reader.read_u32::<LittleEndian>()? as usize;
+        let chunk_start = reader.position() as usize;
+        // some files have a wrong size for their last chunk
+        let chunk_end = (chunk_start + chunk_size).min(data.len());
+        let chunk = &data[chunk_start..chunk_end];
+        match &chunk_id {
+            b"fmt " => format = Some(read_format(chunk)?),
+            b"data" => {
+                let format = format.as_ref().ok_or(DecodingError::MissingChunk("fmt"))?;
+                samples = Some(read_samples(chunk, format.bits_per_sample));
+            },
+            _ => (),
+        }
+        // chunks are aligned on 2 bytes
+        reader.set_position((chunk_end + chunk_size % 2) as u64);
+    }
+    let format = format.ok_or(DecodingError::MissingChunk("fmt"))?;
+    let mut samples = samples.ok_or(DecodingError::MissingChunk("data"))?;
+    // drop an incomplete last frame
+    samples.truncate(samples.len() - samples.len() % format.channels as usize);
+    Ok(Sound::new(format.sample_rate, format.channels, samples))
+}
+
+fn read_format(chunk: &[u8]) -> Result<WavFormat, DecodingError> {
+    let mut reader = Cursor::new(chunk);
+    let audio_format = reader.read_u16::<LittleEndian>()?;
+    let channels = reader.read_u16::<LittleEndian>()?;
+    let sample_rate = reader.read_u32::<LittleEndian>()?;
+    // byte rate and block align
+    reader.read_u32::<LittleEndian>()?;
+    reader.read_u16::<LittleEndian>()?;
+    let bits_per_sample = reader.read_u16::<LittleEndian>()?;
+    let audio_format = match audio_format {
+        EXTENSIBLE_FORMAT => read_sub_format(&mut reader).ok_or(DecodingError::UnsupportedFormat(EXTENSIBLE_FORMAT))?,
+        _ => audio_format,
+    };
+    if audio_format != PCM_FORMAT {
+        return Err(DecodingError::UnsupportedFormat(audio_format));
+    }
+    if bits_per_sample != 8 && bits_per_sample != 16 {
+        return Err(DecodingError::UnsupportedBitsPerSample(bits_per_sample));
+    }
+    if channels == 0 || sample_rate == 0 {
+        return Err(DecodingError::InvalidFormat { channels, sample_rate });
+    }
+    Ok(WavFormat {
+        channels,
+        sample_rate,
+        bits_per_sample,
+    })
+}
+
+/// `audio_format` of the SubFormat GUID of an extension, `None` for a missing extension or an unknown GUID
+fn read_sub_format(reader: &mut Cursor<&[u8]>) -> Option<u16> {
+    // extension size, valid bits per sample and channel mask
+    reader.read_u16::<LittleEndian>().ok()?;
+    reader.read_u16::<LittleEndian>().ok()?;
+    reader.read_u32::<LittleEndian>().ok()?;
+    let audio_format = reader.read_u16::<LittleEndian>().ok()?;
+    let mut guid_suffix = [0; 14];
+    reader.read_exact(&mut guid_suffix).ok()?;
+    (guid_suffix == SUB_FORMAT_GUID_SUFFIX).then_some(audio_format)
+}
+
+/// Normalize the samples between -1 and 1: 8 bits samples are unsigned, 16 bits samples are signed
+fn read_samples(chunk: &[u8], bits_per_sample: u16) -> Vec<f32> {
+    match bits_per_sample {
+        8 => chunk.iter().map(|&sample| (sample as f32 - 128.) / 128.).collect(),
+        _ => chunk.chunks_exact(2).map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.).collect(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// WAV data with PCM samples
+    pub(crate) fn wav_data(channels: u16, sample_rate: u32, bits_per_sample: u16, sample_data: &[u8]) -> Vec<u8> {
+        let block_align = channels * bits_per_sample / 8;
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + sample_data.len() as u32).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&PCM_FORMAT.to_le_bytes());
+        data.extend_from_slice(&channels.to_le_bytes());
+        data.extend_from_slice(&sample_rate.to_le_bytes());
+        data.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        data.extend_from_slice(&block_align.to_le_bytes());
+        data.extend_from_slice(&bits_per_sample.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&(sample_data.len() as u32).to_le_bytes());
+        data.extend_from_slice(sample_data);
+        data
+    }
+
+    #[test]
+    fn decode_pcm() {
+        let sound = decode_wav(&wav_data(1, 11025, 8, &[128, 255, 0])).unwrap();
+        assert_eq!((11025, 1), (sound.sample_rate(), sound.channels()));
+        assert_eq!(&[0., 127. / 128., -1.], sound.samples());
+        let sound = decode_wav(&wav_data(2, 22050, 16, &[0x00, 0x40, 0x00, 0x80, 0xff, 0x7f])).unwrap();
+        assert_eq!((22050, 2), (sound.sample_rate(), sound.channels()));
+        // the incomplete last frame is dropped
+        assert_eq!(&[0.5, -1.], sound.samples());
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert!(matches!(decode_wav(b"RIFX"), Err(DecodingError::NoWaveHeader)));
+        assert!(matches!(decode_wav(&wav_data(1, 11025, 24, &[0; 6])), Err(DecodingError::UnsupportedBitsPerSample(24))));
+        let mut adpcm = wav_data(1, 11025, 8, &[0]);
+        adpcm[20] = 2;
+        assert!(matches!(decode_wav(&adpcm), Err(DecodingError::UnsupportedFormat(2))));
+    }
+
+    /// WAV data with a `fmt ` chunk extended with the SubFormat GUID of an audio format
+    fn extensible_wav_data(sub_format: u16, guid_suffix: &[u8], sample_data: &[u8]) -> Vec<u8> {
+        let mut data = wav_data(1, 11025, 8, sample_data);
+        data[16..20].copy_from_slice(&(16 + 8 + 2 + guid_suffix.len() as u32).to_le_bytes());
+        data[20..22].copy_from_slice(&EXTENSIBLE_FORMAT.to_le_bytes());
+        let mut extension = vec![22, 0, 8, 0, 4, 0, 0, 0];
+        extension.extend_from_slice(&sub_format.to_le_bytes());
+        extension.extend_from_slice(guid_suffix);
+        data.splice(36..36, extension);
+        data
+    }
+
+    #[test]
+    fn decode_extensible_errors() {
+        let sound = decode_wav(&extensible_wav_data(PCM_FORMAT, &SUB_FORMAT_GUID_SUFFIX, &[255])).unwrap();
+        assert_eq!(&[127. / 128.], sound.samples());
+        // IEEE float samples
+        let float = extensible_wav_data(3, &SUB_FORMAT_GUID_SUFFIX, &[0; 4]);
+        assert!(matches!(decode_wav(&float), Err(DecodingError::UnsupportedFormat(3))));
+        let unknown_guid = extensible_wav_data(PCM_FORMAT, &[0; 14], &[0]);
+        assert!(matches!(decode_wav(&unknown_guid), Err(DecodingError::UnsupportedFormat(EXTENSIBLE_FORMAT))));
+        let missing_extension = extensible_wav_data(PCM_FORMAT, &[], &[0]);
+        assert!(matches!(decode_wav(&missing_extension), Err(DecodingError::UnsupportedFormat(EXTENSIBLE_FORMAT))));
+    }
+}